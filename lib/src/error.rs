@@ -0,0 +1,58 @@
+use std::fmt::{Display, Formatter};
+
+use crate::format::FourCC;
+
+/// Typed parse failures.
+///
+/// Parsers still return `anyhow::Result`, with these errors wrapped inside so that
+/// callers can distinguish unsupported data from corrupt data via [`RetroError::find`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RetroError {
+    /// A form or chunk had a different ID than expected.
+    UnexpectedFourCC { expected: FourCC, found: FourCC },
+    /// The form is a known type, but its version is not supported.
+    UnsupportedVersion { id: FourCC, reader_version: u32, writer_version: u32 },
+    /// A required chunk was not present.
+    MissingChunk(FourCC),
+    /// The data ended before the `expected` end offset.
+    Truncated { at: usize, expected: usize },
+    /// There was no data at all where a form or chunk was expected, such as a
+    /// zero-length package entry. Callers can usually skip these.
+    Empty,
+    /// The data is well-formed, but uses a format that isn't supported.
+    UnsupportedFormat(String),
+}
+
+impl RetroError {
+    /// Locates a `RetroError` anywhere in an error's context chain.
+    pub fn find(err: &anyhow::Error) -> Option<&RetroError> {
+        err.chain().find_map(|e| e.downcast_ref::<RetroError>())
+    }
+
+    /// Whether the failure indicates data this tool doesn't support yet,
+    /// as opposed to a corrupt or mismatched file.
+    pub fn is_unsupported(&self) -> bool {
+        matches!(self, RetroError::UnsupportedVersion { .. } | RetroError::UnsupportedFormat(_))
+    }
+}
+
+impl Display for RetroError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            RetroError::UnexpectedFourCC { expected, found } => {
+                write!(f, "Expected {expected}, found {found}")
+            }
+            RetroError::UnsupportedVersion { id, reader_version, writer_version } => {
+                write!(f, "Unsupported {id} version {reader_version}/{writer_version}")
+            }
+            RetroError::MissingChunk(id) => write!(f, "Failed to locate {id}"),
+            RetroError::Truncated { at, expected } => {
+                write!(f, "Data truncated at {at:#X} (expected {expected:#X} bytes)")
+            }
+            RetroError::UnsupportedFormat(format) => write!(f, "Unsupported format {format}"),
+            RetroError::Empty => write!(f, "Empty form or chunk"),
+        }
+    }
+}
+
+impl std::error::Error for RetroError {}
@@ -3,10 +3,10 @@ use std::{
     mem::size_of,
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use zerocopy::{AsBytes, ByteOrder, FromBytes, FromZeroes, U32, U64};
 
-use crate::format::FourCC;
+use crate::{error::RetroError, format::FourCC};
 
 #[derive(Clone, Debug, Default, PartialEq, FromBytes, FromZeroes, AsBytes)]
 #[repr(C, packed)]
@@ -18,12 +18,35 @@ pub struct ChunkDescriptor<O: ByteOrder> {
 }
 
 impl<O: ByteOrder> ChunkDescriptor<O> {
+    /// Splits off a chunk, returning its header, payload and the remaining data.
+    ///
+    /// Some optional chunks legitimately have no data, so a zero-length payload
+    /// yields an empty slice. Empty input returns [`RetroError::Empty`].
     pub fn slice(data: &[u8]) -> Result<(&Self, &[u8], &[u8])> {
-        let header = Self::ref_from_prefix(data).ok_or_else(|| anyhow!("Invalid chunk header"))?;
-        let start = size_of::<Self>() + header.skip.get() as usize;
-        let slice = &data[start..(start + header.size.get() as usize)];
-        let remain = &data[(start + header.size.get() as usize)..];
-        Ok((header, slice, remain))
+        if data.is_empty() {
+            return Err(RetroError::Empty.into());
+        }
+        let header = Self::ref_from_prefix(data)
+            .ok_or(RetroError::Truncated { at: data.len(), expected: size_of::<Self>() })?;
+        // Saturate so that bogus sizes are reported as truncation rather than overflowing
+        let start = size_of::<Self>().saturating_add(header.skip.get() as usize);
+        let end = start.saturating_add(header.size.get() as usize);
+        if end > data.len() {
+            return Err(RetroError::Truncated { at: data.len(), expected: end }.into());
+        }
+        Ok((header, &data[start..end], &data[end..]))
+    }
+
+    /// Whether the chunk has no payload.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.size.get() == 0 }
+
+    /// Checks the chunk ID, returning [`RetroError::UnexpectedFourCC`] on mismatch.
+    pub fn expect_id(&self, id: FourCC) -> Result<()> {
+        if self.id != id {
+            return Err(RetroError::UnexpectedFourCC { expected: id, found: self.id }.into());
+        }
+        Ok(())
     }
 
     pub fn write<W, CB>(&self, w: &mut W, mut cb: CB) -> Result<()>
@@ -0,0 +1,180 @@
+use std::{io::Cursor, marker::PhantomData};
+
+use anyhow::{ensure, Result};
+use binrw::{binrw, BinReaderExt, Endian};
+use zerocopy::ByteOrder;
+
+use crate::{
+    error::RetroError,
+    format::{
+        chunk::ChunkDescriptor, foot::K_FORM_FOOT, rfrm::FormDescriptor, CAABox, COBBox, CVector3f,
+        FourCC,
+    },
+};
+
+// CAABoxCollisionTree
+pub const K_FORM_CLSN: FourCC = FourCC(*b"CLSN");
+// COBBoxCollisionTree
+pub const K_FORM_DCLN: FourCC = FourCC(*b"DCLN");
+
+// COBBCollisionTree Header (only used in DCLN)
+//pub const K_CHUNK_INFO: FourCC = FourCC(*b"INFO");
+
+// Vertex data
+pub const K_CHUNK_VERT: FourCC = FourCC(*b"VERT");
+
+// Material data
+pub const K_CHUNK_MTRL: FourCC = FourCC(*b"MTRL");
+
+// Triangle data
+pub const K_CHUNK_TRIS: FourCC = FourCC(*b"TRIS");
+
+// Octree data
+//pub const K_CHUNK_TREE: FourCC = FourCC(*b"TREE");
+
+pub const K_CLSN_READER_VERSION: u32 = 11;
+pub const K_CLSN_WRITER_VERSION: u32 = 22;
+
+pub const K_DCLN_READER_VERSION: u32 = 9;
+pub const K_DCLN_WRITER_VERSION: u32 = 18;
+
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct Vertices {
+    #[bw(try_calc = vertices.len().try_into())]
+    pub count: u32,
+    #[br(count = count)]
+    pub vertices: Vec<CVector3f>,
+}
+
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct Materials {
+    #[bw(try_calc = materials.len().try_into())]
+    pub count: u32,
+    #[br(count = count)]
+    pub materials: Vec<CCollisionMaterial>,
+}
+
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct CCollisionMaterial {
+    pub orientation: u32,
+    pub material_type: u32,
+    pub world_type: u32,
+    pub behavior_list: u32,
+    pub filter_list: u32,
+}
+
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct CIndexedTriangle {
+    pub idx1: u32,
+    pub idx2: u32,
+    pub idx3: u32,
+    pub material: u16,
+    pub unk: u16,
+}
+
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct Triangles {
+    #[bw(try_calc = triangles.len().try_into())]
+    pub count: u32,
+    #[br(count = count)]
+    pub triangles: Vec<CIndexedTriangle>,
+}
+
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct AABoxTreeNode {
+    pub bounds: CAABox,
+    pub start: u32,
+    pub end: u32,
+    pub unk1: u8,
+    pub unk2: u8,
+    pub unk3: u8,
+    pub unk4: u8,
+}
+
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct OBBoxTreeNode {
+    pub bounds: COBBox,
+    pub start: u32,
+    pub end: u32,
+    pub unk1: u8,
+    pub unk2: u8,
+    pub unk3: u8,
+    pub unk4: u8,
+}
+
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct AABoxCollisionTree {
+    #[bw(try_calc = nodes.len().try_into())]
+    pub count: u32,
+    #[br(count = count)]
+    pub nodes: Vec<AABoxTreeNode>,
+}
+
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct OBBoxCollisionTree {
+    #[bw(try_calc = nodes.len().try_into())]
+    pub count: u32,
+    #[br(count = count)]
+    pub nodes: Vec<OBBoxTreeNode>,
+}
+
+/// Collision geometry of a CLSN or DCLN file. Materials and the bounding tree aren't
+/// read yet.
+#[derive(Debug, Clone)]
+pub struct CollisionData<O: ByteOrder> {
+    /// CLSN (AABB tree) or DCLN (OBB tree)
+    pub kind: FourCC,
+    pub vertices: Vec<CVector3f>,
+    pub triangles: Vec<CIndexedTriangle>,
+    _marker: PhantomData<O>,
+}
+
+impl<O: ByteOrder> CollisionData<O> {
+    /// Parses a collision form followed by its footer.
+    pub fn slice(data: &[u8]) -> Result<Self> {
+        let (form_desc, mut col_data, remain) = FormDescriptor::<O>::slice(data)?;
+        match form_desc.id {
+            K_FORM_CLSN => {
+                form_desc.expect_version(K_CLSN_READER_VERSION, K_CLSN_WRITER_VERSION)?
+            }
+            K_FORM_DCLN => {
+                form_desc.expect_version(K_DCLN_READER_VERSION, K_DCLN_WRITER_VERSION)?
+            }
+            id => {
+                return Err(RetroError::UnexpectedFourCC { expected: K_FORM_CLSN, found: id }.into())
+            }
+        }
+
+        let (foot_desc, _, remain) = FormDescriptor::<O>::slice(remain)?;
+        foot_desc.expect_id(K_FORM_FOOT)?;
+        foot_desc.expect_version(1, 1)?;
+        ensure!(remain.is_empty(), "Unexpected data after the footer");
+
+        let mut vertices = Vec::new();
+        let mut triangles = Vec::new();
+        while !col_data.is_empty() {
+            let (desc, data, remain) = ChunkDescriptor::<O>::slice(col_data)?;
+            match desc.id {
+                K_CHUNK_VERT => {
+                    vertices = Cursor::new(data).read_type::<Vertices>(Endian::Little)?.vertices
+                }
+                K_CHUNK_TRIS => {
+                    triangles = Cursor::new(data).read_type::<Triangles>(Endian::Little)?.triangles
+                }
+                // Materials and the bounding tree aren't used yet
+                _ => {}
+            }
+            col_data = remain;
+        }
+        Ok(Self { kind: form_desc.id, vertices, triangles, _marker: PhantomData })
+    }
+}
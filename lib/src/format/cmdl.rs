@@ -1,11 +1,15 @@
-use std::{io::Cursor, marker::PhantomData};
+use std::{
+    io::{Cursor, Read, Seek, SeekFrom},
+    marker::PhantomData,
+};
 
-use anyhow::{bail, ensure, Result};
-use binrw::{binrw, BinReaderExt, Endian};
+use anyhow::{bail, ensure, Context, Result};
+use binrw::{binrw, BinRead, BinReaderExt, BinResult, Endian};
 use uuid::Uuid;
 use zerocopy::ByteOrder;
 
 use crate::{
+    error::RetroError,
     format::{
         rfrm::FormDescriptor, slice_chunks, CAABox, CColor4f, CMatrix4f, CStringFixed, CVector4i,
         FourCC,
@@ -106,7 +110,49 @@ pub struct SVertexBufferInfo {
     pub component_count: u32,
     #[br(count = component_count)]
     pub components: Vec<SVertexDataComponent>,
-    pub num_buffers: u8, // correct?
+    /// Number of GPU buffers the components are split across. This agrees with the
+    /// components' `buffer_index` values in known files (models where it doesn't are
+    /// rejected), but readers should use [`SVertexBufferInfo::buffer_strides`].
+    pub num_buffers: u8,
+}
+
+impl SVertexBufferInfo {
+    /// Number of GPU buffers referenced by the components.
+    pub fn buffer_count(&self) -> usize {
+        self.components.iter().map(|c| c.buffer_index as usize + 1).max().unwrap_or_default()
+    }
+
+    /// Stride of each GPU buffer referenced by the components, indexed by `buffer_index`.
+    pub fn buffer_strides(&self) -> Result<Vec<u32>> {
+        let mut strides = vec![0u32; self.buffer_count()];
+        for component in &self.components {
+            let stride = &mut strides[component.buffer_index as usize];
+            match *stride {
+                0 => *stride = component.stride,
+                existing if existing != component.stride => bail!(
+                    "Mismatched strides in buffer {}: {} != {}",
+                    component.buffer_index,
+                    component.stride,
+                    existing
+                ),
+                _ => {}
+            }
+        }
+        Ok(strides)
+    }
+
+    /// Number of consecutive UV sets (TexCoord0, TexCoord1, ...) the vertices provide.
+    pub fn tex_coord_count(&self) -> u32 {
+        [
+            EVertexComponent::TexCoord0,
+            EVertexComponent::TexCoord1,
+            EVertexComponent::TexCoord2,
+            EVertexComponent::TexCoord3,
+        ]
+        .iter()
+        .take_while(|&&tex_coord| self.components.iter().any(|c| c.component == tex_coord))
+        .count() as u32
+    }
 }
 
 #[binrw]
@@ -216,6 +262,26 @@ impl EVertexDataFormat {
         }
     }
 
+    pub fn is_half_float(self) -> bool {
+        matches!(
+            self,
+            EVertexDataFormat::R16Float
+                | EVertexDataFormat::Rg16Float
+                | EVertexDataFormat::Rgba16Float
+        )
+    }
+
+    /// Format to export the attribute as. glTF has no half float accessors,
+    /// so half floats are widened to f32.
+    pub fn widened(self) -> Self {
+        match self {
+            EVertexDataFormat::R16Float => EVertexDataFormat::R32Float,
+            EVertexDataFormat::Rg16Float => EVertexDataFormat::Rg32Float,
+            EVertexDataFormat::Rgba16Float => EVertexDataFormat::Rgba32Float,
+            format => format,
+        }
+    }
+
     pub fn normalized(self) -> bool {
         matches!(
             self,
@@ -236,6 +302,396 @@ impl EVertexDataFormat {
     }
 }
 
+/// Reads one vertex's four bone indices stored as `format`.
+pub fn bone_indices(data: &[u8], format: EVertexDataFormat) -> Result<[u32; 4]> {
+    let width = match format {
+        EVertexDataFormat::Rgba8Uint | EVertexDataFormat::Rgba8Sint => 1,
+        EVertexDataFormat::Rgba16Uint | EVertexDataFormat::Rgba16Sint => 2,
+        EVertexDataFormat::Rgba32Uint | EVertexDataFormat::Rgba32Sint => 4,
+        format => {
+            return Err(RetroError::UnsupportedFormat(format!("{format:?} bone indices")).into())
+        }
+    };
+    ensure!(data.len() >= width * 4, "Bone indices truncated");
+    let signed = matches!(
+        format,
+        EVertexDataFormat::Rgba8Sint
+            | EVertexDataFormat::Rgba16Sint
+            | EVertexDataFormat::Rgba32Sint
+    );
+    let mut out = [0u32; 4];
+    for (value, bytes) in out.iter_mut().zip(data.chunks_exact(width)) {
+        *value = bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u32);
+        ensure!(
+            !signed || bytes[width - 1] & 0x80 == 0,
+            "Negative bone index in {format:?} attribute"
+        );
+    }
+    Ok(out)
+}
+
+/// Chooses the format to export bone indices as. glTF only allows four unsigned
+/// bytes or shorts for joints, so the source width is kept where possible and
+/// wider indices are narrowed to 16 bits, failing if any index doesn't fit.
+///
+/// `offset` and `stride` locate the attribute within an interleaved vertex buffer.
+pub fn joints_format(
+    buf: &[u8],
+    offset: usize,
+    stride: usize,
+    format: EVertexDataFormat,
+) -> Result<EVertexDataFormat> {
+    let size = format.byte_size() as usize;
+    ensure!(offset + size <= stride, "Attribute at {offset} does not fit in stride {stride}");
+    let mut max_index = 0;
+    for vertex in buf.chunks_exact(stride) {
+        let indices = bone_indices(&vertex[offset..offset + size], format)?;
+        max_index = indices.into_iter().fold(max_index, u32::max);
+    }
+    ensure!(
+        max_index <= u16::MAX as u32,
+        "Bone index {max_index} exceeds the 16-bit range glTF joints support"
+    );
+    Ok(if size == 4 && max_index <= u8::MAX as u32 {
+        EVertexDataFormat::Rgba8Uint
+    } else {
+        EVertexDataFormat::Rgba16Uint
+    })
+}
+
+/// Rescales each vertex's bone weights to sum to 1.0, as required by glTF.
+///
+/// `offset` and `stride` locate the attribute within an interleaved vertex buffer.
+/// Vertices with all-zero weights are left untouched, as are weights in formats other than
+/// RGBA8/RGBA16 unorm and RGBA32 float. Returns the number of vertices adjusted.
+pub fn normalize_weights(
+    buf: &mut [u8],
+    offset: usize,
+    stride: usize,
+    format: EVertexDataFormat,
+) -> Result<usize> {
+    let normalize: fn(&mut [u8]) -> bool = match format {
+        EVertexDataFormat::Rgba8Unorm => |data| normalize_unorm_weights(data, 1),
+        EVertexDataFormat::Rgba16Unorm => |data| normalize_unorm_weights(data, 2),
+        EVertexDataFormat::Rgba32Float => normalize_float_weights,
+        _ => return Ok(0),
+    };
+    let size = format.byte_size() as usize;
+    ensure!(offset + size <= stride, "Attribute at {offset} does not fit in stride {stride}");
+    let mut adjusted = 0;
+    for vertex in buf.chunks_exact_mut(stride) {
+        if normalize(&mut vertex[offset..offset + size]) {
+            adjusted += 1;
+        }
+    }
+    Ok(adjusted)
+}
+
+fn normalize_unorm_weights(data: &mut [u8], width: usize) -> bool {
+    let max = (1u64 << (width * 8)) - 1;
+    let mut values = [0u64; 4];
+    for (value, bytes) in values.iter_mut().zip(data.chunks_exact(width)) {
+        *value = bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u64);
+    }
+    let sum: u64 = values.iter().sum();
+    if sum == 0 || sum == max {
+        return false;
+    }
+    for value in &mut values {
+        *value = (*value * max + sum / 2) / sum;
+    }
+    // Give any rounding error to the largest weight so the sum is exact
+    let total: u64 = values.iter().sum();
+    let largest = (0..4).max_by_key(|&i| values[i]).unwrap();
+    values[largest] = (values[largest] + max).saturating_sub(total).min(max);
+    for (value, bytes) in values.iter().zip(data.chunks_exact_mut(width)) {
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = (value >> (i * 8)) as u8;
+        }
+    }
+    true
+}
+
+fn normalize_float_weights(data: &mut [u8]) -> bool {
+    let mut values = [0f32; 4];
+    for (value, bytes) in values.iter_mut().zip(data.chunks_exact(4)) {
+        *value = f32::from_le_bytes(bytes.try_into().unwrap());
+    }
+    let sum: f32 = values.iter().sum();
+    if sum <= 0.0 || (sum - 1.0).abs() <= 1.0e-6 {
+        return false;
+    }
+    for (value, bytes) in values.iter().zip(data.chunks_exact_mut(4)) {
+        bytes.copy_from_slice(&(value / sum).to_le_bytes());
+    }
+    true
+}
+
+/// Reads a float attribute from an interleaved vertex buffer, filling missing components with 0.
+#[cfg(feature = "gltf")]
+pub fn read_float_attribute(
+    buf: &[u8],
+    offset: usize,
+    stride: usize,
+    format: EVertexDataFormat,
+) -> Result<Vec<[f32; 4]>> {
+    let components = match format {
+        EVertexDataFormat::R32Float => 1,
+        EVertexDataFormat::Rg32Float => 2,
+        EVertexDataFormat::Rgb32Float => 3,
+        EVertexDataFormat::Rgba32Float => 4,
+        format => {
+            return Err(RetroError::UnsupportedFormat(format!("{format:?} float attribute")).into())
+        }
+    };
+    ensure!(
+        offset + components * 4 <= stride,
+        "Attribute at {offset} does not fit in stride {stride}"
+    );
+    Ok(buf
+        .chunks_exact(stride)
+        .map(|vertex| {
+            let mut out = [0f32; 4];
+            for (value, bytes) in
+                out.iter_mut().zip(vertex[offset..].chunks_exact(4).take(components))
+            {
+                *value = f32::from_le_bytes(bytes.try_into().unwrap());
+            }
+            out
+        })
+        .collect())
+}
+
+/// Computes the per-axis minimum and maximum of the XYZ components of a float attribute,
+/// or `None` if the buffer has no vertices.
+#[cfg(feature = "gltf")]
+pub fn attribute_bounds(
+    buf: &[u8],
+    offset: usize,
+    stride: usize,
+    format: EVertexDataFormat,
+) -> Result<Option<([f32; 3], [f32; 3])>> {
+    let values = read_float_attribute(buf, offset, stride, format)?;
+    Ok(values.into_iter().fold(None, |bounds, [x, y, z, _]| {
+        let (min, max) = bounds.unwrap_or(([x, y, z], [x, y, z]));
+        Some(([min[0].min(x), min[1].min(y), min[2].min(z)], [
+            max[0].max(x),
+            max[1].max(y),
+            max[2].max(z),
+        ]))
+    }))
+}
+
+/// Converts half float attribute data to the f32 format [`EVertexDataFormat::widened`]
+/// exports it as.
+#[cfg(feature = "gltf")]
+pub fn widen_half_floats(data: &[u8], format: EVertexDataFormat) -> Result<Vec<u8>> {
+    ensure!(format.is_half_float(), "{format:?} is not a half float format");
+    ensure!(
+        data.len() % format.byte_size() as usize == 0,
+        "{} bytes is not a whole number of {format:?} elements",
+        data.len()
+    );
+    Ok(data
+        .chunks_exact(2)
+        .flat_map(|bytes| half::f16::from_le_bytes([bytes[0], bytes[1]]).to_f32().to_le_bytes())
+        .collect())
+}
+
+/// glTF accessor component type.
+#[cfg(feature = "gltf")]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AccessorComponentType {
+    I8,
+    U8,
+    I16,
+    U16,
+    U32,
+    F32,
+}
+
+/// glTF accessor element type.
+#[cfg(feature = "gltf")]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AccessorType {
+    Scalar,
+    Vec2,
+    Vec3,
+    Vec4,
+}
+
+/// The glTF accessor component and element type for an exported attribute.
+///
+/// Four component formats also carry narrower attributes, so texture coordinates are exported
+/// as VEC2 and positions and normals as VEC3 regardless of the format's width.
+#[cfg(feature = "gltf")]
+pub fn accessor_layout(
+    format: EVertexDataFormat,
+    component: EVertexComponent,
+) -> Result<(AccessorComponentType, AccessorType)> {
+    let component_type = match format {
+        EVertexDataFormat::R8Unorm
+        | EVertexDataFormat::R8Uint
+        | EVertexDataFormat::Rg8Unorm
+        | EVertexDataFormat::Rg8Uint
+        | EVertexDataFormat::Rgba8Unorm
+        | EVertexDataFormat::Rgba8Uint => AccessorComponentType::U8,
+        EVertexDataFormat::R8Snorm
+        | EVertexDataFormat::R8Sint
+        | EVertexDataFormat::Rg8Snorm
+        | EVertexDataFormat::Rg8Sint
+        | EVertexDataFormat::Rgba8Snorm
+        | EVertexDataFormat::Rgba8Sint => AccessorComponentType::I8,
+        EVertexDataFormat::R16Unorm
+        | EVertexDataFormat::R16Uint
+        | EVertexDataFormat::Rg16Unorm
+        | EVertexDataFormat::Rg16Uint
+        | EVertexDataFormat::Rgba16Unorm
+        | EVertexDataFormat::Rgba16Uint => AccessorComponentType::U16,
+        EVertexDataFormat::R16Snorm
+        | EVertexDataFormat::R16Sint
+        | EVertexDataFormat::Rg16Snorm
+        | EVertexDataFormat::Rg16Sint
+        | EVertexDataFormat::Rgba16Snorm
+        | EVertexDataFormat::Rgba16Sint => AccessorComponentType::I16,
+        EVertexDataFormat::R32Uint
+        | EVertexDataFormat::Rg32Uint
+        | EVertexDataFormat::Rgb32Uint
+        | EVertexDataFormat::Rgba32Uint => AccessorComponentType::U32,
+        EVertexDataFormat::R32Float
+        | EVertexDataFormat::Rg32Float
+        | EVertexDataFormat::Rgb32Float
+        | EVertexDataFormat::Rgba32Float => AccessorComponentType::F32,
+        format => bail!("Unsupported glTF component type {format:?}"),
+    };
+    let type_ = match format {
+        EVertexDataFormat::R8Unorm
+        | EVertexDataFormat::R8Uint
+        | EVertexDataFormat::R8Snorm
+        | EVertexDataFormat::R8Sint
+        | EVertexDataFormat::R16Unorm
+        | EVertexDataFormat::R16Uint
+        | EVertexDataFormat::R16Snorm
+        | EVertexDataFormat::R16Sint
+        | EVertexDataFormat::R32Uint
+        | EVertexDataFormat::R32Float => AccessorType::Scalar,
+        EVertexDataFormat::Rg8Unorm
+        | EVertexDataFormat::Rg8Uint
+        | EVertexDataFormat::Rg8Snorm
+        | EVertexDataFormat::Rg8Sint
+        | EVertexDataFormat::Rg16Unorm
+        | EVertexDataFormat::Rg16Uint
+        | EVertexDataFormat::Rg16Snorm
+        | EVertexDataFormat::Rg16Sint
+        | EVertexDataFormat::Rg32Uint
+        | EVertexDataFormat::Rg32Float => AccessorType::Vec2,
+        EVertexDataFormat::Rgb32Uint | EVertexDataFormat::Rgb32Float => AccessorType::Vec3,
+        EVertexDataFormat::Rgba8Unorm
+        | EVertexDataFormat::Rgba8Uint
+        | EVertexDataFormat::Rgba8Snorm
+        | EVertexDataFormat::Rgba8Sint
+        | EVertexDataFormat::Rgba16Unorm
+        | EVertexDataFormat::Rgba16Uint
+        | EVertexDataFormat::Rgba16Snorm
+        | EVertexDataFormat::Rgba16Sint
+        | EVertexDataFormat::Rgba32Uint
+        | EVertexDataFormat::Rgba32Float => match component {
+            EVertexComponent::TexCoord0
+            | EVertexComponent::TexCoord1
+            | EVertexComponent::TexCoord2
+            | EVertexComponent::TexCoord3 => AccessorType::Vec2,
+            EVertexComponent::Position | EVertexComponent::Normal => AccessorType::Vec3,
+            _ => AccessorType::Vec4,
+        },
+        format => bail!("Unsupported glTF accessor type {format:?}"),
+    };
+    Ok((component_type, type_))
+}
+
+/// Computes per-vertex tangents for a triangle list, for normal mapping.
+///
+/// Each triangle's texture space direction is accumulated onto its vertices, then
+/// orthogonalized against the vertex normal. The `w` component holds the bitangent sign,
+/// as glTF expects. Vertices without usable texture coordinates get an arbitrary tangent
+/// perpendicular to the normal.
+#[cfg(feature = "gltf")]
+pub fn generate_tangents(
+    positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    tex_coords: &[[f32; 2]],
+    indices: &[u32],
+) -> Result<Vec<[f32; 4]>> {
+    let count = positions.len();
+    ensure!(
+        normals.len() == count && tex_coords.len() == count,
+        "Attribute counts differ: {} positions, {} normals, {} texture coordinates",
+        count,
+        normals.len(),
+        tex_coords.len()
+    );
+    let mut tangents = vec![[0f32; 3]; count];
+    let mut bitangents = vec![[0f32; 3]; count];
+    for triangle in indices.chunks_exact(3) {
+        let [i0, i1, i2] = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+        ensure!(
+            i0 < count && i1 < count && i2 < count,
+            "Index out of range in triangle {triangle:?} ({count} vertices)"
+        );
+        let e1 = sub(positions[i1], positions[i0]);
+        let e2 = sub(positions[i2], positions[i0]);
+        let (du1, dv1) =
+            (tex_coords[i1][0] - tex_coords[i0][0], tex_coords[i1][1] - tex_coords[i0][1]);
+        let (du2, dv2) =
+            (tex_coords[i2][0] - tex_coords[i0][0], tex_coords[i2][1] - tex_coords[i0][1]);
+        let det = du1 * dv2 - du2 * dv1;
+        if det.abs() <= f32::EPSILON {
+            continue;
+        }
+        let r = 1.0 / det;
+        let t = scale(sub(scale(e1, dv2), scale(e2, dv1)), r);
+        let b = scale(sub(scale(e2, du1), scale(e1, du2)), r);
+        for i in [i0, i1, i2] {
+            tangents[i] = add(tangents[i], t);
+            bitangents[i] = add(bitangents[i], b);
+        }
+    }
+    Ok(normals
+        .iter()
+        .zip(tangents)
+        .zip(bitangents)
+        .map(|((&n, t), b)| {
+            let len = dot(n, n).sqrt();
+            let n = if len > f32::EPSILON { scale(n, 1.0 / len) } else { [0.0, 0.0, 1.0] };
+            // Gram-Schmidt orthogonalize
+            let mut t = sub(t, scale(n, dot(n, t)));
+            if dot(t, t) <= f32::EPSILON {
+                let axis = if n[0].abs() < 0.9 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] };
+                t = sub(axis, scale(n, dot(n, axis)));
+            }
+            let t = scale(t, 1.0 / dot(t, t).sqrt());
+            let w = if dot(cross(n, t), b) < 0.0 { -1.0 } else { 1.0 };
+            [t[0], t[1], t[2], w]
+        })
+        .collect())
+}
+
+#[cfg(feature = "gltf")]
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] { [a[0] + b[0], a[1] + b[1], a[2] + b[2]] }
+
+#[cfg(feature = "gltf")]
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] { [a[0] - b[0], a[1] - b[1], a[2] - b[2]] }
+
+#[cfg(feature = "gltf")]
+fn scale(a: [f32; 3], s: f32) -> [f32; 3] { [a[0] * s, a[1] * s, a[2] * s] }
+
+#[cfg(feature = "gltf")]
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 { a[0] * b[0] + a[1] * b[1] + a[2] * b[2] }
+
+#[cfg(feature = "gltf")]
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
 #[binrw]
 #[repr(u32)]
 #[brw(repr(u32))]
@@ -300,9 +756,9 @@ pub struct SMeshLoadInformation {
     pub mesh_count: u32,
     #[br(count = mesh_count)]
     pub meshes: Vec<CRenderMesh>,
-    #[br(count = (mesh_count + 3) / 4)]
+    #[br(count = mesh_count.div_ceil(4))]
     pub unk_data_1: Vec<u8>,
-    #[br(count = (mesh_count + 7) / 8)]
+    #[br(count = mesh_count.div_ceil(8))]
     pub unk_data_2: Vec<u8>,
     #[bw(try_calc = shorts.len().try_into())]
     pub short_count: u32,
@@ -318,6 +774,22 @@ pub struct SMeshLoadInformation {
     pub lod_rules: Vec<SRenderModelLODRule>,
 }
 
+impl SMeshLoadInformation {
+    /// Mesh indices visible at the given level of detail, sorted and deduplicated.
+    pub fn lod_meshes(&self, lod: usize) -> Option<Vec<usize>> {
+        let outer = self.lod_info.get(lod)?;
+        let mut out = Vec::new();
+        for inner in &outer.inner {
+            let start = inner.offset as usize;
+            let end = start + inner.count as usize;
+            out.extend(self.shorts.get(start..end)?.iter().map(|&idx| idx as usize));
+        }
+        out.sort_unstable();
+        out.dedup();
+        Some(out)
+    }
+}
+
 #[binrw]
 #[derive(Clone, Debug)]
 pub struct CRenderMesh {
@@ -338,10 +810,36 @@ pub struct CRenderMesh {
 #[binrw]
 #[derive(Clone, Debug)]
 pub struct SModelHeader {
+    /// Header chunk ID: HEAD (static), SKHD (skinned) or WDHD (world)
+    #[brw(ignore)]
+    pub kind: FourCC,
+    /// Unknown, observed as flags
     pub unk: u32,
+    /// Model-space bounds of all meshes
     pub bounds: CAABox,
-    // pub data_source_count: u32,
-    // pub data_sources: Vec<DataSourceLoader>,
+    /// Remaining header words, not yet identified.
+    /// Likely data source loaders; skinned headers also carry skeleton info here.
+    #[br(parse_with = read_header_words)]
+    pub unk_words: Vec<u32>,
+    /// Bytes after the last whole word, if the header isn't word-aligned.
+    #[br(parse_with = binrw::until_eof)]
+    pub unk_tail: Vec<u8>,
+}
+
+/// Reads the whole words up to the end of the header, leaving any partial word.
+fn read_header_words<R: Read + Seek>(reader: &mut R, endian: Endian, _: ()) -> BinResult<Vec<u32>> {
+    let pos = reader.stream_position()?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(pos))?;
+    (0..end.saturating_sub(pos) / 4).map(|_| u32::read_options(reader, endian, ())).collect()
+}
+
+impl SModelHeader {
+    /// Whether the header indicates a skinned model (SKHD).
+    pub fn is_skinned(&self) -> bool { self.kind == K_CHUNK_SKHD }
+
+    /// Whether the header indicates a world model (WDHD).
+    pub fn is_world(&self) -> bool { self.kind == K_CHUNK_WDHD }
 }
 
 #[binrw]
@@ -439,6 +937,11 @@ pub struct CMaterialTextureTokenData {
     pub usage: Option<STextureUsageInfo>,
 }
 
+/// Blend parameters of a layered texture.
+///
+/// Unconfirmed: `colors[n]` appears to tint layer `n` and the low three bits of `flags` to
+/// enable the matching layer. How the layers combine (presumably layer 1 and 2 blended over
+/// layer 0 by a vertex or mask channel) isn't known, and `unk` is unidentified.
 #[binrw]
 #[derive(Clone, Debug)]
 pub struct CLayeredTextureBaseData {
@@ -447,6 +950,8 @@ pub struct CLayeredTextureBaseData {
     pub flags: u8,
 }
 
+/// Up to three textures blended into a single material input (`BCRL`, `MTLL`, `NRML`).
+/// Unused layers have a nil texture ID.
 #[binrw]
 #[derive(Clone, Debug)]
 pub struct CLayeredTextureData {
@@ -454,9 +959,22 @@ pub struct CLayeredTextureData {
     pub textures: [CMaterialTextureTokenData; 3],
 }
 
+impl CLayeredTextureData {
+    /// The layers that have a texture, as `(layer, texture, blend color)`.
+    pub fn layers(&self) -> impl Iterator<Item = (usize, &CMaterialTextureTokenData, CColor4f)> {
+        self.textures
+            .iter()
+            .zip(self.base.colors)
+            .enumerate()
+            .filter(|(_, (texture, _))| !texture.id.is_nil())
+            .map(|(layer, (texture, color))| (layer, texture, color))
+    }
+}
+
 #[binrw]
 #[derive(Clone, Debug)]
 pub struct STextureUsageInfo {
+    /// Index of the UV set (TexCoord0-3) the texture samples.
     pub tex_coord: u32,
     pub filter: i32,
     pub wrap_x: i32,
@@ -464,6 +982,13 @@ pub struct STextureUsageInfo {
     pub wrap_z: i32,
 }
 
+impl STextureUsageInfo {
+    /// The UV set the texture samples, or `None` if it's not among the first `available` sets.
+    pub fn uv_set(&self, available: u32) -> Option<u32> {
+        (self.tex_coord < available).then_some(self.tex_coord)
+    }
+}
+
 #[binrw]
 #[repr(u32)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -801,12 +1326,19 @@ fn decompress_gpu_buffers(
     buffer_info: &[SModelBufferInfo],
 ) -> Result<Vec<Vec<u8>>> {
     let mut out = Vec::with_capacity(buffer_info.len());
-    for info in buffer_info {
+    for (idx, info) in buffer_info.iter().enumerate() {
         let read_info = &read_info[info.read_index as usize];
         let read_buffer =
             &file_data[read_info.offset as usize..(read_info.offset + read_info.size) as usize];
         let comp_buf = &read_buffer[info.offset as usize..(info.offset + info.size) as usize];
-        let (_, buf) = decompress_buffer(comp_buf, info.dest_size as u64)?;
+        let (_, buf) = decompress_buffer(comp_buf, info.dest_size as u64).with_context(|| {
+            format!(
+                "Failed to decompress GPU buffer {} (read buffer {}, file offset {:#X})",
+                idx,
+                info.read_index,
+                read_info.offset + info.offset
+            )
+        })?;
         out.push(buf.into_owned());
     }
     Ok(out)
@@ -827,26 +1359,33 @@ pub struct ModelData<O: ByteOrder> {
 impl<O> ModelData<O>
 where O: ByteOrder + 'static
 {
-    pub fn slice(data: &[u8], meta: &[u8]) -> Result<Self> {
+    /// Parses a model, skipping chunks and forms it doesn't recognize with a warning.
+    pub fn slice(data: &[u8], meta: &[u8]) -> Result<Self> { Self::slice_inner(data, meta, false) }
+
+    /// Parses a model, failing on any chunk or form it doesn't recognize.
+    pub fn slice_strict(data: &[u8], meta: &[u8]) -> Result<Self> {
+        Self::slice_inner(data, meta, true)
+    }
+
+    fn slice_inner(data: &[u8], meta: &[u8], strict: bool) -> Result<Self> {
         let (cmdl_desc, cmdl_data, _) = FormDescriptor::<O>::slice(data)?;
         match cmdl_desc.id {
             K_FORM_CMDL => {
-                ensure!(cmdl_desc.reader_version.get() == 114);
-                ensure!(cmdl_desc.writer_version.get() == 125);
+                cmdl_desc.expect_version(114, 125)?;
             }
             K_FORM_SMDL => {
-                ensure!(cmdl_desc.reader_version.get() == 127);
-                ensure!(cmdl_desc.writer_version.get() == 133);
+                cmdl_desc.expect_version(127, 133)?;
             }
             K_FORM_WMDL => {
-                ensure!(cmdl_desc.reader_version.get() == 118);
-                ensure!(cmdl_desc.writer_version.get() == 124);
+                cmdl_desc.expect_version(118, 124)?;
+            }
+            found => {
+                return Err(RetroError::UnexpectedFourCC { expected: K_FORM_CMDL, found }.into())
             }
-            id => bail!("Unknown FourCC {:?}", id),
         }
 
         let meta: SModelMetaData = Cursor::new(meta).read_type(Endian::Little)?;
-        let vtx_buffers = decompress_gpu_buffers(data, &meta.read_info, &meta.vtx_buffer_info)?;
+        let mut vtx_buffers = decompress_gpu_buffers(data, &meta.read_info, &meta.vtx_buffer_info)?;
         let idx_buffers = decompress_gpu_buffers(data, &meta.read_info, &meta.idx_buffer_info)?;
 
         let mut head: Option<SModelHeader> = None;
@@ -859,7 +1398,10 @@ where O: ByteOrder + 'static
             |desc, data| {
                 match desc.id {
                     K_CHUNK_HEAD | K_CHUNK_SKHD | K_CHUNK_WDHD => {
-                        head = Some(Cursor::new(data).read_type(Endian::Little)?)
+                        let mut header: SModelHeader =
+                            Cursor::new(data).read_type(Endian::Little)?;
+                        header.kind = desc.id;
+                        head = Some(header);
                     }
                     K_CHUNK_MTRL => mtrl = Some(Cursor::new(data).read_type(Endian::Little)?),
                     K_CHUNK_MESH => mesh = Some(Cursor::new(data).read_type(Endian::Little)?),
@@ -867,17 +1409,78 @@ where O: ByteOrder + 'static
                     K_CHUNK_IBUF => ibuf = Some(Cursor::new(data).read_type(Endian::Little)?),
                     // GPU data decompressed via META
                     K_CHUNK_GPU => {}
-                    id => bail!("Unknown {} chunk {id:?}", cmdl_desc.id),
+                    id if strict => bail!("Unknown {} chunk {id:?}", cmdl_desc.id),
+                    id => log::warn!(
+                        "Skipping unknown {} chunk {id:?} ({:#X} bytes)",
+                        cmdl_desc.id,
+                        data.len()
+                    ),
                 }
                 Ok(())
             },
-            |form, _data| bail!("Unknown {} form {:?}", cmdl_desc.id, form.id),
+            |form, data| {
+                ensure!(!strict, "Unknown {} form {:?}", cmdl_desc.id, form.id);
+                log::warn!(
+                    "Skipping unknown {} form {:?} ({:#X} bytes)",
+                    cmdl_desc.id,
+                    form.id,
+                    data.len()
+                );
+                Ok(())
+            },
         )?;
-        let Some(head) = head else { bail!("Failed to locate HEAD") };
-        let Some(mtrl) = mtrl else { bail!("Failed to locate MTRL") };
-        let Some(mesh) = mesh else { bail!("Failed to locate MESH") };
-        let Some(vbuf) = vbuf else { bail!("Failed to locate VBUF") };
-        let Some(ibuf) = ibuf else { bail!("Failed to locate IBUF") };
+        let head = head.ok_or(RetroError::MissingChunk(K_CHUNK_HEAD))?;
+        let mtrl = mtrl.ok_or(RetroError::MissingChunk(K_CHUNK_MTRL))?;
+        let mesh = mesh.ok_or(RetroError::MissingChunk(K_CHUNK_MESH))?;
+        let vbuf = vbuf.ok_or(RetroError::MissingChunk(K_CHUNK_VBUF))?;
+        let ibuf = ibuf.ok_or(RetroError::MissingChunk(K_CHUNK_IBUF))?;
+
+        // Ensure the GPU buffers split according to the component strides. Outside of
+        // strict mode, inconsistencies that don't affect reading are only warned about.
+        let mut cur_buf = 0usize;
+        for (idx, info) in vbuf.info.iter().enumerate() {
+            let strides = info.buffer_strides().with_context(|| format!("Vertex buffer {idx}"))?;
+            if strides.len() != info.num_buffers as usize {
+                let msg = format!(
+                    "Vertex buffer {idx}: components reference {} GPU buffers, but the header \
+                     declares {}",
+                    strides.len(),
+                    info.num_buffers
+                );
+                ensure!(!strict, msg);
+                log::warn!("{msg}");
+            }
+            for (buf_idx, stride) in strides.into_iter().enumerate() {
+                let Some(buf) = vtx_buffers.get_mut(cur_buf + buf_idx) else {
+                    bail!(
+                        "Vertex buffer {idx} references missing GPU buffer {}",
+                        cur_buf + buf_idx
+                    );
+                };
+                let expected = info.vertex_count as usize * stride as usize;
+                if buf.len() != expected {
+                    let msg = format!(
+                        "Vertex buffer {idx} GPU buffer {buf_idx}: size {:#X} != {} vertices * \
+                         stride {}",
+                        buf.len(),
+                        info.vertex_count,
+                        stride
+                    );
+                    ensure!(!strict && buf.len() > expected, msg);
+                    log::warn!("{msg}, ignoring the excess");
+                    buf.truncate(expected);
+                }
+            }
+            cur_buf += info.buffer_count();
+        }
+        if cur_buf != vtx_buffers.len() {
+            let msg = format!(
+                "Vertex buffers reference {cur_buf} GPU buffers, but the model contains {}",
+                vtx_buffers.len()
+            );
+            ensure!(!strict, msg);
+            log::warn!("{msg}");
+        }
 
         // log::debug!("HEAD: {head:#?}");
         // log::debug!("MTRL: {mtrl:#?}");
@@ -887,4 +1490,280 @@ where O: ByteOrder + 'static
 
         Ok(Self { head, mtrl, mesh, vbuf, ibuf, vtx_buffers, idx_buffers, _marker: PhantomData })
     }
+
+    /// Keeps only the meshes at the given indices, dropping any materials, vertex buffers
+    /// and index buffers no longer referenced, and remapping the remaining indices.
+    pub fn retain_meshes(&mut self, indices: &[usize]) -> Result<()> {
+        let mesh_count = self.mesh.meshes.len();
+        for &idx in indices {
+            ensure!(
+                idx < mesh_count,
+                "Mesh {idx} out of range (valid: 0-{})",
+                mesh_count.saturating_sub(1)
+            );
+        }
+        let mut keep = indices.to_vec();
+        keep.sort_unstable();
+        keep.dedup();
+
+        // Old index -> new index for each kept mesh, material and buffer
+        fn remap(used: impl Iterator<Item = usize>, len: usize) -> Vec<Option<usize>> {
+            let mut out = vec![None; len];
+            for idx in used {
+                out[idx] = Some(0);
+            }
+            let mut next = 0;
+            for slot in out.iter_mut().flatten() {
+                *slot = next;
+                next += 1;
+            }
+            out
+        }
+        let meshes = keep.iter().map(|&idx| &self.mesh.meshes[idx]).collect::<Vec<_>>();
+        let mesh_map = remap(keep.iter().copied(), mesh_count);
+        let mat_map = remap(
+            meshes
+                .iter()
+                .map(|m| m.material_idx as usize)
+                .filter(|&i| i < self.mtrl.materials.len()),
+            self.mtrl.materials.len(),
+        );
+        let vtx_map = remap(
+            meshes.iter().map(|m| m.vtx_buf_idx as usize).filter(|&i| i < self.vbuf.info.len()),
+            self.vbuf.info.len(),
+        );
+        let idx_map = remap(
+            meshes.iter().map(|m| m.idx_buf_idx as usize).filter(|&i| i < self.ibuf.info.len()),
+            self.ibuf.info.len(),
+        );
+        let meshes = meshes
+            .into_iter()
+            .map(|m| CRenderMesh {
+                material_idx: mat_map
+                    .get(m.material_idx as usize)
+                    .copied()
+                    .flatten()
+                    .map_or(m.material_idx, |i| i as u16),
+                vtx_buf_idx: vtx_map
+                    .get(m.vtx_buf_idx as usize)
+                    .copied()
+                    .flatten()
+                    .map_or(m.vtx_buf_idx, |i| i as u8),
+                idx_buf_idx: idx_map
+                    .get(m.idx_buf_idx as usize)
+                    .copied()
+                    .flatten()
+                    .map_or(m.idx_buf_idx, |i| i as u8),
+                ..m.clone()
+            })
+            .collect::<Vec<_>>();
+
+        // Vertex buffers are split across one or more GPU buffers each
+        let mut vtx_buffers = Vec::new();
+        let mut in_buffers = std::mem::take(&mut self.vtx_buffers).into_iter();
+        for (info, used) in self.vbuf.info.iter().zip(&vtx_map) {
+            let group = in_buffers.by_ref().take(info.buffer_count());
+            if used.is_some() {
+                vtx_buffers.extend(group);
+            } else {
+                group.for_each(drop);
+            }
+        }
+        self.vtx_buffers = vtx_buffers;
+        let mut vtx_used = vtx_map.iter();
+        self.vbuf.info.retain(|_| vtx_used.next().is_some_and(|u| u.is_some()));
+        let mut idx_used = idx_map.iter();
+        self.ibuf.info.retain(|_| idx_used.next().is_some_and(|u| u.is_some()));
+        let mut idx_used = idx_map.iter();
+        self.idx_buffers.retain(|_| idx_used.next().is_some_and(|u| u.is_some()));
+        let mut mat_used = mat_map.iter();
+        self.mtrl.materials.retain(|_| mat_used.next().is_some_and(|u| u.is_some()));
+
+        // Rebuild the LOD mesh lists against the new mesh indices
+        let mut shorts = Vec::with_capacity(self.mesh.shorts.len());
+        for outer in &mut self.mesh.lod_info {
+            for inner in &mut outer.inner {
+                let start = inner.offset as usize;
+                let end = start + inner.count as usize;
+                let old = self.mesh.shorts.get(start..end).unwrap_or_default();
+                inner.offset = shorts.len() as u32;
+                shorts.extend(
+                    old.iter()
+                        .filter_map(|&idx| mesh_map.get(idx as usize).copied().flatten())
+                        .map(|idx| idx as u16),
+                );
+                inner.count = shorts.len() as u32 - inner.offset;
+            }
+        }
+        self.mesh.shorts = shorts;
+        // Per-mesh flags of unknown meaning follow their meshes
+        self.mesh.unk_data_1 = remap_mesh_bits(&self.mesh.unk_data_1, 2, &keep);
+        self.mesh.unk_data_2 = remap_mesh_bits(&self.mesh.unk_data_2, 1, &keep);
+        self.mesh.meshes = meshes;
+        Ok(())
+    }
+
+    /// Merges meshes sharing a material and vertex layout into a single mesh each.
+    ///
+    /// The vertex buffers of each merged group are concatenated and its indices are
+    /// rebased into a new 32-bit index buffer. Meshes with nothing to merge with are
+    /// left as-is, and LOD mesh lists reference the merged meshes.
+    pub fn flatten_meshes(&mut self) -> Result<()> {
+        fn layout(
+            info: &SVertexBufferInfo,
+        ) -> Vec<(u32, u32, u32, EVertexDataFormat, EVertexComponent)> {
+            info.components
+                .iter()
+                .map(|c| (c.buffer_index, c.offset, c.stride, c.format, c.component))
+                .collect()
+        }
+
+        // Group mesh indices by material and vertex layout, in order of first use
+        let mut groups: Vec<(u16, usize, Vec<usize>)> = Vec::new();
+        for (idx, mesh) in self.mesh.meshes.iter().enumerate() {
+            let Some(info) = self.vbuf.info.get(mesh.vtx_buf_idx as usize) else {
+                bail!("Mesh {idx} references invalid vertex buffer {}", mesh.vtx_buf_idx);
+            };
+            let existing = groups.iter_mut().find(|(material_idx, vtx_buf_idx, _)| {
+                *material_idx == mesh.material_idx
+                    && layout(&self.vbuf.info[*vtx_buf_idx]) == layout(info)
+            });
+            match existing {
+                Some((_, _, indices)) => indices.push(idx),
+                None => groups.push((mesh.material_idx, mesh.vtx_buf_idx as usize, vec![idx])),
+            }
+        }
+
+        // First GPU buffer of each vertex buffer
+        let mut first_buffers = Vec::with_capacity(self.vbuf.info.len());
+        let mut cur_buf = 0usize;
+        for info in &self.vbuf.info {
+            first_buffers.push(cur_buf);
+            cur_buf += info.buffer_count();
+        }
+
+        let mut meshes = Vec::with_capacity(groups.len());
+        let mut mesh_map = vec![0usize; self.mesh.meshes.len()];
+        for (_, _, indices) in &groups {
+            for &idx in indices {
+                mesh_map[idx] = meshes.len();
+            }
+            let first = &self.mesh.meshes[indices[0]];
+            if indices.len() == 1 {
+                meshes.push(first.clone());
+                continue;
+            }
+
+            // Concatenate each distinct vertex buffer once, recording its base vertex
+            let mut sources: Vec<(u8, u32)> = Vec::new();
+            let mut info = self.vbuf.info[first.vtx_buf_idx as usize].clone();
+            let mut buffers = vec![Vec::new(); info.buffer_count()];
+            info.vertex_count = 0;
+            for &idx in indices {
+                let vtx_buf_idx = self.mesh.meshes[idx].vtx_buf_idx;
+                if sources.iter().any(|&(i, _)| i == vtx_buf_idx) {
+                    continue;
+                }
+                sources.push((vtx_buf_idx, info.vertex_count));
+                let first_buf = first_buffers[vtx_buf_idx as usize];
+                for (buf_idx, out) in buffers.iter_mut().enumerate() {
+                    out.extend_from_slice(&self.vtx_buffers[first_buf + buf_idx]);
+                }
+                info.vertex_count += self.vbuf.info[vtx_buf_idx as usize].vertex_count;
+            }
+
+            // Rebase every mesh's indices into a single 32-bit index buffer
+            let mut index_data = Vec::new();
+            for &idx in indices {
+                let mesh = &self.mesh.meshes[idx];
+                let base = sources.iter().find(|&&(i, _)| i == mesh.vtx_buf_idx).unwrap().1;
+                let (Some(&index_type), Some(buf)) = (
+                    self.ibuf.info.get(mesh.idx_buf_idx as usize),
+                    self.idx_buffers.get(mesh.idx_buf_idx as usize),
+                ) else {
+                    bail!("Mesh {idx} references invalid index buffer {}", mesh.idx_buf_idx);
+                };
+                let size = match index_type {
+                    EBufferType::U8 => 1,
+                    EBufferType::U16 => 2,
+                    EBufferType::U32 => 4,
+                };
+                let start = mesh.index_start as usize * size;
+                let end = start + mesh.index_count as usize * size;
+                let Some(data) = buf.get(start..end) else {
+                    bail!("Mesh {idx} index range {start:#X}..{end:#X} out of bounds");
+                };
+                for bytes in data.chunks_exact(size) {
+                    let index = match index_type {
+                        EBufferType::U8 => bytes[0] as u32,
+                        EBufferType::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+                        EBufferType::U32 => u32::from_le_bytes(bytes.try_into().unwrap()),
+                    };
+                    index_data.extend_from_slice(&(base + index).to_le_bytes());
+                }
+            }
+
+            ensure!(
+                self.vbuf.info.len() < u8::MAX as usize && self.ibuf.info.len() < u8::MAX as usize,
+                "Too many buffers to merge meshes"
+            );
+            meshes.push(CRenderMesh {
+                vtx_buf_idx: self.vbuf.info.len() as u8,
+                idx_buf_idx: self.ibuf.info.len() as u8,
+                index_start: 0,
+                index_count: (index_data.len() / 4) as u32,
+                ..first.clone()
+            });
+            first_buffers.push(self.vtx_buffers.len());
+            self.vbuf.info.push(info);
+            self.vtx_buffers.append(&mut buffers);
+            self.ibuf.info.push(EBufferType::U32);
+            self.idx_buffers.push(index_data);
+        }
+
+        // Point the LOD mesh lists at the merged meshes
+        let mut shorts = Vec::with_capacity(self.mesh.shorts.len());
+        for outer in &mut self.mesh.lod_info {
+            for inner in &mut outer.inner {
+                let start = inner.offset as usize;
+                let end = start + inner.count as usize;
+                let mut merged = self
+                    .mesh
+                    .shorts
+                    .get(start..end)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|&idx| mesh_map.get(idx as usize).map(|&idx| idx as u16))
+                    .collect::<Vec<_>>();
+                merged.sort_unstable();
+                merged.dedup();
+                inner.offset = shorts.len() as u32;
+                inner.count = merged.len() as u32;
+                shorts.append(&mut merged);
+            }
+        }
+        self.mesh.shorts = shorts;
+        self.mesh.meshes = meshes;
+        // Merged meshes take the flags of the first mesh in their group
+        let firsts = groups.iter().map(|(_, _, indices)| indices[0]).collect::<Vec<_>>();
+        self.mesh.unk_data_1 = remap_mesh_bits(&self.mesh.unk_data_1, 2, &firsts);
+        self.mesh.unk_data_2 = remap_mesh_bits(&self.mesh.unk_data_2, 1, &firsts);
+
+        // Drop the buffers that only the original meshes referenced
+        let count = self.mesh.meshes.len();
+        self.retain_meshes(&(0..count).collect::<Vec<_>>())
+    }
+}
+
+/// Copies the `bits`-wide per-mesh entries of the meshes in `keep` to their new indices.
+/// Entries are packed starting at the least significant bit of each byte.
+fn remap_mesh_bits(data: &[u8], bits: usize, keep: &[usize]) -> Vec<u8> {
+    let per_byte = 8 / bits;
+    let mask = (1u8 << bits) - 1;
+    let mut out = vec![0u8; keep.len().div_ceil(per_byte)];
+    for (new, &old) in keep.iter().enumerate() {
+        let value = data.get(old / per_byte).map_or(0, |b| (b >> (old % per_byte * bits)) & mask);
+        out[new / per_byte] |= value << (new % per_byte * bits);
+    }
+    out
 }
@@ -0,0 +1,14 @@
+use anyhow::Result;
+use zerocopy::ByteOrder;
+
+use crate::format::{rfrm::FormDescriptor, FourCC};
+
+// Video
+pub const K_FORM_FMV0: FourCC = FourCC(*b"FMV0");
+
+/// Returns the video stream (MP4) wrapped by an FMV0 form.
+pub fn slice_video<O: ByteOrder>(data: &[u8]) -> Result<&[u8]> {
+    let (desc, video, _) = FormDescriptor::<O>::slice(data)?;
+    desc.expect_id(K_FORM_FMV0)?;
+    Ok(video)
+}
@@ -1,15 +1,18 @@
 use std::io::Cursor;
 
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{ensure, Result};
 use binrw::{BinReaderExt, Endian};
 use uuid::Uuid;
 use zerocopy::ByteOrder;
 
-use crate::format::{
-    chunk::ChunkDescriptor,
-    pack::{AssetInfo, K_CHUNK_META},
-    rfrm::FormDescriptor,
-    FourCC,
+use crate::{
+    error::RetroError,
+    format::{
+        chunk::ChunkDescriptor,
+        pack::{AssetInfo, K_CHUNK_META},
+        rfrm::FormDescriptor,
+        FourCC,
+    },
 };
 
 // Custom footer for extracted files
@@ -24,9 +27,8 @@ pub fn locate_meta<O>(file_data: &[u8]) -> Result<&[u8]>
 where O: ByteOrder + 'static {
     let (_, _, remain) = FormDescriptor::<O>::slice(file_data)?;
     let (foot_desc, mut foot_data, remain) = FormDescriptor::<O>::slice(remain)?;
-    ensure!(foot_desc.id == K_FORM_FOOT);
-    ensure!(foot_desc.reader_version.get() == 1);
-    ensure!(foot_desc.writer_version.get() == 1);
+    foot_desc.expect_id(K_FORM_FOOT)?;
+    foot_desc.expect_version(1, 1)?;
     ensure!(remain.is_empty());
 
     while !foot_data.is_empty() {
@@ -36,16 +38,15 @@ where O: ByteOrder + 'static {
         }
         foot_data = remain;
     }
-    Err(anyhow!("Failed to locate META chunk"))
+    Err(RetroError::MissingChunk(K_CHUNK_META).into())
 }
 
 /// Locate the asset ID in extracted files
 pub fn locate_asset_id<O: ByteOrder>(file_data: &[u8]) -> Result<Uuid> {
     let (_, _, remain) = FormDescriptor::<O>::slice(file_data)?;
     let (foot_desc, mut foot_data, remain) = FormDescriptor::<O>::slice(remain)?;
-    ensure!(foot_desc.id == K_FORM_FOOT);
-    ensure!(foot_desc.reader_version.get() == 1);
-    ensure!(foot_desc.writer_version.get() == 1);
+    foot_desc.expect_id(K_FORM_FOOT)?;
+    foot_desc.expect_version(1, 1)?;
     ensure!(remain.is_empty());
 
     while !foot_data.is_empty() {
@@ -56,5 +57,24 @@ pub fn locate_asset_id<O: ByteOrder>(file_data: &[u8]) -> Result<Uuid> {
         }
         foot_data = remain;
     }
-    Err(anyhow!("Failed to locate AINF chunk"))
+    Err(RetroError::MissingChunk(K_CHUNK_AINF).into())
+}
+
+/// Locate the asset names in extracted files
+pub fn locate_names<O: ByteOrder>(file_data: &[u8]) -> Result<Vec<String>> {
+    let (_, _, remain) = FormDescriptor::<O>::slice(file_data)?;
+    let (foot_desc, mut foot_data, remain) = FormDescriptor::<O>::slice(remain)?;
+    foot_desc.expect_id(K_FORM_FOOT)?;
+    foot_desc.expect_version(1, 1)?;
+    ensure!(remain.is_empty());
+
+    let mut names = vec![];
+    while !foot_data.is_empty() {
+        let (desc, data, remain) = ChunkDescriptor::<O>::slice(foot_data)?;
+        if desc.id == K_CHUNK_NAME {
+            names.push(String::from_utf8(data.to_vec())?);
+        }
+        foot_data = remain;
+    }
+    Ok(names)
 }
@@ -4,11 +4,14 @@ use anyhow::{bail, ensure, Result};
 use binrw::{binrw, BinReaderExt, Endian};
 use zerocopy::ByteOrder;
 
-use crate::format::{
-    chunk::ChunkDescriptor,
-    rfrm::FormDescriptor,
-    txtr::{STextureMetaData, TextureData},
-    CVector3f, CVector3i, FourCC, TaggedVec,
+use crate::{
+    error::RetroError,
+    format::{
+        chunk::ChunkDescriptor,
+        rfrm::FormDescriptor,
+        txtr::{STextureMetaData, TextureData},
+        CVector3f, CVector3i, FourCC, TaggedVec,
+    },
 };
 
 // Texture
@@ -66,9 +69,8 @@ pub struct LightProbeData<O: ByteOrder> {
 impl<O: ByteOrder> LightProbeData<O> {
     pub fn slice(data: &[u8], meta: &[u8]) -> Result<Self> {
         let (ltpb_desc, mut ltpb_data, _) = FormDescriptor::<O>::slice(data)?;
-        ensure!(ltpb_desc.id == K_FORM_LTPB);
-        ensure!(ltpb_desc.reader_version.get() == 66);
-        ensure!(ltpb_desc.writer_version.get() == 73);
+        ltpb_desc.expect_id(K_FORM_LTPB)?;
+        ltpb_desc.expect_version(66, 73)?;
 
         let meta: SLightProbeMetaData = Cursor::new(meta).read_type(Endian::Little)?;
         ensure!(meta.meta_offsets.len() == meta.txtr_offsets.len());
@@ -85,7 +87,7 @@ impl<O: ByteOrder> LightProbeData<O> {
             }
             ltpb_data = remain;
         }
-        let Some(head) = head else { bail!("Failed to locate PHDR") };
+        let head = head.ok_or(RetroError::MissingChunk(K_CHUNK_PHDR))?;
 
         let mut textures = Vec::with_capacity(texture_count);
         let mut extra: Vec<LightProbeExtra> = Vec::with_capacity(texture_count);
@@ -1,6 +1,6 @@
 use std::{io::Cursor, marker::PhantomData};
 
-use anyhow::{bail, ensure, Result};
+use anyhow::{bail, Result};
 use binrw::{binrw, BinReaderExt, Endian};
 use binrw_derive::binread;
 use uuid::Uuid;
@@ -68,9 +68,8 @@ pub struct ModConData<O: ByteOrder> {
 impl<O: ByteOrder> ModConData<O> {
     pub fn slice(data: &[u8]) -> Result<Self> {
         let (mcon_desc, mut mcon_data, _) = FormDescriptor::<O>::slice(data)?;
-        ensure!(mcon_desc.id == K_FORM_MCON);
-        ensure!(mcon_desc.reader_version.get() == 41);
-        ensure!(mcon_desc.writer_version.get() == 44);
+        mcon_desc.expect_id(K_FORM_MCON)?;
+        mcon_desc.expect_version(41, 44)?;
 
         let mut data = Self { visual_data: None, _marker: PhantomData };
         while !mcon_data.is_empty() {
@@ -79,6 +78,7 @@ impl<O: ByteOrder> ModConData<O> {
             }
             let (chunk_desc, chunk_data, remain) = ChunkDescriptor::<O>::slice(mcon_data)?;
             match chunk_desc.id {
+                K_CHUNK_MCVD if chunk_desc.is_empty() => {}
                 K_CHUNK_MCVD => {
                     data.visual_data = Some(Cursor::new(chunk_data).read_type(Endian::Little)?)
                 }
@@ -1,24 +1,33 @@
 #![allow(clippy::useless_conversion)] // for TaggedVec / VecIndex
 
 pub mod chunk;
+pub mod clsn;
+#[cfg(feature = "models")]
 pub mod cmdl;
+pub mod fmv0;
 pub mod foot;
+#[cfg(feature = "textures")]
 pub mod ltpb;
 pub mod mcon;
 pub mod mtrl;
 pub mod pack;
+pub mod registry;
 pub mod rfrm;
 pub mod room;
+pub mod strp;
+#[cfg(feature = "textures")]
 pub mod txtr;
 
 use std::{
     fmt::{Debug, Display, Formatter, Write as FmtWrite},
     io::{Read, Seek, Write},
     marker::PhantomData,
+    ops::Mul,
+    str::FromStr,
     string::FromUtf8Error,
 };
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use binrw::{binrw, BinRead, BinReaderExt, BinResult, BinWrite, BinWriterExt, Endian};
 use uuid::Uuid;
 use zerocopy::{AsBytes, BigEndian, ByteOrder, FromBytes, FromZeroes, LittleEndian};
@@ -71,12 +80,30 @@ impl Debug for FourCC {
     }
 }
 
+impl FromStr for FourCC {
+    type Err = anyhow::Error;
+
+    /// Parses up to four ASCII characters, padding short codes with spaces (e.g. `GPU`).
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() || s.len() > 4 || !s.is_ascii() {
+            bail!("Invalid FourCC '{s}'");
+        }
+        let mut out = [b' '; 4];
+        out[..s.len()].copy_from_slice(s.as_bytes());
+        Ok(Self(out))
+    }
+}
+
 impl PartialEq<[u8; 4]> for FourCC {
     fn eq(&self, other: &[u8; 4]) -> bool { &self.0 == other }
 }
 
 #[inline]
-pub fn peek_four_cc(data: &[u8]) -> FourCC { FourCC(*array_ref!(data, 0, 4)) }
+/// Reads the FourCC at the start of `data`, or a zeroed FourCC if there are
+/// fewer than 4 bytes.
+pub fn peek_four_cc(data: &[u8]) -> FourCC {
+    data.get(..4).map(|id| FourCC(*array_ref!(id, 0, 4))).unwrap_or_default()
+}
 
 #[binrw]
 #[derive(Copy, Clone, Debug, Default)]
@@ -306,6 +333,76 @@ impl Default for CAABox {
         Self { min: CVector3f::splat(f32::MAX), max: CVector3f::splat(f32::MIN) }
     }
 }
+impl CAABox {
+    /// Whether the box contains no points, e.g. the default (inverted) box.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    #[inline]
+    pub fn contains(&self, point: CVector3f) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x)
+            && (self.min.y..=self.max.y).contains(&point.y)
+            && (self.min.z..=self.max.z).contains(&point.z)
+    }
+
+    /// Smallest box enclosing both boxes.
+    pub fn union(&self, other: &CAABox) -> CAABox {
+        CAABox {
+            min: CVector3f::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: CVector3f::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    #[inline]
+    pub fn center(&self) -> CVector3f {
+        CVector3f::new(
+            (self.min.x + self.max.x) * 0.5,
+            (self.min.y + self.max.y) * 0.5,
+            (self.min.z + self.max.z) * 0.5,
+        )
+    }
+
+    /// Half the size of the box along each axis.
+    #[inline]
+    pub fn extents(&self) -> CVector3f {
+        CVector3f::new(
+            (self.max.x - self.min.x) * 0.5,
+            (self.max.y - self.min.y) * 0.5,
+            (self.max.z - self.min.z) * 0.5,
+        )
+    }
+
+    /// Axis-aligned box enclosing all eight transformed corners.
+    pub fn transformed(&self, xf: &CTransform4f) -> CAABox {
+        if self.is_empty() {
+            return *self;
+        }
+        // Project the extents onto each output axis using the absolute rotation/scale
+        let center = xf.transform_point(self.center()).to_array();
+        let extents = self.extents().to_array();
+        let rows = xf.rows();
+        let mut min = [0.0; 3];
+        let mut max = [0.0; 3];
+        for axis in 0..3 {
+            let row = rows[axis];
+            let e =
+                row[0].abs() * extents[0] + row[1].abs() * extents[1] + row[2].abs() * extents[2];
+            min[axis] = center[axis] - e;
+            max[axis] = center[axis] + e;
+        }
+        CAABox { min: min.into(), max: max.into() }
+    }
+}
 
 #[binrw]
 #[derive(Copy, Clone, Debug)]
@@ -327,6 +424,126 @@ impl Default for CTransform4f {
 impl CTransform4f {
     #[inline]
     pub fn translation(&self) -> CVector3f { CVector3f::new(self.m0.w, self.m1.w, self.m2.w) }
+
+    #[inline]
+    fn rows(&self) -> [[f32; 4]; 3] { [self.m0.to_array(), self.m1.to_array(), self.m2.to_array()] }
+
+    #[inline]
+    fn from_rows(rows: [[f32; 4]; 3]) -> Self {
+        Self { m0: rows[0].into(), m1: rows[1].into(), m2: rows[2].into() }
+    }
+
+    /// The transform as a column-major 4x4 matrix, as glTF node matrices are laid out.
+    pub fn to_cols_array(&self) -> [f32; 16] {
+        let [a, b, c] = self.rows();
+        [a[0], b[0], c[0], 0.0, a[1], b[1], c[1], 0.0, a[2], b[2], c[2], 0.0, a[3], b[3], c[3], 1.0]
+    }
+
+    /// Applies the transform to a point, including translation.
+    pub fn transform_point(&self, point: CVector3f) -> CVector3f {
+        let [a, b, c] = self.rows();
+        let p = point;
+        CVector3f::new(
+            a[0] * p.x + a[1] * p.y + a[2] * p.z + a[3],
+            b[0] * p.x + b[1] * p.y + b[2] * p.z + b[3],
+            c[0] * p.x + c[1] * p.y + c[2] * p.z + c[3],
+        )
+    }
+
+    /// Inverts the affine transform. Returns `None` if the 3x3 part is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let [a, b, c] = self.rows();
+        // Cofactors of the 3x3 part
+        let c00 = b[1] * c[2] - b[2] * c[1];
+        let c01 = b[2] * c[0] - b[0] * c[2];
+        let c02 = b[0] * c[1] - b[1] * c[0];
+        let det = a[0] * c00 + a[1] * c01 + a[2] * c02;
+        if det == 0.0 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let r = [
+            [c00, a[2] * c[1] - a[1] * c[2], a[1] * b[2] - a[2] * b[1]],
+            [c01, a[0] * c[2] - a[2] * c[0], a[2] * b[0] - a[0] * b[2]],
+            [c02, a[1] * c[0] - a[0] * c[1], a[0] * b[1] - a[1] * b[0]],
+        ]
+        .map(|row| row.map(|v| v * inv_det));
+        let t = [a[3], b[3], c[3]];
+        Some(Self::from_rows(
+            r.map(|row| [row[0], row[1], row[2], -(row[0] * t[0] + row[1] * t[1] + row[2] * t[2])]),
+        ))
+    }
+
+    /// Splits the transform into translation, rotation and scale.
+    ///
+    /// Assumes the transform has no shear. A negative determinant is folded into the X scale.
+    pub fn decompose(&self) -> (CVector3f, mint::Quaternion<f32>, CVector3f) {
+        let m = self.rows();
+        let column = |i: usize| CVector3f::new(m[0][i], m[1][i], m[2][i]);
+        let length = |v: CVector3f| (v.x * v.x + v.y * v.y + v.z * v.z).sqrt();
+        let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        let scale =
+            CVector3f::new(length(column(0)) * det.signum(), length(column(1)), length(column(2)));
+        let s = scale.to_array();
+        let r = |row: usize, col: usize| if s[col] == 0.0 { 0.0 } else { m[row][col] / s[col] };
+
+        // Rotation matrix to quaternion
+        let trace = r(0, 0) + r(1, 1) + r(2, 2);
+        let (x, y, z, w) = if trace > 0.0 {
+            let k = 0.5 / (trace + 1.0).sqrt();
+            ((r(2, 1) - r(1, 2)) * k, (r(0, 2) - r(2, 0)) * k, (r(1, 0) - r(0, 1)) * k, 0.25 / k)
+        } else if r(0, 0) > r(1, 1) && r(0, 0) > r(2, 2) {
+            let k = 0.5 / (1.0 + r(0, 0) - r(1, 1) - r(2, 2)).sqrt();
+            (0.25 / k, (r(0, 1) + r(1, 0)) * k, (r(0, 2) + r(2, 0)) * k, (r(2, 1) - r(1, 2)) * k)
+        } else if r(1, 1) > r(2, 2) {
+            let k = 0.5 / (1.0 + r(1, 1) - r(0, 0) - r(2, 2)).sqrt();
+            ((r(0, 1) + r(1, 0)) * k, 0.25 / k, (r(1, 2) + r(2, 1)) * k, (r(0, 2) - r(2, 0)) * k)
+        } else {
+            let k = 0.5 / (1.0 + r(2, 2) - r(0, 0) - r(1, 1)).sqrt();
+            ((r(0, 2) + r(2, 0)) * k, (r(1, 2) + r(2, 1)) * k, 0.25 / k, (r(1, 0) - r(0, 1)) * k)
+        };
+        (self.translation(), mint::Quaternion { v: mint::Vector3 { x, y, z }, s: w }, scale)
+    }
+
+    /// Builds a transform from translation, rotation and scale, the inverse of [`decompose`].
+    ///
+    /// [`decompose`]: Self::decompose
+    pub fn from_trs(
+        translation: CVector3f,
+        rotation: mint::Quaternion<f32>,
+        scale: CVector3f,
+    ) -> Self {
+        let mint::Quaternion { v: mint::Vector3 { x, y, z }, s: w } = rotation;
+        let r = [
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w)],
+            [2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w)],
+            [2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y)],
+        ];
+        let s = scale.to_array();
+        let t = translation.to_array();
+        Self::from_rows(
+            [0, 1, 2].map(|row| [r[row][0] * s[0], r[row][1] * s[1], r[row][2] * s[2], t[row]]),
+        )
+    }
+}
+impl Mul for CTransform4f {
+    type Output = Self;
+
+    /// Composes two transforms; `a * b` applies `b` first.
+    fn mul(self, rhs: Self) -> Self {
+        let a = self.rows();
+        let b = rhs.rows();
+        Self::from_rows(a.map(|row| {
+            let mut out = [0.0; 4];
+            for (col, v) in out.iter_mut().enumerate() {
+                *v = row[0] * b[0][col] + row[1] * b[1][col] + row[2] * b[2][col];
+            }
+            out[3] += row[3];
+            out
+        }))
+    }
 }
 impl From<CTransform4f> for mint::RowMatrix3x4<f32> {
     fn from(value: CTransform4f) -> Self {
@@ -366,11 +583,32 @@ impl From<CTransform4f> for mint::ColumnMatrix4<f32> {
     }
 }
 
+/// Oriented bounding box: a box of half-size `extents` centered on the origin,
+/// placed by `xf`.
 #[binrw]
-#[derive(Clone, Debug)]
+#[derive(Copy, Clone, Debug)]
 pub struct COBBox {
-    xf: CTransform4f,
-    extents: CVector3f,
+    pub xf: CTransform4f,
+    pub extents: CVector3f,
+}
+
+impl COBBox {
+    /// Oriented box covering the same space as an axis-aligned box.
+    pub fn from_aabb(aabb: &CAABox) -> Self {
+        let center = aabb.center();
+        Self {
+            xf: CTransform4f::from_rows([[1.0, 0.0, 0.0, center.x], [0.0, 1.0, 0.0, center.y], [
+                0.0, 0.0, 1.0, center.z,
+            ]]),
+            extents: aabb.extents(),
+        }
+    }
+
+    /// Axis-aligned box enclosing the oriented box.
+    pub fn aabb(&self) -> CAABox {
+        let extents = self.extents.to_array();
+        CAABox { min: extents.map(|v| -v).into(), max: extents.into() }.transformed(&self.xf)
+    }
 }
 
 #[binrw]
@@ -3,7 +3,7 @@ use std::{
     marker::PhantomData,
 };
 
-use anyhow::{ensure, Result};
+use anyhow::Result;
 use binrw::{binrw, BinReaderExt, Endian};
 use flate2::bufread::ZlibDecoder;
 use zerocopy::ByteOrder;
@@ -32,9 +32,8 @@ pub struct MaterialData<O: ByteOrder> {
 impl<O: ByteOrder> MaterialData<O> {
     pub fn slice(data: &[u8], meta: &[u8]) -> Result<Self> {
         let (mtrl_desc, _, _) = FormDescriptor::<O>::slice(data)?;
-        ensure!(mtrl_desc.id == K_FORM_MTRL);
-        ensure!(mtrl_desc.reader_version.get() == 168);
-        ensure!(mtrl_desc.writer_version.get() == 168);
+        mtrl_desc.expect_id(K_FORM_MTRL)?;
+        mtrl_desc.expect_version(168, 168)?;
 
         let meta: SMaterialMetaData = Cursor::new(meta).read_type(Endian::Little)?;
         let mut reader = ZlibDecoder::new(
@@ -1,23 +1,32 @@
 use std::{
     borrow::Cow,
     collections::{hash_map, HashMap},
-    io::{Cursor, Seek, SeekFrom, Write},
+    fmt::{Display, Formatter},
+    io::{Cursor, Read, Seek, SeekFrom, Write},
     marker::PhantomData,
 };
 
 use anyhow::{anyhow, bail, ensure, Context, Result};
 use binrw::{binrw, BinReaderExt, BinWriterExt, Endian};
+use flate2::bufread::{GzDecoder, ZlibDecoder};
+use serde_derive::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
 use uuid::Uuid;
-use zerocopy::{AsBytes, ByteOrder, FromBytes, FromZeroes, U32, U64};
+use zerocopy::{AsBytes, ByteOrder, FromBytes, FromZeroes, LittleEndian, U32, U64};
 
 use crate::{
+    error::RetroError,
     format::{
         chunk::ChunkDescriptor,
         foot::{K_CHUNK_AINF, K_CHUNK_NAME, K_FORM_FOOT},
-        rfrm::FormDescriptor,
+        peek_four_cc,
+        rfrm::{FormDescriptor, K_CHUNK_RFRM},
         ByteOrderExt, ByteOrderUuid, FourCC,
     },
-    util::{compression::decompress_buffer, read::read_u32},
+    util::{
+        compression::{compress_buffer, decompress_buffer},
+        read::read_u32,
+    },
 };
 
 // Package file
@@ -103,6 +112,71 @@ pub struct AssetInfo {
     pub id: Uuid,
     pub compression_mode: u32,
     pub orig_offset: u64,
+    /// 1 if the stored data is prefixed with its compression mode, which uncompressed
+    /// (mode 0) data may or may not be. Absent in files extracted before this was recorded,
+    /// where only compressed data is taken to be prefixed.
+    #[br(try)]
+    pub mode_prefixed: Option<u32>,
+}
+
+impl AssetInfo {
+    /// Whether the stored data is prefixed with its compression mode.
+    pub fn is_mode_prefixed(&self) -> bool {
+        self.mode_prefixed.map_or(self.compression_mode != 0, |v| v != 0)
+    }
+}
+
+/// Outer compression layer of a package container
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EContainerCompression {
+    Zlib,
+    Gzip,
+}
+
+impl EContainerCompression {
+    /// Detects the compression scheme from the container's leading bytes.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x78, 0x01 | 0x5E | 0x9C | 0xDA, ..] => Some(Self::Zlib),
+            [0x1F, 0x8B, ..] => Some(Self::Gzip),
+            _ => None,
+        }
+    }
+}
+
+impl Display for EContainerCompression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EContainerCompression::Zlib => write!(f, "zlib"),
+            EContainerCompression::Gzip => write!(f, "gzip"),
+        }
+    }
+}
+
+/// Removes an outer compression layer from a package container, if present.
+/// Uncompressed packages are returned as-is.
+pub fn decompress_package(data: &[u8]) -> Result<Cow<[u8]>> {
+    if data.len() >= 4 && peek_four_cc(data) == K_CHUNK_RFRM {
+        return Ok(Cow::Borrowed(data));
+    }
+    let Some(compression) = EContainerCompression::detect(data) else {
+        bail!("Unknown package container (not RFRM and no known compression marker)");
+    };
+    let mut out = Vec::new();
+    match compression {
+        EContainerCompression::Zlib => {
+            ZlibDecoder::new(data).read_to_end(&mut out)?;
+        }
+        EContainerCompression::Gzip => {
+            GzDecoder::new(data).read_to_end(&mut out)?;
+        }
+    }
+    ensure!(
+        out.len() >= 4 && peek_four_cc(&out) == K_CHUNK_RFRM,
+        "Decompressed {compression} package container is not an RFRM"
+    );
+    log::info!("Decompressed {compression} package container ({:#X} bytes)", out.len());
+    Ok(Cow::Owned(out))
 }
 
 /// Combined asset representation
@@ -113,12 +187,148 @@ pub struct Asset<'a> {
     pub names: Vec<String>,
     // TODO lazy decompression?
     pub data: Cow<'a, [u8]>,
+    /// Stored payload (mode word and compressed data), written in place of `data` if present
+    pub compressed: Option<Cow<'a, [u8]>>,
     pub meta: Option<Cow<'a, [u8]>>,
     pub info: AssetInfo,
     pub version: u32,
     pub other_version: u32,
 }
 
+impl<'a> Asset<'a> {
+    /// Data as stored in the package.
+    pub fn stored_data(&self) -> &[u8] { self.compressed.as_deref().unwrap_or(&self.data) }
+
+    /// Compresses the asset data for storage with the given mode.
+    pub fn compress(&mut self, mode: u32) -> Result<()> {
+        self.compressed = Some(Cow::Owned(compress_buffer(&self.data, mode)?));
+        self.info.compression_mode = mode;
+        self.info.mode_prefixed = Some(1);
+        Ok(())
+    }
+
+    /// Parses an extracted asset: the asset form followed by the FOOT form written by
+    /// [`Asset::write_extracted`].
+    pub fn read_extracted(data: &'a [u8]) -> Result<Self> {
+        let (form, _, remain) = FormDescriptor::<LittleEndian>::slice(data)?;
+        let (foot, mut foot_data, _) = FormDescriptor::<LittleEndian>::slice(remain)?;
+        foot.expect_id(K_FORM_FOOT)?;
+        foot.expect_reader_version(1)?;
+        let mut info: Option<AssetInfo> = None;
+        let mut meta: Option<&[u8]> = None;
+        let mut names: Vec<String> = vec![];
+        while !foot_data.is_empty() {
+            let (chunk, chunk_data, remain) = ChunkDescriptor::<LittleEndian>::slice(foot_data)?;
+            match chunk.id {
+                K_CHUNK_AINF => {
+                    info = Some(Cursor::new(chunk_data).read_type(Endian::Little)?);
+                }
+                K_CHUNK_META => {
+                    meta = Some(chunk_data);
+                }
+                K_CHUNK_NAME => {
+                    names.push(String::from_utf8(chunk_data.to_vec())?);
+                }
+                _ => {}
+            }
+            foot_data = remain;
+        }
+        let info = info.ok_or(RetroError::MissingChunk(K_CHUNK_AINF))?;
+        Ok(Asset {
+            id: info.id,
+            kind: form.id,
+            names,
+            data: Cow::Borrowed(&data[..data.len() - remain.len()]),
+            compressed: None,
+            meta: meta.map(Cow::Borrowed),
+            info,
+            version: form.reader_version.get(),
+            other_version: form.writer_version.get(),
+        })
+    }
+
+    /// Describes the asset for a [`PackageManifest`], given its extracted file name.
+    pub fn manifest_entry(&self, file: String) -> ManifestEntry {
+        ManifestEntry {
+            file,
+            id: self.id,
+            kind: self.kind,
+            names: self.names.clone(),
+            compression_mode: self.info.compression_mode,
+            offset: self.info.orig_offset,
+            size: self.stored_data().len() as u64,
+            decompressed_size: self.data.len() as u64,
+        }
+    }
+
+    /// Writes the asset data followed by a FOOT form with its info, metadata and names.
+    pub fn write_extracted<W: Write + Seek>(&self, w: &mut W) -> Result<()> {
+        w.write_all(&self.data)?;
+        FormDescriptor::<LittleEndian> {
+            id: K_FORM_FOOT,
+            reader_version: U32::new(1),
+            writer_version: U32::new(1),
+            ..Default::default()
+        }
+        .write(w, |w| {
+            ChunkDescriptor::<LittleEndian> { id: K_CHUNK_AINF, ..Default::default() }
+                .write(w, |w| Ok(w.write_le(&self.info)?))?;
+            if let Some(meta) = &self.meta {
+                w.write_all(
+                    ChunkDescriptor::<LittleEndian> {
+                        id: K_CHUNK_META,
+                        size: U64::new(meta.len() as u64),
+                        ..Default::default()
+                    }
+                    .as_bytes(),
+                )?;
+                w.write_all(meta)?;
+            }
+            for name in &self.names {
+                let bytes = name.as_bytes();
+                w.write_all(
+                    ChunkDescriptor::<LittleEndian> {
+                        id: K_CHUNK_NAME,
+                        size: U64::new(bytes.len() as u64),
+                        ..Default::default()
+                    }
+                    .as_bytes(),
+                )?;
+                w.write_all(bytes)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Name of the manifest written alongside extracted assets
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Maps extracted files back to their package entries
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PackageManifest {
+    pub assets: Vec<ManifestEntry>,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Extracted file, relative to the manifest
+    pub file: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub id: Uuid,
+    #[serde_as(as = "DisplayFromStr")]
+    pub kind: FourCC,
+    #[serde(default)]
+    pub names: Vec<String>,
+    pub compression_mode: u32,
+    /// Offset of the stored data within the original package
+    pub offset: u64,
+    /// Stored size; differs from `decompressed_size` when compressed
+    pub size: u64,
+    pub decompressed_size: u64,
+}
+
 /// Combined package information
 #[derive(Debug, Clone, Default)]
 pub struct Package<'a, O: ByteOrder> {
@@ -134,6 +344,11 @@ pub struct SparsePackageEntry {
     pub names: Vec<String>,
     pub reader_version: u32,
     pub writer_version: u32,
+    /// Offset of the (possibly compressed) asset data within the package
+    pub offset: u64,
+    /// Stored size of the asset data; differs from `decompressed_size` when compressed
+    pub size: u64,
+    pub decompressed_size: u64,
 }
 
 impl<O> Package<'_, O>
@@ -141,11 +356,11 @@ where O: ByteOrderExt + 'static
 {
     pub fn read_header(data: &[u8]) -> Result<Vec<u8>> {
         let (pack, pack_data, _) = FormDescriptor::<O>::slice(data)?;
-        ensure!(pack.id == K_FORM_PACK);
-        ensure!(pack.reader_version.get() == 1);
+        pack.expect_id(K_FORM_PACK)?;
+        pack.expect_reader_version(1)?;
         let (tocc, tocc_data, _) = FormDescriptor::<O>::slice(pack_data)?;
-        ensure!(tocc.id == K_FORM_TOCC);
-        ensure!(tocc.reader_version.get() == 3);
+        tocc.expect_id(K_FORM_TOCC)?;
+        tocc.expect_reader_version(3)?;
 
         // Rewrite PACK with only TOCC chunk
         let mut out = Cursor::new(Vec::new());
@@ -160,11 +375,11 @@ where O: ByteOrderExt + 'static
 
     pub fn read_sparse(data: &[u8]) -> Result<Vec<SparsePackageEntry>> {
         let (pack, pack_data, _) = FormDescriptor::<O>::slice(data)?;
-        ensure!(pack.id == K_FORM_PACK);
-        ensure!(pack.reader_version.get() == 1);
+        pack.expect_id(K_FORM_PACK)?;
+        pack.expect_reader_version(1)?;
         let (tocc, mut tocc_data, _) = FormDescriptor::<O>::slice(pack_data)?;
-        ensure!(tocc.id == K_FORM_TOCC);
-        ensure!(tocc.reader_version.get() == 3);
+        tocc.expect_id(K_FORM_TOCC)?;
+        tocc.expect_reader_version(3)?;
         let mut adir: Option<&[AssetDirectoryEntry<O>]> = None;
         let mut strg: HashMap<Uuid, Vec<String>> = HashMap::new();
         while !tocc_data.is_empty() {
@@ -221,6 +436,9 @@ where O: ByteOrderExt + 'static
                     names: strg.get(&asset_id).cloned().unwrap_or_default(),
                     reader_version: asset_entry.version.get(),
                     writer_version: asset_entry.other_version.get(),
+                    offset: asset_entry.offset.get(),
+                    size: asset_entry.size.get(),
+                    decompressed_size: asset_entry.decompressed_size.get(),
                 })
             })
             .collect();
@@ -229,11 +447,11 @@ where O: ByteOrderExt + 'static
 
     pub fn read_asset(data: &[u8], id: Uuid) -> Result<Vec<u8>> {
         let (pack, pack_data, _) = FormDescriptor::<O>::slice(data)?;
-        ensure!(pack.id == K_FORM_PACK);
-        ensure!(pack.reader_version.get() == 1);
+        pack.expect_id(K_FORM_PACK)?;
+        pack.expect_reader_version(1)?;
         let (tocc, mut tocc_data, _) = FormDescriptor::<O>::slice(pack_data)?;
-        ensure!(tocc.id == K_FORM_TOCC);
-        ensure!(tocc.reader_version.get() == 3);
+        tocc.expect_id(K_FORM_TOCC)?;
+        tocc.expect_reader_version(3)?;
 
         let mut asset: Option<AssetDirectoryEntry<O>> = None;
         let mut meta: Option<&[u8]> = None;
@@ -297,7 +515,16 @@ where O: ByteOrderExt + 'static
         let compressed_data =
             &data[asset.offset.get() as usize..(asset.offset.get() + asset.size.get()) as usize];
         let (compression_mode, data) = if asset.size != asset.decompressed_size {
-            decompress_buffer(compressed_data, asset.decompressed_size.get())?
+            decompress_buffer(compressed_data, asset.decompressed_size.get()).with_context(
+                || {
+                    format!(
+                        "Failed to decompress asset {} {} at offset {:#X}",
+                        asset.asset_type,
+                        id,
+                        asset.offset.get()
+                    )
+                },
+            )?
         } else {
             (0, Cow::Borrowed(compressed_data))
         };
@@ -324,7 +551,12 @@ where O: ByteOrderExt + 'static
         }
         .write(&mut w, |w| {
             ChunkDescriptor::<O> { id: K_CHUNK_AINF, ..Default::default() }.write(w, |w| {
-                w.write_le(&AssetInfo { id, compression_mode, orig_offset: asset.offset.get() })?;
+                w.write_le(&AssetInfo {
+                    id,
+                    compression_mode,
+                    orig_offset: asset.offset.get(),
+                    mode_prefixed: Some((asset.size != asset.decompressed_size).into()),
+                })?;
                 Ok(())
             })?;
             if let Some(meta) = meta {
@@ -358,12 +590,12 @@ where O: ByteOrderExt + 'static
 
     pub fn read_full<'a>(data: &'a [u8], e: Endian) -> Result<Package<'a, O>> {
         let (pack, pack_data, _) = FormDescriptor::<O>::slice(data)?;
-        ensure!(pack.id == K_FORM_PACK);
-        ensure!(pack.reader_version.get() == 1);
+        pack.expect_id(K_FORM_PACK)?;
+        pack.expect_reader_version(1)?;
         log::debug!("PACK: {:?}", pack);
         let (tocc, mut tocc_data, _) = FormDescriptor::<O>::slice(pack_data)?;
-        ensure!(tocc.id == K_FORM_TOCC);
-        ensure!(tocc.reader_version.get() == 3);
+        tocc.expect_id(K_FORM_TOCC)?;
+        tocc.expect_reader_version(3)?;
         log::debug!("TOCC: {:?}", tocc);
         let mut adir: Option<&[AssetDirectoryEntry<O>]> = None;
         let mut meta: HashMap<Uuid, &[u8]> = HashMap::new();
@@ -428,7 +660,15 @@ where O: ByteOrderExt + 'static
             let compressed_data = &data[asset_entry.offset.get() as usize
                 ..(asset_entry.offset.get() + asset_entry.size.get()) as usize];
             let (compression_mode, data) = if asset_entry.size != asset_entry.decompressed_size {
-                decompress_buffer(compressed_data, asset_entry.decompressed_size.get())?
+                decompress_buffer(compressed_data, asset_entry.decompressed_size.get())
+                    .with_context(|| {
+                        format!(
+                            "Failed to decompress asset {} {} at offset {:#X}",
+                            asset_entry.asset_type,
+                            asset_entry.asset_id.get(),
+                            asset_entry.offset.get()
+                        )
+                    })?
             } else {
                 (0, Cow::Borrowed(compressed_data))
             };
@@ -448,11 +688,14 @@ where O: ByteOrderExt + 'static
                 kind: asset_entry.asset_type,
                 names: strg.get(&asset_id).cloned().unwrap_or_default(),
                 data,
+                compressed: (asset_entry.size != asset_entry.decompressed_size)
+                    .then_some(Cow::Borrowed(compressed_data)),
                 meta: meta.get(&asset_id).map(|data| Cow::Borrowed(*data)),
                 info: AssetInfo {
                     id: asset_id,
                     compression_mode,
                     orig_offset: asset_entry.offset.get(),
+                    mode_prefixed: Some((asset_entry.size != asset_entry.decompressed_size).into()),
                 },
                 version: asset_entry.version.get(),
                 other_version: asset_entry.other_version.get(),
@@ -461,6 +704,55 @@ where O: ByteOrderExt + 'static
         Ok(package)
     }
 
+    /// Rebuilds a package from extracted files and their manifest entries. Assets that were
+    /// stored compressed are recompressed with their original mode.
+    pub fn from_extracted<'a, 'm, I>(files: I) -> Result<Package<'a, O>>
+    where I: IntoIterator<Item = (&'m ManifestEntry, &'a [u8])> {
+        let mut package = Package::<'a, O> { assets: Vec::new(), _marker: PhantomData };
+        for (entry, data) in files {
+            let mut asset = Asset::read_extracted(data)
+                .with_context(|| format!("Failed to read extracted asset '{}'", entry.file))?;
+            ensure!(
+                asset.id == entry.id && asset.kind == entry.kind,
+                "'{}' contains {} {}, but the manifest expects {} {}",
+                entry.file,
+                asset.kind,
+                asset.id,
+                entry.kind,
+                entry.id
+            );
+            asset.names = entry.names.clone();
+            asset.info.orig_offset = entry.offset;
+            if entry.size != entry.decompressed_size {
+                asset
+                    .compress(entry.compression_mode)
+                    .with_context(|| format!("Failed to compress '{}'", entry.file))?;
+            }
+            package.assets.push(asset);
+        }
+        package.assets.sort_by_key(|a| a.id);
+        Ok(package)
+    }
+
+    /// Rebuilds a package from extracted files without a manifest, given each file's name
+    /// and data. The asset info in each file's footer provides the original offset and
+    /// compression mode, so the result matches [`Package::from_extracted`].
+    pub fn from_extracted_files<'a, 'n, I>(files: I) -> Result<Package<'a, O>>
+    where I: IntoIterator<Item = (&'n str, &'a [u8])> {
+        let mut package = Package::<'a, O> { assets: Vec::new(), _marker: PhantomData };
+        for (file, data) in files {
+            let mut asset = Asset::read_extracted(data)
+                .with_context(|| format!("Failed to read extracted asset '{file}'"))?;
+            let mode = asset.info.compression_mode;
+            if asset.info.is_mode_prefixed() {
+                asset.compress(mode).with_context(|| format!("Failed to compress '{file}'"))?;
+            }
+            package.assets.push(asset);
+        }
+        package.assets.sort_by_key(|a| a.id);
+        Ok(package)
+    }
+
     pub fn write<W: Write + Seek>(&self, w: &mut W) -> Result<()> {
         let mut asset_directory = AssetDirectory::default();
         let mut metadata = MetadataTable::default();
@@ -477,7 +769,7 @@ where O: ByteOrderExt + 'static
                 other_version: U32::new(asset.other_version),
                 offset: U64::new(0),
                 decompressed_size: U64::new(asset.data.len() as u64),
-                size: U64::new(asset.data.len() as u64),
+                size: U64::new(asset.stored_data().len() as u64),
             });
             if asset.meta.is_some() {
                 metadata.entries.push(MetadataTableEntry { asset_id: asset.id, offset: 0 });
@@ -545,7 +837,7 @@ where O: ByteOrderExt + 'static
             entries.sort_by_key(|(a, _)| a.info.orig_offset);
             for (asset, entry) in entries {
                 entry.offset.set(w.stream_position()?);
-                w.write_all(&asset.data)?;
+                w.write_all(asset.stored_data())?;
             }
             Ok(())
         })?;
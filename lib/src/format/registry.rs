@@ -0,0 +1,174 @@
+//! Form types known to the library, keyed by form ID.
+//!
+//! Frontends use this to name and identify assets, rather than matching on form
+//! IDs themselves.
+
+use anyhow::Result;
+use zerocopy::LittleEndian;
+
+#[cfg(feature = "models")]
+use crate::format::cmdl::{ModelData, K_FORM_CMDL, K_FORM_SMDL, K_FORM_WMDL};
+#[cfg(feature = "textures")]
+use crate::format::{
+    ltpb::{LightProbeData, K_FORM_LTPB},
+    txtr::{TextureData, K_FORM_TXTR},
+};
+use crate::{
+    error::RetroError,
+    format::{
+        clsn::{CollisionData, K_FORM_CLSN, K_FORM_DCLN},
+        fmv0::{slice_video, K_FORM_FMV0},
+        foot::locate_meta,
+        mcon::{ModConData, K_FORM_MCON},
+        mtrl::{MaterialData, K_FORM_MTRL},
+        pack::{decompress_package, Package, K_FORM_PACK},
+        rfrm::FormDescriptor,
+        room::{RoomData, K_FORM_ROOM},
+        FourCC,
+    },
+};
+
+/// Viewer tab a frontend opens for a form.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Viewer {
+    Texture,
+    Model,
+    ModelContainer,
+    LightProbe,
+    Room,
+}
+
+/// A form type and how to parse it.
+#[derive(Copy, Clone)]
+pub struct FormatInfo {
+    pub id: FourCC,
+    /// Human readable name.
+    pub name: &'static str,
+    /// Parses a complete file, including its footer. `None` if the form is only
+    /// recognized, not parsed.
+    pub parse: Option<fn(&[u8]) -> Result<()>>,
+    /// retrotool commands specific to the form. `rfrm extract` and `hash` accept any form.
+    pub commands: &'static [&'static str],
+    /// Viewer for the form, if it has one.
+    pub viewer: Option<Viewer>,
+}
+
+impl FormatInfo {
+    const fn new(id: FourCC, name: &'static str, parse: Option<fn(&[u8]) -> Result<()>>) -> Self {
+        Self { id, name, parse, commands: &[], viewer: None }
+    }
+
+    const fn commands(self, commands: &'static [&'static str]) -> Self { Self { commands, ..self } }
+
+    const fn viewer(self, viewer: Viewer) -> Self { Self { viewer: Some(viewer), ..self } }
+}
+
+/// Every known form type.
+pub static FORMATS: &[FormatInfo] = &[
+    #[cfg(feature = "textures")]
+    FormatInfo::new(K_FORM_TXTR, "Texture", Some(parse_txtr))
+        .commands(&["txtr convert", "txtr info"])
+        .viewer(Viewer::Texture),
+    #[cfg(feature = "models")]
+    FormatInfo::new(K_FORM_CMDL, "Model", Some(parse_model))
+        .commands(MODEL_COMMANDS)
+        .viewer(Viewer::Model),
+    #[cfg(feature = "models")]
+    FormatInfo::new(K_FORM_SMDL, "Skinned model", Some(parse_model))
+        .commands(MODEL_COMMANDS)
+        .viewer(Viewer::Model),
+    #[cfg(feature = "models")]
+    FormatInfo::new(K_FORM_WMDL, "World model", Some(parse_model))
+        .commands(MODEL_COMMANDS)
+        .viewer(Viewer::Model),
+    FormatInfo::new(K_FORM_MTRL, "Material", Some(parse_mtrl)),
+    #[cfg(feature = "textures")]
+    FormatInfo::new(K_FORM_LTPB, "Light probe", Some(parse_ltpb))
+        .commands(&["ltpb preview"])
+        .viewer(Viewer::LightProbe),
+    FormatInfo::new(K_FORM_MCON, "Model container", Some(parse_mcon))
+        .viewer(Viewer::ModelContainer),
+    FormatInfo::new(K_FORM_ROOM, "Room", Some(parse_room))
+        .commands(&["room header", "room lightmap", "room docks", "room generated", "room graph"])
+        .viewer(Viewer::Room),
+    FormatInfo::new(K_FORM_PACK, "Package", Some(parse_pack)).commands(&[
+        "pak extract",
+        "pak repack",
+        "pak diff",
+    ]),
+    FormatInfo::new(K_FORM_FMV0, "Movie", Some(parse_fmv0))
+        .commands(&["fmv0 extract", "fmv0 replace"]),
+    FormatInfo::new(K_FORM_CLSN, "Collision", Some(parse_collision)).commands(COLLISION_COMMANDS),
+    FormatInfo::new(K_FORM_DCLN, "Dynamic collision", Some(parse_collision))
+        .commands(COLLISION_COMMANDS),
+];
+
+#[cfg(feature = "models")]
+const MODEL_COMMANDS: &[&str] = &["cmdl convert", "cmdl info"];
+const COLLISION_COMMANDS: &[&str] = &["collision convert"];
+
+/// Looks up a form type by ID.
+pub fn find_format(id: FourCC) -> Option<&'static FormatInfo> {
+    FORMATS.iter().find(|info| info.id == id)
+}
+
+/// Identifies a file by its form ID and parses it.
+pub fn parse_form(data: &[u8]) -> Result<&'static FormatInfo> {
+    let (desc, _, _) = FormDescriptor::<LittleEndian>::slice(data)?;
+    let Some(info) = find_format(desc.id) else {
+        return Err(RetroError::UnsupportedFormat(desc.id.to_string()).into());
+    };
+    let Some(parse) = info.parse else {
+        return Err(RetroError::UnsupportedFormat(format!("{} ({})", info.id, info.name)).into());
+    };
+    parse(data)?;
+    Ok(info)
+}
+
+#[cfg(feature = "textures")]
+fn parse_txtr(data: &[u8]) -> Result<()> {
+    TextureData::<LittleEndian>::slice(data, locate_meta::<LittleEndian>(data)?)?;
+    Ok(())
+}
+
+#[cfg(feature = "models")]
+fn parse_model(data: &[u8]) -> Result<()> {
+    ModelData::<LittleEndian>::slice(data, locate_meta::<LittleEndian>(data)?)?;
+    Ok(())
+}
+
+fn parse_mtrl(data: &[u8]) -> Result<()> {
+    MaterialData::<LittleEndian>::slice(data, locate_meta::<LittleEndian>(data)?)?;
+    Ok(())
+}
+
+#[cfg(feature = "textures")]
+fn parse_ltpb(data: &[u8]) -> Result<()> {
+    LightProbeData::<LittleEndian>::slice(data, locate_meta::<LittleEndian>(data)?)?;
+    Ok(())
+}
+
+fn parse_mcon(data: &[u8]) -> Result<()> {
+    ModConData::<LittleEndian>::slice(data)?;
+    Ok(())
+}
+
+fn parse_room(data: &[u8]) -> Result<()> {
+    RoomData::<LittleEndian>::slice(data)?;
+    Ok(())
+}
+
+fn parse_fmv0(data: &[u8]) -> Result<()> {
+    slice_video::<LittleEndian>(data)?;
+    Ok(())
+}
+
+fn parse_collision(data: &[u8]) -> Result<()> {
+    CollisionData::<LittleEndian>::slice(data)?;
+    Ok(())
+}
+
+fn parse_pack(data: &[u8]) -> Result<()> {
+    Package::<LittleEndian>::read_header(&decompress_package(data)?)?;
+    Ok(())
+}
@@ -3,10 +3,13 @@ use std::{
     mem::size_of,
 };
 
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{Context, Result};
 use zerocopy::{AsBytes, ByteOrder, FromBytes, FromZeroes, U32, U64};
 
-use crate::format::{chunk::ChunkDescriptor, peek_four_cc, FourCC};
+use crate::{
+    error::RetroError,
+    format::{chunk::ChunkDescriptor, peek_four_cc, FourCC},
+};
 
 // Resource format
 pub const K_CHUNK_RFRM: FourCC = FourCC(*b"RFRM");
@@ -36,13 +39,68 @@ impl<O: ByteOrder> Default for FormDescriptor<O> {
 }
 
 impl<O: ByteOrder> FormDescriptor<O> {
+    /// Splits off a form, returning its header, payload and the remaining data.
+    ///
+    /// A form with a zero-length payload is valid and yields an empty slice. Empty
+    /// input returns [`RetroError::Empty`].
     pub fn slice(data: &[u8]) -> Result<(&Self, &[u8], &[u8])> {
-        let header = Self::ref_from_prefix(data).ok_or_else(|| anyhow!("Invalid RFRM header"))?;
-        ensure!(header.magic == K_CHUNK_RFRM);
+        if data.is_empty() {
+            return Err(RetroError::Empty.into());
+        }
+        let header = Self::ref_from_prefix(data)
+            .ok_or(RetroError::Truncated { at: data.len(), expected: size_of::<Self>() })?;
+        if header.magic != K_CHUNK_RFRM {
+            return Err(RetroError::UnexpectedFourCC {
+                expected: K_CHUNK_RFRM,
+                found: header.magic,
+            }
+            .into());
+        }
         let start = size_of::<Self>();
-        let slice = &data[start..(start + header.size.get() as usize)];
-        let remain = &data[(start + header.size.get() as usize)..];
-        Ok((header, slice, remain))
+        let end = start.saturating_add(header.size.get() as usize);
+        if end > data.len() {
+            return Err(RetroError::Truncated { at: data.len(), expected: end }.into());
+        }
+        Ok((header, &data[start..end], &data[end..]))
+    }
+
+    /// Whether the form has no payload.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.size.get() == 0 }
+
+    /// Checks the form ID, returning [`RetroError::UnexpectedFourCC`] on mismatch.
+    pub fn expect_id(&self, id: FourCC) -> Result<()> {
+        if self.id != id {
+            return Err(RetroError::UnexpectedFourCC { expected: id, found: self.id }.into());
+        }
+        Ok(())
+    }
+
+    /// Checks the form versions, returning [`RetroError::UnsupportedVersion`] on mismatch.
+    pub fn expect_version(&self, reader_version: u32, writer_version: u32) -> Result<()> {
+        if self.reader_version.get() != reader_version
+            || self.writer_version.get() != writer_version
+        {
+            return Err(self.unsupported_version().into());
+        }
+        Ok(())
+    }
+
+    /// Checks only the reader version, for forms with varying writer versions.
+    pub fn expect_reader_version(&self, reader_version: u32) -> Result<()> {
+        if self.reader_version.get() != reader_version {
+            return Err(self.unsupported_version().into());
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn unsupported_version(&self) -> RetroError {
+        RetroError::UnsupportedVersion {
+            id: self.id,
+            reader_version: self.reader_version.get(),
+            writer_version: self.writer_version.get(),
+        }
     }
 
     pub fn write<W, CB>(&self, w: &mut W, mut cb: CB) -> Result<()>
@@ -71,6 +129,59 @@ impl<O: ByteOrder> FormDescriptor<O> {
     }
 }
 
+/// A form or chunk header visited by [`walk_chunks`].
+#[derive(Debug)]
+pub enum ChunkHeader<'a, O: ByteOrder> {
+    Form(&'a FormDescriptor<O>),
+    Chunk(&'a ChunkDescriptor<O>),
+}
+
+impl<O: ByteOrder> ChunkHeader<'_, O> {
+    /// The form or chunk ID.
+    pub fn id(&self) -> FourCC {
+        match self {
+            ChunkHeader::Form(desc) => desc.id,
+            ChunkHeader::Chunk(desc) => desc.id,
+        }
+    }
+
+    #[inline]
+    pub fn is_form(&self) -> bool { matches!(self, ChunkHeader::Form(_)) }
+}
+
+/// Recursively visits each form and chunk in file order, passing its FourCC path
+/// (e.g. `CMDL/HEAD`), header and contents. Forms are visited before their contents, and
+/// everything sliced before a failure has been visited when the error is returned.
+pub fn walk_chunks<'a, O, F>(data: &'a [u8], mut visit: F) -> Result<()>
+where
+    O: ByteOrder + 'static,
+    F: FnMut(&str, ChunkHeader<'a, O>, &'a [u8]) -> Result<()>,
+{
+    walk_chunks_inner("", data, &mut visit)
+}
+
+fn walk_chunks_inner<'a, O, F>(prefix: &str, mut data: &'a [u8], visit: &mut F) -> Result<()>
+where
+    O: ByteOrder + 'static,
+    F: FnMut(&str, ChunkHeader<'a, O>, &'a [u8]) -> Result<()>,
+{
+    while !data.is_empty() {
+        if peek_four_cc(data) == K_CHUNK_RFRM {
+            let (desc, form_data, remain) = FormDescriptor::<O>::slice(data)?;
+            let path = format!("{prefix}{}", desc.id);
+            visit(&path, ChunkHeader::Form(desc), form_data)?;
+            walk_chunks_inner(&format!("{path}/"), form_data, visit)
+                .with_context(|| format!("Failed to slice form {path}"))?;
+            data = remain;
+        } else {
+            let (desc, chunk_data, remain) = ChunkDescriptor::<O>::slice(data)?;
+            visit(&format!("{prefix}{}", desc.id), ChunkHeader::Chunk(desc), chunk_data)?;
+            data = remain;
+        }
+    }
+    Ok(())
+}
+
 /// Recursively dump an RFRM + contained chunks
 #[allow(unused)]
 pub fn dump_rfrm<'a, O, W>(w: &mut W, data: &'a [u8], indent: usize) -> Result<&'a [u8]>
@@ -1,18 +1,21 @@
 use std::{
     fmt::Debug,
-    io::{Cursor, Read, Seek},
+    io::{Cursor, Read, Seek, SeekFrom},
     marker::PhantomData,
     path::Path,
 };
 
-use anyhow::{anyhow, bail, ensure, Result};
-use binrw::{binrw, BinReaderExt, Endian};
+use anyhow::{bail, ensure, Result};
+use binrw::{binrw, BinRead, BinReaderExt, BinResult, Endian};
 use zerocopy::ByteOrder;
 
 use crate::{
+    error::RetroError,
     format::{
-        rfrm::FormDescriptor, slice_chunks, CColor4f, CObjectId, CStringFixed, CVector3f,
-        CVector4f, FourCC, TaggedVec,
+        rfrm::FormDescriptor,
+        slice_chunks,
+        strp::{PooledString, StringPool, K_CHUNK_STRP},
+        CAABox, CColor4f, CObjectId, CStringFixed, CVector3f, CVector4f, FourCC, TaggedVec,
     },
     util::templates::{
         load_templates, EnumTemplate, HexU32, PropertyListTemplate, PropertyTemplateType,
@@ -46,9 +49,6 @@ pub const K_CHUNK_LRES: FourCC = FourCC(*b"LRES");
 // Load unit layers
 pub const K_CHUNK_LLYR: FourCC = FourCC(*b"LLYR");
 
-// String pool
-pub const K_CHUNK_STRP: FourCC = FourCC(*b"STRP");
-
 // Script data
 pub const K_FORM_SDTA: FourCC = FourCC(*b"SDTA");
 // Script data header
@@ -71,21 +71,68 @@ pub const K_FORM_SRIP: FourCC = FourCC(*b"SRIP");
 // Game object component
 pub const K_CHUNK_COMP: FourCC = FourCC(*b"COMP");
 
+/// Room header (RMHD).
+///
+/// Unconfirmed: only `parent_room_id` and `path_find_area_id` have known meanings;
+/// the remaining fields are named by position.
 #[binrw]
 #[derive(Clone, Debug)]
 pub struct SGameAreaHeader {
+    /// Room this room is nested in, or nil for top-level rooms.
     pub parent_room_id: CObjectId,
+    /// Unknown, usually zero.
     pub unk1: u16,
+    /// Unknown, usually zero.
     pub unk2: u16,
+    /// Unknown flag byte.
     pub unk3: u8,
+    /// Unknown asset or object reference.
     pub id_b: CObjectId,
+    /// Unknown asset or object reference.
     pub id_c: CObjectId,
+    /// Unknown asset or object reference.
     pub id_d: CObjectId,
+    /// Unknown asset or object reference.
     pub id_e: CObjectId,
+    /// PFA2 path finding area for the room.
     pub path_find_area_id: CObjectId,
-    // TODO ProductionWorkStages
+    /// Development progress per discipline. Empty when the header ends before the list.
+    #[br(parse_with = read_work_stages)]
+    #[bw(map = |v| TaggedVec::<u32, _>::new(v.clone()))]
+    pub production_work_stages: Vec<SProductionWorkStage>,
 }
 
+/// Reads the production work stage list, which some headers end before. A list that is
+/// present but malformed is an error.
+fn read_work_stages<R: Read + Seek>(
+    reader: &mut R,
+    endian: Endian,
+    _: (),
+) -> BinResult<Vec<SProductionWorkStage>> {
+    let pos = reader.stream_position()?;
+    if reader.seek(SeekFrom::End(0))? == pos {
+        return Ok(Vec::new());
+    }
+    reader.seek(SeekFrom::Start(pos))?;
+    Ok(TaggedVec::<u32, SProductionWorkStage>::read_options(reader, endian, ())?.data)
+}
+
+/// Development metadata recording how far along a room is for one discipline.
+///
+/// Unconfirmed: both values are editor enums whose names aren't known.
+#[binrw]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SProductionWorkStage {
+    /// Discipline the stage applies to (e.g. art or design).
+    pub discipline: u32,
+    /// Stage reached for the discipline.
+    pub stage: u32,
+}
+
+/// Lightmap atlas rectangle for a single baked surface.
+///
+/// Unconfirmed: `x`/`y` appear to be the UV scale and `z`/`w` the UV offset
+/// of the surface within the lightmap texture.
 #[binrw]
 #[derive(Copy, Clone, Debug, Default)]
 pub struct SAtlasLookup(pub CVector4f);
@@ -119,6 +166,49 @@ pub struct BakedLighting {
     pub light_probe: Option<BakedLightingLightProbe>,
 }
 
+/// Connection from this room to a neighboring room.
+///
+/// Unconfirmed: only the connected room ID has been verified; `dock_id` and the
+/// bounds of the connecting geometry are a best guess at the remaining fields.
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct SDock {
+    pub connected_room_id: CObjectId,
+    pub dock_id: CObjectId,
+    pub bounds: CAABox,
+}
+
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct SDockList {
+    #[bw(try_calc = docks.len().try_into())]
+    pub count: u32,
+    #[br(count = count)]
+    pub docks: Vec<SDock>,
+}
+
+/// Object spawned at runtime rather than placed in a layer (LGEN entry).
+///
+/// Unconfirmed: `object_id` matches IDs referenced by script connections but not by any layer;
+/// `generator_id` is the placed object that spawns it, and `index` appears to order objects
+/// spawned by the same generator.
+#[binrw]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SGeneratedObject {
+    pub object_id: CObjectId,
+    pub generator_id: CObjectId,
+    pub index: u32,
+}
+
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct SGeneratedObjectMap {
+    #[bw(try_calc = objects.len().try_into())]
+    pub count: u32,
+    #[br(count = count)]
+    pub objects: Vec<SGeneratedObject>,
+}
+
 #[binrw]
 #[derive(Clone, Debug)]
 // name?
@@ -147,29 +237,6 @@ pub struct ComponentProperties {
     pub data: Vec<u8>,
 }
 
-#[binrw]
-#[derive(Clone, Debug)]
-pub struct PooledString {
-    a: u32,
-    b: u32,
-    #[br(count = if a == u32::MAX { b } else { 0 })]
-    bytes: Vec<u8>,
-}
-
-impl PooledString {
-    pub fn get(&self, pool: Option<&StringPool>) -> Option<String> {
-        if self.a == u32::MAX {
-            String::from_utf8(self.bytes.clone()).ok()
-        } else if let Some(data) =
-            pool.and_then(|pool| pool.pool_data.get(self.a as usize..(self.a + self.b) as usize))
-        {
-            String::from_utf8(data.to_vec()).ok()
-        } else {
-            None
-        }
-    }
-}
-
 #[binrw]
 #[derive(Clone, Debug)]
 pub struct SizeofAllocations {
@@ -242,28 +309,13 @@ pub struct GameObjectComponents {
     pub data: Vec<GameObjectComponent>,
 }
 
-#[binrw]
-#[derive(Clone, Debug)]
-pub struct StringPool {
-    pub unk1: u32,
-    pub num_strings: u32,
-    #[br(if(num_strings > 0))]
-    pub pool_len: u32,
-    #[br(if(num_strings > 0), count = pool_len)]
-    pub pool_data: Vec<u8>,
-    // Unused StringPool struct
-    pub unk2: u32,
-    pub unk3: u32,
-    pub unk_pool_len: u32,
-    #[br(count = unk_pool_len)]
-    pub unk_pool_data: Vec<u8>,
-}
-
 #[derive(Debug, Clone)]
 pub struct RoomData<O: ByteOrder> {
     pub string_pool: Option<StringPool>,
     pub room_header: SGameAreaHeader,
     pub baked_lighting: BakedLighting,
+    pub docks: Vec<SDock>,
+    pub generated_objects: Vec<SGeneratedObject>,
     pub component_properties: Vec<ComponentProperties>,
     pub constructed_properties: Vec<ConstructedProperty>,
     pub instance_data: Vec<SGOComponentInstanceData>,
@@ -348,22 +400,22 @@ where O: ByteOrder + 'static
 {
     pub fn slice(data: &[u8]) -> Result<Self> {
         let (room_desc, room_data, _) = FormDescriptor::<O>::slice(data)?;
-        ensure!(room_desc.id == K_FORM_ROOM);
-        ensure!(room_desc.reader_version.get() == 147);
-        ensure!(room_desc.writer_version.get() == 160);
+        room_desc.expect_id(K_FORM_ROOM)?;
+        room_desc.expect_version(147, 160)?;
 
         let mut string_pool: Option<StringPool> = None;
         let mut room_header: Option<SGameAreaHeader> = None;
         let mut baked_lighting: Option<BakedLighting> = None;
+        let mut docks: Vec<SDock> = vec![];
+        let mut generated_objects: Vec<SGeneratedObject> = vec![];
         let mut component_properties: Vec<ComponentProperties> = vec![];
         let mut instance_data: Vec<SGOComponentInstanceData> = vec![];
         let mut layers: Vec<Layer> = vec![];
         slice_chunks::<O, _, _>(
             room_data,
             |chunk, data| {
-                let mut reader = Cursor::new(data);
                 match chunk.id {
-                    K_CHUNK_STRP => string_pool = Some(reader.read_type(Endian::Little)?),
+                    K_CHUNK_STRP => string_pool = Some(StringPool::slice(data)?),
                     id => bail!("Unknown ROOM chunk: {id:?}"),
                 }
                 Ok(())
@@ -382,7 +434,35 @@ where O: ByteOrder + 'static
                                     K_CHUNK_BLIT => {
                                         baked_lighting = Some(reader.read_type(Endian::Little)?)
                                     }
-                                    K_CHUNK_PGRP | K_CHUNK_LGEN | K_CHUNK_DOCK | K_CHUNK_LUNS => {
+                                    K_CHUNK_DOCK => {
+                                        match reader.read_type::<SDockList>(Endian::Little) {
+                                            Ok(_) if reader.position() != data.len() as u64 => {
+                                                log::warn!(
+                                                    "Discarding DOCK: parsed {:#X} of {:#X} bytes",
+                                                    reader.position(),
+                                                    data.len()
+                                                )
+                                            }
+                                            Ok(list) => docks = list.docks,
+                                            Err(e) => log::warn!("Failed to parse DOCK: {e}"),
+                                        }
+                                    }
+                                    K_CHUNK_LGEN => {
+                                        match reader
+                                            .read_type::<SGeneratedObjectMap>(Endian::Little)
+                                        {
+                                            Ok(_) if reader.position() != data.len() as u64 => {
+                                                log::warn!(
+                                                    "Discarding LGEN: parsed {:#X} of {:#X} bytes",
+                                                    reader.position(),
+                                                    data.len()
+                                                )
+                                            }
+                                            Ok(map) => generated_objects = map.objects,
+                                            Err(e) => log::warn!("Failed to parse LGEN: {e}"),
+                                        }
+                                    }
+                                    K_CHUNK_PGRP | K_CHUNK_LUNS => {
                                         // TODO
                                     }
                                     id => bail!("Unknown HEAD chunk: {id:?}"),
@@ -450,12 +530,14 @@ where O: ByteOrder + 'static
             });
         }
 
-        let room_header = room_header.ok_or_else(|| anyhow!("Missing RMHD chunk"))?;
-        let baked_lighting = baked_lighting.ok_or_else(|| anyhow!("Missing BLIT chunk"))?;
+        let room_header = room_header.ok_or(RetroError::MissingChunk(K_CHUNK_RMHD))?;
+        let baked_lighting = baked_lighting.ok_or(RetroError::MissingChunk(K_CHUNK_BLIT))?;
         Ok(Self {
             string_pool,
             room_header,
             baked_lighting,
+            docks,
+            generated_objects,
             component_properties,
             constructed_properties,
             instance_data,
@@ -731,7 +813,7 @@ where
         },
         |form, _data| bail!("Unknown SDTA form: {:?}", form.id),
     )?;
-    let sdhr = sdhr.ok_or_else(|| anyhow!("Missing SDHR chunk"))?;
+    let sdhr = sdhr.ok_or(RetroError::MissingChunk(K_CHUNK_SDHR))?;
     ensure!(sdhr.properties_count as usize == component_properties.len());
     ensure!(sdhr.instance_data_count as usize == instance_data.len());
     Ok((component_properties, instance_data))
@@ -786,7 +868,7 @@ where O: ByteOrder + 'static {
                             Ok(())
                         },
                     )?;
-                    let header = header.ok_or_else(|| anyhow!("Missing LHED chunk"))?;
+                    let header = header.ok_or(RetroError::MissingChunk(K_CHUNK_LHED))?;
                     layers.push(Layer { header, components });
                 }
                 id => bail!("Unknown LYRS form: {id:?}"),
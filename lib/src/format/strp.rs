@@ -0,0 +1,98 @@
+//! String pools shared by the strings of a form.
+//!
+//! Forms store a `STRP` chunk alongside their other chunks; [`PooledString`]s read
+//! from those chunks either carry their bytes inline or reference a range of the pool.
+
+use std::io::Cursor;
+
+use anyhow::Result;
+use binrw::{binrw, BinReaderExt, Endian};
+use zerocopy::ByteOrder;
+
+use crate::format::{slice_chunks, FourCC};
+
+// String pool
+pub const K_CHUNK_STRP: FourCC = FourCC(*b"STRP");
+
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct PooledString {
+    a: u32,
+    b: u32,
+    #[br(count = if a == u32::MAX { b } else { 0 })]
+    bytes: Vec<u8>,
+}
+
+impl PooledString {
+    /// A string stored inline rather than in the pool.
+    pub fn inline(value: &str) -> Self {
+        Self { a: u32::MAX, b: value.len() as u32, bytes: value.as_bytes().to_vec() }
+    }
+
+    /// A string referencing `len` bytes at `offset` in the form's pool.
+    pub fn pooled(offset: u32, len: u32) -> Self { Self { a: offset, b: len, bytes: vec![] } }
+
+    pub fn is_inline(&self) -> bool { self.a == u32::MAX }
+
+    /// Resolves the string, returning `None` if it references data outside the pool
+    /// (or there is no pool) or isn't valid UTF-8.
+    pub fn get(&self, pool: Option<&StringPool>) -> Option<String> {
+        if self.is_inline() {
+            String::from_utf8(self.bytes.clone()).ok()
+        } else if let Some(data) = pool.and_then(|pool| {
+            let end = self.a.checked_add(self.b)?;
+            pool.pool_data.get(self.a as usize..end as usize)
+        }) {
+            String::from_utf8(data.to_vec()).ok()
+        } else {
+            None
+        }
+    }
+}
+
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct StringPool {
+    pub unk1: u32,
+    pub num_strings: u32,
+    #[br(if(num_strings > 0))]
+    pub pool_len: u32,
+    #[br(if(num_strings > 0), count = pool_len)]
+    pub pool_data: Vec<u8>,
+    // Unused StringPool struct
+    pub unk2: u32,
+    pub unk3: u32,
+    pub unk_pool_len: u32,
+    #[br(count = unk_pool_len)]
+    pub unk_pool_data: Vec<u8>,
+}
+
+impl StringPool {
+    /// Parses the contents of a `STRP` chunk.
+    pub fn slice(data: &[u8]) -> Result<Self> { Ok(Cursor::new(data).read_type(Endian::Little)?) }
+
+    /// Finds and parses the `STRP` chunk among a form's top-level chunks, if it has one.
+    pub fn find<O>(form_data: &[u8]) -> Result<Option<Self>>
+    where O: ByteOrder + 'static {
+        let mut pool = None;
+        slice_chunks::<O, _, _>(
+            form_data,
+            |chunk, data| {
+                if chunk.id == K_CHUNK_STRP {
+                    pool = Some(Self::slice(data)?);
+                }
+                Ok(())
+            },
+            |_, _| Ok(()),
+        )?;
+        Ok(pool)
+    }
+}
+
+/// Resolves each string against `pool`, in order. See [`PooledString::get`].
+pub fn resolve_all<'a>(
+    strings: impl IntoIterator<Item = &'a PooledString>,
+    pool: Option<&StringPool>,
+) -> Vec<Option<String>> {
+    strings.into_iter().map(|s| s.get(pool)).collect()
+}
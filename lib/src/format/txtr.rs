@@ -5,19 +5,22 @@ use std::{
     marker::PhantomData,
     num::NonZeroUsize,
     ops::Range,
+    str::FromStr,
 };
 
-use anyhow::{anyhow, bail, ensure, Context, Result};
+use anyhow::{anyhow, ensure, Context, Result};
 use binrw::{binrw, BinReaderExt, Endian};
 use image::{
-    DynamicImage, GrayImage, ImageBuffer, Luma, LumaA, Pixel, Rgb, RgbImage, Rgba, Rgba32FImage,
-    RgbaImage,
+    imageops, DynamicImage, GrayImage, ImageBuffer, Luma, LumaA, Pixel, Rgb, RgbImage, Rgba,
+    Rgba32FImage, RgbaImage,
 };
+use strum::{EnumIter, IntoEnumIterator, IntoStaticStr};
 use tegra_swizzle::surface::BlockDim;
-use zerocopy::ByteOrder;
+use zerocopy::{ByteOrder, LittleEndian};
 
 use crate::{
-    format::{chunk::ChunkDescriptor, rfrm::FormDescriptor, FourCC},
+    error::RetroError,
+    format::{chunk::ChunkDescriptor, foot::locate_meta, rfrm::FormDescriptor, FourCC},
     util::compression::decompress_into,
 };
 
@@ -112,7 +115,9 @@ pub struct STextureHeader {
     pub width: u32,
     pub height: u32,
     pub layers: u32,
+    /// Not interpreted; surfaces are deswizzled with block heights inferred from their size.
     pub tile_mode: u32,
+    /// Not interpreted; decoded channels follow `format`.
     pub swizzle: u32,
     #[bw(try_calc = mip_sizes.len().try_into())]
     pub mip_count: u32,
@@ -156,9 +161,15 @@ pub struct STextureCompressedBufferInfo {
 pub struct STextureMetaData {
     pub unk1: u32,
     pub unk2: u32,
+    /// Memory pool the game allocates the texture from. Not needed for decoding.
     pub alloc_category: u32,
+    /// File offset of the first GPU data read. Each read info carries its own offset,
+    /// so decoding doesn't use this.
     pub gpu_offset: u32,
+    /// Alignment of the GPU allocation. The decompressed buffer may be padded up to a
+    /// multiple of it past the end of the surface.
     pub align: u32,
+    /// Size of the buffer the compressed buffers decompress into, including padding.
     pub decompressed_size: u32,
     #[bw(try_calc = info.len().try_into())]
     pub info_count: u32,
@@ -173,7 +184,7 @@ pub struct STextureMetaData {
 #[binrw]
 #[repr(u32)]
 #[brw(repr(u32))]
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, EnumIter, IntoStaticStr)]
 pub enum ETextureFormat {
     R8Unorm = 0,
     R8Snorm = 1,
@@ -299,12 +310,12 @@ impl Display for ETextureFormat {
             ETextureFormat::Rg8Unorm => "RG8 UNORM",
             ETextureFormat::Rg8Snorm => "RG8 SNORM",
             ETextureFormat::Rg8Uint => "RG8 UINT",
-            ETextureFormat::Rg8Sint => "RG16 SINT",
+            ETextureFormat::Rg8Sint => "RG8 SINT",
             ETextureFormat::Rg16Float => "RG16 FLOAT",
             ETextureFormat::Rg16Unorm => "RG16 UNORM",
             ETextureFormat::Rg16Snorm => "RG16 SNORM",
             ETextureFormat::Rg16Uint => "RG16 UINT",
-            ETextureFormat::Rg16Sint => "RG16 SINt",
+            ETextureFormat::Rg16Sint => "RG16 SINT",
             ETextureFormat::Rgb10A2Unorm => "RGB10A2 UNORM",
             ETextureFormat::Rgb10A2Uint => "RGB10A2 UINT",
             ETextureFormat::Rg32Uint => "RG32 UINT",
@@ -353,6 +364,21 @@ impl Display for ETextureFormat {
     }
 }
 
+impl FromStr for ETextureFormat {
+    type Err = anyhow::Error;
+
+    /// Parses a variant name (e.g. `BptcUnormSrgb`) or display name (e.g. `BC7 UNORM (sRGB)`),
+    /// ignoring case. `D16 UNORM` parses as [`ETextureFormat::Depth16Unorm`].
+    fn from_str(s: &str) -> Result<Self> {
+        ETextureFormat::iter()
+            .find(|format| <&str>::from(format).eq_ignore_ascii_case(s))
+            .or_else(|| {
+                ETextureFormat::iter().find(|format| format.to_string().eq_ignore_ascii_case(s))
+            })
+            .ok_or_else(|| anyhow!("Unknown texture format '{s}'"))
+    }
+}
+
 impl ETextureFormat {
     pub fn block_size(self) -> (u8, u8, u8) {
         match self {
@@ -422,6 +448,30 @@ impl ETextureFormat {
         )
     }
 
+    /// Whether texels are unnormalized integers, such as masks or lookup indices.
+    pub fn is_integer(self) -> bool {
+        matches!(
+            self,
+            ETextureFormat::R8Uint
+                | ETextureFormat::R8Sint
+                | ETextureFormat::R16Uint
+                | ETextureFormat::R16Sint
+                | ETextureFormat::R32Uint
+                | ETextureFormat::R32Sint
+                | ETextureFormat::Rg8Uint
+                | ETextureFormat::Rg8Sint
+                | ETextureFormat::Rg16Uint
+                | ETextureFormat::Rg16Sint
+                | ETextureFormat::Rgb10A2Uint
+                | ETextureFormat::Rg32Uint
+                | ETextureFormat::Rg32Sint
+                | ETextureFormat::Rgba16Uint
+                | ETextureFormat::Rgba16Sint
+                | ETextureFormat::Rgba32Uint
+                | ETextureFormat::Rgba32Sint
+        )
+    }
+
     pub fn is_srgb(self) -> bool {
         matches!(
             self,
@@ -526,7 +576,9 @@ impl ETextureFormat {
     }
 }
 
-fn deswizzle(header: &STextureHeader, data: &[u8]) -> Result<Vec<u8>> {
+/// Converts a decompressed texture buffer from the Switch's block-linear GPU layout to a
+/// linear layout. `data` may include padding up to `align` after the surface.
+pub fn deswizzle(header: &STextureHeader, data: &[u8], align: u32) -> Result<Vec<u8>> {
     let (bw, bh, bd) = header.format.block_size();
     let block_dim = BlockDim {
         width: NonZeroUsize::new(bw as usize).unwrap(),
@@ -539,6 +591,13 @@ fn deswizzle(header: &STextureHeader, data: &[u8]) -> Result<Vec<u8>> {
     } else {
         (1, header.layers as usize)
     };
+    log::debug!(
+        "Deswizzling {}x{}: block {bw}x{bh}x{bd}, {bpp} bytes per block, depth {depth}, \
+         {layers} layers, {} mips",
+        header.width,
+        header.height,
+        header.mip_sizes.len()
+    );
     let expected_size = tegra_swizzle::surface::swizzled_surface_size(
         header.width as usize,
         header.height as usize,
@@ -549,12 +608,22 @@ fn deswizzle(header: &STextureHeader, data: &[u8]) -> Result<Vec<u8>> {
         header.mip_sizes.len(),
         layers,
     );
-    ensure!(data.len() == expected_size);
+    // The surface starts at the beginning of the allocation, followed by alignment padding
+    let align = (align as usize).max(1);
+    let padded_size = expected_size.next_multiple_of(align);
+    ensure!(
+        data.len() == expected_size || data.len() == padded_size,
+        "Decompressed size {:#X} doesn't match surface size {:#X} (aligned to {:#X}: {:#X})",
+        data.len(),
+        expected_size,
+        align,
+        padded_size
+    );
     Ok(tegra_swizzle::surface::deswizzle_surface(
         header.width as usize,
         header.height as usize,
         depth,
-        data,
+        &data[..expected_size],
         block_dim,
         None,
         bpp,
@@ -571,22 +640,43 @@ pub struct TextureData<O: ByteOrder> {
 }
 
 impl<O: ByteOrder> TextureData<O> {
-    pub fn slice(data: &[u8], meta: &[u8]) -> Result<Self> {
+    /// Parses the texture header and metadata without decompressing any texture data.
+    pub fn slice_header(data: &[u8], meta: &[u8]) -> Result<(STextureHeader, STextureMetaData)> {
         let (txtr_desc, txtr_data, _) = FormDescriptor::<O>::slice(data)?;
-        ensure!(txtr_desc.id == K_FORM_TXTR);
-        ensure!(txtr_desc.reader_version.get() == 47);
-        ensure!(txtr_desc.writer_version.get() == 51);
+        txtr_desc.expect_id(K_FORM_TXTR)?;
+        txtr_desc.expect_version(47, 51)?;
 
         let (head_desc, head_data, _) = ChunkDescriptor::<O>::slice(txtr_data)?;
-        ensure!(head_desc.id == K_CHUNK_HEAD);
+        head_desc.expect_id(K_CHUNK_HEAD)?;
         let head: STextureHeader = Cursor::new(head_data).read_type(Endian::Little)?;
 
         // log::debug!("META: {meta:#?}");
         // log::debug!("HEAD: {head:#?}");
 
         let meta: STextureMetaData = Cursor::new(meta).read_type(Endian::Little)?;
+        Ok((head, meta))
+    }
+
+    pub fn slice(data: &[u8], meta: &[u8]) -> Result<Self> {
+        let (head, meta, buffer) = Self::slice_swizzled(data, meta)?;
+        let deswizzled = deswizzle(&head, &buffer, meta.align)?;
+        Ok(Self::from_raw(head, deswizzled))
+    }
+
+    /// Wraps texture data that's already decompressed and in a linear layout.
+    pub fn from_raw(head: STextureHeader, data: Vec<u8>) -> Self {
+        Self { head, data, _marker: PhantomData }
+    }
+
+    /// Decompresses the texture buffers without deswizzling them, returning the data in the
+    /// GPU layout (including any alignment padding) for diagnosing [`deswizzle`] issues.
+    pub fn slice_swizzled(
+        data: &[u8],
+        meta: &[u8],
+    ) -> Result<(STextureHeader, STextureMetaData, Vec<u8>)> {
+        let (head, meta) = Self::slice_header(data, meta)?;
         let mut buffer = vec![0u8; meta.decompressed_size as usize];
-        for info in &meta.buffers {
+        for (buf_idx, info) in meta.buffers.iter().enumerate() {
             let (read_idx, read) = meta
                 .info
                 .iter()
@@ -594,16 +684,40 @@ impl<O: ByteOrder> TextureData<O> {
                 .find(|(_, i)| i.index as u32 == info.index)
                 .ok_or_else(|| anyhow!("Failed to locate read info for buffer {}", info.index))?;
             ensure!(read.index as usize == read_idx); // do these ever differ?
-            let read_buf = &data[read.offset as usize..(read.offset + read.size) as usize];
-            let comp_buf = &read_buf[info.offset as usize..(info.offset + info.size) as usize];
-            decompress_into(
-                comp_buf,
-                &mut buffer
-                    [info.dest_offset as usize..(info.dest_offset + info.dest_size) as usize],
-            )?;
+            let read_buf = data
+                .get(read.offset as usize..read.offset as usize + read.size as usize)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Read {} ({:#X}, {:#X}) exceeds file size",
+                        read_idx,
+                        read.offset,
+                        read.size
+                    )
+                })?;
+            let comp_buf = read_buf
+                .get(info.offset as usize..info.offset as usize + info.size as usize)
+                .ok_or_else(|| anyhow!("Texture buffer {} exceeds read {}", buf_idx, read_idx))?;
+            let dest_range =
+                info.dest_offset as usize..info.dest_offset as usize + info.dest_size as usize;
+            let dest_buf = buffer.get_mut(dest_range.clone()).ok_or_else(|| {
+                anyhow!(
+                    "Texture buffer {} destination {:#X?} exceeds decompressed size {:#X}",
+                    buf_idx,
+                    dest_range,
+                    meta.decompressed_size
+                )
+            })?;
+            decompress_into(comp_buf, dest_buf).with_context(|| {
+                format!(
+                    "Failed to decompress texture buffer {} (file offset {:#X}, dest {:#X}..{:#X})",
+                    buf_idx,
+                    read.offset + info.offset,
+                    info.dest_offset,
+                    info.dest_offset + info.dest_size
+                )
+            })?;
         }
-        let deswizzled = deswizzle(&head, &buffer)?;
-        Ok(Self { head, data: deswizzled, _marker: PhantomData })
+        Ok((head, meta, buffer))
     }
 }
 
@@ -661,6 +775,84 @@ pub fn slice_texture<O: ByteOrder>(texture: &TextureData<O>) -> Result<Vec<Vec<T
     Ok(out)
 }
 
+/// Picks the mip to use as a preview: the smallest one whose first layer is at least
+/// `min_size` on its longest side, or mip 0 if none are. Returns `None` without image data.
+///
+/// Textures without a mip chain only have mip 0, so their preview is full resolution.
+pub fn thumbnail_mip(slices: &[Vec<TextureSlice>], min_size: u32) -> Option<usize> {
+    slices
+        .iter()
+        .enumerate()
+        .filter_map(|(mip, layers)| Some((mip, layers.first()?)))
+        .rev()
+        .find(|(_, slice)| slice.width.max(slice.height) >= min_size)
+        .map(|(mip, _)| mip)
+        .or_else(|| slices.first().filter(|layers| !layers.is_empty()).map(|_| 0))
+}
+
+/// Decodes a single mip level and layer (or depth slice, for 3D textures).
+///
+/// Only the requested slice is decompressed. `texture.data` must already be
+/// deswizzled, as [`TextureData::slice`] leaves it.
+pub fn decode_slice<O: ByteOrder>(
+    texture: &TextureData<O>,
+    mip: usize,
+    layer: usize,
+) -> Result<DynamicImage> {
+    let slices = slice_texture(texture)?;
+    let layers = slices
+        .get(mip)
+        .ok_or_else(|| anyhow!("Mip {mip} out of range (texture has {})", slices.len()))?;
+    let slice = layers
+        .get(layer)
+        .ok_or_else(|| anyhow!("Layer {layer} out of range (mip {mip} has {})", layers.len()))?;
+    decode_texture_slice(texture, slice)
+        .with_context(|| format!("Failed to decode mip {mip} layer {layer}"))
+}
+
+fn decode_texture_slice<O: ByteOrder>(
+    texture: &TextureData<O>,
+    slice: &TextureSlice,
+) -> Result<DynamicImage> {
+    let data = texture.data.get(slice.data_range.clone()).ok_or_else(|| {
+        anyhow!("Slice {:?} exceeds texture data size {:#X}", slice.data_range, texture.data.len())
+    })?;
+    decompress_image(texture.head.format, slice.width, slice.height, data)
+}
+
+/// A texture decoded by [`decode_texture_file`].
+#[derive(Debug, Clone)]
+pub struct DecodedTexture {
+    pub head: STextureHeader,
+    /// Images indexed by mip level, then layer (or depth slice, for 3D textures).
+    pub mips: Vec<Vec<DynamicImage>>,
+}
+
+/// Decodes every mip level and layer of an extracted TXTR file.
+///
+/// The file must end with the FOOT form written by `pak extract`, which carries the
+/// metadata locating the texture buffers. This is [`locate_meta`], [`TextureData::slice`],
+/// [`slice_texture`] and [`decompress_image`] in one call.
+pub fn decode_texture_file(data: &[u8]) -> Result<DecodedTexture> {
+    let meta = locate_meta::<LittleEndian>(data)?;
+    let texture = TextureData::<LittleEndian>::slice(data, meta)?;
+    let mips = slice_texture(&texture)?
+        .iter()
+        .enumerate()
+        .map(|(mip, layers)| {
+            layers
+                .iter()
+                .enumerate()
+                .map(|(layer, slice)| {
+                    decode_texture_slice(&texture, slice)
+                        .with_context(|| format!("Failed to decode mip {mip} layer {layer}"))
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(DecodedTexture { head: texture.head, mips })
+}
+
 const BC1_BLOCK_SIZE: usize = 8;
 const BC2_BLOCK_SIZE: usize = 16;
 const BC3_BLOCK_SIZE: usize = 16;
@@ -669,6 +861,11 @@ const BC5_BLOCK_SIZE: usize = 16;
 const BC6H_BLOCK_SIZE: usize = 16;
 const BC7_BLOCK_SIZE: usize = 16;
 
+/// Decodes a texture slice into an image.
+///
+/// 8- and 16-bit integer formats are decoded as a visualization of the raw values rather
+/// than a color: unsigned values are kept as-is, signed values are offset so that zero is
+/// mid-gray, and two-channel formats are written as RGB with an empty blue channel.
 pub fn decompress_image(
     format: ETextureFormat,
     w: u32,
@@ -687,6 +884,38 @@ pub fn decompress_image(
                 anyhow!("Conversion failed: {:?} {}x{} from size {}", format, w, h, data.len())
             })?,
         ),
+        ETextureFormat::R8Uint | ETextureFormat::R8Sint => DynamicImage::ImageLuma8(
+            GrayImage::from_raw(w, h, visualize_int8(data, format == ETextureFormat::R8Sint))
+                .ok_or_else(|| {
+                    anyhow!("Conversion failed: {:?} {}x{} from size {}", format, w, h, data.len())
+                })?,
+        ),
+        ETextureFormat::Rg8Uint | ETextureFormat::Rg8Sint => {
+            let values = visualize_int8(data, format == ETextureFormat::Rg8Sint);
+            let rgb = values.chunks_exact(2).flat_map(|rg| [rg[0], rg[1], 0]).collect();
+            DynamicImage::ImageRgb8(RgbImage::from_raw(w, h, rgb).ok_or_else(|| {
+                anyhow!("Conversion failed: {:?} {}x{} from size {}", format, w, h, data.len())
+            })?)
+        }
+        ETextureFormat::R16Uint | ETextureFormat::R16Sint => DynamicImage::ImageLuma16(
+            ImageBuffer::<Luma<u16>, Vec<u16>>::from_raw(
+                w,
+                h,
+                visualize_int16(data, format == ETextureFormat::R16Sint),
+            )
+            .ok_or_else(|| {
+                anyhow!("Conversion failed: {:?} {}x{} from size {}", format, w, h, data.len())
+            })?,
+        ),
+        ETextureFormat::Rg16Uint | ETextureFormat::Rg16Sint => {
+            let values = visualize_int16(data, format == ETextureFormat::Rg16Sint);
+            let rgb = values.chunks_exact(2).flat_map(|rg| [rg[0], rg[1], 0]).collect();
+            DynamicImage::ImageRgb16(
+                ImageBuffer::<Rgb<u16>, Vec<u16>>::from_raw(w, h, rgb).ok_or_else(|| {
+                    anyhow!("Conversion failed: {:?} {}x{} from size {}", format, w, h, data.len())
+                })?,
+            )
+        }
         ETextureFormat::Rgb8Unorm => {
             DynamicImage::ImageRgb8(RgbImage::from_raw(w, h, data.to_vec()).ok_or_else(|| {
                 anyhow!("Conversion failed: {:?} {}x{} from size {}", format, w, h, data.len())
@@ -763,16 +992,28 @@ pub fn decompress_image(
         | ETextureFormat::RgbaAstc10x10Srgb
         | ETextureFormat::RgbaAstc12x10Srgb
         | ETextureFormat::RgbaAstc12x12Srgb => {
-            let (bw, bh, _) = format.block_size();
-            let rw = (w + (bw as u32 - 1)) / bw as u32;
-            let rh = (h + (bh as u32 - 1)) / bh as u32;
-            ensure!(data.len() == rw as usize * rh as usize * 16);
-            let mut image = RgbaImage::new(w, h);
+            let (bw, bh, bd) = format.block_size();
+            if bd != 1 {
+                return Err(RetroError::UnsupportedFormat(format!("{format} 3D blocks")).into());
+            }
+            let (bw, bh) = (bw as u32, bh as u32);
+            let rw = (w + (bw - 1)) / bw;
+            let rh = (h + (bh - 1)) / bh;
+            let expected = rw as usize * rh as usize * 16;
+            ensure!(
+                data.len() == expected,
+                "ASTC {format} {w}x{h} expected data size {expected:#X} ({rw}x{rh} blocks of \
+                 {bw}x{bh}, 16 bytes each), got {:#X}",
+                data.len()
+            );
+            // Decode whole blocks, then crop to mips smaller than a block
+            let (pw, ph) = (rw * bw, rh * bh);
+            let mut image = RgbaImage::new(pw, ph);
             astc_decode::astc_decode(
                 data,
-                w,
-                h,
-                astc_decode::Footprint::new(bw as u32, bh as u32),
+                pw,
+                ph,
+                astc_decode::Footprint::new(bw, bh),
                 |x, y, z| image.put_pixel(x, y, z.into()),
             )
             .with_context(|| {
@@ -785,6 +1026,9 @@ pub fn decompress_image(
                     data.len()
                 )
             })?;
+            if (pw, ph) != (w, h) {
+                image = imageops::crop_imm(&image, 0, 0, w, h).to_image();
+            }
             DynamicImage::ImageRgba8(image)
         }
         ETextureFormat::BptcUfloat | ETextureFormat::BptcSfloat => {
@@ -801,10 +1045,25 @@ pub fn decompress_image(
                 bcdec_rs::bc7(src, dst, pitch)
             })?,
         ),
-        format => bail!("Unsupported conversion from {format:?}"),
+        format => return Err(RetroError::UnsupportedFormat(format!("{format} conversion")).into()),
     })
 }
 
+/// Flips the sign bit of signed 8-bit values so that they sort as unsigned.
+fn visualize_int8(data: &[u8], signed: bool) -> Vec<u8> {
+    if signed {
+        data.iter().map(|&v| v ^ 0x80).collect()
+    } else {
+        data.to_vec()
+    }
+}
+
+/// Reads little endian 16-bit values, flipping the sign bit of signed values.
+fn visualize_int16(data: &[u8], signed: bool) -> Vec<u16> {
+    let bias = if signed { 0x8000 } else { 0 };
+    data.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]]) ^ bias).collect()
+}
+
 fn decompress_bcn<P, F, const BLOCK_SIZE: usize>(
     data: &[u8],
     w: u32,
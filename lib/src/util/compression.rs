@@ -1,8 +1,8 @@
 use std::borrow::Cow;
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 
-use crate::util::lzss;
+use crate::{error::RetroError, util::lzss};
 
 pub fn decompress_buffer(
     compressed_data: &[u8],
@@ -20,27 +20,51 @@ pub fn decompress_buffer(
     Ok((mode, Cow::Owned(out)))
 }
 
+/// Compresses data with the given mode, prefixed with the mode as read by [`decompress_into`].
+pub fn compress_buffer(data: &[u8], mode: u32) -> Result<Vec<u8>> {
+    let payload = match mode {
+        0 => Cow::Borrowed(data),
+        1 => Cow::Owned(lzss::compress::<1>(data)?),
+        2 => Cow::Owned(lzss::compress::<2>(data)?),
+        3 => Cow::Owned(lzss::compress::<3>(data)?),
+        _ => return Err(RetroError::UnsupportedFormat(format!("compression mode {mode}")).into()),
+    };
+    let mut out = Vec::with_capacity(payload.len() + 4);
+    out.extend_from_slice(&mode.to_le_bytes());
+    out.extend_from_slice(&payload);
+    Ok(out)
+}
+
 pub fn decompress_into(compressed_data: &[u8], out: &mut [u8]) -> Result<u32> {
     if compressed_data.len() < 4 {
         bail!("Invalid compressed data size: {}", compressed_data.len());
     }
     let mode = u32::from_le_bytes(compressed_data[0..4].try_into().unwrap());
     let data = &compressed_data[4..];
-    if !match mode {
+    match mode {
         0 => {
-            if data.len() == out.len() {
-                out.copy_from_slice(data);
-                true
-            } else {
-                false
+            if data.len() != out.len() {
+                bail!(
+                    "Uncompressed data size mismatch: {:#X} bytes, expected {:#X}",
+                    data.len(),
+                    out.len()
+                );
             }
+            out.copy_from_slice(data);
+            Ok(())
         }
         1 => lzss::decompress::<1>(data, out),
         2 => lzss::decompress::<2>(data, out),
         3 => lzss::decompress::<3>(data, out),
-        _ => bail!("Unsupported compression mode {}", mode),
-    } {
-        bail!("Decompression failed");
+        _ => Err(RetroError::UnsupportedFormat(format!("compression mode {mode}")).into()),
     }
+    .with_context(|| {
+        format!(
+            "Decompression failed (mode {}, {:#X} -> {:#X} bytes)",
+            mode,
+            compressed_data.len(),
+            out.len()
+        )
+    })?;
     Ok(mode)
 }
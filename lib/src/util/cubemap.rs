@@ -0,0 +1,233 @@
+use std::f32::consts::PI;
+
+use anyhow::{ensure, Result};
+use image::{imageops, imageops::FilterType, ImageBuffer, Pixel, Rgba, Rgba32FImage};
+
+type Face<P> = ImageBuffer<P, Vec<<P as Pixel>::Subpixel>>;
+
+/// Cross arrangement of the six cube faces.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CrossLayout {
+    /// 4x3 faces: -X, +Z, +X, -Z across the middle row.
+    Horizontal,
+    /// 3x4 faces: +Z at the center with -Z below -Y, upside down.
+    Vertical,
+}
+
+/// Validates that there are six square faces of equal size, returning the face size.
+fn face_size<P: Pixel>(faces: &[Face<P>]) -> Result<u32> {
+    ensure!(faces.len() == 6, "Expected 6 cube faces, got {}", faces.len());
+    let size = faces[0].width();
+    ensure!(
+        faces.iter().all(|face| face.width() == size && face.height() == size),
+        "Cube faces must be square and equally sized"
+    );
+    Ok(size)
+}
+
+/// Assembles cube faces (ordered +X, -X, +Y, -Y, +Z, -Z) into a cross.
+pub fn cube_cross<P>(faces: &[Face<P>], layout: CrossLayout) -> Result<Face<P>>
+where P: Pixel + 'static {
+    let size = face_size(faces)?;
+    // Column and row of each face
+    let (columns, rows, cells) = match layout {
+        CrossLayout::Horizontal => (4, 3, [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)]),
+        CrossLayout::Vertical => (3, 4, [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)]),
+    };
+    let mut out = Face::<P>::new(columns * size, rows * size);
+    for (idx, (face, (column, row))) in faces.iter().zip(cells).enumerate() {
+        let x = (column * size) as i64;
+        let y = (row * size) as i64;
+        if layout == CrossLayout::Vertical && idx == 5 {
+            imageops::replace(&mut out, &imageops::rotate180(face), x, y);
+        } else {
+            imageops::replace(&mut out, face, x, y);
+        }
+    }
+    Ok(out)
+}
+
+/// Reprojects cube faces (ordered +X, -X, +Y, -Y, +Z, -Z) into an equirectangular
+/// (latitude/longitude) map of `width` x `width / 2`, centered on +Z with +Y up.
+pub fn cube_equirect<P>(faces: &[Face<P>], width: u32) -> Result<Face<P>>
+where P: Pixel + 'static {
+    let size = face_size(faces)?;
+    ensure!(width >= 2, "Equirectangular width must be at least 2");
+    let height = width / 2;
+    let mut out = Face::<P>::new(width, height);
+    for (x, y, pixel) in out.enumerate_pixels_mut() {
+        let longitude = ((x as f32 + 0.5) / width as f32 * 2.0 - 1.0) * PI;
+        let latitude = (0.5 - (y as f32 + 0.5) / height as f32) * PI;
+        let direction =
+            [latitude.cos() * longitude.sin(), latitude.sin(), latitude.cos() * longitude.cos()];
+        let (face, u, v) = cube_lookup(direction);
+        let fx = ((u * size as f32) as u32).min(size - 1);
+        let fy = ((v * size as f32) as u32).min(size - 1);
+        *pixel = *faces[face].get_pixel(fx, fy);
+    }
+    Ok(out)
+}
+
+/// Largest face size sampled when convolving; irradiance has little high frequency detail.
+const IRRADIANCE_SAMPLE_SIZE: u32 = 16;
+
+/// Computes a diffuse irradiance cube map of `size` x `size` faces from radiance cube faces
+/// (ordered +X, -X, +Y, -Y, +Z, -Z), by cosine-weighting every texel of the (downsampled)
+/// source over the hemisphere around each output direction.
+///
+/// Values are normalized by the total weight, i.e. divided by π, as expected by a Lambertian
+/// diffuse term.
+pub fn cube_irradiance(faces: &[Rgba32FImage], size: u32) -> Result<Vec<Rgba32FImage>> {
+    let source_size = face_size(faces)?;
+    ensure!(size > 0, "Irradiance face size must be positive");
+    let sample_size = source_size.min(IRRADIANCE_SAMPLE_SIZE);
+    let samples = faces
+        .iter()
+        .enumerate()
+        .flat_map(|(face, image)| {
+            let image = if sample_size == source_size {
+                image.clone()
+            } else {
+                imageops::resize(image, sample_size, sample_size, FilterType::Triangle)
+            };
+            (0..sample_size * sample_size)
+                .map(|idx| {
+                    let (x, y) = (idx % sample_size, idx / sample_size);
+                    let (direction, solid_angle) = texel_direction(face, x, y, sample_size);
+                    (direction, solid_angle, image.get_pixel(x, y).0)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    Ok((0..6)
+        .map(|face| {
+            Rgba32FImage::from_fn(size, size, |x, y| {
+                let (normal, _) = texel_direction(face, x, y, size);
+                let mut sum = [0f32; 3];
+                let mut total = 0f32;
+                for (direction, solid_angle, color) in &samples {
+                    let cos = normal[0] * direction[0]
+                        + normal[1] * direction[1]
+                        + normal[2] * direction[2];
+                    if cos <= 0.0 {
+                        continue;
+                    }
+                    let weight = cos * solid_angle;
+                    for (acc, value) in sum.iter_mut().zip(color) {
+                        *acc += value * weight;
+                    }
+                    total += weight;
+                }
+                let [r, g, b] = sum.map(|v| if total > 0.0 { v / total } else { 0.0 });
+                Rgba([r, g, b, 1.0])
+            })
+        })
+        .collect())
+}
+
+/// Projects radiance cube faces (ordered +X, -X, +Y, -Y, +Z, -Z) onto the real spherical
+/// harmonics of bands 0 to 2, returning nine RGB coefficients in [`sh9_basis`] order.
+pub fn cube_sh9(faces: &[Rgba32FImage]) -> Result<[[f32; 3]; 9]> {
+    let size = face_size(faces)?;
+    let mut coeffs = [[0f32; 3]; 9];
+    let mut total = 0f32;
+    for (face, image) in faces.iter().enumerate() {
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let (direction, solid_angle) = texel_direction(face, x, y, size);
+            for (coeff, basis) in coeffs.iter_mut().zip(sh9_basis(direction)) {
+                for (acc, value) in coeff.iter_mut().zip(&pixel.0) {
+                    *acc += value * basis * solid_angle;
+                }
+            }
+            total += solid_angle;
+        }
+    }
+    // Correct for the approximate texel solid angles not summing to exactly 4π
+    let scale = 4.0 * PI / total;
+    Ok(coeffs.map(|coeff| coeff.map(|v| v * scale)))
+}
+
+/// Real spherical harmonics of bands 0 to 2 for a unit direction, ordered
+/// Y(0,0), Y(1,-1), Y(1,0), Y(1,1), Y(2,-2), Y(2,-1), Y(2,0), Y(2,1), Y(2,2).
+pub fn sh9_basis([x, y, z]: [f32; 3]) -> [f32; 9] {
+    [
+        0.282_095,
+        0.488_603 * y,
+        0.488_603 * z,
+        0.488_603 * x,
+        1.092_548 * x * y,
+        1.092_548 * y * z,
+        0.315_392 * (3.0 * z * z - 1.0),
+        1.092_548 * x * z,
+        0.546_274 * (x * x - y * y),
+    ]
+}
+
+/// Evaluates the diffuse irradiance of radiance coefficients from [`cube_sh9`] into cube
+/// faces of `size` x `size`, using the analytic cosine lobe convolution per band
+/// (Ramamoorthi & Hanrahan). Normalized like [`cube_irradiance`], with negative ringing
+/// clamped to zero.
+pub fn sh9_irradiance_cube(coeffs: &[[f32; 3]; 9], size: u32) -> Result<Vec<Rgba32FImage>> {
+    ensure!(size > 0, "Irradiance face size must be positive");
+    // Cosine lobe band factors π, 2π/3 and π/4, divided by π
+    const BAND_SCALE: [f32; 9] =
+        [1.0, 2.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0, 0.25, 0.25, 0.25, 0.25, 0.25];
+    Ok((0..6)
+        .map(|face| {
+            Rgba32FImage::from_fn(size, size, |x, y| {
+                let (normal, _) = texel_direction(face, x, y, size);
+                let mut sum = [0f32; 3];
+                for ((coeff, basis), scale) in coeffs.iter().zip(sh9_basis(normal)).zip(BAND_SCALE)
+                {
+                    for (acc, value) in sum.iter_mut().zip(coeff) {
+                        *acc += value * basis * scale;
+                    }
+                }
+                let [r, g, b] = sum.map(|v| v.max(0.0));
+                Rgba([r, g, b, 1.0])
+            })
+        })
+        .collect())
+}
+
+/// Direction through the center of texel (`x`, `y`) of `face`, and the texel's approximate
+/// solid angle. The inverse of [`cube_lookup`].
+fn texel_direction(face: usize, x: u32, y: u32, size: u32) -> ([f32; 3], f32) {
+    let s = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+    let t = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+    let [dx, dy, dz] = match face {
+        0 => [1.0, -t, -s],
+        1 => [-1.0, -t, s],
+        2 => [s, 1.0, t],
+        3 => [s, -1.0, -t],
+        4 => [s, -t, 1.0],
+        _ => [-s, -t, -1.0],
+    };
+    let length = (dx * dx + dy * dy + dz * dz).sqrt();
+    let texel_area = (2.0 / size as f32).powi(2);
+    ([dx / length, dy / length, dz / length], texel_area / length.powi(3))
+}
+
+/// Selects the face and face coordinates (0..1) for a direction using the standard
+/// cube map major axis rules.
+fn cube_lookup([x, y, z]: [f32; 3]) -> (usize, f32, f32) {
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+        if x > 0.0 {
+            (0, -z, -y, ax)
+        } else {
+            (1, z, -y, ax)
+        }
+    } else if ay >= az {
+        if y > 0.0 {
+            (2, x, z, ay)
+        } else {
+            (3, x, -z, ay)
+        }
+    } else if z > 0.0 {
+        (4, x, -y, az)
+    } else {
+        (5, -x, -y, az)
+    };
+    (face, (sc / ma + 1.0) * 0.5, (tc / ma + 1.0) * 0.5)
+}
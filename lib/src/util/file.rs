@@ -1,13 +1,44 @@
-use std::{fs::File, path::Path};
+use std::{fs, fs::File, ops::Deref, path::Path};
 
 use anyhow::{Context, Result};
 use memmap2::{Mmap, MmapOptions};
 
+/// File contents, either memory mapped or read into memory.
+pub enum MappedFile {
+    Mmap(Mmap),
+    Buffer(Vec<u8>),
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            MappedFile::Mmap(map) => map,
+            MappedFile::Buffer(buf) => buf,
+        }
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] { self }
+}
+
 /// Opens a memory mapped file.
-pub fn map_file<P: AsRef<Path>>(path: P) -> Result<Mmap> {
-    let file = File::open(&path)
-        .with_context(|| format!("Failed to open file '{}'", path.as_ref().display()))?;
-    let map = unsafe { MmapOptions::new().map(&file) }
-        .with_context(|| format!("Failed to mmap file: '{}'", path.as_ref().display()))?;
-    Ok(map)
+///
+/// Falls back to reading the whole file when mapping fails, as it can on some
+/// network shares and virtual filesystems.
+pub fn map_file<P: AsRef<Path>>(path: P) -> Result<MappedFile> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).with_context(|| format!("Failed to open file '{}'", path.display()))?;
+    match unsafe { MmapOptions::new().map(&file) } {
+        Ok(map) => Ok(MappedFile::Mmap(map)),
+        Err(e) => {
+            log::debug!("Failed to mmap file '{}', reading instead: {e}", path.display());
+            let buf = fs::read(path)
+                .with_context(|| format!("Failed to read file '{}'", path.display()))?;
+            Ok(MappedFile::Buffer(buf))
+        }
+    }
 }
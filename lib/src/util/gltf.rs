@@ -0,0 +1,1907 @@
+//! Model conversion to glTF, or to USDZ via [`usd`].
+
+use std::{
+    collections::HashMap,
+    fs,
+    fs::DirBuilder,
+    io::{BufWriter, Cursor, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use gltf_json as json;
+use image::{imageops::FilterType, ColorType};
+use json::validation::Checked::Valid;
+use png::SrgbRenderingIntent;
+use serde_json::json;
+use uuid::Uuid;
+use zerocopy::LittleEndian;
+
+use crate::{
+    format::{
+        cmdl::{
+            accessor_layout, attribute_bounds, bone_indices, generate_tangents, joints_format,
+            normalize_weights, read_float_attribute, widen_half_floats, AccessorComponentType,
+            AccessorType, CMaterialDataInner, CMaterialTextureTokenData, CRenderMesh, EBufferType,
+            EMaterialDataId, EMaterialDataType, EVertexComponent, EVertexDataFormat, ModelData,
+        },
+        foot::{locate_asset_id, locate_meta, locate_names},
+        mcon::ModConData,
+        txtr::{decode_texture_file, DecodedTexture, ETextureWrap},
+        CTransform4f, CVector3f,
+    },
+    util::{file::map_file, usd},
+};
+
+/// How [`ConvertOptions::scale`] is applied.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ScaleMode {
+    /// Wrap the scene in a root node with a scale matrix.
+    #[default]
+    Node,
+    /// Scale vertex positions and bounds directly.
+    Bake,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ModelFormat {
+    /// A `.gltf` with separate buffers and PNG textures.
+    #[default]
+    Gltf,
+    /// A USD stage and its textures, packaged into a `.usdz`.
+    Usdz,
+}
+
+impl FromStr for ModelFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gltf" => Ok(ModelFormat::Gltf),
+            "usdz" => Ok(ModelFormat::Usdz),
+            _ => Err(format!("Invalid model format '{s}' (expected gltf or usdz)")),
+        }
+    }
+}
+
+/// How textures referenced by materials are written.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum ImageOutput {
+    /// PNG files next to the .gltf.
+    Files,
+    /// Base64 data URIs inside the .gltf.
+    Embed,
+    /// Not converted at all.
+    Skip,
+}
+
+impl FromStr for ScaleMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "node" => Ok(ScaleMode::Node),
+            "bake" => Ok(ScaleMode::Bake),
+            _ => Err(format!("Invalid scale mode '{s}' (expected node or bake)")),
+        }
+    }
+}
+
+/// Model conversion settings, mirroring the flags of `retrotool cmdl convert`.
+#[derive(Clone, Debug)]
+pub struct ConvertOptions {
+    /// Level of detail to export; `None` is LOD 0, the highest detail.
+    pub lod: Option<usize>,
+    /// Export every level of detail as a separate node group.
+    pub all_lods: bool,
+    /// Output file name within the output directory, instead of the input file stem.
+    pub output: Option<PathBuf>,
+    pub format: ModelFormat,
+    /// Write all vertex and index data into a single `.bin` buffer.
+    pub single_buffer: bool,
+    /// Copyright notice embedded in the glTF asset metadata.
+    pub copyright: Option<String>,
+    /// Keep bone weights as stored instead of rescaling them to sum to 1.0.
+    pub no_normalize_weights: bool,
+    pub scale: f32,
+    pub scale_mode: ScaleMode,
+    /// Don't generate tangents for normal mapped meshes without a tangent stream.
+    pub no_generate_tangents: bool,
+    /// Embed textures as base64 data URIs instead of writing PNG files.
+    pub embed_images: bool,
+    /// Skip converting textures; materials still reference the PNG files.
+    pub no_textures: bool,
+    /// Only export these meshes.
+    pub select_mesh: Option<Vec<usize>>,
+    /// Skip these meshes.
+    pub exclude_mesh: Option<Vec<usize>>,
+    /// Log each material data entry that isn't mapped to glTF.
+    pub warn_unknown_material_data: bool,
+    /// Export every material as unlit (`KHR_materials_unlit`).
+    pub unlit_all: bool,
+    /// Downscale textures whose longest side exceeds this many pixels.
+    pub max_texture_size: Option<u32>,
+    /// Merge meshes sharing a material into a single primitive each.
+    pub flatten: bool,
+    /// Fail on unknown chunks instead of skipping them with a warning.
+    pub strict: bool,
+    /// Center the model's bounds on the origin, recording the offset in the scene extras.
+    pub center_origin: bool,
+    /// Compute position accessor bounds from the vertices instead of the model bounds.
+    pub json_accessors_minmax: bool,
+    /// Also export vertex streams without a standard glTF semantic as custom attributes.
+    pub include_unknown_attributes: bool,
+    /// Also export layers 1 and 2 of layered textures, described in the material extras.
+    pub layered_textures: bool,
+    /// MCON placing the model, to export every placement.
+    pub instances: Option<PathBuf>,
+    /// With `instances`, write a node per placement instead of using `EXT_mesh_gpu_instancing`.
+    pub no_instancing: bool,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            lod: None,
+            all_lods: false,
+            output: None,
+            format: ModelFormat::Gltf,
+            single_buffer: false,
+            copyright: None,
+            no_normalize_weights: false,
+            scale: 1.0,
+            scale_mode: ScaleMode::Node,
+            no_generate_tangents: false,
+            embed_images: false,
+            no_textures: false,
+            select_mesh: None,
+            exclude_mesh: None,
+            warn_unknown_material_data: false,
+            unlit_all: false,
+            max_texture_size: None,
+            flatten: false,
+            strict: false,
+            center_origin: false,
+            json_accessors_minmax: false,
+            include_unknown_attributes: false,
+            layered_textures: false,
+            instances: None,
+            no_instancing: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct VertexBufferAttribute {
+    pub in_offset: u32,
+    pub out_offset: u32,
+    pub in_format: EVertexDataFormat,
+    pub in_size: u32,
+    pub out_format: EVertexDataFormat,
+    pub component: EVertexComponent,
+}
+
+#[derive(Debug, Clone, Default)]
+struct VertexBufferInfo {
+    pub vertex_count: u32,
+    pub in_stride: u32,
+    pub out_stride: u32,
+    pub attributes: Vec<VertexBufferAttribute>,
+}
+
+/// Converts the model file at `input` into `out_dir`, returning the path of the written
+/// `.gltf` or `.usdz`. Textures are read from `.TXTR` files next to the model.
+pub fn convert_model(input: &Path, out_dir: &Path, options: &ConvertOptions) -> Result<PathBuf> {
+    ensure!(options.scale.is_finite() && options.scale > 0.0, "Scale must be positive");
+    ensure!(options.max_texture_size != Some(0), "Max texture size must be positive");
+    ensure!(
+        !(options.embed_images && options.no_textures),
+        "--embed-images and --no-textures are mutually exclusive"
+    );
+    let usdz = options.format == ModelFormat::Usdz;
+    ensure!(!(usdz && options.all_lods), "--all-lods is not supported with --format usdz");
+    ensure!(!(usdz && options.embed_images), "--embed-images is not supported with --format usdz");
+    ensure!(
+        !(usdz && options.instances.is_some()),
+        "--instances is not supported with --format usdz"
+    );
+    ensure!(
+        !(usdz && options.layered_textures),
+        "--layered-textures is not supported with --format usdz"
+    );
+    // The stage has nowhere to record the offset, so the original placement would be lost
+    ensure!(
+        !(usdz && options.center_origin),
+        "--center-origin is not supported with --format usdz"
+    );
+    ensure!(
+        !(options.all_lods && options.instances.is_some()),
+        "--all-lods and --instances are mutually exclusive"
+    );
+    ensure!(
+        !options.no_instancing || options.instances.is_some(),
+        "--no-instancing requires --instances"
+    );
+    // USDZ packages convert the referenced textures themselves
+    let image_output = if options.no_textures || usdz {
+        ImageOutput::Skip
+    } else if options.embed_images {
+        ImageOutput::Embed
+    } else {
+        ImageOutput::Files
+    };
+    let data = map_file(input)?;
+    let dir = input.parent().unwrap_or(Path::new("."));
+    let meta = locate_meta::<LittleEndian>(&data)?;
+    let model_name = model_name(input, &data);
+    let mut model = if options.strict {
+        ModelData::<LittleEndian>::slice_strict(&data, meta)?
+    } else {
+        ModelData::<LittleEndian>::slice(&data, meta)?
+    };
+    if options.select_mesh.is_some() || options.exclude_mesh.is_some() {
+        let mesh_count = model.mesh.meshes.len();
+        for &idx in options.exclude_mesh.iter().flatten() {
+            ensure!(
+                idx < mesh_count,
+                "Mesh {idx} out of range (valid: 0-{})",
+                mesh_count.saturating_sub(1)
+            );
+        }
+        let mut selected = options.select_mesh.clone().unwrap_or_else(|| (0..mesh_count).collect());
+        selected.retain(|idx| !options.exclude_mesh.iter().flatten().any(|e| e == idx));
+        ensure!(!selected.is_empty(), "No meshes selected for export");
+        model.retain_meshes(&selected)?;
+        log::info!("Exporting {} of {mesh_count} meshes", model.mesh.meshes.len());
+    }
+    if options.flatten {
+        ensure!(!options.all_lods, "--flatten and --all-lods are mutually exclusive");
+        // Merged meshes can't be split between LODs, so drop the others first
+        let lod = options.lod.unwrap_or_default();
+        if let Some(indices) = model.mesh.lod_meshes(lod) {
+            model.retain_meshes(&indices)?;
+        }
+        let mesh_count = model.mesh.meshes.len();
+        model.flatten_meshes()?;
+        log::info!("Flattened {mesh_count} meshes into {}", model.mesh.meshes.len());
+    }
+    let ModelData { mut head, mtrl, mesh, vbuf, ibuf, mut vtx_buffers, idx_buffers, .. } = model;
+    let instances = match &options.instances {
+        Some(path) => Some(model_instances(path, &data)?),
+        None => None,
+    };
+
+    // Build buffer to component index
+    let mut buf_infos: Vec<VertexBufferInfo> = Vec::with_capacity(vtx_buffers.len());
+    for info in &vbuf.info {
+        let mut infos = info
+            .buffer_strides()?
+            .into_iter()
+            .map(|in_stride| VertexBufferInfo {
+                vertex_count: info.vertex_count,
+                in_stride,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        for component in &info.components {
+            let out = &mut infos[component.buffer_index as usize];
+            out.attributes.push(VertexBufferAttribute {
+                in_offset: component.offset,
+                out_offset: 0,
+                in_format: component.format,
+                in_size: component.format.byte_size(),
+                out_format: EVertexDataFormat::Unknown,
+                component: component.component,
+            });
+        }
+        buf_infos.append(&mut infos);
+    }
+
+    // Calculate out strides & offsets
+    for (idx, (info, buf)) in buf_infos.iter_mut().zip(&vtx_buffers).enumerate() {
+        info.attributes.sort_by_key(|c| c.in_offset);
+        let mut out_stride = 0u32;
+        for attribute in &mut info.attributes {
+            attribute.out_offset = out_stride;
+            attribute.out_format = if attribute.component == EVertexComponent::BoneIndices {
+                joints_format(
+                    buf,
+                    attribute.in_offset as usize,
+                    info.in_stride as usize,
+                    attribute.in_format,
+                )
+                .with_context(|| format!("Failed to export bone indices in vertex buffer {idx}"))?
+            } else {
+                attribute.in_format.widened()
+            };
+            out_stride += attribute.out_format.byte_size();
+        }
+        info.out_stride = out_stride;
+    }
+
+    // Rebuild vertex buffers if necessary
+    for (buf, info) in vtx_buffers.iter_mut().zip(&buf_infos) {
+        // Sanity check buffer size
+        ensure!(buf.len() == info.vertex_count as usize * info.in_stride as usize);
+        if info.attributes.iter().all(|a| a.in_format == a.out_format) {
+            // No rebuild necessary
+            continue;
+        }
+
+        let mut reader = Cursor::new(&**buf);
+        let mut out_buf: Vec<u8> = vec![0; info.vertex_count as usize * info.out_stride as usize];
+        let mut w = Cursor::new(&mut *out_buf);
+        let mut tmp_buf = vec![0u8; 16]; // max size of attribute
+        let mut in_buf = vec![0u8; info.in_stride as usize];
+        for _ in 0..info.vertex_count as usize {
+            reader.read_exact(&mut in_buf)?;
+            let mut r = Cursor::new(&*in_buf);
+            for attribute in &info.attributes {
+                r.set_position(attribute.in_offset as u64);
+                if attribute.in_format == attribute.out_format {
+                    let tmp = &mut tmp_buf[0..attribute.in_size as usize];
+                    r.read_exact(tmp)?;
+                    w.write_all(tmp)?;
+                } else {
+                    match (attribute.in_format, attribute.out_format) {
+                        (in_format, out_format)
+                            if in_format.is_half_float() && in_format.widened() == out_format =>
+                        {
+                            let tmp = &mut tmp_buf[0..attribute.in_size as usize];
+                            r.read_exact(tmp)?;
+                            w.write_all(&widen_half_floats(tmp, in_format)?)?;
+                        }
+                        (in_format, EVertexDataFormat::Rgba8Uint)
+                            if attribute.component == EVertexComponent::BoneIndices =>
+                        {
+                            let tmp = &mut tmp_buf[0..attribute.in_size as usize];
+                            r.read_exact(tmp)?;
+                            w.write_all(&bone_indices(tmp, in_format)?.map(|i| i as u8))?;
+                        }
+                        (in_format, EVertexDataFormat::Rgba16Uint)
+                            if attribute.component == EVertexComponent::BoneIndices =>
+                        {
+                            let tmp = &mut tmp_buf[0..attribute.in_size as usize];
+                            r.read_exact(tmp)?;
+                            for index in bone_indices(tmp, in_format)? {
+                                w.write_all(&(index as u16).to_le_bytes())?;
+                            }
+                        }
+                        (in_format, out_format) => {
+                            bail!("Unsupported conversion from {in_format:?} to {out_format:?}")
+                        }
+                    }
+                }
+            }
+        }
+        *buf = out_buf;
+    }
+
+    if !options.no_normalize_weights {
+        for (idx, (buf, info)) in vtx_buffers.iter_mut().zip(&buf_infos).enumerate() {
+            for attribute in &info.attributes {
+                if attribute.component != EVertexComponent::BoneWeights {
+                    continue;
+                }
+                let adjusted = normalize_weights(
+                    buf,
+                    attribute.out_offset as usize,
+                    info.out_stride as usize,
+                    attribute.out_format,
+                )
+                .with_context(|| format!("Failed to normalize weights in vertex buffer {idx}"))?;
+                if adjusted > 0 {
+                    log::info!("Normalized bone weights for {adjusted} vertices in buffer {idx}");
+                }
+            }
+        }
+    }
+
+    if options.scale != 1.0 && options.scale_mode == ScaleMode::Bake {
+        for (idx, (buf, info)) in vtx_buffers.iter_mut().zip(&buf_infos).enumerate() {
+            for attribute in &info.attributes {
+                if attribute.component != EVertexComponent::Position {
+                    continue;
+                }
+                transform_positions(
+                    buf,
+                    attribute.out_offset as usize,
+                    info.out_stride as usize,
+                    attribute.out_format,
+                    |_, v| v * options.scale,
+                )
+                .with_context(|| format!("Failed to scale positions in vertex buffer {idx}"))?;
+            }
+        }
+        head.bounds.min = head.bounds.min.to_array().map(|v| v * options.scale).into();
+        head.bounds.max = head.bounds.max.to_array().map(|v| v * options.scale).into();
+    }
+
+    // Offset subtracted from every position; adding it back restores the original placement
+    let mut origin_offset = None;
+    if options.center_origin {
+        let (min, max) = (head.bounds.min.to_array(), head.bounds.max.to_array());
+        let center = [0, 1, 2].map(|i| (min[i] + max[i]) / 2.0);
+        for (idx, (buf, info)) in vtx_buffers.iter_mut().zip(&buf_infos).enumerate() {
+            for attribute in &info.attributes {
+                if attribute.component != EVertexComponent::Position {
+                    continue;
+                }
+                transform_positions(
+                    buf,
+                    attribute.out_offset as usize,
+                    info.out_stride as usize,
+                    attribute.out_format,
+                    |axis, v| v - center[axis],
+                )
+                .with_context(|| format!("Failed to center positions in vertex buffer {idx}"))?;
+            }
+        }
+        head.bounds.min = [0, 1, 2].map(|i| min[i] - center[i]).into();
+        head.bounds.max = [0, 1, 2].map(|i| max[i] - center[i]).into();
+        log::info!("Centered model on origin, offset {center:?}");
+        origin_offset = Some(center);
+    }
+
+    // Generated tangent data for each vertex buffer group that needs it
+    let mut tangent_buffers: Vec<(usize, Vec<u8>)> = Vec::new();
+    if !options.no_generate_tangents {
+        let mut first_buf = 0usize;
+        for (vtx_buf_idx, buf_info) in vbuf.info.iter().enumerate() {
+            let group = first_buf..first_buf + buf_info.buffer_count();
+            first_buf = group.end;
+            if buf_info.components.iter().any(|c| c.component == EVertexComponent::Tangent0) {
+                continue;
+            }
+            let meshes = mesh
+                .meshes
+                .iter()
+                .filter(|m| {
+                    m.vtx_buf_idx as usize == vtx_buf_idx
+                        && mtrl.materials.get(m.material_idx as usize).is_some_and(|mat| {
+                            mat.data.iter().any(|d| {
+                                matches!(d.data_id, EMaterialDataId::NMAP | EMaterialDataId::NRML)
+                            })
+                        })
+                })
+                .collect::<Vec<_>>();
+            if meshes.is_empty() {
+                continue;
+            }
+            match build_tangents(
+                &vtx_buffers[group.clone()],
+                &buf_infos[group],
+                &meshes,
+                &ibuf.info,
+                &idx_buffers,
+            ) {
+                Ok(data) => {
+                    log::info!("Generated tangents for vertex buffer {vtx_buf_idx}");
+                    tangent_buffers.push((vtx_buf_idx, data));
+                }
+                Err(e) => {
+                    log::warn!("Failed to generate tangents for vertex buffer {vtx_buf_idx}: {e:#}")
+                }
+            }
+        }
+    }
+
+    // Translations, then rotations, then scales of every placement, for EXT_mesh_gpu_instancing
+    let instance_buffer = match &instances {
+        Some(transforms) if !options.no_instancing => Some(instance_data(transforms)),
+        _ => None,
+    };
+
+    let gltf_path = match &options.output {
+        Some(output) if output.extension().is_some() => out_dir.join(output),
+        Some(output) => out_dir.join(output).with_extension("gltf"),
+        None => out_dir
+            .join(Path::new(input.file_stem().unwrap_or("out".as_ref())).with_extension("gltf")),
+    };
+    let out_stem = gltf_path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let out_dir = gltf_path.parent().unwrap_or(out_dir).to_path_buf();
+
+    DirBuilder::new().recursive(true).create(&out_dir)?;
+    let mut json_buffers = Vec::with_capacity(vtx_buffers.len() + idx_buffers.len());
+    // Output buffer index and byte offset for each vertex buffer, followed by each index buffer
+    let mut buffer_locations = Vec::with_capacity(vtx_buffers.len() + idx_buffers.len());
+    if options.single_buffer {
+        let mut out_buf = Vec::new();
+        for buf in vtx_buffers
+            .iter()
+            .chain(&idx_buffers)
+            .chain(tangent_buffers.iter().map(|(_, b)| b))
+            .chain(&instance_buffer)
+        {
+            // Keep every buffer view 4-byte aligned
+            out_buf.resize((out_buf.len() + 3) & !3, 0);
+            buffer_locations.push((0u32, out_buf.len() as u32));
+            out_buf.extend_from_slice(buf);
+        }
+        let file_name = format!("{out_stem}.bin");
+        if !usdz {
+            fs::write(out_dir.join(&file_name), &out_buf)?;
+        }
+        json_buffers.push(json::Buffer {
+            byte_length: out_buf.len() as u32,
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: None,
+            uri: Some(file_name),
+        });
+    } else {
+        for (idx, buf) in vtx_buffers.iter().enumerate() {
+            let file_name = format!("{out_stem}_vtxbuf{idx}.bin");
+            if !usdz {
+                fs::write(out_dir.join(&file_name), buf)?;
+            }
+            buffer_locations.push((json_buffers.len() as u32, 0u32));
+            json_buffers.push(json::Buffer {
+                byte_length: buf.len() as u32,
+                extensions: Default::default(),
+                extras: Default::default(),
+                name: None,
+                uri: Some(file_name),
+            });
+        }
+        for (idx, buf) in idx_buffers.iter().enumerate() {
+            let file_name = format!("{out_stem}_idxbuf{idx}.bin");
+            if !usdz {
+                fs::write(out_dir.join(&file_name), buf)?;
+            }
+            buffer_locations.push((json_buffers.len() as u32, 0u32));
+            json_buffers.push(json::Buffer {
+                byte_length: buf.len() as u32,
+                extensions: Default::default(),
+                extras: Default::default(),
+                name: None,
+                uri: Some(file_name),
+            });
+        }
+        for (idx, buf) in &tangent_buffers {
+            let file_name = format!("{out_stem}_tangents{idx}.bin");
+            if !usdz {
+                fs::write(out_dir.join(&file_name), buf)?;
+            }
+            buffer_locations.push((json_buffers.len() as u32, 0u32));
+            json_buffers.push(json::Buffer {
+                byte_length: buf.len() as u32,
+                extensions: Default::default(),
+                extras: Default::default(),
+                name: None,
+                uri: Some(file_name),
+            });
+        }
+        if let Some(buf) = &instance_buffer {
+            let file_name = format!("{out_stem}_instances.bin");
+            fs::write(out_dir.join(&file_name), buf)?;
+            buffer_locations.push((json_buffers.len() as u32, 0u32));
+            json_buffers.push(json::Buffer {
+                byte_length: buf.len() as u32,
+                extensions: Default::default(),
+                extras: Default::default(),
+                name: None,
+                uri: Some(file_name),
+            });
+        }
+    }
+
+    let mut cur_buf = 0usize;
+    let mut json_buffer_views = Vec::new();
+    let mut json_accessors = Vec::new();
+    let mut json_attributes: Vec<
+        HashMap<json::validation::Checked<json::mesh::Semantic>, json::Index<json::Accessor>>,
+    > = Vec::new();
+    for buf_info in &vbuf.info {
+        let num_buffers = buf_info.buffer_count();
+        let mut attribute_map = HashMap::new();
+        for idx in 0..num_buffers {
+            let target_vtx_buf = cur_buf + idx;
+            let info = &buf_infos[target_vtx_buf];
+            let (buffer, byte_offset) = buffer_locations[target_vtx_buf];
+            json_buffer_views.push(json::buffer::View {
+                buffer: json::Index::new(buffer),
+                byte_length: vtx_buffers[target_vtx_buf].len() as u32,
+                byte_offset: (byte_offset != 0).then_some(byte_offset),
+                byte_stride: Some(info.out_stride),
+                extensions: Default::default(),
+                extras: Default::default(),
+                name: Some(format!("Vertex buffer view {target_vtx_buf}")),
+                target: Some(Valid(json::buffer::Target::ArrayBuffer)),
+            });
+            for attribute in &info.attributes {
+                ensure!(
+                    !attribute.out_format.is_half_float(),
+                    "{:?} attribute was not widened from {:?}",
+                    attribute.component,
+                    attribute.in_format
+                );
+                let semantic = attribute_semantic(attribute.component);
+                if matches!(semantic, json::mesh::Semantic::Extras(_))
+                    && !options.include_unknown_attributes
+                {
+                    continue;
+                }
+                let (min, max) = if attribute.component != EVertexComponent::Position {
+                    (None, None)
+                } else if options.json_accessors_minmax {
+                    let bounds = attribute_bounds(
+                        &vtx_buffers[target_vtx_buf],
+                        attribute.out_offset as usize,
+                        info.out_stride as usize,
+                        attribute.out_format,
+                    )
+                    .with_context(|| {
+                        format!("Failed to compute bounds of vertex buffer {target_vtx_buf}")
+                    })?;
+                    match bounds {
+                        Some((min, max)) => (Some(json!(min)), Some(json!(max))),
+                        None => (None, None),
+                    }
+                } else {
+                    (
+                        Some(json!([head.bounds.min.x, head.bounds.min.y, head.bounds.min.z])),
+                        Some(json!([head.bounds.max.x, head.bounds.max.y, head.bounds.max.z])),
+                    )
+                };
+                let (component_type, type_) =
+                    accessor_layout(attribute.out_format, attribute.component)?;
+                let accessor = json::Accessor {
+                    buffer_view: Some(json::Index::new(target_vtx_buf as u32)),
+                    byte_offset: attribute.out_offset,
+                    count: info.vertex_count,
+                    component_type: Valid(json::accessor::GenericComponentType(
+                        match component_type {
+                            AccessorComponentType::I8 => json::accessor::ComponentType::I8,
+                            AccessorComponentType::U8 => json::accessor::ComponentType::U8,
+                            AccessorComponentType::I16 => json::accessor::ComponentType::I16,
+                            AccessorComponentType::U16 => json::accessor::ComponentType::U16,
+                            AccessorComponentType::U32 => json::accessor::ComponentType::U32,
+                            AccessorComponentType::F32 => json::accessor::ComponentType::F32,
+                        },
+                    )),
+                    extensions: Default::default(),
+                    extras: Default::default(),
+                    type_: Valid(match type_ {
+                        AccessorType::Scalar => json::accessor::Type::Scalar,
+                        AccessorType::Vec2 => json::accessor::Type::Vec2,
+                        AccessorType::Vec3 => json::accessor::Type::Vec3,
+                        AccessorType::Vec4 => json::accessor::Type::Vec4,
+                    }),
+                    min,
+                    max,
+                    name: Some(format!(
+                        "{:?} {:?} => {:?}",
+                        attribute.component, attribute.in_format, attribute.out_format
+                    )),
+                    normalized: attribute.out_format.normalized(),
+                    sparse: None,
+                };
+                let accessor_idx = json_accessors.len();
+                json_accessors.push(accessor);
+                attribute_map.insert(Valid(semantic), json::Index::new(accessor_idx as u32));
+            }
+        }
+        json_attributes.push(attribute_map);
+        cur_buf += num_buffers;
+    }
+
+    for (idx, _) in ibuf.info.iter().enumerate() {
+        let target_buf = cur_buf + idx;
+        let (buffer, byte_offset) = buffer_locations[target_buf];
+        json_buffer_views.push(json::buffer::View {
+            buffer: json::Index::new(buffer),
+            byte_length: idx_buffers[idx].len() as u32,
+            byte_offset: (byte_offset != 0).then_some(byte_offset),
+            byte_stride: None,
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: Some(format!("Index buffer view {}", target_buf - cur_buf)),
+            target: Some(Valid(json::buffer::Target::ElementArrayBuffer)),
+        });
+    }
+
+    for (idx, (vtx_buf_idx, buf)) in tangent_buffers.iter().enumerate() {
+        let (buffer, byte_offset) = buffer_locations[cur_buf + idx_buffers.len() + idx];
+        let view_idx = json_buffer_views.len() as u32;
+        json_buffer_views.push(json::buffer::View {
+            buffer: json::Index::new(buffer),
+            byte_length: buf.len() as u32,
+            byte_offset: (byte_offset != 0).then_some(byte_offset),
+            byte_stride: Some(16),
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: Some(format!("Tangent buffer view {vtx_buf_idx}")),
+            target: Some(Valid(json::buffer::Target::ArrayBuffer)),
+        });
+        let accessor_idx = json_accessors.len() as u32;
+        json_accessors.push(json::Accessor {
+            buffer_view: Some(json::Index::new(view_idx)),
+            byte_offset: 0,
+            count: buf.len() as u32 / 16,
+            component_type: Valid(json::accessor::GenericComponentType(
+                json::accessor::ComponentType::F32,
+            )),
+            extensions: Default::default(),
+            extras: Default::default(),
+            type_: Valid(json::accessor::Type::Vec4),
+            min: None,
+            max: None,
+            name: Some(format!("Generated tangents {vtx_buf_idx}")),
+            normalized: false,
+            sparse: None,
+        });
+        json_attributes[*vtx_buf_idx]
+            .insert(Valid(json::mesh::Semantic::Tangents), json::Index::new(accessor_idx));
+    }
+
+    let mut texture_export = TextureExport {
+        in_dir: dir,
+        out_dir: &out_dir,
+        image_output,
+        max_size: options.max_texture_size,
+        uv_sets: u32::MAX,
+        map: HashMap::new(),
+        samplers: Vec::new(),
+        textures: Vec::new(),
+        images: Vec::new(),
+    };
+    let mut json_materials = Vec::with_capacity(mtrl.materials.len());
+    // Material data entries not mapped to glTF, with their occurrence counts
+    let mut unknown_data: Vec<(EMaterialDataId, EMaterialDataType, usize)> = Vec::new();
+    let mut any_unlit = false;
+    for (mat_idx, mat) in mtrl.materials.iter().enumerate() {
+        // UV sets provided by every mesh using the material
+        let uv_sets = mesh
+            .meshes
+            .iter()
+            .filter(|m| m.material_idx as usize == mat_idx)
+            .map(|m| vbuf.info.get(m.vtx_buf_idx as usize).map_or(0, |i| i.tex_coord_count()))
+            .min()
+            .unwrap_or(u32::MAX);
+        texture_export.uv_sets = uv_sets;
+        let mut json_material = json::Material {
+            alpha_cutoff: None,
+            alpha_mode: Valid(json::material::AlphaMode::Opaque),
+            double_sided: false,
+            name: Some(mat.name.clone()),
+            pbr_metallic_roughness: json::material::PbrMetallicRoughness {
+                base_color_factor: json::material::PbrBaseColorFactor([0.0, 0.0, 0.0, 0.0]),
+                base_color_texture: None,
+                metallic_factor: json::material::StrengthFactor(0.0),
+                roughness_factor: json::material::StrengthFactor(0.0),
+                metallic_roughness_texture: None,
+                extensions: None,
+                extras: None,
+            },
+            normal_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+            emissive_factor: Default::default(),
+            extensions: None,
+            extras: None,
+        };
+        // Opacity is applied once the base color is known
+        let mut diffuse_color = false;
+        let mut base_color_id: Option<Uuid> = None;
+        let mut opacity: Option<f32> = None;
+        let mut opacity_texture: Option<Uuid> = None;
+        let mut layered = serde_json::Map::new();
+        for data in &mat.data {
+            // glTF has a single texture per input, so layer 0 is bound below and the full
+            // set of layers is only described in the extras
+            if let CMaterialDataInner::LayeredTexture(texture) = &data.data {
+                if options.layered_textures {
+                    let mut layers = Vec::new();
+                    for (layer, token, color) in texture.layers() {
+                        let info = texture_export.add(token)?;
+                        layers.push(json!({
+                            "layer": layer,
+                            "texture": info.index.value(),
+                            "tex_coord": info.tex_coord,
+                            "color": color.to_array(),
+                        }));
+                    }
+                    layered.insert(
+                        format!("{:?}", data.data_id),
+                        json!({
+                            "flags": texture.base.flags,
+                            "unk": texture.base.unk,
+                            "layers": layers,
+                        }),
+                    );
+                }
+            }
+            // Other data types fall through to the unknown data handling below
+            match (data.data_id, &data.data) {
+                (EMaterialDataId::OPCT, CMaterialDataInner::Texture(texture)) => {
+                    opacity_texture = Some(texture.id);
+                    continue;
+                }
+                (EMaterialDataId::OPCS, CMaterialDataInner::Scalar(value)) => {
+                    opacity = Some(*value);
+                    continue;
+                }
+                _ => {}
+            }
+            match data.data_id {
+                EMaterialDataId::DIFT => match &data.data {
+                    CMaterialDataInner::Texture(texture) => {
+                        base_color_id = Some(texture.id);
+                        json_material.pbr_metallic_roughness.base_color_texture =
+                            Some(texture_export.add(texture)?);
+                    }
+                    _ => bail!("Unsupported data type for DIFT"),
+                },
+                EMaterialDataId::DIFC => match &data.data {
+                    CMaterialDataInner::Color(color) => {
+                        diffuse_color = true;
+                        json_material.pbr_metallic_roughness.base_color_factor =
+                            json::material::PbrBaseColorFactor([
+                                color.r, color.g, color.b, color.a,
+                            ]);
+                    }
+                    _ => bail!("Unsupported data type for DIFC"),
+                },
+                EMaterialDataId::ICAN => match &data.data {
+                    CMaterialDataInner::Texture(texture) => {
+                        json_material.emissive_texture = Some(texture_export.add(texture)?);
+                    }
+                    _ => bail!("Unsupported data type for ICAN"),
+                },
+                EMaterialDataId::ICNC => match &data.data {
+                    CMaterialDataInner::Color(color) => {
+                        json_material.emissive_factor =
+                            json::material::EmissiveFactor([color.r, color.g, color.b]);
+                    }
+                    _ => bail!("Unsupported data type for ICNC"),
+                },
+                EMaterialDataId::NMAP => match &data.data {
+                    CMaterialDataInner::Texture(texture) => {
+                        let info = texture_export.add(texture)?;
+                        json_material.normal_texture = Some(json::material::NormalTexture {
+                            index: info.index,
+                            scale: 1.0,
+                            tex_coord: info.tex_coord,
+                            extensions: None,
+                            extras: None,
+                        });
+                    }
+                    _ => bail!("Unsupported data type for NMAP"),
+                },
+                EMaterialDataId::BCLR => match &data.data {
+                    CMaterialDataInner::Texture(texture) => {
+                        base_color_id = Some(texture.id);
+                        // json_material.pbr_metallic_roughness.metallic_factor =
+                        //     json::material::StrengthFactor(1.0); // metal
+                        json_material.pbr_metallic_roughness.base_color_texture =
+                            Some(texture_export.add(texture)?);
+                    }
+                    _ => bail!("Unsupported data type for BCLR"),
+                },
+                EMaterialDataId::METL => match &data.data {
+                    CMaterialDataInner::Texture(texture) => {
+                        json_material.pbr_metallic_roughness.metallic_factor =
+                            json::material::StrengthFactor(1.0); // metal
+                        json_material.pbr_metallic_roughness.roughness_factor =
+                            json::material::StrengthFactor(1.0); // metal
+                        json_material.pbr_metallic_roughness.metallic_roughness_texture =
+                            Some(texture_export.add(texture)?);
+                    }
+                    _ => bail!("Unsupported data type for METL"),
+                },
+                // Layered textures only bind layer 0, see --layered-textures
+                EMaterialDataId::BCRL => match &data.data {
+                    CMaterialDataInner::LayeredTexture(texture) => {
+                        base_color_id = Some(texture.textures[0].id);
+                        // json_material.pbr_metallic_roughness.metallic_factor =
+                        //     json::material::StrengthFactor(1.0); // metal
+                        json_material.pbr_metallic_roughness.base_color_texture =
+                            Some(texture_export.add(&texture.textures[0])?);
+                    }
+                    _ => bail!("Unsupported data type for BCLR"),
+                },
+                EMaterialDataId::MTLL => match &data.data {
+                    CMaterialDataInner::LayeredTexture(texture) => {
+                        json_material.pbr_metallic_roughness.metallic_factor =
+                            json::material::StrengthFactor(1.0); // metal
+                        json_material.pbr_metallic_roughness.roughness_factor =
+                            json::material::StrengthFactor(1.0); // metal
+                        json_material.pbr_metallic_roughness.metallic_roughness_texture =
+                            Some(texture_export.add(&texture.textures[0])?);
+                    }
+                    _ => bail!("Unsupported data type for MTLL"),
+                },
+                EMaterialDataId::NRML => match &data.data {
+                    CMaterialDataInner::LayeredTexture(texture) => {
+                        let info = texture_export.add(&texture.textures[0])?;
+                        json_material.normal_texture = Some(json::material::NormalTexture {
+                            index: info.index,
+                            scale: 1.0,
+                            tex_coord: info.tex_coord,
+                            extensions: None,
+                            extras: None,
+                        });
+                    }
+                    _ => bail!("Unsupported data type for NRML"),
+                },
+                id => {
+                    if options.warn_unknown_material_data {
+                        log::warn!(
+                            "Material {mat_idx} '{}': ignoring {id:?} ({:?}, {:#X}): {:?}",
+                            mat.name,
+                            data.data_type,
+                            id as u32,
+                            data.data
+                        );
+                        match unknown_data
+                            .iter_mut()
+                            .find(|(i, ty, _)| *i == id && *ty == data.data_type)
+                        {
+                            Some((_, _, count)) => *count += 1,
+                            None => unknown_data.push((id, data.data_type, 1)),
+                        }
+                    }
+                    continue;
+                }
+            }
+        }
+        // Materials with emissive data but no base color or normal map (HUD elements,
+        // icons) are treated as unlit. Unlit viewers only draw the base color, so the
+        // emissive data is moved there.
+        let has_base_color = diffuse_color || base_color_id.is_some();
+        let has_emissive =
+            json_material.emissive_texture.is_some() || json_material.emissive_factor.0 != [0.0; 3];
+        if options.unlit_all
+            || (has_emissive && !has_base_color && json_material.normal_texture.is_none())
+        {
+            if !has_base_color && has_emissive {
+                let [r, g, b] = json_material.emissive_factor.0;
+                let pbr = &mut json_material.pbr_metallic_roughness;
+                pbr.base_color_texture = json_material.emissive_texture.take();
+                pbr.base_color_factor =
+                    json::material::PbrBaseColorFactor(if [r, g, b] == [0.0; 3] {
+                        [1.0; 4]
+                    } else {
+                        [r, g, b, 1.0]
+                    });
+                json_material.emissive_factor = Default::default();
+                diffuse_color = true;
+            }
+            json_material.extensions = Some(json::extensions::material::Material {
+                unlit: Some(json::extensions::material::Unlit {}),
+                ..Default::default()
+            });
+            any_unlit = true;
+        }
+        // glTF only reads opacity from the base color alpha, so alpha masking is only
+        // enabled when the material's opacity texture is its base color texture. A separate
+        // OPCT texture would need to be merged into the base color image, which isn't done,
+        // so those materials stay opaque.
+        if let Some(id) = opacity_texture {
+            if base_color_id == Some(id) {
+                json_material.alpha_mode = Valid(json::material::AlphaMode::Mask);
+                json_material.alpha_cutoff = Some(json::material::AlphaCutoff(0.5));
+            } else {
+                log::warn!(
+                    "Material {mat_idx} '{}': opacity texture {id} is not the base color, exporting as opaque",
+                    mat.name
+                );
+            }
+        }
+        if let Some(opacity) = opacity {
+            let alpha = &mut json_material.pbr_metallic_roughness.base_color_factor.0[3];
+            *alpha = if diffuse_color { *alpha * opacity } else { opacity };
+            if opacity < 1.0 {
+                json_material.alpha_mode = Valid(json::material::AlphaMode::Blend);
+                json_material.alpha_cutoff = None;
+            }
+        }
+        if !layered.is_empty() {
+            json_material.extras = Some(
+                serde_json::value::to_raw_value(&json!({ "layered_textures": layered }))
+                    .expect("Serialization error"),
+            );
+        }
+        json_materials.push(json_material);
+    }
+    if !unknown_data.is_empty() {
+        let total = unknown_data.iter().map(|(_, _, count)| count).sum::<usize>();
+        log::warn!("Ignored {total} material data entries:");
+        for (id, ty, count) in &unknown_data {
+            log::warn!("  {id:?} ({ty:?}): {count}");
+        }
+    }
+
+    let mesh_names = mesh
+        .meshes
+        .iter()
+        .enumerate()
+        .map(|(mesh_idx, mesh)| {
+            match mtrl.materials.get(mesh.material_idx as usize).map(|m| m.name.as_str()) {
+                Some(material_name) if !material_name.is_empty() => {
+                    format!("{model_name}_{mesh_idx}_{material_name}")
+                }
+                _ => format!("{model_name}_{mesh_idx}"),
+            }
+        })
+        .collect::<Vec<String>>();
+    if usdz {
+        let lod_count = mesh.lod_info.len();
+        let lod = options.lod.unwrap_or_default();
+        ensure!(lod == 0 || lod < lod_count, "LOD {lod} out of range (model has {lod_count} LODs)");
+        let mesh_indices = if lod_count == 0 {
+            (0..mesh.meshes.len()).collect()
+        } else {
+            mesh.lod_meshes(lod).ok_or_else(|| anyhow!("Invalid mesh ranges for LOD {lod}"))?
+        };
+        // First vertex buffer of each vertex buffer group
+        let group_starts = vbuf
+            .info
+            .iter()
+            .scan(0usize, |start, info| {
+                let group = *start..*start + info.buffer_count();
+                *start = group.end;
+                Some(group)
+            })
+            .collect::<Vec<_>>();
+        let mut usd_meshes = Vec::with_capacity(mesh_indices.len());
+        for idx in mesh_indices {
+            let Some(in_mesh) = mesh.meshes.get(idx) else {
+                bail!("LOD {lod} references invalid mesh {idx}");
+            };
+            let group =
+                group_starts.get(in_mesh.vtx_buf_idx as usize).cloned().ok_or_else(|| {
+                    anyhow!("Mesh {idx} references invalid vertex buffer {}", in_mesh.vtx_buf_idx)
+                })?;
+            let idx_buf_idx = in_mesh.idx_buf_idx as usize;
+            let (Some(&idx_type), Some(idx_buf)) =
+                (ibuf.info.get(idx_buf_idx), idx_buffers.get(idx_buf_idx))
+            else {
+                bail!("Mesh {idx} references invalid index buffer {idx_buf_idx}");
+            };
+            let (Some(vtx_bufs), Some(infos)) =
+                (vtx_buffers.get(group.clone()), buf_infos.get(group))
+            else {
+                bail!("Mesh {idx} references missing GPU buffers");
+            };
+            let usd_mesh = usd_mesh(&mesh_names[idx], in_mesh, vtx_bufs, infos, idx_type, idx_buf)
+                .with_context(|| format!("Failed to export mesh {idx}"))?;
+            usd_meshes.push(usd_mesh);
+        }
+        let images = if options.no_textures { &[][..] } else { texture_export.images.as_slice() };
+        let stage = usd::Stage {
+            name: model_name,
+            scale: (options.scale != 1.0 && options.scale_mode == ScaleMode::Node)
+                .then_some(options.scale),
+            meshes: usd_meshes,
+            materials: json_materials
+                .iter()
+                .map(|material| {
+                    usd::Material::from_gltf(material, &texture_export.textures, images)
+                })
+                .collect(),
+        };
+        let mut files = vec![(format!("{out_stem}.usda"), stage.to_usda().into_bytes())];
+        for image in images {
+            let (Some(name), Some(uri)) = (&image.name, &image.uri) else {
+                continue;
+            };
+            let png_data = texture_to_png(dir, Uuid::parse_str(name)?, options.max_texture_size)?;
+            files.push((format!("textures/{uri}"), png_data));
+        }
+        let usdz_path = gltf_path.with_extension("usdz");
+        let writer = BufWriter::new(
+            fs::File::create(&usdz_path)
+                .with_context(|| format!("Failed to create file '{}'", usdz_path.display()))?,
+        );
+        usd::write_usdz(writer, &files)?;
+        log::info!("Wrote {}", usdz_path.display());
+        return Ok(usdz_path);
+    }
+
+    let mut json_primitives = Vec::with_capacity(mesh.meshes.len());
+    for (mesh_idx, mesh) in mesh.meshes.iter().enumerate() {
+        let index_type = ibuf.info[mesh.idx_buf_idx as usize];
+        let index_buf_idx = cur_buf as u32 + mesh.idx_buf_idx as u32;
+        let index_accessor_idx = json_accessors.len() as u32;
+        json_accessors.push(json::Accessor {
+            buffer_view: Some(json::Index::new(index_buf_idx)),
+            byte_offset: mesh.index_start
+                * match index_type {
+                    EBufferType::U8 => 1,
+                    EBufferType::U16 => 2,
+                    EBufferType::U32 => 4,
+                },
+            count: mesh.index_count,
+            component_type: Valid(json::accessor::GenericComponentType(match index_type {
+                EBufferType::U8 => json::accessor::ComponentType::U8,
+                EBufferType::U16 => json::accessor::ComponentType::U16,
+                EBufferType::U32 => json::accessor::ComponentType::U32,
+            })),
+            extensions: None,
+            extras: Default::default(),
+            type_: Valid(json::accessor::Type::Scalar),
+            min: None,
+            max: None,
+            name: Some(format!("{} indices", mesh_names[mesh_idx])),
+            normalized: false,
+            sparse: None,
+        });
+        json_primitives.push(json::mesh::Primitive {
+            attributes: json_attributes[mesh.vtx_buf_idx as usize].clone(),
+            extensions: None,
+            extras: Default::default(),
+            indices: Some(json::Index::new(index_accessor_idx)),
+            material: Some(json::Index::new(mesh.material_idx as u32)),
+            mode: Default::default(),
+            targets: None,
+        });
+    }
+
+    let lod_count = mesh.lod_info.len();
+    let lods = if options.all_lods && lod_count > 0 {
+        (0..lod_count).collect::<Vec<usize>>()
+    } else {
+        let lod = options.lod.unwrap_or_default();
+        ensure!(lod == 0 || lod < lod_count, "LOD {lod} out of range (model has {lod_count} LODs)");
+        vec![lod]
+    };
+    let mut json_meshes = Vec::new();
+    let mut json_scene_nodes = Vec::with_capacity(lods.len());
+    let mut json_nodes = Vec::new();
+    // Nodes referencing a mesh, the only ones EXT_mesh_gpu_instancing may extend
+    let mut json_mesh_nodes: Vec<json::Index<json::Node>> = Vec::new();
+    for &lod in &lods {
+        let mesh_indices = if lod_count == 0 {
+            (0..mesh.meshes.len()).collect()
+        } else {
+            let Some(indices) = mesh.lod_meshes(lod) else {
+                bail!("Invalid mesh ranges for LOD {lod}");
+            };
+            indices
+        };
+
+        // Group meshes sharing a vertex buffer into a single glTF mesh
+        let mut groups: Vec<(u8, Vec<usize>)> = Vec::new();
+        for idx in mesh_indices {
+            let Some(in_mesh) = mesh.meshes.get(idx) else {
+                bail!("LOD {lod} references invalid mesh {idx}");
+            };
+            match groups.iter_mut().find(|(vtx_buf_idx, _)| *vtx_buf_idx == in_mesh.vtx_buf_idx) {
+                Some((_, indices)) => indices.push(idx),
+                None => groups.push((in_mesh.vtx_buf_idx, vec![idx])),
+            }
+        }
+
+        let mut children = Vec::with_capacity(groups.len());
+        for (vtx_buf_idx, indices) in groups {
+            let name = if let [idx] = indices.as_slice() {
+                mesh_names[*idx].clone()
+            } else {
+                let mut material_indices =
+                    indices.iter().map(|&idx| mesh.meshes[idx].material_idx).collect::<Vec<_>>();
+                material_indices.sort_unstable();
+                material_indices.dedup();
+                match material_indices
+                    .as_slice()
+                    .first()
+                    .and_then(|&idx| mtrl.materials.get(idx as usize))
+                    .map(|m| m.name.as_str())
+                {
+                    Some(material_name)
+                        if material_indices.len() == 1 && !material_name.is_empty() =>
+                    {
+                        format!("{model_name}_vtx{vtx_buf_idx}_{material_name}")
+                    }
+                    _ => format!("{model_name}_vtx{vtx_buf_idx}"),
+                }
+            };
+            let json_mesh_idx = json_meshes.len() as u32;
+            json_meshes.push(json::Mesh {
+                extensions: None,
+                extras: Default::default(),
+                name: Some(name.clone()),
+                primitives: indices.iter().map(|&idx| json_primitives[idx].clone()).collect(),
+                weights: None,
+            });
+            children.push(json::Index::new(json_nodes.len() as u32));
+            json_mesh_nodes.push(json::Index::new(json_nodes.len() as u32));
+            json_nodes.push(json::Node {
+                camera: None,
+                children: None,
+                extensions: None,
+                extras: None,
+                matrix: None,
+                mesh: Some(json::Index::new(json_mesh_idx)),
+                name: Some(name),
+                rotation: None,
+                scale: None,
+                translation: None,
+                skin: None,
+                weights: None,
+            });
+        }
+        if options.all_lods && lod_count > 0 {
+            json_scene_nodes.push(json::Index::new(json_nodes.len() as u32));
+            json_nodes.push(json::Node {
+                camera: None,
+                children: Some(children),
+                extensions: None,
+                extras: None,
+                matrix: None,
+                mesh: None,
+                name: Some(format!("{model_name}_LOD{lod}")),
+                rotation: None,
+                scale: None,
+                translation: None,
+                skin: None,
+                weights: None,
+            });
+        } else {
+            json_scene_nodes.append(&mut children);
+        }
+    }
+    // Mesh nodes to extend with EXT_mesh_gpu_instancing, and the extension's attributes
+    let mut instancing = None;
+    if let (Some(transforms), Some(buf)) = (&instances, &instance_buffer) {
+        let count = transforms.len() as u32;
+        let (buffer, byte_offset) = *buffer_locations.last().unwrap();
+        let view = json::Index::new(json_buffer_views.len() as u32);
+        json_buffer_views.push(json::buffer::View {
+            buffer: json::Index::new(buffer),
+            byte_length: buf.len() as u32,
+            byte_offset: (byte_offset != 0).then_some(byte_offset),
+            byte_stride: None,
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: Some("Instance buffer view".to_string()),
+            target: None,
+        });
+        let mut accessor = |byte_offset: u32, type_: json::accessor::Type, name: &str| {
+            json_accessors.push(json::Accessor {
+                buffer_view: Some(view),
+                byte_offset,
+                count,
+                component_type: Valid(json::accessor::GenericComponentType(
+                    json::accessor::ComponentType::F32,
+                )),
+                extensions: None,
+                extras: Default::default(),
+                type_: Valid(type_),
+                min: None,
+                max: None,
+                name: Some(name.to_string()),
+                normalized: false,
+                sparse: None,
+            });
+            json_accessors.len() - 1
+        };
+        let attributes = json!({
+            "TRANSLATION": accessor(0, json::accessor::Type::Vec3, "Instance translations"),
+            "ROTATION": accessor(count * 12, json::accessor::Type::Vec4, "Instance rotations"),
+            "SCALE": accessor(count * 28, json::accessor::Type::Vec3, "Instance scales"),
+        });
+        log::info!("Instancing {count} placements with EXT_mesh_gpu_instancing");
+        instancing = Some((json_mesh_nodes, attributes));
+    } else if let Some(transforms) = &instances {
+        // A node per placement; the mesh nodes are duplicated, sharing their meshes
+        let mesh_nodes = std::mem::take(&mut json_scene_nodes);
+        let mut matrix_count = 0;
+        for (idx, xf) in transforms.iter().enumerate() {
+            let children = if idx == 0 {
+                mesh_nodes.clone()
+            } else {
+                mesh_nodes
+                    .iter()
+                    .map(|node| {
+                        let node = json_nodes[node.value()].clone();
+                        json_nodes.push(node);
+                        json::Index::new(json_nodes.len() as u32 - 1)
+                    })
+                    .collect()
+            };
+            // Some importers ignore node matrices, so placements are written as TRS where
+            // the scale allows it
+            let (translation, rotation, scale) = xf.decompose();
+            let trs = is_uniform(scale);
+            if !trs {
+                matrix_count += 1;
+            }
+            json_scene_nodes.push(json::Index::new(json_nodes.len() as u32));
+            json_nodes.push(json::Node {
+                camera: None,
+                children: Some(children),
+                extensions: None,
+                extras: None,
+                matrix: (!trs).then(|| xf.to_cols_array()),
+                mesh: None,
+                name: Some(format!("{model_name}_{idx}")),
+                rotation: trs.then_some(json::scene::UnitQuaternion([
+                    rotation.v.x,
+                    rotation.v.y,
+                    rotation.v.z,
+                    rotation.s,
+                ])),
+                scale: trs.then_some(scale.to_array()),
+                translation: trs.then_some(translation.to_array()),
+                skin: None,
+                weights: None,
+            });
+        }
+        if matrix_count > 0 {
+            log::warn!(
+                "{matrix_count} of {} placements have non-uniform or mirrored scale and are \
+                 written as node matrices",
+                transforms.len()
+            );
+        }
+    }
+    if options.scale != 1.0 && options.scale_mode == ScaleMode::Node {
+        let s = options.scale;
+        let root_idx = json::Index::new(json_nodes.len() as u32);
+        json_nodes.push(json::Node {
+            camera: None,
+            children: Some(std::mem::take(&mut json_scene_nodes)),
+            extensions: None,
+            extras: None,
+            matrix: Some([
+                s, 0.0, 0.0, 0.0, 0.0, s, 0.0, 0.0, 0.0, 0.0, s, 0.0, 0.0, 0.0, 0.0, 1.0,
+            ]),
+            mesh: None,
+            name: Some(format!("{model_name}_root")),
+            rotation: None,
+            scale: None,
+            translation: None,
+            skin: None,
+            weights: None,
+        });
+        json_scene_nodes.push(root_idx);
+    }
+
+    let json_root = json::Root {
+        accessors: json_accessors,
+        animations: vec![],
+        asset: json::Asset {
+            copyright: options.copyright.clone(),
+            generator: Some(format!("retrotool {}", env!("CARGO_PKG_VERSION"))),
+            ..Default::default()
+        },
+        buffers: json_buffers,
+        buffer_views: json_buffer_views,
+        scene: Some(json::Index::new(0)),
+        extensions: None,
+        extras: Default::default(),
+        extensions_used: [
+            any_unlit.then_some("KHR_materials_unlit"),
+            instancing.is_some().then_some("EXT_mesh_gpu_instancing"),
+        ]
+        .into_iter()
+        .flatten()
+        .map(str::to_string)
+        .collect(),
+        extensions_required: vec![],
+        cameras: vec![],
+        images: texture_export.images,
+        materials: json_materials,
+        meshes: json_meshes,
+        nodes: json_nodes,
+        samplers: texture_export.samplers,
+        scenes: vec![json::Scene {
+            extensions: Default::default(),
+            extras: origin_offset.map(|offset| {
+                serde_json::value::to_raw_value(&json!({ "origin_offset": offset }))
+                    .expect("Serialization error")
+            }),
+            name: Some(model_name),
+            nodes: json_scene_nodes,
+        }],
+        skins: vec![],
+        textures: texture_export.textures,
+    };
+    let writer = fs::File::create(&gltf_path)
+        .with_context(|| format!("Failed to create file '{}'", gltf_path.display()))?;
+    if let Some((nodes, attributes)) = instancing {
+        // gltf-json has no field for the extension, so add it to the serialized nodes
+        let mut value = serde_json::to_value(&json_root)?;
+        for node in nodes {
+            value["nodes"][node.value()]["extensions"]["EXT_mesh_gpu_instancing"] =
+                json!({ "attributes": attributes });
+        }
+        serde_json::to_writer_pretty(writer, &value)?;
+    } else {
+        json::serialize::to_writer_pretty(writer, &json_root).expect("Serialization error");
+    }
+
+    Ok(gltf_path)
+}
+
+/// Texture conversion settings, and the glTF samplers, textures and images written so far.
+struct TextureExport<'a> {
+    in_dir: &'a Path,
+    out_dir: &'a Path,
+    image_output: ImageOutput,
+    max_size: Option<u32>,
+    /// UV sets provided by every mesh using the current material.
+    uv_sets: u32,
+    /// Texture ID to glTF texture index.
+    map: HashMap<Uuid, usize>,
+    samplers: Vec<json::texture::Sampler>,
+    textures: Vec<json::Texture>,
+    images: Vec<json::Image>,
+}
+
+impl TextureExport<'_> {
+    /// Returns a reference to `texture` for the current material, converting it on first use.
+    fn add(&mut self, texture: &CMaterialTextureTokenData) -> Result<json::texture::Info> {
+        let Some(usage) = &texture.usage else { bail!("Texture without usage!") };
+        let tex_coord = usage.uv_set(self.uv_sets).unwrap_or_else(|| {
+            log::warn!(
+                "Texture {} samples UV set {}, but only {} are present; using UV set 0",
+                texture.id,
+                usage.tex_coord,
+                self.uv_sets
+            );
+            0
+        });
+        let texture_idx = if let Some(&existing) = self.map.get(&texture.id) {
+            existing
+        } else {
+            let texture_idx = self.textures.len();
+            let mut approximations = Vec::new();
+            let wrap_s = convert_wrap(usage.wrap_x, &mut approximations)?;
+            let wrap_t = convert_wrap(usage.wrap_y, &mut approximations)?;
+            approximations.dedup();
+            for approximation in &approximations {
+                log::warn!("Texture {}: {}", texture.id, approximation);
+            }
+            let sampler_name = if approximations.is_empty() {
+                format!("{} sampler", texture.id)
+            } else {
+                format!("{} sampler ({})", texture.id, approximations.join(", "))
+            };
+            self.samplers.push(json::texture::Sampler {
+                mag_filter: match usage.filter {
+                    0 => Some(Valid(json::texture::MagFilter::Nearest)),
+                    1 => Some(Valid(json::texture::MagFilter::Linear)),
+                    -1 => None,
+                    filter => todo!("Filter {filter}"),
+                },
+                min_filter: match usage.filter {
+                    0 => Some(Valid(json::texture::MinFilter::Nearest)),
+                    1 => Some(Valid(json::texture::MinFilter::Linear)),
+                    -1 => None,
+                    filter => todo!("Filter {filter}"),
+                },
+                name: Some(sampler_name),
+                wrap_s: Valid(wrap_s),
+                wrap_t: Valid(wrap_t),
+                extensions: None,
+                extras: None,
+            });
+            self.textures.push(json::Texture {
+                name: Some(format!("{}", texture.id)),
+                sampler: Some(json::Index::new(texture_idx as u32)),
+                source: json::Index::new(texture_idx as u32),
+                extensions: None,
+                extras: None,
+            });
+            let file_name = format!("{}.png", texture.id);
+            let uri = match self.image_output {
+                // Reference the file a full export would write
+                ImageOutput::Skip => file_name,
+                ImageOutput::Embed => {
+                    let png_data = texture_to_png(self.in_dir, texture.id, self.max_size)?;
+                    format!("data:image/png;base64,{}", base64::encode(png_data))
+                }
+                ImageOutput::Files => {
+                    fs::write(
+                        self.out_dir.join(&file_name),
+                        texture_to_png(self.in_dir, texture.id, self.max_size)?,
+                    )?;
+                    file_name
+                }
+            };
+            self.images.push(json::Image {
+                buffer_view: None,
+                mime_type: None,
+                name: Some(format!("{}", texture.id)),
+                uri: Some(uri),
+                extensions: None,
+                extras: None,
+            });
+            self.map.insert(texture.id, texture_idx);
+            texture_idx
+        };
+        Ok(json::texture::Info {
+            index: json::Index::new(texture_idx as u32),
+            tex_coord,
+            extensions: None,
+            extras: None,
+        })
+    }
+}
+
+/// The glTF attribute for a vertex component. Components without a standard semantic map to
+/// custom attributes, which gltf-json prefixes with an underscore (e.g. `_MATERIAL_CHOICE_0`).
+fn attribute_semantic(component: EVertexComponent) -> json::mesh::Semantic {
+    match component {
+        EVertexComponent::Position => json::mesh::Semantic::Positions,
+        EVertexComponent::Normal => json::mesh::Semantic::Normals,
+        EVertexComponent::Tangent0 => json::mesh::Semantic::Tangents,
+        EVertexComponent::Tangent1 => json::mesh::Semantic::Extras("TANGENT_1".into()),
+        EVertexComponent::Tangent2 => json::mesh::Semantic::Extras("TANGENT_2".into()),
+        EVertexComponent::TexCoord0 => json::mesh::Semantic::TexCoords(0),
+        EVertexComponent::TexCoord1 => json::mesh::Semantic::TexCoords(1),
+        EVertexComponent::TexCoord2 => json::mesh::Semantic::TexCoords(2),
+        EVertexComponent::TexCoord3 => json::mesh::Semantic::TexCoords(3),
+        // Not COLOR_0, as its formats don't match what glTF allows there
+        EVertexComponent::Color => json::mesh::Semantic::Extras("COLOR".into()),
+        EVertexComponent::BoneIndices => json::mesh::Semantic::Joints(0),
+        EVertexComponent::BoneWeights => json::mesh::Semantic::Weights(0),
+        EVertexComponent::BakedLightingCoord => {
+            json::mesh::Semantic::Extras("BAKED_LIGHTING_COORD".into())
+        }
+        EVertexComponent::BakedLightingTangent => {
+            json::mesh::Semantic::Extras("BAKED_LIGHTING_TANGENT".into())
+        }
+        EVertexComponent::VertInstanceParams => {
+            json::mesh::Semantic::Extras("VERT_INSTANCE_PARAMS".into())
+        }
+        EVertexComponent::VertInstanceColor => {
+            json::mesh::Semantic::Extras("VERT_INSTANCE_COLOR".into())
+        }
+        EVertexComponent::VertTransform0 => json::mesh::Semantic::Extras("VERT_TRANSFORM_0".into()),
+        EVertexComponent::VertTransform1 => json::mesh::Semantic::Extras("VERT_TRANSFORM_1".into()),
+        EVertexComponent::VertTransform2 => json::mesh::Semantic::Extras("VERT_TRANSFORM_2".into()),
+        EVertexComponent::CurrentPosition => {
+            json::mesh::Semantic::Extras("CURRENT_POSITION".into())
+        }
+        EVertexComponent::VertInstanceOpacityParams => {
+            json::mesh::Semantic::Extras("VERT_INSTANCE_OPACITY_PARAMS".into())
+        }
+        EVertexComponent::VertInstanceColorIndexingParams => {
+            json::mesh::Semantic::Extras("VERT_INSTANCE_COLOR_INDEXING_PARAMS".into())
+        }
+        EVertexComponent::VertInstanceOpacityIndexingParams => {
+            json::mesh::Semantic::Extras("VERT_INSTANCE_OPACITY_INDEXING_PARAMS".into())
+        }
+        EVertexComponent::VertInstancePaintParams => {
+            json::mesh::Semantic::Extras("VERT_INSTANCE_PAINT_PARAMS".into())
+        }
+        EVertexComponent::BakedLightingLookup => {
+            json::mesh::Semantic::Extras("BAKED_LIGHTING_LOOKUP".into())
+        }
+        EVertexComponent::MaterialChoice0 => {
+            json::mesh::Semantic::Extras("MATERIAL_CHOICE_0".into())
+        }
+        EVertexComponent::MaterialChoice1 => {
+            json::mesh::Semantic::Extras("MATERIAL_CHOICE_1".into())
+        }
+        EVertexComponent::MaterialChoice2 => {
+            json::mesh::Semantic::Extras("MATERIAL_CHOICE_2".into())
+        }
+        EVertexComponent::MaterialChoice3 => {
+            json::mesh::Semantic::Extras("MATERIAL_CHOICE_3".into())
+        }
+    }
+}
+
+/// Transforms of each placement of the model in an MCON, matched by the model's asset ID.
+fn model_instances(mcon_path: &Path, model_data: &[u8]) -> Result<Vec<CTransform4f>> {
+    let id = locate_asset_id::<LittleEndian>(model_data).context("Failed to locate model ID")?;
+    let data = map_file(mcon_path)?;
+    let mcon = ModConData::<LittleEndian>::slice(&data)
+        .with_context(|| format!("Failed to read MCON '{}'", mcon_path.display()))?;
+    let Some(visual_data) = mcon.visual_data else {
+        bail!("MCON '{}' has no visual data", mcon_path.display());
+    };
+    let Some(model_idx) = visual_data.models.iter().position(|&model| model == id) else {
+        bail!("MCON '{}' doesn't reference model {id}", mcon_path.display());
+    };
+    // Each transform's model index
+    let transforms = visual_data
+        .shorts_1
+        .iter()
+        .zip(&visual_data.transforms)
+        .filter(|(&idx, _)| idx as usize == model_idx)
+        .map(|(_, xf)| *xf)
+        .collect::<Vec<_>>();
+    ensure!(!transforms.is_empty(), "MCON '{}' doesn't place model {id}", mcon_path.display());
+    log::info!("Exporting {} placements of {id}", transforms.len());
+    Ok(transforms)
+}
+
+/// Whether all scale components are equal (and positive), within rounding.
+fn is_uniform(scale: CVector3f) -> bool {
+    let [x, y, z] = scale.to_array();
+    let tolerance = x.abs().max(y.abs()).max(z.abs()) * 1e-4;
+    x > 0.0 && (x - y).abs() <= tolerance && (x - z).abs() <= tolerance
+}
+
+/// EXT_mesh_gpu_instancing attribute data: each placement's translation, then each rotation
+/// quaternion, then each scale.
+fn instance_data(transforms: &[CTransform4f]) -> Vec<u8> {
+    let decomposed = transforms.iter().map(CTransform4f::decompose).collect::<Vec<_>>();
+    let mut out = Vec::with_capacity(transforms.len() * 40);
+    for (translation, _, _) in &decomposed {
+        out.extend(translation.to_array().iter().flat_map(|v| v.to_le_bytes()));
+    }
+    for (_, rotation, _) in &decomposed {
+        let rotation = [rotation.v.x, rotation.v.y, rotation.v.z, rotation.s];
+        out.extend(rotation.iter().flat_map(|v| v.to_le_bytes()));
+    }
+    for (_, _, scale) in &decomposed {
+        out.extend(scale.to_array().iter().flat_map(|v| v.to_le_bytes()));
+    }
+    out
+}
+
+/// Collects the vertices a mesh uses for USD export, renumbering its indices to match.
+fn usd_mesh(
+    name: &str,
+    mesh: &CRenderMesh,
+    buffers: &[Vec<u8>],
+    infos: &[VertexBufferInfo],
+    index_type: EBufferType,
+    index_buf: &[u8],
+) -> Result<usd::Mesh> {
+    let index_size = match index_type {
+        EBufferType::U8 => 1,
+        EBufferType::U16 => 2,
+        EBufferType::U32 => 4,
+    };
+    let start = mesh.index_start as usize * index_size;
+    let end = start + mesh.index_count as usize * index_size;
+    let Some(index_data) = index_buf.get(start..end) else {
+        bail!("Indices {start:#X}..{end:#X} exceed index buffer size {:#X}", index_buf.len());
+    };
+    let mut remap = HashMap::new();
+    let mut used = Vec::new();
+    let indices = index_data
+        .chunks_exact(index_size)
+        .map(|bytes| {
+            let index = match bytes.len() {
+                1 => bytes[0] as u32,
+                2 => u16::from_le_bytes(bytes.try_into().unwrap()) as u32,
+                _ => u32::from_le_bytes(bytes.try_into().unwrap()),
+            };
+            *remap.entry(index).or_insert_with(|| {
+                used.push(index as usize);
+                used.len() as u32 - 1
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut points = None;
+    let mut normals = None;
+    let mut tex_coords: [Option<Vec<[f32; 2]>>; 4] = Default::default();
+    for (buf, info) in buffers.iter().zip(infos) {
+        for attribute in &info.attributes {
+            let read = || -> Result<Vec<[f32; 4]>> {
+                let values = read_float_attribute(
+                    buf,
+                    attribute.out_offset as usize,
+                    info.out_stride as usize,
+                    attribute.out_format,
+                )?;
+                used.iter()
+                    .map(|&i| {
+                        values.get(i).copied().ok_or_else(|| anyhow!("Vertex {i} out of range"))
+                    })
+                    .collect()
+            };
+            let set = match attribute.component {
+                EVertexComponent::Position => {
+                    points = Some(read()?.into_iter().map(|[x, y, z, _]| [x, y, z]).collect());
+                    continue;
+                }
+                EVertexComponent::Normal => {
+                    match read() {
+                        Ok(values) => {
+                            normals =
+                                Some(values.into_iter().map(|[x, y, z, _]| [x, y, z]).collect())
+                        }
+                        Err(e) => log::warn!("{name}: skipping normals: {e:#}"),
+                    }
+                    continue;
+                }
+                EVertexComponent::TexCoord0 => 0,
+                EVertexComponent::TexCoord1 => 1,
+                EVertexComponent::TexCoord2 => 2,
+                EVertexComponent::TexCoord3 => 3,
+                _ => continue,
+            };
+            match read() {
+                Ok(values) => {
+                    tex_coords[set] = Some(values.into_iter().map(|[u, v, _, _]| [u, v]).collect())
+                }
+                Err(e) => log::warn!("{name}: skipping UV set {set}: {e:#}"),
+            }
+        }
+    }
+    let Some(points) = points else {
+        bail!("Mesh has no positions");
+    };
+    Ok(usd::Mesh {
+        name: name.to_string(),
+        points,
+        normals,
+        tex_coords: tex_coords.into_iter().map_while(|set| set).collect(),
+        indices,
+        material: Some(mesh.material_idx as usize),
+    })
+}
+
+/// Replaces the XYZ components of a 32-bit float position attribute with `f(axis, value)`.
+fn transform_positions(
+    buf: &mut [u8],
+    offset: usize,
+    stride: usize,
+    format: EVertexDataFormat,
+    f: impl Fn(usize, f32) -> f32,
+) -> Result<()> {
+    let components = match format {
+        EVertexDataFormat::Rgb32Float | EVertexDataFormat::Rgba32Float => 3,
+        format => bail!("Unsupported position format {format:?}"),
+    };
+    ensure!(stride > 0 && buf.len() % stride == 0, "Invalid vertex stride {stride}");
+    for vertex in buf.chunks_exact_mut(stride) {
+        let data = &mut vertex[offset..offset + components * 4];
+        for (axis, value) in data.chunks_exact_mut(4).enumerate() {
+            let result = f(axis, f32::from_le_bytes(value.try_into().unwrap()));
+            value.copy_from_slice(&result.to_le_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Computes tangents for a vertex buffer group from the triangles of the given meshes,
+/// returning them as tightly packed `[f32; 4]`s.
+fn build_tangents(
+    buffers: &[Vec<u8>],
+    infos: &[VertexBufferInfo],
+    meshes: &[&CRenderMesh],
+    index_types: &[EBufferType],
+    idx_buffers: &[Vec<u8>],
+) -> Result<Vec<u8>> {
+    let read = |component: EVertexComponent| -> Result<Vec<[f32; 4]>> {
+        for (buf, info) in buffers.iter().zip(infos) {
+            if let Some(attribute) = info.attributes.iter().find(|a| a.component == component) {
+                return read_float_attribute(
+                    buf,
+                    attribute.out_offset as usize,
+                    info.out_stride as usize,
+                    attribute.out_format,
+                );
+            }
+        }
+        bail!("Missing {component:?} attribute")
+    };
+    let positions = read(EVertexComponent::Position)?
+        .into_iter()
+        .map(|[x, y, z, _]| [x, y, z])
+        .collect::<Vec<_>>();
+    let normals = read(EVertexComponent::Normal)?
+        .into_iter()
+        .map(|[x, y, z, _]| [x, y, z])
+        .collect::<Vec<_>>();
+    let tex_coords = read(EVertexComponent::TexCoord0)?
+        .into_iter()
+        .map(|[u, v, _, _]| [u, v])
+        .collect::<Vec<_>>();
+    let mut indices = Vec::new();
+    for mesh in meshes {
+        let idx = mesh.idx_buf_idx as usize;
+        let (Some(&index_type), Some(buf)) = (index_types.get(idx), idx_buffers.get(idx)) else {
+            bail!("Invalid index buffer {idx}");
+        };
+        let size = match index_type {
+            EBufferType::U8 => 1,
+            EBufferType::U16 => 2,
+            EBufferType::U32 => 4,
+        };
+        let start = mesh.index_start as usize * size;
+        let end = start + mesh.index_count as usize * size;
+        let Some(data) = buf.get(start..end) else {
+            bail!("Index range {start:#X}..{end:#X} out of bounds for index buffer {idx}");
+        };
+        indices.extend(data.chunks_exact(size).map(|b| match index_type {
+            EBufferType::U8 => b[0] as u32,
+            EBufferType::U16 => u16::from_le_bytes([b[0], b[1]]) as u32,
+            EBufferType::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        }));
+    }
+    let tangents = generate_tangents(&positions, &normals, &tex_coords, &indices)?;
+    Ok(tangents.iter().flatten().flat_map(|v| v.to_le_bytes()).collect())
+}
+
+/// Decodes the first mip of a sibling TXTR and encodes it as PNG, downscaling it
+/// to fit within `max_size` if given.
+fn texture_to_png(in_dir: &Path, id: Uuid, max_size: Option<u32>) -> Result<Vec<u8>> {
+    log::info!("Converting TXTR {}", id);
+    let txtr_file = map_file(in_dir.join(format!("{}.TXTR", id)))?;
+    let DecodedTexture { head, mips } = decode_texture_file(&txtr_file)?;
+    let Some(mut image) = mips.into_iter().next().and_then(|layers| layers.into_iter().next())
+    else {
+        bail!("TXTR {} has no images", id);
+    };
+    if let Some(max_size) = max_size {
+        if image.width().max(image.height()) > max_size {
+            let (width, height) = (image.width(), image.height());
+            image = image.resize(max_size, max_size, FilterType::Lanczos3);
+            log::info!(
+                "TXTR {}: downscaled {}x{} -> {}x{}",
+                id,
+                width,
+                height,
+                image.width(),
+                image.height()
+            );
+        }
+    }
+    let mut png_data = Vec::new();
+    let mut p = png::Encoder::new(&mut png_data, image.width(), image.height());
+    if head.format.is_srgb() {
+        p.set_srgb(SrgbRenderingIntent::Perceptual);
+    }
+    p.set_color(match image.color() {
+        ColorType::L8 | ColorType::L16 => png::ColorType::Grayscale,
+        ColorType::La8 | ColorType::La16 => png::ColorType::GrayscaleAlpha,
+        ColorType::Rgb8 | ColorType::Rgb16 | ColorType::Rgb32F => png::ColorType::Rgb,
+        ColorType::Rgba8 | ColorType::Rgba16 | ColorType::Rgba32F => png::ColorType::Rgba,
+        color => todo!("PNG {color:?}"),
+    });
+    p.set_depth(match image.color() {
+        ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8 => png::BitDepth::Eight,
+        ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => {
+            png::BitDepth::Sixteen
+        }
+        color => todo!("PNG {color:?}"),
+    });
+    p.add_text_chunk("TXTR".into(), format!("{}", id))?;
+    let mut w = p.write_header()?;
+    w.write_image_data(image.as_bytes())?;
+    w.finish()?;
+    Ok(png_data)
+}
+
+/// Maps a texture wrap mode to the closest glTF wrapping mode. Modes that glTF
+/// can't represent are approximated, with a description pushed to `approximations`.
+fn convert_wrap(
+    wrap: i32,
+    approximations: &mut Vec<String>,
+) -> Result<json::texture::WrappingMode> {
+    use json::texture::WrappingMode;
+    let (mode, original) = match wrap {
+        w if w == ETextureWrap::ClampToEdge as i32 => (WrappingMode::ClampToEdge, None),
+        w if w == ETextureWrap::Repeat as i32 => (WrappingMode::Repeat, None),
+        w if w == ETextureWrap::MirroredRepeat as i32 => (WrappingMode::MirroredRepeat, None),
+        w if w == ETextureWrap::MirrorClamp as i32 => {
+            (WrappingMode::MirroredRepeat, Some(ETextureWrap::MirrorClamp))
+        }
+        w if w == ETextureWrap::ClampToBorder as i32 => {
+            (WrappingMode::ClampToEdge, Some(ETextureWrap::ClampToBorder))
+        }
+        w if w == ETextureWrap::Clamp as i32 => {
+            (WrappingMode::ClampToEdge, Some(ETextureWrap::Clamp))
+        }
+        wrap => bail!("Unknown texture wrap mode {wrap}"),
+    };
+    if let Some(original) = original {
+        approximations.push(format!("{original:?} approximated as {mode:?}"));
+    }
+    Ok(mode)
+}
+
+/// Determines the model name, preferring the resource name stored in the footer.
+pub fn model_name(path: &Path, data: &[u8]) -> String {
+    locate_names::<LittleEndian>(data)
+        .ok()
+        .and_then(|names| names.into_iter().next())
+        .map(|name| name.rsplit(['/', '\\']).next().unwrap_or_default().to_string())
+        .filter(|name| !name.is_empty())
+        .or_else(|| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "Model".to_string())
+}
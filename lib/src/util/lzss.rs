@@ -1,27 +1,60 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, ensure, Result};
+
 /// https://wiki.axiodl.com/w/LZSS_Compression
-pub fn decompress<const M: u8>(mut input: &[u8], output: &mut [u8]) -> bool {
+pub fn decompress<const M: u8>(input: &[u8], output: &mut [u8]) -> Result<()> {
     let group_len = 2usize.pow(M as u32 - 1);
+    let mut in_cur = 0usize;
     let mut out_cur = 0usize;
 
     let mut header_byte = 0u8;
     let mut group = 0u8;
-    while !input.is_empty() {
+    while in_cur < input.len() {
         if group == 0 {
-            header_byte = input[0];
-            input = &input[1..];
+            header_byte = input[in_cur];
+            in_cur += 1;
             group = 8;
         }
 
         if header_byte & 0x80 == 0 {
-            output[out_cur..group_len + out_cur].copy_from_slice(&input[..group_len]);
-            input = &input[group_len..];
+            let (Some(src), Some(dst)) = (
+                input.get(in_cur..in_cur + group_len),
+                output.get_mut(out_cur..out_cur + group_len),
+            ) else {
+                bail!(
+                    "LZSS literal at input offset {:#X} overruns buffers (output {:#X}/{:#X})",
+                    in_cur,
+                    out_cur,
+                    output.len()
+                );
+            };
+            dst.copy_from_slice(src);
+            in_cur += group_len;
             out_cur += group_len;
         } else {
-            let count = (input[0] as usize >> 4) + (4 - M as usize);
-            let length = (((input[0] as usize & 0xF) << 0x8) | input[1] as usize) << (M - 1);
-            input = &input[2..];
+            let (Some(&b0), Some(&b1)) = (input.get(in_cur), input.get(in_cur + 1)) else {
+                bail!("LZSS back-reference truncated at input offset {:#X}", in_cur);
+            };
+            let count = (b0 as usize >> 4) + (4 - M as usize);
+            let length = (((b0 as usize & 0xF) << 0x8) | b1 as usize) << (M - 1);
 
-            let seek = out_cur - length;
+            let Some(seek) = out_cur.checked_sub(length) else {
+                bail!(
+                    "LZSS back-reference at input offset {:#X} seeks {:#X} bytes back from output {:#X}",
+                    in_cur,
+                    length,
+                    out_cur
+                );
+            };
+            ensure!(
+                out_cur + count * group_len <= output.len(),
+                "LZSS back-reference at input offset {:#X} overruns output ({:#X}/{:#X})",
+                in_cur,
+                out_cur,
+                output.len()
+            );
+            in_cur += 2;
             for n in 0..count * group_len {
                 output[out_cur + n] = output[seek + n];
             }
@@ -32,5 +65,96 @@ pub fn decompress<const M: u8>(mut input: &[u8], output: &mut [u8]) -> bool {
         group -= 1;
     }
 
-    out_cur == output.len()
+    ensure!(
+        out_cur == output.len(),
+        "LZSS output size mismatch: decompressed {:#X} bytes, expected {:#X}",
+        out_cur,
+        output.len()
+    );
+    Ok(())
+}
+
+/// Maximum back-reference distance, in groups.
+const MAX_DISTANCE: usize = 0xFFF;
+/// Maximum number of earlier positions checked per match.
+const MAX_CHAIN: usize = 256;
+
+/// Compresses `input` into the format read by [`decompress`]. The input size must be a
+/// multiple of the mode's group size (1, 2 or 4 bytes).
+pub fn compress<const M: u8>(input: &[u8]) -> Result<Vec<u8>> {
+    let group_len = 2usize.pow(M as u32 - 1);
+    ensure!(
+        input.len() % group_len == 0,
+        "LZSS mode {} input size {:#X} is not a multiple of {}",
+        M,
+        input.len(),
+        group_len
+    );
+    let min_count = 4 - M as usize;
+    let max_count = 0xF + min_count;
+    let groups = input.len() / group_len;
+    let group = |idx: usize| &input[idx * group_len..(idx + 1) * group_len];
+    // Hash of the bytes a minimum length match covers
+    let key_len = min_count * group_len;
+    let key = |idx: usize| {
+        input
+            .get(idx * group_len..idx * group_len + key_len)
+            .map(|bytes| bytes.iter().fold(0u32, |key, &b| (key << 8) | b as u32))
+    };
+
+    let mut head = HashMap::<u32, usize>::new();
+    let mut prev = vec![usize::MAX; groups];
+    let mut out = Vec::with_capacity(input.len() + input.len() / 8 + 1);
+    let mut header_pos = 0usize;
+    let mut token = 0u8;
+    let mut cur = 0usize;
+    while cur < groups {
+        if token == 0 {
+            header_pos = out.len();
+            out.push(0);
+        }
+
+        // Find the longest earlier match within range
+        let mut best = (0usize, 0usize);
+        if let Some(k) = key(cur) {
+            let mut candidate = head.get(&k).copied().unwrap_or(usize::MAX);
+            let mut chain = 0;
+            while candidate != usize::MAX && cur - candidate <= MAX_DISTANCE && chain < MAX_CHAIN {
+                let mut count = 0;
+                while count < max_count
+                    && cur + count < groups
+                    && group(candidate + count) == group(cur + count)
+                {
+                    count += 1;
+                }
+                if count > best.0 {
+                    best = (count, cur - candidate);
+                    if count == max_count {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+
+        let (count, distance) = best;
+        let advance = if count >= min_count {
+            out[header_pos] |= 0x80 >> token;
+            out.push((((count - min_count) as u8) << 4) | (distance >> 8) as u8);
+            out.push(distance as u8);
+            count
+        } else {
+            out.extend_from_slice(group(cur));
+            1
+        };
+        for idx in cur..cur + advance {
+            if let Some(k) = key(idx) {
+                prev[idx] = head.insert(k, idx).unwrap_or(usize::MAX);
+            }
+        }
+        cur += advance;
+        token = (token + 1) % 8;
+    }
+    Ok(out)
 }
@@ -1,10 +1,18 @@
+#[cfg(feature = "textures")]
 pub mod astc;
 pub mod compression;
+#[cfg(feature = "textures")]
+pub mod cubemap;
+#[cfg(feature = "textures")]
 pub mod dds;
 pub mod file;
+#[cfg(feature = "gltf")]
+pub mod gltf;
 pub mod lzss;
 pub mod read;
 pub mod templates;
+#[cfg(feature = "gltf")]
+pub mod usd;
 
 /// Creates a fixed-size array reference from a slice.
 #[macro_export]
@@ -0,0 +1,402 @@
+//! USD export for models: an ASCII stage with UsdPreviewSurface materials, packaged as `.usdz`.
+
+use std::{fmt::Write as _, io::Write};
+
+use anyhow::{ensure, Result};
+use gltf_json as json;
+
+/// A triangle mesh with per-vertex attributes.
+pub struct Mesh {
+    pub name: String,
+    pub points: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    /// UV sets in glTF orientation (V down); flipped when written.
+    pub tex_coords: Vec<Vec<[f32; 2]>>,
+    pub indices: Vec<u32>,
+    pub material: Option<usize>,
+}
+
+/// A texture sampled by a material, relative to the stage.
+pub struct Texture {
+    pub file: String,
+    pub tex_coord: u32,
+}
+
+pub struct Material {
+    pub name: String,
+    pub base_color: [f32; 4],
+    pub base_color_texture: Option<Texture>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Roughness in G and metallic in B, as in glTF.
+    pub metallic_roughness_texture: Option<Texture>,
+    pub normal_texture: Option<Texture>,
+    pub emissive: [f32; 3],
+    pub emissive_texture: Option<Texture>,
+    pub alpha_mode: json::material::AlphaMode,
+    pub alpha_cutoff: f32,
+    pub unlit: bool,
+}
+
+impl Material {
+    /// Maps a glTF material, resolving texture images to `textures/<uri>`.
+    pub fn from_gltf(
+        material: &json::Material,
+        textures: &[json::Texture],
+        images: &[json::Image],
+    ) -> Self {
+        let texture = |info: Option<(&json::Index<json::Texture>, u32)>| {
+            let (index, tex_coord) = info?;
+            let texture = textures.get(index.value())?;
+            let uri = images.get(texture.source.value())?.uri.as_ref()?;
+            Some(Texture { file: format!("textures/{uri}"), tex_coord })
+        };
+        let pbr = &material.pbr_metallic_roughness;
+        Self {
+            name: material.name.clone().unwrap_or_default(),
+            base_color: pbr.base_color_factor.0,
+            base_color_texture: texture(
+                pbr.base_color_texture.as_ref().map(|i| (&i.index, i.tex_coord)),
+            ),
+            metallic: pbr.metallic_factor.0,
+            roughness: pbr.roughness_factor.0,
+            metallic_roughness_texture: texture(
+                pbr.metallic_roughness_texture.as_ref().map(|i| (&i.index, i.tex_coord)),
+            ),
+            normal_texture: texture(
+                material.normal_texture.as_ref().map(|i| (&i.index, i.tex_coord)),
+            ),
+            emissive: material.emissive_factor.0,
+            emissive_texture: texture(
+                material.emissive_texture.as_ref().map(|i| (&i.index, i.tex_coord)),
+            ),
+            alpha_mode: match material.alpha_mode {
+                json::validation::Checked::Valid(mode) => mode,
+                json::validation::Checked::Invalid => json::material::AlphaMode::Opaque,
+            },
+            alpha_cutoff: material.alpha_cutoff.map_or(0.5, |c| c.0),
+            unlit: material.extensions.as_ref().is_some_and(|e| e.unlit.is_some()),
+        }
+    }
+}
+
+pub struct Stage {
+    pub name: String,
+    /// Uniform scale applied to the root prim.
+    pub scale: Option<f32>,
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+}
+
+/// Converts a name to a valid prim identifier.
+fn identifier(name: &str) -> String {
+    let mut out = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect::<String>();
+    if !out.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        out.insert(0, '_');
+    }
+    out
+}
+
+/// Prim identifiers for `names`, suffixed with their index where they would collide.
+fn unique_identifiers<'a>(names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for (idx, name) in names.enumerate() {
+        let mut id = identifier(name);
+        if out.contains(&id) {
+            id = format!("{id}_{idx}");
+        }
+        out.push(id);
+    }
+    out
+}
+
+fn tuple<const N: usize>(v: [f32; N]) -> String {
+    let values = v.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    format!("({})", values.join(", "))
+}
+
+fn array<T>(items: &[T], f: impl Fn(&T) -> String) -> String {
+    let values = items.iter().map(f).collect::<Vec<_>>();
+    format!("[{}]", values.join(", "))
+}
+
+fn primvar_name(tex_coord: u32) -> String {
+    if tex_coord == 0 {
+        "st".to_string()
+    } else {
+        format!("st{tex_coord}")
+    }
+}
+
+impl Stage {
+    pub fn to_usda(&self) -> String {
+        let root = identifier(&self.name);
+        let mut s = String::new();
+        writeln!(s, "#usda 1.0").unwrap();
+        writeln!(
+            s,
+            "(\n    defaultPrim = \"{root}\"\n    metersPerUnit = 1\n    upAxis = \"Y\"\n)\n"
+        )
+        .unwrap();
+        writeln!(s, "def Xform \"{root}\"\n{{").unwrap();
+        if let Some(scale) = self.scale {
+            writeln!(s, "    double3 xformOp:scale = {}", tuple([scale; 3])).unwrap();
+            writeln!(s, "    uniform token[] xformOpOrder = [\"xformOp:scale\"]\n").unwrap();
+        }
+
+        let material_ids = unique_identifiers(self.materials.iter().map(|m| m.name.as_str()));
+        writeln!(s, "    def Scope \"Materials\"\n    {{").unwrap();
+        for (material, id) in self.materials.iter().zip(&material_ids) {
+            Self::write_material(&mut s, &format!("/{root}/Materials/{id}"), id, material);
+        }
+        writeln!(s, "    }}").unwrap();
+
+        let mesh_ids = unique_identifiers(self.meshes.iter().map(|m| m.name.as_str()));
+        for (mesh, id) in self.meshes.iter().zip(&mesh_ids) {
+            let material = mesh
+                .material
+                .and_then(|idx| material_ids.get(idx))
+                .map(|mat| format!("/{root}/Materials/{mat}"));
+            Self::write_mesh(&mut s, id, mesh, material.as_deref());
+        }
+        writeln!(s, "}}").unwrap();
+        s
+    }
+
+    fn write_material(s: &mut String, path: &str, id: &str, material: &Material) {
+        writeln!(s, "        def Material \"{id}\"\n        {{").unwrap();
+        writeln!(s, "            token outputs:surface.connect = <{path}/Surface.outputs:surface>")
+            .unwrap();
+
+        // Texture samplers, each with its own UV reader
+        let mut shaders = String::new();
+        // Normal maps are remapped from [0, 1] by `scale` and `bias`
+        let mut sampler = |name: &str, texture: &Texture, scale: [f32; 4], normal: bool| {
+            let reader = format!("{name}TexCoord");
+            writeln!(shaders, "\n            def Shader \"{reader}\"\n            {{").unwrap();
+            writeln!(
+                shaders,
+                "                uniform token info:id = \"UsdPrimvarReader_float2\""
+            )
+            .unwrap();
+            writeln!(
+                shaders,
+                "                string inputs:varname = \"{}\"",
+                primvar_name(texture.tex_coord)
+            )
+            .unwrap();
+            writeln!(shaders, "                float2 outputs:result\n            }}").unwrap();
+            writeln!(shaders, "\n            def Shader \"{name}\"\n            {{").unwrap();
+            writeln!(shaders, "                uniform token info:id = \"UsdUVTexture\"").unwrap();
+            writeln!(shaders, "                asset inputs:file = @{}@", texture.file).unwrap();
+            writeln!(
+                shaders,
+                "                float2 inputs:st.connect = <{path}/{reader}.outputs:result>"
+            )
+            .unwrap();
+            writeln!(shaders, "                token inputs:wrapS = \"repeat\"").unwrap();
+            writeln!(shaders, "                token inputs:wrapT = \"repeat\"").unwrap();
+            if scale != [1.0; 4] {
+                writeln!(shaders, "                float4 inputs:scale = {}", tuple(scale))
+                    .unwrap();
+            }
+            if normal {
+                writeln!(shaders, "                float4 inputs:bias = (-1, -1, -1, 0)").unwrap();
+            }
+            if normal || name == "MetallicRoughness" {
+                writeln!(shaders, "                token inputs:sourceColorSpace = \"raw\"")
+                    .unwrap();
+            }
+            writeln!(shaders, "                float3 outputs:rgb").unwrap();
+            writeln!(shaders, "                float outputs:r").unwrap();
+            writeln!(shaders, "                float outputs:g").unwrap();
+            writeln!(shaders, "                float outputs:b").unwrap();
+            writeln!(shaders, "                float outputs:a\n            }}").unwrap();
+            format!("{path}/{name}.outputs")
+        };
+
+        let mut inputs = Vec::new();
+        // As with emissive below, a zero factor alongside a texture means it wasn't set
+        let [r, g, b, a] = match material.base_color {
+            [0.0, 0.0, 0.0, 0.0] if material.base_color_texture.is_some() => [1.0; 4],
+            color => color,
+        };
+        let base_color = material
+            .base_color_texture
+            .as_ref()
+            .map(|texture| sampler("BaseColor", texture, [r, g, b, a], false));
+        // UsdPreviewSurface has no unlit model, so unlit materials only emit their base color
+        let color_input = if material.unlit { "emissiveColor" } else { "diffuseColor" };
+        match &base_color {
+            Some(outputs) => {
+                inputs.push(format!("color3f inputs:{color_input}.connect = <{outputs}:rgb>"))
+            }
+            None => inputs.push(format!("color3f inputs:{color_input} = {}", tuple([r, g, b]))),
+        }
+        if material.unlit {
+            inputs.push("color3f inputs:diffuseColor = (0, 0, 0)".to_string());
+        }
+        match material.alpha_mode {
+            json::material::AlphaMode::Opaque => {}
+            mode => {
+                match &base_color {
+                    Some(outputs) => {
+                        inputs.push(format!("float inputs:opacity.connect = <{outputs}:a>"))
+                    }
+                    None => inputs.push(format!("float inputs:opacity = {a}")),
+                }
+                if mode == json::material::AlphaMode::Mask {
+                    inputs
+                        .push(format!("float inputs:opacityThreshold = {}", material.alpha_cutoff));
+                }
+            }
+        }
+        match &material.metallic_roughness_texture {
+            Some(texture) => {
+                let scale = [1.0, material.roughness, material.metallic, 1.0];
+                let outputs = sampler("MetallicRoughness", texture, scale, false);
+                inputs.push(format!("float inputs:metallic.connect = <{outputs}:b>"));
+                inputs.push(format!("float inputs:roughness.connect = <{outputs}:g>"));
+            }
+            None => {
+                inputs.push(format!("float inputs:metallic = {}", material.metallic));
+                inputs.push(format!("float inputs:roughness = {}", material.roughness));
+            }
+        }
+        if let Some(texture) = &material.normal_texture {
+            let outputs = sampler("Normal", texture, [2.0, 2.0, 2.0, 1.0], true);
+            inputs.push(format!("normal3f inputs:normal.connect = <{outputs}:rgb>"));
+        }
+        if !material.unlit {
+            match &material.emissive_texture {
+                Some(texture) => {
+                    // The glTF export leaves the factor at zero when only a texture is given
+                    let [r, g, b] =
+                        if material.emissive == [0.0; 3] { [1.0; 3] } else { material.emissive };
+                    let outputs = sampler("Emissive", texture, [r, g, b, 1.0], false);
+                    inputs.push(format!("color3f inputs:emissiveColor.connect = <{outputs}:rgb>"));
+                }
+                None if material.emissive != [0.0; 3] => {
+                    inputs.push(format!(
+                        "color3f inputs:emissiveColor = {}",
+                        tuple(material.emissive)
+                    ));
+                }
+                None => {}
+            }
+        }
+
+        writeln!(s, "\n            def Shader \"Surface\"\n            {{").unwrap();
+        writeln!(s, "                uniform token info:id = \"UsdPreviewSurface\"").unwrap();
+        for input in inputs {
+            writeln!(s, "                {input}").unwrap();
+        }
+        writeln!(s, "                token outputs:surface\n            }}").unwrap();
+        s.push_str(&shaders);
+        writeln!(s, "        }}").unwrap();
+    }
+
+    fn write_mesh(s: &mut String, id: &str, mesh: &Mesh, material: Option<&str>) {
+        writeln!(s, "\n    def Mesh \"{id}\" (").unwrap();
+        writeln!(s, "        prepend apiSchemas = [\"MaterialBindingAPI\"]\n    )\n    {{")
+            .unwrap();
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        for point in &mesh.points {
+            for i in 0..3 {
+                min[i] = min[i].min(point[i]);
+                max[i] = max[i].max(point[i]);
+            }
+        }
+        if !mesh.points.is_empty() {
+            writeln!(s, "        float3[] extent = [{}, {}]", tuple(min), tuple(max)).unwrap();
+        }
+        let counts = vec!["3"; mesh.indices.len() / 3];
+        writeln!(s, "        int[] faceVertexCounts = [{}]", counts.join(", ")).unwrap();
+        writeln!(s, "        int[] faceVertexIndices = {}", array(&mesh.indices, u32::to_string))
+            .unwrap();
+        if let Some(material) = material {
+            writeln!(s, "        rel material:binding = <{material}>").unwrap();
+        }
+        const VERTEX_INTERPOLATION: &str = "(\n            interpolation = \"vertex\"\n        )";
+        if let Some(normals) = &mesh.normals {
+            let normals = array(normals, |&n| tuple(n));
+            writeln!(s, "        normal3f[] normals = {normals} {VERTEX_INTERPOLATION}").unwrap();
+        }
+        writeln!(s, "        point3f[] points = {}", array(&mesh.points, |&p| tuple(p))).unwrap();
+        for (set, tex_coords) in mesh.tex_coords.iter().enumerate() {
+            writeln!(
+                s,
+                "        texCoord2f[] primvars:{} = {} {VERTEX_INTERPOLATION}",
+                primvar_name(set as u32),
+                array(tex_coords, |&[u, v]| tuple([u, 1.0 - v]))
+            )
+            .unwrap();
+        }
+        writeln!(s, "        uniform token subdivisionScheme = \"none\"\n    }}").unwrap();
+    }
+}
+
+/// Alignment of file data within a `.usdz` package.
+const USDZ_ALIGN: usize = 64;
+
+/// Writes an uncompressed zip archive with each file's data aligned as USDZ requires.
+/// The first file is the package's default layer.
+pub fn write_usdz<W: Write>(mut w: W, files: &[(String, Vec<u8>)]) -> Result<()> {
+    ensure!(files.len() <= u16::MAX as usize, "Too many files for USDZ package");
+    // 1980-01-01 00:00 in MS-DOS format
+    const DOS_TIME: u16 = 0;
+    const DOS_DATE: u16 = (1 << 5) | 1;
+    let mut offset = 0usize;
+    let mut central = Vec::new();
+    for (name, data) in files {
+        ensure!(data.len() <= u32::MAX as usize, "{name} is too large for USDZ package");
+        let crc = crc32fast::hash(data);
+        // Pad with an extra field so that the data starts on an aligned offset
+        let header_size = 30 + name.len() + 4;
+        let padding = (USDZ_ALIGN - (offset + header_size) % USDZ_ALIGN) % USDZ_ALIGN;
+        let mut header = Vec::with_capacity(header_size + padding);
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        header.extend_from_slice(&0u16.to_le_bytes()); // flags
+        header.extend_from_slice(&0u16.to_le_bytes()); // stored
+        header.extend_from_slice(&DOS_TIME.to_le_bytes());
+        header.extend_from_slice(&DOS_DATE.to_le_bytes());
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&(4 + padding as u16).to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        header.extend_from_slice(&0x1986u16.to_le_bytes()); // padding field ID
+        header.extend_from_slice(&(padding as u16).to_le_bytes());
+        header.resize(header_size + padding, 0);
+        w.write_all(&header)?;
+        w.write_all(data)?;
+
+        central.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        central.extend_from_slice(&header[4..28]);
+        central.extend_from_slice(&0u16.to_le_bytes()); // extra length
+        central.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        central.extend_from_slice(&0u16.to_le_bytes()); // disk
+        central.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+        central.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+        central.extend_from_slice(&(offset as u32).to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+        offset += header.len() + data.len();
+    }
+    w.write_all(&central)?;
+    w.write_all(&0x06054b50u32.to_le_bytes())?;
+    w.write_all(&0u16.to_le_bytes())?; // disk
+    w.write_all(&0u16.to_le_bytes())?; // central directory disk
+    w.write_all(&(files.len() as u16).to_le_bytes())?;
+    w.write_all(&(files.len() as u16).to_le_bytes())?;
+    w.write_all(&(central.len() as u32).to_le_bytes())?;
+    w.write_all(&(offset as u32).to_le_bytes())?;
+    w.write_all(&0u16.to_le_bytes())?; // comment length
+    Ok(())
+}
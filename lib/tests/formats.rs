@@ -0,0 +1,1709 @@
+//! Parser regression tests driven by fixture forms generated with the binrw writers.
+
+use std::{
+    borrow::Cow,
+    io::{Cursor, Seek, SeekFrom, Write},
+    mem::size_of,
+    num::NonZeroUsize,
+};
+
+use anyhow::Result;
+use binrw::{BinReaderExt, BinWriterExt, Endian};
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+use image::{Rgba, Rgba32FImage};
+use retrolib::{
+    error::RetroError,
+    format::{
+        chunk::ChunkDescriptor,
+        clsn::{
+            CIndexedTriangle, CollisionData, Triangles, Vertices, K_CHUNK_TRIS, K_CHUNK_VERT,
+            K_CLSN_READER_VERSION, K_CLSN_WRITER_VERSION, K_FORM_CLSN,
+        },
+        cmdl::{
+            accessor_layout, attribute_bounds, bone_indices, generate_tangents, joints_format,
+            normalize_weights, read_float_attribute, widen_half_floats, AccessorComponentType,
+            AccessorType, CLayeredTextureBaseData, CLayeredTextureData, CMaterialData,
+            CMaterialDataInner, CMaterialTextureTokenData, CRenderMesh, EBufferType,
+            EMaterialDataId, EMaterialDataType, EVertexComponent, EVertexDataFormat, ModelData,
+            SIndexBufferInfoSection, SLodInfoInner, SLodInfoOuter, SMaterialChunk,
+            SMeshLoadInformation, SModelBufferInfo, SModelHeader, SModelMetaData,
+            SModelReadBufferInfo, STextureUsageInfo, SVertexBufferInfo, SVertexBufferInfoSection,
+            SVertexDataComponent, K_CHUNK_IBUF, K_CHUNK_MESH, K_CHUNK_MTRL, K_CHUNK_VBUF,
+            K_FORM_CMDL,
+        },
+        foot::K_FORM_FOOT,
+        pack::{
+            decompress_package, Asset, AssetInfo, EContainerCompression, Package, PackageManifest,
+            K_CHUNK_META,
+        },
+        registry::{find_format, parse_form, Viewer},
+        rfrm::{walk_chunks, FormDescriptor},
+        room::{
+            BakedLighting, BakedLightingLightMap, RoomData, SAtlasLookup, SDock, SDockList,
+            SGameAreaHeader, SGeneratedObject, SGeneratedObjectMap, SProductionWorkStage,
+            K_CHUNK_BLIT, K_CHUNK_DOCK, K_CHUNK_LGEN, K_CHUNK_RMHD, K_FORM_HEAD, K_FORM_ROOM,
+        },
+        strp::{resolve_all, PooledString, StringPool, K_CHUNK_STRP},
+        txtr::{
+            decode_slice, decode_texture_file, decompress_image, deswizzle, slice_texture,
+            thumbnail_mip, ETextureAnisotropicRatio, ETextureFilter, ETextureFormat,
+            ETextureMipFilter, ETextureType, ETextureWrap, STextureCompressedBufferInfo,
+            STextureHeader, STextureMetaData, STextureReadInfo, STextureSamplerData, TextureData,
+            TextureSlice, K_CHUNK_HEAD, K_FORM_TXTR,
+        },
+        CAABox, CColor4f, CObjectId, CVector3f, CVector4f, FourCC,
+    },
+    util::cubemap::{cube_irradiance, cube_sh9, sh9_irradiance_cube},
+};
+use strum::IntoEnumIterator;
+use tegra_swizzle::surface::BlockDim;
+use uuid::Uuid;
+use zerocopy::{LittleEndian, U32};
+
+type LE = LittleEndian;
+
+fn form(id: FourCC, reader_version: u32, writer_version: u32) -> FormDescriptor<LE> {
+    FormDescriptor {
+        id,
+        reader_version: U32::new(reader_version),
+        writer_version: U32::new(writer_version),
+        ..Default::default()
+    }
+}
+
+fn chunk(id: FourCC) -> ChunkDescriptor<LE> { ChunkDescriptor { id, ..Default::default() } }
+
+/// Prefix `data` with the uncompressed buffer marker.
+fn uncompressed(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0u8; 4];
+    out.extend_from_slice(data);
+    out
+}
+
+#[test]
+fn form_chunk_round_trip() -> Result<()> {
+    let mut w = Cursor::new(Vec::new());
+    form(FourCC(*b"TEST"), 1, 2).write(&mut w, |w| {
+        chunk(FourCC(*b"AAAA")).write(w, |w| Ok(w.write_le(&0x12345678u32)?))?;
+        chunk(FourCC(*b"BBBB")).write(w, |w| Ok(w.write_le(&[1u8, 2, 3])?))?;
+        Ok(())
+    })?;
+    let data = w.into_inner();
+
+    let (desc, form_data, remain) = FormDescriptor::<LE>::slice(&data)?;
+    assert_eq!(desc.id, FourCC(*b"TEST"));
+    assert_eq!(desc.reader_version.get(), 1);
+    assert_eq!(desc.writer_version.get(), 2);
+    assert_eq!(desc.size.get() as usize, form_data.len());
+    assert!(remain.is_empty());
+
+    let (a, a_data, form_data) = ChunkDescriptor::<LE>::slice(form_data)?;
+    assert_eq!(a.id, FourCC(*b"AAAA"));
+    assert_eq!(a_data, 0x12345678u32.to_le_bytes());
+    let (b, b_data, form_data) = ChunkDescriptor::<LE>::slice(form_data)?;
+    assert_eq!(b.id, FourCC(*b"BBBB"));
+    assert_eq!(b_data, [1, 2, 3]);
+    assert!(form_data.is_empty());
+    Ok(())
+}
+
+#[test]
+fn empty_chunks() -> Result<()> {
+    let mut w = Cursor::new(Vec::new());
+    form(FourCC(*b"TEST"), 1, 1).write(&mut w, |w| {
+        chunk(FourCC(*b"AAAA")).write(w, |_| Ok(()))?;
+        chunk(FourCC(*b"BBBB")).write(w, |w| Ok(w.write_le(&1u32)?))?;
+        Ok(())
+    })?;
+    let data = w.into_inner();
+
+    let (desc, form_data, _) = FormDescriptor::<LE>::slice(&data)?;
+    assert!(!desc.is_empty());
+    let (desc, chunk_data, remain) = ChunkDescriptor::<LE>::slice(form_data)?;
+    assert!(desc.is_empty());
+    assert!(chunk_data.is_empty());
+    let (desc, _, remain) = ChunkDescriptor::<LE>::slice(remain)?;
+    assert_eq!(desc.id, FourCC(*b"BBBB"));
+
+    let err = ChunkDescriptor::<LE>::slice(remain).unwrap_err();
+    assert_eq!(RetroError::find(&err), Some(&RetroError::Empty));
+    let err = FormDescriptor::<LE>::slice(&[]).unwrap_err();
+    assert_eq!(RetroError::find(&err), Some(&RetroError::Empty));
+    Ok(())
+}
+
+#[test]
+fn walk_chunk_paths() -> Result<()> {
+    let mut w = Cursor::new(Vec::new());
+    form(FourCC(*b"TEST"), 1, 1).write(&mut w, |w| {
+        chunk(FourCC(*b"AAAA")).write(w, |w| Ok(w.write_le(&1u32)?))?;
+        form(FourCC(*b"INNR"), 1, 1)
+            .write(w, |w| chunk(FourCC(*b"BBBB")).write(w, |w| Ok(w.write_le(&[1u8, 2])?)))?;
+        Ok(())
+    })?;
+    let mut data = w.into_inner();
+
+    let mut visited = Vec::new();
+    walk_chunks::<LE, _>(&data, |path, header, data| {
+        visited.push((path.to_string(), header.is_form(), data.len()));
+        Ok(())
+    })?;
+    assert_eq!(visited, [
+        ("TEST".to_string(), true, data.len() - size_of::<FormDescriptor<LE>>()),
+        ("TEST/AAAA".to_string(), false, 4),
+        ("TEST/INNR".to_string(), true, size_of::<ChunkDescriptor<LE>>() + 2),
+        ("TEST/INNR/BBBB".to_string(), false, 2),
+    ]);
+
+    // Everything before a truncated trailing chunk is still visited
+    data.extend_from_slice(b"CCCC");
+    let mut count = 0;
+    let result = walk_chunks::<LE, _>(&data, |_, _, _| {
+        count += 1;
+        Ok(())
+    });
+    assert!(result.is_err());
+    assert_eq!(count, 4);
+    Ok(())
+}
+
+#[test]
+fn typed_errors() -> Result<()> {
+    let mut w = Cursor::new(Vec::new());
+    form(K_FORM_TXTR, 46, 51).write(&mut w, |w| {
+        chunk(K_CHUNK_HEAD).write(w, |_| Ok(()))?;
+        Ok(())
+    })?;
+    let data = w.into_inner();
+
+    let err = TextureData::<LE>::slice_header(&data, &[]).unwrap_err();
+    assert_eq!(
+        RetroError::find(&err),
+        Some(&RetroError::UnsupportedVersion {
+            id: K_FORM_TXTR,
+            reader_version: 46,
+            writer_version: 51
+        })
+    );
+    assert!(RetroError::find(&err).unwrap().is_unsupported());
+
+    let err = RoomData::<LE>::slice(&data).unwrap_err();
+    assert_eq!(
+        RetroError::find(&err),
+        Some(&RetroError::UnexpectedFourCC { expected: K_FORM_ROOM, found: K_FORM_TXTR })
+    );
+
+    let err = FormDescriptor::<LE>::slice(&data[..data.len() - 1]).unwrap_err();
+    assert_eq!(
+        RetroError::find(&err),
+        Some(&RetroError::Truncated { at: data.len() - 1, expected: data.len() })
+    );
+
+    assert_eq!(find_format(K_FORM_TXTR).map(|info| info.name), Some("Texture"));
+    assert_eq!(find_format(K_FORM_TXTR).and_then(|info| info.viewer), Some(Viewer::Texture));
+    let mut w = Cursor::new(Vec::new());
+    form(FourCC(*b"TEST"), 1, 1).write(&mut w, |_| Ok(()))?;
+    let err = parse_form(&w.into_inner()).unwrap_err();
+    assert_eq!(RetroError::find(&err), Some(&RetroError::UnsupportedFormat("TEST".to_string())));
+    Ok(())
+}
+
+#[test]
+fn collision_geometry() -> Result<()> {
+    let vertices = Vertices {
+        vertices: vec![
+            CVector3f::new(0.0, 0.0, 0.0),
+            CVector3f::new(1.0, 0.0, 0.0),
+            CVector3f::new(0.0, 1.0, 0.0),
+        ],
+    };
+    let triangles = Triangles {
+        triangles: vec![CIndexedTriangle { idx1: 0, idx2: 1, idx3: 2, material: 0, unk: 0 }],
+    };
+    let mut w = Cursor::new(Vec::new());
+    form(K_FORM_CLSN, K_CLSN_READER_VERSION, K_CLSN_WRITER_VERSION).write(&mut w, |w| {
+        chunk(K_CHUNK_VERT).write(w, |w| Ok(w.write_le(&vertices)?))?;
+        chunk(K_CHUNK_TRIS).write(w, |w| Ok(w.write_le(&triangles)?))?;
+        Ok(())
+    })?;
+    form(K_FORM_FOOT, 1, 1).write(&mut w, |_| Ok(()))?;
+    let data = w.into_inner();
+
+    let info = parse_form(&data)?;
+    assert_eq!(info.name, "Collision");
+    assert_eq!(info.commands, ["collision convert"]);
+    let collision = CollisionData::<LE>::slice(&data)?;
+    assert_eq!(collision.kind, K_FORM_CLSN);
+    assert_eq!(collision.vertices.len(), 3);
+    assert_eq!(collision.vertices[1].x, 1.0);
+    assert_eq!(collision.triangles.len(), 1);
+    assert_eq!(collision.triangles[0].idx3, 2);
+    Ok(())
+}
+
+/// Build a single-mip 2D texture file and its metadata with zeroed (swizzled) block data.
+fn texture_fixture(format: ETextureFormat, width: u32, height: u32) -> Result<(Vec<u8>, Vec<u8>)> {
+    let (bw, bh, bd) = format.block_size();
+    let bpp = format.bytes_per_pixel();
+    let mip_size = (width / bw as u32) * (height / bh as u32) * bpp;
+    let swizzled_size = tegra_swizzle::surface::swizzled_surface_size(
+        width as usize,
+        height as usize,
+        1,
+        BlockDim {
+            width: NonZeroUsize::new(bw as usize).unwrap(),
+            height: NonZeroUsize::new(bh as usize).unwrap(),
+            depth: NonZeroUsize::new(bd as usize).unwrap(),
+        },
+        None,
+        bpp as usize,
+        1,
+        1,
+    );
+    let head = STextureHeader {
+        kind: ETextureType::D2,
+        format,
+        width,
+        height,
+        layers: 1,
+        tile_mode: 0,
+        swizzle: 0,
+        mip_sizes: vec![mip_size],
+        sampler_data: STextureSamplerData {
+            unk: 0,
+            filter: ETextureFilter::Linear,
+            mip_filter: ETextureMipFilter::Linear,
+            wrap_x: ETextureWrap::Repeat,
+            wrap_y: ETextureWrap::Repeat,
+            wrap_z: ETextureWrap::Repeat,
+            aniso: ETextureAnisotropicRatio::None,
+        },
+    };
+
+    let mut w = Cursor::new(Vec::new());
+    form(K_FORM_TXTR, 47, 51)
+        .write(&mut w, |w| chunk(K_CHUNK_HEAD).write(w, |w| Ok(w.write_le(&head)?)))?;
+    let mut data = w.into_inner();
+    let gpu_offset = data.len() as u32;
+    let gpu_data = uncompressed(&vec![0u8; swizzled_size]);
+    data.extend_from_slice(&gpu_data);
+
+    let meta = STextureMetaData {
+        unk1: 0,
+        unk2: 0,
+        alloc_category: 0,
+        gpu_offset,
+        align: 0,
+        decompressed_size: swizzled_size as u32,
+        info: vec![STextureReadInfo { index: 0, offset: gpu_offset, size: gpu_data.len() as u32 }],
+        buffers: vec![STextureCompressedBufferInfo {
+            index: 0,
+            offset: 0,
+            size: gpu_data.len() as u32,
+            dest_offset: 0,
+            dest_size: swizzled_size as u32,
+        }],
+    };
+    let mut w = Cursor::new(Vec::new());
+    w.write_le(&meta)?;
+    Ok((data, w.into_inner()))
+}
+
+#[test]
+fn texture_bc1() -> Result<()> {
+    let (data, meta) = texture_fixture(ETextureFormat::RgbaBc1Unorm, 16, 16)?;
+    let texture = TextureData::<LE>::slice(&data, &meta)?;
+    assert_eq!(texture.head.format, ETextureFormat::RgbaBc1Unorm);
+    assert_eq!((texture.head.width, texture.head.height), (16, 16));
+    // 4x4 blocks of 8 bytes
+    assert_eq!(texture.data.len(), 128);
+
+    let slices = slice_texture(&texture)?;
+    assert_eq!(slices.len(), 1);
+    assert_eq!(slices[0].len(), 1);
+    assert_eq!((slices[0][0].width, slices[0][0].height), (16, 16));
+    assert_eq!(slices[0][0].data_range, 0..128);
+
+    let image = decode_slice(&texture, 0, 0)?;
+    assert_eq!((image.width(), image.height()), (16, 16));
+    assert!(decode_slice(&texture, 1, 0).is_err());
+    assert!(decode_slice(&texture, 0, 1).is_err());
+    Ok(())
+}
+
+#[test]
+fn texture_thumbnail_mip() -> Result<()> {
+    // A single-mip texture has nothing smaller to offer, so its preview is full resolution
+    let (data, meta) = texture_fixture(ETextureFormat::RgbaBc1Unorm, 16, 16)?;
+    let texture = TextureData::<LE>::slice(&data, &meta)?;
+    let slices = slice_texture(&texture)?;
+    assert_eq!(thumbnail_mip(&slices, 64), Some(0));
+    assert_eq!(thumbnail_mip(&slices, 8), Some(0));
+
+    // 256x128 down to 16x8: the smallest mip at least the requested size is chosen
+    let chain = (0..5)
+        .map(|mip| vec![TextureSlice { width: 256 >> mip, height: 128 >> mip, data_range: 0..0 }])
+        .collect::<Vec<_>>();
+    assert_eq!(thumbnail_mip(&chain, 64), Some(2));
+    assert_eq!(thumbnail_mip(&chain, 65), Some(1));
+    assert_eq!(thumbnail_mip(&chain, 16), Some(4));
+    assert_eq!(thumbnail_mip(&chain, 512), Some(0));
+
+    assert_eq!(thumbnail_mip(&[], 64), None);
+    assert_eq!(thumbnail_mip(&[vec![]], 64), None);
+    Ok(())
+}
+
+#[test]
+fn cube_irradiance_convolution() -> Result<()> {
+    let face = |value: f32| Rgba32FImage::from_pixel(8, 8, Rgba([value, value, value, 1.0]));
+
+    // Uniform radiance gives the same irradiance in every direction
+    let uniform = cube_irradiance(&(0..6).map(|_| face(2.0)).collect::<Vec<_>>(), 4)?;
+    assert_eq!(uniform.len(), 6);
+    for image in &uniform {
+        assert_eq!(image.dimensions(), (4, 4));
+        for pixel in image.pixels() {
+            assert!((pixel.0[0] - 2.0).abs() < 1e-4, "{pixel:?}");
+        }
+    }
+
+    // Light only from above: up-facing normals are lit, down-facing ones aren't,
+    // and sideways normals see part of the hemisphere
+    let mut faces = vec![face(0.0); 6];
+    faces[2] = face(1.0);
+    let irradiance = cube_irradiance(&faces, 4)?;
+    let up = irradiance[2].get_pixel(1, 1).0[0];
+    let side = irradiance[4].get_pixel(1, 1).0[0];
+    let down = irradiance[3].get_pixel(1, 1).0[0];
+    assert!(up > side && side > 0.0, "up {up} side {side}");
+    assert_eq!(down, 0.0);
+
+    assert!(cube_irradiance(&faces[..5], 4).is_err());
+    assert!(cube_irradiance(&faces, 0).is_err());
+    Ok(())
+}
+
+#[test]
+fn cube_sh9_irradiance() -> Result<()> {
+    let face = |value: f32| Rgba32FImage::from_pixel(8, 8, Rgba([value, value, value, 1.0]));
+
+    let uniform = sh9_irradiance_cube(&cube_sh9(&vec![face(2.0); 6])?, 4)?;
+    for pixel in uniform.iter().flat_map(|image| image.pixels()) {
+        assert!((pixel.0[0] - 2.0).abs() < 1e-3, "{pixel:?}");
+    }
+
+    // Irradiance is smooth enough for bands 0-2 to match the brute force convolution
+    let mut faces = vec![face(0.0); 6];
+    faces[2] = face(1.0);
+    let evaluated = sh9_irradiance_cube(&cube_sh9(&faces)?, 4)?;
+    let convolved = cube_irradiance(&faces, 4)?;
+    for (a, b) in evaluated.iter().zip(&convolved) {
+        for (a, b) in a.pixels().zip(b.pixels()) {
+            assert!((a.0[0] - b.0[0]).abs() < 0.01, "{a:?} {b:?}");
+        }
+    }
+
+    assert!(cube_sh9(&faces[..5]).is_err());
+    assert!(sh9_irradiance_cube(&[[0.0; 3]; 9], 0).is_err());
+    Ok(())
+}
+
+#[test]
+fn texture_decode_file() -> Result<()> {
+    let (mut data, meta) = texture_fixture(ETextureFormat::RgbaBc1Unorm, 16, 16)?;
+    // Extend the form over the trailing GPU data, as in extracted files
+    let form_len = data.len();
+    let payload_size = (form_len - size_of::<FormDescriptor<LE>>()) as u64;
+    data[4..12].copy_from_slice(&payload_size.to_le_bytes());
+    let mut w = Cursor::new(data);
+    w.seek(SeekFrom::End(0))?;
+    form(K_FORM_FOOT, 1, 1)
+        .write(&mut w, |w| chunk(K_CHUNK_META).write(w, |w| Ok(w.write_all(&meta)?)))?;
+    let data = w.into_inner();
+
+    let texture = decode_texture_file(&data)?;
+    assert_eq!(texture.head.format, ETextureFormat::RgbaBc1Unorm);
+    assert_eq!(texture.mips.len(), 1);
+    assert_eq!(texture.mips[0].len(), 1);
+    assert_eq!((texture.mips[0][0].width(), texture.mips[0][0].height()), (16, 16));
+
+    // Without the footer there's no metadata to locate
+    assert!(decode_texture_file(&data[..form_len]).is_err());
+    Ok(())
+}
+
+#[test]
+fn texture_slice_swizzled() -> Result<()> {
+    let (data, meta) = texture_fixture(ETextureFormat::RgbaBc1Unorm, 32, 32)?;
+    let (head, meta_data, buffer) = TextureData::<LE>::slice_swizzled(&data, &meta)?;
+    assert_eq!(buffer.len(), meta_data.decompressed_size as usize);
+    let texture = TextureData::<LE>::slice(&data, &meta)?;
+    assert_eq!(deswizzle(&head, &buffer, meta_data.align)?, texture.data);
+    Ok(())
+}
+
+#[test]
+fn texture_astc() -> Result<()> {
+    let (data, meta) = texture_fixture(ETextureFormat::RgbaAstc4x4, 16, 16)?;
+    let texture = TextureData::<LE>::slice(&data, &meta)?;
+    assert!(texture.head.format.is_astc());
+    assert_eq!((texture.head.width, texture.head.height), (16, 16));
+    // 4x4 blocks of 16 bytes
+    assert_eq!(texture.data.len(), 256);
+
+    let slices = slice_texture(&texture)?;
+    assert_eq!(slices.len(), 1);
+    assert_eq!(slices[0][0].data_range, 0..256);
+    Ok(())
+}
+
+#[test]
+fn texture_multiple_buffers() -> Result<()> {
+    // Exactly one GOB, so the swizzled surface has no padding
+    let (mut data, meta) = texture_fixture(ETextureFormat::RgbaBc1Unorm, 32, 32)?;
+    let mut meta: STextureMetaData = Cursor::new(meta).read_le()?;
+    let size = meta.decompressed_size as usize;
+    assert_eq!(size, 512);
+
+    // Split the surface into two buffers from separate reads
+    data.truncate(meta.gpu_offset as usize);
+    meta.info.clear();
+    meta.buffers.clear();
+    for (index, fill) in [(0u8, 0x00u8), (1, 0xFF)] {
+        let offset = data.len() as u32;
+        let buf = uncompressed(&vec![fill; size / 2]);
+        data.extend_from_slice(&buf);
+        meta.info.push(STextureReadInfo { index, offset, size: buf.len() as u32 });
+        meta.buffers.push(STextureCompressedBufferInfo {
+            index: index as u32,
+            offset: 0,
+            size: buf.len() as u32,
+            dest_offset: index as u32 * (size / 2) as u32,
+            dest_size: (size / 2) as u32,
+        });
+    }
+    let mut w = Cursor::new(Vec::new());
+    w.write_le(&meta)?;
+
+    let texture = TextureData::<LE>::slice(&data, &w.into_inner())?;
+    assert_eq!(texture.data.len(), size);
+    // Deswizzling reorders bytes, but both halves must have been decompressed
+    assert_eq!(texture.data.iter().filter(|&&b| b == 0xFF).count(), texture.data.len() / 2);
+    Ok(())
+}
+
+#[test]
+fn texture_format_from_str() -> Result<()> {
+    for format in ETextureFormat::iter() {
+        assert_eq!(format!("{format:?}").parse::<ETextureFormat>()?, format);
+        // Display names aren't unique (D16 UNORM), so compare the names
+        let display = format.to_string();
+        assert_eq!(display.parse::<ETextureFormat>()?.to_string(), display);
+    }
+    assert_eq!("bc7 unorm (srgb)".parse::<ETextureFormat>()?, ETextureFormat::BptcUnormSrgb);
+    assert_eq!("D16 UNORM".parse::<ETextureFormat>()?, ETextureFormat::Depth16Unorm);
+    assert!("BC8".parse::<ETextureFormat>().is_err());
+    Ok(())
+}
+
+#[test]
+fn texture_aligned_buffer() -> Result<()> {
+    // Exactly one GOB, so the surface itself has no padding
+    let (mut data, meta) = texture_fixture(ETextureFormat::RgbaBc1Unorm, 32, 32)?;
+    let mut meta: STextureMetaData = Cursor::new(meta).read_le()?;
+    let size = meta.decompressed_size as usize;
+    assert_eq!(size, 512);
+    data.truncate(meta.gpu_offset as usize);
+    let surface = (0..size).map(|i| i as u8).collect::<Vec<_>>();
+    let buf = uncompressed(&surface);
+    data.extend_from_slice(&buf);
+    meta.info[0].size = buf.len() as u32;
+    meta.buffers[0].size = buf.len() as u32;
+    let write_meta = |meta: &STextureMetaData| -> Result<Vec<u8>> {
+        let mut w = Cursor::new(Vec::new());
+        w.write_le(meta)?;
+        Ok(w.into_inner())
+    };
+    let expected = TextureData::<LE>::slice(&data, &write_meta(&meta)?)?;
+
+    // Allocation padded to its alignment after the surface
+    meta.align = 0x1000;
+    meta.decompressed_size = 0x1000;
+    let texture = TextureData::<LE>::slice(&data, &write_meta(&meta)?)?;
+    assert_eq!(texture.data, expected.data);
+    assert_ne!(texture.data, surface);
+
+    // Padding that doesn't match the alignment
+    meta.decompressed_size = 0x800;
+    assert!(TextureData::<LE>::slice(&data, &write_meta(&meta)?).is_err());
+
+    // Destination outside the decompressed buffer
+    meta.decompressed_size = 0x100;
+    assert!(TextureData::<LE>::slice(&data, &write_meta(&meta)?).is_err());
+    Ok(())
+}
+
+#[test]
+fn texture_astc_sub_block_mip() -> Result<()> {
+    // LDR void-extent block: constant color, no extent coordinates
+    let mut block = Vec::with_capacity(16);
+    block.extend_from_slice(&0xFFFF_FFFF_FFFF_FDFCu64.to_le_bytes());
+    for channel in [0xFFFFu16, 0, 0, 0xFFFF] {
+        block.extend_from_slice(&channel.to_le_bytes());
+    }
+
+    // A 2x2 mip still occupies a whole 8x8 block
+    let image = decompress_image(ETextureFormat::RgbaAstc8x8, 2, 2, &block)?;
+    assert_eq!((image.width(), image.height()), (2, 2));
+    let image = image.to_rgba8();
+    assert!(image.pixels().all(|p| p.0 == [255, 0, 0, 255]));
+
+    let err = decompress_image(ETextureFormat::RgbaAstc8x8, 2, 2, &block[..8]).unwrap_err();
+    assert!(err.to_string().contains("expected data size"));
+    assert!(err.to_string().contains("1x1 blocks of 8x8"));
+    Ok(())
+}
+
+#[test]
+fn texture_integer_formats() -> Result<()> {
+    // Signed values are offset so that zero is mid-gray
+    let image = decompress_image(ETextureFormat::R8Sint, 2, 1, &[0x80, 0x00])?;
+    assert_eq!(image.to_luma8().into_raw(), vec![0x00, 0x80]);
+
+    let image = decompress_image(ETextureFormat::Rg8Uint, 1, 1, &[3, 7])?;
+    assert_eq!(image.to_rgb8().into_raw(), vec![3, 7, 0]);
+
+    let data: Vec<u8> = [-1i16, 1].iter().flat_map(|v| v.to_le_bytes()).collect();
+    let image = decompress_image(ETextureFormat::R16Sint, 2, 1, &data)?;
+    assert_eq!(image.to_luma16().into_raw(), vec![0x7FFF, 0x8001]);
+
+    let err = decompress_image(ETextureFormat::R32Uint, 1, 1, &[0; 4]).unwrap_err();
+    assert!(err.to_string().contains("R32 UINT"));
+    Ok(())
+}
+
+/// Builds a single mesh CMDL and its META from uncompressed vertex and index buffers.
+fn model_fixture(
+    vbuf: &SVertexBufferInfoSection,
+    vtx_buffers: &[Vec<u8>],
+    idx_data: &[u8],
+    index_count: u32,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    model_fixture_with_chunks(vbuf, vtx_buffers, idx_data, index_count, &[])
+}
+
+/// Like `model_fixture`, appending extra chunks after the known ones.
+fn model_fixture_with_chunks(
+    vbuf: &SVertexBufferInfoSection,
+    vtx_buffers: &[Vec<u8>],
+    idx_data: &[u8],
+    index_count: u32,
+    extra_chunks: &[(FourCC, &[u8])],
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let head = SModelHeader {
+        kind: K_CHUNK_HEAD,
+        unk: 0,
+        bounds: CAABox { min: CVector3f::new(0.0, 0.0, 0.0), max: CVector3f::new(1.0, 2.0, 0.5) },
+        unk_words: vec![],
+        unk_tail: vec![],
+    };
+    let mtrl = SMaterialChunk { unk: 0, materials: vec![] };
+    let empty_lod = SLodInfoInner { offset: 0, count: 0 };
+    let mesh = SMeshLoadInformation {
+        meshes: vec![CRenderMesh {
+            material_idx: 0,
+            vtx_buf_idx: 0,
+            idx_buf_idx: 0,
+            index_start: 0,
+            index_count,
+            unk_c: 0,
+            unk_e: 0,
+        }],
+        unk_data_1: vec![0],
+        unk_data_2: vec![0],
+        shorts: vec![0],
+        lod_count: 1,
+        lod_info: vec![SLodInfoOuter {
+            inner: [
+                SLodInfoInner { offset: 0, count: 1 },
+                empty_lod.clone(),
+                empty_lod.clone(),
+                empty_lod.clone(),
+                empty_lod,
+            ],
+        }],
+        lod_rules: vec![],
+    };
+    let ibuf = SIndexBufferInfoSection { info: vec![EBufferType::U16] };
+
+    let mut w = Cursor::new(Vec::new());
+    form(K_FORM_CMDL, 114, 125).write(&mut w, |w| {
+        chunk(K_CHUNK_HEAD).write(w, |w| Ok(w.write_le(&head)?))?;
+        chunk(K_CHUNK_MTRL).write(w, |w| Ok(w.write_le(&mtrl)?))?;
+        chunk(K_CHUNK_MESH).write(w, |w| Ok(w.write_le(&mesh)?))?;
+        chunk(K_CHUNK_VBUF).write(w, |w| Ok(w.write_le(vbuf)?))?;
+        chunk(K_CHUNK_IBUF).write(w, |w| Ok(w.write_le(&ibuf)?))?;
+        for (id, data) in extra_chunks {
+            chunk(*id).write(w, |w| Ok(w.write_all(data)?))?;
+        }
+        Ok(())
+    })?;
+    let mut data = w.into_inner();
+    let gpu_offset = data.len() as u32;
+    let mut vtx_buffer_info = Vec::with_capacity(vtx_buffers.len());
+    for buf in vtx_buffers {
+        let comp = uncompressed(buf);
+        vtx_buffer_info.push(SModelBufferInfo {
+            read_index: 0,
+            offset: data.len() as u32 - gpu_offset,
+            size: comp.len() as u32,
+            dest_size: buf.len() as u32,
+        });
+        data.extend_from_slice(&comp);
+    }
+    let idx_comp = uncompressed(idx_data);
+    let idx_buffer_info = vec![SModelBufferInfo {
+        read_index: 0,
+        offset: data.len() as u32 - gpu_offset,
+        size: idx_comp.len() as u32,
+        dest_size: idx_data.len() as u32,
+    }];
+    data.extend_from_slice(&idx_comp);
+
+    let meta = SModelMetaData {
+        unk: 0,
+        gpu_offset,
+        read_info: vec![SModelReadBufferInfo {
+            size: data.len() as u32 - gpu_offset,
+            offset: gpu_offset,
+        }],
+        vtx_buffer_info,
+        idx_buffer_info,
+    };
+    let mut w = Cursor::new(Vec::new());
+    w.write_le(&meta)?;
+    Ok((data, w.into_inner()))
+}
+
+#[test]
+fn model_single_triangle() -> Result<()> {
+    let positions: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 2.0, 0.5]];
+    let vtx_data: Vec<u8> = positions.iter().flatten().flat_map(|v| v.to_le_bytes()).collect();
+    let idx_data: Vec<u8> = [0u16, 1, 2].iter().flat_map(|v| v.to_le_bytes()).collect();
+    let vbuf = SVertexBufferInfoSection {
+        info: vec![SVertexBufferInfo {
+            vertex_count: 3,
+            components: vec![SVertexDataComponent {
+                buffer_index: 0,
+                offset: 0,
+                stride: 12,
+                format: EVertexDataFormat::Rgb32Float,
+                component: EVertexComponent::Position,
+            }],
+            num_buffers: 1,
+        }],
+    };
+    let (data, meta) = model_fixture(&vbuf, &[vtx_data.clone()], &idx_data, 3)?;
+
+    let model = ModelData::<LE>::slice(&data, &meta)?;
+    assert!(!model.head.is_skinned());
+    assert!(!model.head.is_world());
+    assert_eq!(model.head.bounds.min.x, 0.0);
+    assert_eq!(model.head.bounds.max.y, 2.0);
+    assert_eq!(model.head.bounds.max.z, 0.5);
+    assert!(model.mtrl.materials.is_empty());
+    assert_eq!(model.mesh.meshes.len(), 1);
+    assert_eq!(model.mesh.meshes[0].index_count, 3);
+    assert_eq!(model.mesh.lod_info.len(), 1);
+    assert_eq!(model.mesh.lod_meshes(0), Some(vec![0]));
+    assert_eq!(model.vbuf.info.len(), 1);
+    assert_eq!(model.vbuf.info[0].vertex_count, 3);
+    assert_eq!(model.ibuf.info.len(), 1);
+    assert_eq!(model.vtx_buffers, vec![vtx_data]);
+    assert_eq!(model.idx_buffers, vec![idx_data]);
+    Ok(())
+}
+
+#[test]
+fn model_header_unknowns() -> Result<()> {
+    let header = SModelHeader {
+        kind: K_CHUNK_HEAD,
+        unk: 1,
+        bounds: CAABox { min: CVector3f::new(0.0, 0.0, 0.0), max: CVector3f::new(1.0, 1.0, 1.0) },
+        unk_words: vec![2, 0xDEADBEEF],
+        unk_tail: vec![3, 4],
+    };
+    let mut w = Cursor::new(Vec::new());
+    w.write_le(&header)?;
+    let data = w.into_inner();
+    assert_eq!(data.len(), 4 + 24 + 8 + 2);
+
+    let read: SModelHeader = Cursor::new(&data).read_le()?;
+    assert_eq!(read.unk, 1);
+    assert_eq!(read.unk_words, header.unk_words);
+    assert_eq!(read.unk_tail, header.unk_tail);
+    let mut w = Cursor::new(Vec::new());
+    w.write_le(&read)?;
+    assert_eq!(w.into_inner(), data);
+    Ok(())
+}
+
+#[test]
+fn model_unknown_chunks() -> Result<()> {
+    let vtx_data = vec![0u8; 3 * 12];
+    let idx_data: Vec<u8> = [0u16, 1, 2].iter().flat_map(|v| v.to_le_bytes()).collect();
+    let vbuf = SVertexBufferInfoSection {
+        info: vec![SVertexBufferInfo {
+            vertex_count: 3,
+            components: vec![SVertexDataComponent {
+                buffer_index: 0,
+                offset: 0,
+                stride: 12,
+                format: EVertexDataFormat::Rgb32Float,
+                component: EVertexComponent::Position,
+            }],
+            num_buffers: 1,
+        }],
+    };
+    let (data, meta) = model_fixture_with_chunks(&vbuf, &[vtx_data.clone()], &idx_data, 3, &[(
+        FourCC(*b"NEW1"),
+        &[1, 2, 3, 4],
+    )])?;
+
+    // Skipped by default, rejected in strict mode
+    let model = ModelData::<LE>::slice(&data, &meta)?;
+    assert_eq!(model.mesh.meshes.len(), 1);
+    assert_eq!(model.vtx_buffers, vec![vtx_data]);
+    assert!(ModelData::<LE>::slice_strict(&data, &meta).is_err());
+    Ok(())
+}
+
+#[test]
+fn model_multiple_vertex_buffers() -> Result<()> {
+    // Positions in buffer 0 (stride 12), normals and UVs interleaved in buffer 1 (stride 20)
+    let component = |buffer_index, offset, stride, format, component| SVertexDataComponent {
+        buffer_index,
+        offset,
+        stride,
+        format,
+        component,
+    };
+    let vbuf = SVertexBufferInfoSection {
+        info: vec![SVertexBufferInfo {
+            vertex_count: 3,
+            components: vec![
+                component(0, 0, 12, EVertexDataFormat::Rgb32Float, EVertexComponent::Position),
+                component(1, 0, 20, EVertexDataFormat::Rgb32Float, EVertexComponent::Normal),
+                component(1, 12, 20, EVertexDataFormat::Rg32Float, EVertexComponent::TexCoord0),
+            ],
+            num_buffers: 2,
+        }],
+    };
+    let info = &vbuf.info[0];
+    assert_eq!(info.buffer_count(), 2);
+    assert_eq!(info.buffer_strides()?, vec![12, 20]);
+
+    let positions = vec![0u8; 3 * 12];
+    let attributes = vec![1u8; 3 * 20];
+    let idx_data: Vec<u8> = [0u16, 1, 2].iter().flat_map(|v| v.to_le_bytes()).collect();
+    let (data, meta) =
+        model_fixture(&vbuf, &[positions.clone(), attributes.clone()], &idx_data, 3)?;
+    let model = ModelData::<LE>::slice(&data, &meta)?;
+    assert_eq!(model.vtx_buffers, vec![positions.clone(), attributes]);
+
+    // A buffer too small for its component strides is rejected
+    let (data, meta) = model_fixture(&vbuf, &[positions.clone(), vec![1u8; 3 * 16]], &idx_data, 3)?;
+    assert!(ModelData::<LE>::slice(&data, &meta).is_err());
+
+    // Padding past the vertex data is only rejected in strict mode
+    let padded = vec![1u8; 3 * 20 + 8];
+    let (data, meta) = model_fixture(&vbuf, &[positions.clone(), padded], &idx_data, 3)?;
+    assert_eq!(ModelData::<LE>::slice(&data, &meta)?.vtx_buffers[1].len(), 3 * 20);
+    assert!(ModelData::<LE>::slice_strict(&data, &meta).is_err());
+
+    // So is an unused trailing GPU buffer
+    let buffers = [positions.clone(), vec![1u8; 3 * 20], vec![2u8; 4]];
+    let (data, meta) = model_fixture(&vbuf, &buffers, &idx_data, 3)?;
+    assert!(ModelData::<LE>::slice(&data, &meta).is_ok());
+    assert!(ModelData::<LE>::slice_strict(&data, &meta).is_err());
+
+    // And a header buffer count that disagrees with the components
+    let mut miscounted = vbuf.clone();
+    miscounted.info[0].num_buffers = 3;
+    let attributes = vec![1u8; 3 * 20];
+    let (data, meta) = model_fixture(&miscounted, &[positions, attributes], &idx_data, 3)?;
+    assert!(ModelData::<LE>::slice(&data, &meta).is_ok());
+    let err = ModelData::<LE>::slice_strict(&data, &meta).unwrap_err();
+    assert!(format!("{err:#}").contains("header declares 3"));
+
+    // Components sharing a buffer must agree on its stride
+    let mut mismatched = info.clone();
+    mismatched.components[2].stride = 24;
+    assert!(mismatched.buffer_strides().is_err());
+    Ok(())
+}
+
+#[test]
+fn model_retain_meshes() -> Result<()> {
+    let vtx_data = vec![0u8; 3 * 12];
+    let idx_data: Vec<u8> = [0u16, 1, 2].iter().flat_map(|v| v.to_le_bytes()).collect();
+    let vbuf = SVertexBufferInfoSection {
+        info: vec![SVertexBufferInfo {
+            vertex_count: 3,
+            components: vec![SVertexDataComponent {
+                buffer_index: 0,
+                offset: 0,
+                stride: 12,
+                format: EVertexDataFormat::Rgb32Float,
+                component: EVertexComponent::Position,
+            }],
+            num_buffers: 1,
+        }],
+    };
+    let (data, meta) = model_fixture(&vbuf, &[vtx_data.clone()], &idx_data, 3)?;
+    let mut model = ModelData::<LE>::slice(&data, &meta)?;
+
+    // Mesh 1 uses a second vertex and index buffer; mesh 2 shares the first ones
+    let other_vtx = vec![1u8; 3 * 12];
+    let other_idx: Vec<u8> = [2u16, 1, 0].iter().flat_map(|v| v.to_le_bytes()).collect();
+    model.vbuf.info.push(model.vbuf.info[0].clone());
+    model.vtx_buffers.push(other_vtx.clone());
+    model.ibuf.info.push(EBufferType::U16);
+    model.idx_buffers.push(other_idx.clone());
+    let mut mesh = model.mesh.meshes[0].clone();
+    mesh.vtx_buf_idx = 1;
+    mesh.idx_buf_idx = 1;
+    model.mesh.meshes.push(mesh);
+    model.mesh.meshes.push(model.mesh.meshes[0].clone());
+    model.mesh.shorts = vec![0, 1, 2];
+    model.mesh.lod_info[0].inner[0].count = 3;
+    // Per-mesh flags: 2 bits each in unk_data_1, 1 bit each in unk_data_2
+    model.mesh.unk_data_1 = vec![0b10_01_11];
+    model.mesh.unk_data_2 = vec![0b101];
+
+    let mut selected = model.clone();
+    selected.retain_meshes(&[1])?;
+    assert_eq!(selected.mesh.meshes.len(), 1);
+    assert_eq!(selected.mesh.meshes[0].vtx_buf_idx, 0);
+    assert_eq!(selected.mesh.meshes[0].idx_buf_idx, 0);
+    assert_eq!(selected.vbuf.info.len(), 1);
+    assert_eq!(selected.vtx_buffers, vec![other_vtx]);
+    assert_eq!(selected.idx_buffers, vec![other_idx]);
+    assert_eq!(selected.mesh.lod_meshes(0), Some(vec![0]));
+    assert_eq!(selected.mesh.unk_data_1, vec![0b01]);
+    assert_eq!(selected.mesh.unk_data_2, vec![0b0]);
+
+    let mut selected = model.clone();
+    selected.retain_meshes(&[2, 0])?;
+    assert_eq!(selected.mesh.meshes.len(), 2);
+    assert_eq!(selected.vtx_buffers, vec![vtx_data]);
+    assert_eq!(selected.idx_buffers, vec![idx_data]);
+    assert_eq!(selected.mesh.lod_meshes(0), Some(vec![0, 1]));
+    assert_eq!(selected.mesh.unk_data_1, vec![0b10_11]);
+    assert_eq!(selected.mesh.unk_data_2, vec![0b11]);
+
+    let err = model.retain_meshes(&[3]).unwrap_err();
+    assert!(err.to_string().contains("valid: 0-2"));
+    Ok(())
+}
+
+#[test]
+fn model_flatten_meshes() -> Result<()> {
+    let vtx_data = vec![0u8; 3 * 12];
+    let idx_data: Vec<u8> = [0u16, 1, 2].iter().flat_map(|v| v.to_le_bytes()).collect();
+    let vbuf = SVertexBufferInfoSection {
+        info: vec![SVertexBufferInfo {
+            vertex_count: 3,
+            components: vec![SVertexDataComponent {
+                buffer_index: 0,
+                offset: 0,
+                stride: 12,
+                format: EVertexDataFormat::Rgb32Float,
+                component: EVertexComponent::Position,
+            }],
+            num_buffers: 1,
+        }],
+    };
+    let (data, meta) = model_fixture(&vbuf, &[vtx_data.clone()], &idx_data, 3)?;
+    let mut model = ModelData::<LE>::slice(&data, &meta)?;
+
+    // Mesh 1 uses a second vertex and index buffer; mesh 2 shares the first ones
+    let other_vtx = vec![1u8; 3 * 12];
+    let other_idx: Vec<u8> = [2u16, 1, 0].iter().flat_map(|v| v.to_le_bytes()).collect();
+    model.vbuf.info.push(model.vbuf.info[0].clone());
+    model.vtx_buffers.push(other_vtx.clone());
+    model.ibuf.info.push(EBufferType::U16);
+    model.idx_buffers.push(other_idx);
+    let mut mesh = model.mesh.meshes[0].clone();
+    mesh.vtx_buf_idx = 1;
+    mesh.idx_buf_idx = 1;
+    model.mesh.meshes.push(mesh);
+    let mut mesh = model.mesh.meshes[0].clone();
+    mesh.index_start = 1;
+    mesh.index_count = 2;
+    model.mesh.meshes.push(mesh);
+    model.mesh.shorts = vec![0, 1, 2];
+    model.mesh.lod_info[0].inner[0].count = 3;
+
+    model.flatten_meshes()?;
+    assert_eq!(model.mesh.meshes.len(), 1);
+    assert_eq!(model.mesh.meshes[0].index_start, 0);
+    assert_eq!(model.mesh.meshes[0].index_count, 8);
+    assert_eq!(model.vbuf.info.len(), 1);
+    assert_eq!(model.vbuf.info[0].vertex_count, 6);
+    assert_eq!(model.vtx_buffers, vec![[vtx_data, other_vtx].concat()]);
+    assert!(matches!(model.ibuf.info.as_slice(), [EBufferType::U32]));
+    // Mesh 1's indices are rebased past the first vertex buffer
+    let indices = model.idx_buffers[0]
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(indices, vec![0, 1, 2, 5, 4, 3, 1, 2]);
+    assert_eq!(model.mesh.lod_meshes(0), Some(vec![0]));
+    Ok(())
+}
+
+#[test]
+fn model_half_float_attributes() -> Result<()> {
+    let component = |offset, format, component| SVertexDataComponent {
+        buffer_index: 0,
+        offset,
+        stride: 16,
+        format,
+        component,
+    };
+    let vbuf = SVertexBufferInfoSection {
+        info: vec![SVertexBufferInfo {
+            vertex_count: 3,
+            components: vec![
+                component(0, EVertexDataFormat::Rgba16Float, EVertexComponent::Position),
+                component(8, EVertexDataFormat::Rg16Float, EVertexComponent::TexCoord0),
+                component(12, EVertexDataFormat::R16Float, EVertexComponent::VertInstanceParams),
+            ],
+            num_buffers: 1,
+        }],
+    };
+    let positions = [[-1.5f32, 0.0, 2.0, 1.0], [0.5, -4.0, 0.25, 1.0], [3.0, 1.0, -0.5, 1.0]];
+    let halves = |values: &[f32]| -> Vec<u8> {
+        values.iter().flat_map(|&v| half::f16::from_f32(v).to_le_bytes()).collect()
+    };
+    let mut vtx_data = Vec::new();
+    for (idx, position) in positions.iter().enumerate() {
+        vtx_data.extend(halves(position));
+        vtx_data.extend(halves(&[idx as f32 * 0.5, 1.0 - idx as f32 * 0.5]));
+        vtx_data.extend(halves(&[idx as f32]));
+        vtx_data.extend([0u8; 2]);
+    }
+    let idx_data: Vec<u8> = [0u16, 1, 2].iter().flat_map(|v| v.to_le_bytes()).collect();
+    let (data, meta) = model_fixture(&vbuf, &[vtx_data.clone()], &idx_data, 3)?;
+    let model = ModelData::<LE>::slice(&data, &meta)?;
+    let info = &model.vbuf.info[0];
+
+    // Every half float attribute is exported as a non-normalized f32 attribute of the same width
+    let expected =
+        [EVertexDataFormat::Rgba32Float, EVertexDataFormat::Rg32Float, EVertexDataFormat::R32Float];
+    for (component, expected) in model.vbuf.info[0].components.iter().zip(expected) {
+        assert!(component.format.is_half_float());
+        let widened = component.format.widened();
+        assert_eq!(widened, expected);
+        assert!(!widened.is_half_float());
+        assert!(!widened.normalized());
+        assert_eq!(widened.byte_size(), component.format.byte_size() * 2);
+    }
+    assert_eq!(EVertexDataFormat::Rgba16Unorm.widened(), EVertexDataFormat::Rgba16Unorm);
+
+    // Accessor layouts follow the semantic, not just the widened format's width
+    let layouts = info
+        .components
+        .iter()
+        .map(|c| accessor_layout(c.format.widened(), c.component))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(layouts, [
+        (AccessorComponentType::F32, AccessorType::Vec3),
+        (AccessorComponentType::F32, AccessorType::Vec2),
+        (AccessorComponentType::F32, AccessorType::Scalar),
+    ]);
+    assert!(accessor_layout(EVertexDataFormat::Rgba16Float, EVertexComponent::Position).is_err());
+
+    // Widen into the exported layout: positions at 0, texture coordinates at 16, params at 24
+    let out_stride = 28;
+    let mut out = Vec::new();
+    for vertex in vtx_data.chunks_exact(info.components[0].stride as usize) {
+        for c in &info.components {
+            let start = c.offset as usize;
+            out.extend(widen_half_floats(
+                &vertex[start..start + c.format.byte_size() as usize],
+                c.format,
+            )?);
+        }
+    }
+    assert_eq!(out.len(), info.vertex_count as usize * out_stride);
+    assert!(widen_half_floats(&[0u8; 3], EVertexDataFormat::R16Float).is_err());
+    assert!(widen_half_floats(&[0u8; 4], EVertexDataFormat::R32Float).is_err());
+
+    let read = read_float_attribute(&out, 0, out_stride, EVertexDataFormat::Rgba32Float)?;
+    assert_eq!(read, positions);
+    assert_eq!(read.len(), info.vertex_count as usize);
+    assert_eq!(
+        attribute_bounds(&out, 0, out_stride, EVertexDataFormat::Rgba32Float)?,
+        Some(([-1.5, -4.0, -0.5], [3.0, 1.0, 2.0]))
+    );
+    let uvs = read_float_attribute(&out, 16, out_stride, EVertexDataFormat::Rg32Float)?;
+    assert_eq!(uvs[2][..2], [1.0, 0.0]);
+    let params = read_float_attribute(&out, 24, out_stride, EVertexDataFormat::R32Float)?;
+    assert_eq!(params.iter().map(|v| v[0]).collect::<Vec<_>>(), [0.0, 1.0, 2.0]);
+    Ok(())
+}
+
+#[test]
+fn normalize_bone_weights() -> Result<()> {
+    // 4 bytes of padding followed by Rgba8Unorm weights
+    let mut buf = vec![
+        0, 0, 0, 0, 100, 50, 0, 0, // unnormalized
+        0, 0, 0, 0, 255, 0, 0, 0, // already normalized
+        0, 0, 0, 0, 0, 0, 0, 0, // unweighted
+        0, 0, 0, 0, 1, 1, 1, 1, // rounds up to 256
+        0, 0, 0, 0, 3, 3, 1, 0, // rounds down to 254
+    ];
+    assert_eq!(normalize_weights(&mut buf, 4, 8, EVertexDataFormat::Rgba8Unorm)?, 3);
+    for vertex in buf.chunks_exact(8) {
+        let sum: u32 = vertex[4..8].iter().map(|&w| w as u32).sum();
+        assert!(sum == 255 || sum == 0);
+    }
+    assert_eq!(&buf[4..8], &[170, 85, 0, 0]);
+    assert_eq!(&buf[20..24], &[0, 0, 0, 0]);
+    // The rounding error goes to the largest weight
+    assert_eq!(&buf[28..32], &[64, 64, 64, 63]);
+    assert_eq!(&buf[36..40], &[109, 110, 36, 0]);
+
+    let mut buf: Vec<u8> = [0.5f32, 0.5, 0.5, 0.5].iter().flat_map(|f| f.to_le_bytes()).collect();
+    assert_eq!(normalize_weights(&mut buf, 0, 16, EVertexDataFormat::Rgba32Float)?, 1);
+    for bytes in buf.chunks_exact(4) {
+        assert_eq!(f32::from_le_bytes(bytes.try_into()?), 0.25);
+    }
+
+    // Other formats are passed through unchanged
+    let mut buf = vec![1u8, 2, 3, 4];
+    assert_eq!(normalize_weights(&mut buf, 0, 4, EVertexDataFormat::Rgba8Uint)?, 0);
+    assert_eq!(buf, [1, 2, 3, 4]);
+    Ok(())
+}
+
+#[test]
+fn model_joint_indices() -> Result<()> {
+    // Positions followed by 16-bit bone indices, for a rig with more than 255 joints
+    let joints: [[u16; 4]; 3] = [[0, 1, 2, 3], [255, 256, 0, 0], [300, 0, 0, 0]];
+    let mut vtx_data = Vec::new();
+    for indices in &joints {
+        vtx_data.extend_from_slice(&[0u8; 12]);
+        vtx_data.extend(indices.iter().flat_map(|v| v.to_le_bytes()));
+    }
+    let idx_data: Vec<u8> = [0u16, 1, 2].iter().flat_map(|v| v.to_le_bytes()).collect();
+    let vbuf = SVertexBufferInfoSection {
+        info: vec![SVertexBufferInfo {
+            vertex_count: 3,
+            components: vec![
+                SVertexDataComponent {
+                    buffer_index: 0,
+                    offset: 0,
+                    stride: 20,
+                    format: EVertexDataFormat::Rgb32Float,
+                    component: EVertexComponent::Position,
+                },
+                SVertexDataComponent {
+                    buffer_index: 0,
+                    offset: 12,
+                    stride: 20,
+                    format: EVertexDataFormat::Rgba16Uint,
+                    component: EVertexComponent::BoneIndices,
+                },
+            ],
+            num_buffers: 1,
+        }],
+    };
+    let (data, meta) = model_fixture(&vbuf, &[vtx_data], &idx_data, 3)?;
+    let model = ModelData::<LE>::slice(&data, &meta)?;
+    let buf = &model.vtx_buffers[0];
+    assert_eq!(bone_indices(&buf[32..40], EVertexDataFormat::Rgba16Uint)?, [255, 256, 0, 0]);
+    assert_eq!(
+        joints_format(buf, 12, 20, EVertexDataFormat::Rgba16Uint)?,
+        EVertexDataFormat::Rgba16Uint
+    );
+
+    // 8-bit indices stay 8-bit; 32-bit indices narrow to 16-bit when they fit
+    let buf = [1u8, 2, 3, 255];
+    assert_eq!(
+        joints_format(&buf, 0, 4, EVertexDataFormat::Rgba8Uint)?,
+        EVertexDataFormat::Rgba8Uint
+    );
+    let buf: Vec<u8> = [1u32, 2, 3, 300].iter().flat_map(|v| v.to_le_bytes()).collect();
+    assert_eq!(
+        joints_format(&buf, 0, 16, EVertexDataFormat::Rgba32Uint)?,
+        EVertexDataFormat::Rgba16Uint
+    );
+    let buf: Vec<u8> = [0u32, 0, 0, 70000].iter().flat_map(|v| v.to_le_bytes()).collect();
+    assert!(joints_format(&buf, 0, 16, EVertexDataFormat::Rgba32Uint).is_err());
+
+    // Negative and non-integer indices are rejected
+    assert!(bone_indices(&[0, 0, 0, 0xFF], EVertexDataFormat::Rgba8Sint).is_err());
+    assert!(bone_indices(&[0, 0, 0, 0], EVertexDataFormat::Rgba8Unorm).is_err());
+    Ok(())
+}
+
+#[test]
+fn generate_quad_tangents() -> Result<()> {
+    // Unit quad in the XY plane facing +Z, with U along +X
+    let positions = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+    let normals = [[0.0, 0.0, 1.0]; 4];
+    let indices = [0, 1, 2, 0, 2, 3];
+    let tex_coords = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+    for tangent in generate_tangents(&positions, &normals, &tex_coords, &indices)? {
+        assert_eq!(tangent, [1.0, 0.0, 0.0, 1.0]);
+    }
+
+    // Flipping V mirrors the bitangent
+    let tex_coords = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+    for tangent in generate_tangents(&positions, &normals, &tex_coords, &indices)? {
+        assert_eq!(tangent, [1.0, 0.0, 0.0, -1.0]);
+    }
+
+    assert!(generate_tangents(&positions, &normals, &tex_coords, &[0, 1, 4]).is_err());
+
+    // Position (Rgb32Float) followed by TexCoord0 (Rg32Float)
+    let buf: Vec<u8> =
+        [1.0f32, 2.0, 3.0, 0.25, 0.75].iter().flat_map(|f| f.to_le_bytes()).collect();
+    let tex_coords = read_float_attribute(&buf, 12, 20, EVertexDataFormat::Rg32Float)?;
+    assert_eq!(tex_coords, vec![[0.25, 0.75, 0.0, 0.0]]);
+    Ok(())
+}
+
+#[test]
+fn position_attribute_bounds() -> Result<()> {
+    // Positions (Rgb32Float) interleaved with 4 bytes of other data
+    let positions: [[f32; 3]; 3] = [[1.0, -2.0, 3.0], [-4.0, 5.0, 0.5], [2.0, 0.0, -6.0]];
+    let mut buf = Vec::new();
+    for position in &positions {
+        buf.extend(position.iter().flat_map(|v| v.to_le_bytes()));
+        buf.extend_from_slice(&[0xFF; 4]);
+    }
+    let bounds = attribute_bounds(&buf, 0, 16, EVertexDataFormat::Rgb32Float)?;
+    assert_eq!(bounds, Some(([-4.0, -2.0, -6.0], [2.0, 5.0, 3.0])));
+
+    assert_eq!(attribute_bounds(&[], 0, 16, EVertexDataFormat::Rgb32Float)?, None);
+    assert!(attribute_bounds(&buf, 8, 16, EVertexDataFormat::Rgb32Float).is_err());
+    Ok(())
+}
+
+#[test]
+fn material_texture_uv_set() -> Result<()> {
+    // Texture token sampling UV set 1
+    let id = Uuid::from_u128(1);
+    let mut data = id.to_bytes_le().to_vec();
+    for value in [1u32, 0, 0, 0, 0] {
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    let texture: CMaterialTextureTokenData = Cursor::new(data).read_le()?;
+    assert_eq!(texture.id, id);
+    let usage = texture.usage.unwrap();
+    assert_eq!(usage.tex_coord, 1);
+
+    let info = |components: &[EVertexComponent]| SVertexBufferInfo {
+        vertex_count: 0,
+        components: components
+            .iter()
+            .map(|&component| SVertexDataComponent {
+                buffer_index: 0,
+                offset: 0,
+                stride: 0,
+                format: EVertexDataFormat::Rg32Float,
+                component,
+            })
+            .collect(),
+        num_buffers: 1,
+    };
+    let two_sets = info(&[EVertexComponent::TexCoord0, EVertexComponent::TexCoord1]);
+    assert_eq!(two_sets.tex_coord_count(), 2);
+    assert_eq!(usage.uv_set(two_sets.tex_coord_count()), Some(1));
+    // Sets after a gap aren't counted
+    let gap = info(&[EVertexComponent::TexCoord0, EVertexComponent::TexCoord2]);
+    assert_eq!(gap.tex_coord_count(), 1);
+    assert_eq!(usage.uv_set(gap.tex_coord_count()), None);
+    Ok(())
+}
+
+#[test]
+fn material_layered_texture() -> Result<()> {
+    let token = |n: u128, tex_coord: u32| CMaterialTextureTokenData {
+        id: Uuid::from_u128(n),
+        usage: (n != 0).then_some(STextureUsageInfo {
+            tex_coord,
+            filter: 1,
+            wrap_x: 0,
+            wrap_y: 0,
+            wrap_z: 0,
+        }),
+    };
+    // Base color with a second layer on UV set 1 and an unused third layer
+    let data = CMaterialData {
+        data_id: EMaterialDataId::BCRL,
+        data_type: EMaterialDataType::Complex,
+        data: CMaterialDataInner::LayeredTexture(CLayeredTextureData {
+            base: CLayeredTextureBaseData {
+                unk: 0,
+                colors: [
+                    CColor4f::splat(1.0),
+                    CColor4f::new(0.5, 0.25, 0.0, 1.0),
+                    CColor4f::default(),
+                ],
+                flags: 0b011,
+            },
+            textures: [token(1, 0), token(2, 1), token(0, 0)],
+        }),
+    };
+    let mut w = Cursor::new(Vec::new());
+    w.write_le(&data)?;
+    let data: CMaterialData = Cursor::new(w.into_inner()).read_le()?;
+    assert_eq!(data.data_id, EMaterialDataId::BCRL);
+    let CMaterialDataInner::LayeredTexture(texture) = &data.data else {
+        panic!("Expected layered texture, got {:?}", data.data);
+    };
+    assert_eq!(texture.base.flags, 0b011);
+    let layers = texture.layers().collect::<Vec<_>>();
+    assert_eq!(layers.len(), 2);
+    assert_eq!(layers[1].0, 1);
+    assert_eq!(layers[1].1.id, Uuid::from_u128(2));
+    assert_eq!(layers[1].1.usage.as_ref().unwrap().tex_coord, 1);
+    assert_eq!(layers[1].2.to_array(), [0.5, 0.25, 0.0, 1.0]);
+    Ok(())
+}
+
+#[test]
+fn string_pool_resolve() -> Result<()> {
+    let pool = StringPool {
+        unk1: 0,
+        num_strings: 2,
+        pool_len: 10,
+        pool_data: b"HelloWorld".to_vec(),
+        unk2: 0,
+        unk3: 0,
+        unk_pool_len: 0,
+        unk_pool_data: vec![],
+    };
+    let mut w = Cursor::new(Vec::new());
+    form(K_FORM_ROOM, 147, 160).write(&mut w, |w| {
+        form(K_FORM_HEAD, 0, 0).write(w, |_| Ok(()))?;
+        chunk(K_CHUNK_STRP).write(w, |w| Ok(w.write_le(&pool)?))?;
+        Ok(())
+    })?;
+    let data = w.into_inner();
+    let (_, form_data, _) = FormDescriptor::<LE>::slice(&data)?;
+    let pool = StringPool::find::<LE>(form_data)?.expect("STRP chunk not found");
+    assert_eq!(pool.pool_data, b"HelloWorld");
+
+    // Inline strings survive a round trip and don't need the pool
+    let mut w = Cursor::new(Vec::new());
+    w.write_le(&PooledString::inline("Inline"))?;
+    let inline: PooledString = Cursor::new(w.into_inner()).read_le()?;
+    assert!(inline.is_inline());
+
+    let strings = [
+        inline,
+        PooledString::pooled(0, 5),
+        PooledString::pooled(5, 5),
+        PooledString::pooled(8, 5),
+    ];
+    assert_eq!(resolve_all(&strings, Some(&pool)), vec![
+        Some("Inline".to_string()),
+        Some("Hello".to_string()),
+        Some("World".to_string()),
+        None,
+    ]);
+    assert_eq!(resolve_all(&strings, None), vec![Some("Inline".to_string()), None, None, None]);
+    Ok(())
+}
+
+#[test]
+fn room_minimal() -> Result<()> {
+    let id = |n: u128| CObjectId::from(Uuid::from_u128(n));
+    let header = SGameAreaHeader {
+        parent_room_id: id(1),
+        unk1: 0,
+        unk2: 0,
+        unk3: 0,
+        id_b: id(2),
+        id_c: id(3),
+        id_d: id(4),
+        id_e: id(5),
+        path_find_area_id: id(6),
+        production_work_stages: vec![
+            SProductionWorkStage { discipline: 0, stage: 3 },
+            SProductionWorkStage { discipline: 2, stage: 1 },
+        ],
+    };
+    let lighting = BakedLighting {
+        light_map: Some(BakedLightingLightMap {
+            txtr_id: id(7),
+            ids: vec![id(8), id(9)],
+            atlas_lookups: vec![
+                SAtlasLookup(CVector4f::new(0.0, 0.0, 0.5, 0.5)),
+                SAtlasLookup(CVector4f::new(0.5, 0.0, 0.5, 0.5)),
+            ],
+        }),
+        light_probe: None,
+    };
+    let docks = SDockList {
+        docks: vec![SDock {
+            connected_room_id: id(10),
+            dock_id: id(11),
+            bounds: CAABox {
+                min: CVector3f::new(-1.0, -1.0, 0.0),
+                max: CVector3f::new(1.0, 1.0, 2.0),
+            },
+        }],
+    };
+    let generated = SGeneratedObjectMap {
+        objects: vec![
+            SGeneratedObject { object_id: id(12), generator_id: id(13), index: 0 },
+            SGeneratedObject { object_id: id(14), generator_id: id(13), index: 1 },
+        ],
+    };
+
+    let mut w = Cursor::new(Vec::new());
+    form(K_FORM_ROOM, 147, 160).write(&mut w, |w| {
+        form(K_FORM_HEAD, 0, 0).write(w, |w| {
+            chunk(K_CHUNK_RMHD).write(w, |w| Ok(w.write_le(&header)?))?;
+            chunk(K_CHUNK_BLIT).write(w, |w| Ok(w.write_le(&lighting)?))?;
+            chunk(K_CHUNK_DOCK).write(w, |w| Ok(w.write_le(&docks)?))?;
+            chunk(K_CHUNK_LGEN).write(w, |w| Ok(w.write_le(&generated)?))?;
+            Ok(())
+        })
+    })?;
+    let data = w.into_inner();
+
+    let room = RoomData::<LE>::slice(&data)?;
+    assert_eq!(parse_form(&data)?.name, "Room");
+    assert!(room.string_pool.is_none());
+    assert_eq!(room.room_header.parent_room_id, id(1));
+    assert_eq!(room.room_header.path_find_area_id, id(6));
+    assert_eq!(room.room_header.production_work_stages, header.production_work_stages);
+    let light_map = room.baked_lighting.light_map.as_ref().unwrap();
+    assert_eq!(light_map.txtr_id, id(7));
+    assert_eq!(light_map.ids.len(), 2);
+    assert_eq!(light_map.atlas_lookups.len(), 2);
+    assert_eq!(light_map.atlas_lookups[1].0.x, 0.5);
+    assert!(room.baked_lighting.light_probe.is_none());
+    assert_eq!(room.docks.len(), 1);
+    assert_eq!(room.docks[0].connected_room_id, id(10));
+    assert_eq!(room.docks[0].bounds.max.z, 2.0);
+    assert_eq!(room.generated_objects, generated.objects);
+    assert!(room.component_properties.is_empty());
+    assert!(room.layers.is_empty());
+
+    // Headers that end before the stage list still parse
+    let mut w = Cursor::new(Vec::new());
+    w.write_le(&header)?;
+    let mut data = w.into_inner();
+    data.truncate(data.len() - 20);
+    let header: SGameAreaHeader = Cursor::new(data).read_le()?;
+    assert_eq!(header.path_find_area_id, id(6));
+    assert!(header.production_work_stages.is_empty());
+
+    // A dock list or generated object map that doesn't fill its chunk is discarded
+    let mut w = Cursor::new(Vec::new());
+    form(K_FORM_ROOM, 147, 160).write(&mut w, |w| {
+        form(K_FORM_HEAD, 0, 0).write(w, |w| {
+            chunk(K_CHUNK_RMHD).write(w, |w| Ok(w.write_le(&header)?))?;
+            chunk(K_CHUNK_BLIT).write(w, |w| Ok(w.write_le(&lighting)?))?;
+            chunk(K_CHUNK_DOCK).write(w, |w| {
+                w.write_le(&docks)?;
+                Ok(w.write_le(&0u32)?)
+            })?;
+            chunk(K_CHUNK_LGEN).write(w, |w| {
+                w.write_le(&generated)?;
+                Ok(w.write_le(&0u32)?)
+            })?;
+            Ok(())
+        })
+    })?;
+    let room = RoomData::<LE>::slice(&w.into_inner())?;
+    assert!(room.docks.is_empty());
+    assert!(room.generated_objects.is_empty());
+    Ok(())
+}
+
+#[test]
+fn room_dock_list_round_trip() -> Result<()> {
+    let id = |n: u128| CObjectId::from(Uuid::from_u128(n));
+    let dock = |n: u128, min: [f32; 3], max: [f32; 3]| SDock {
+        connected_room_id: id(n),
+        dock_id: id(n + 100),
+        bounds: CAABox { min: min.into(), max: max.into() },
+    };
+    let docks = SDockList {
+        docks: vec![
+            dock(1, [-1.0, -2.0, -3.0], [1.0, 2.0, 3.0]),
+            dock(2, [10.0, 0.0, 0.5], [12.0, 0.25, 4.5]),
+            dock(3, [-8.5, 7.0, -0.75], [-6.5, 9.0, 1.25]),
+        ],
+    };
+    let mut w = Cursor::new(Vec::new());
+    w.write_le(&docks)?;
+    let data = w.into_inner();
+    // Count, then 16-byte IDs and 24-byte bounds per dock
+    assert_eq!(data.len(), 4 + 3 * (16 + 16 + 24));
+    assert_eq!(&data[..4], &3u32.to_le_bytes());
+
+    let parsed: SDockList = Cursor::new(&data).read_le()?;
+    assert_eq!(parsed.docks.len(), docks.docks.len());
+    for (parsed, expected) in parsed.docks.iter().zip(&docks.docks) {
+        assert_eq!(parsed.connected_room_id, expected.connected_room_id);
+        assert_eq!(parsed.dock_id, expected.dock_id);
+        assert_eq!(parsed.bounds.min.to_array(), expected.bounds.min.to_array());
+        assert_eq!(parsed.bounds.max.to_array(), expected.bounds.max.to_array());
+    }
+
+    // Writing the parsed list reproduces the original bytes
+    let mut w = Cursor::new(Vec::new());
+    w.write_le(&parsed)?;
+    assert_eq!(w.into_inner(), data);
+    Ok(())
+}
+
+#[test]
+fn room_header_work_stages() -> Result<()> {
+    let header = SGameAreaHeader {
+        parent_room_id: CObjectId::default(),
+        unk1: 0,
+        unk2: 0,
+        unk3: 1,
+        id_b: CObjectId::default(),
+        id_c: CObjectId::default(),
+        id_d: CObjectId::default(),
+        id_e: CObjectId::default(),
+        path_find_area_id: CObjectId::default(),
+        production_work_stages: vec![
+            SProductionWorkStage { discipline: 0, stage: 3 },
+            SProductionWorkStage { discipline: 1, stage: 0 },
+            SProductionWorkStage { discipline: 2, stage: 5 },
+        ],
+    };
+    let mut w = Cursor::new(Vec::new());
+    w.write_le(&header)?;
+    let data = w.into_inner();
+    let parsed: SGameAreaHeader = Cursor::new(&data).read_le()?;
+    assert_eq!(parsed.unk3, 1);
+    assert_eq!(parsed.production_work_stages, header.production_work_stages);
+
+    // A list cut off partway through is an error rather than silently empty
+    let truncated = &data[..data.len() - 4];
+    assert!(Cursor::new(truncated).read_le::<SGameAreaHeader>().is_err());
+    Ok(())
+}
+
+#[test]
+fn pack_compressed_container() -> Result<()> {
+    let id = Uuid::from_u128(1);
+    let mut w = Cursor::new(Vec::new());
+    form(FourCC(*b"TEST"), 1, 2).write(&mut w, |w| Ok(w.write_all(&[0xAB; 32])?))?;
+    let mut package = Package::<LE>::default();
+    package.assets.push(Asset {
+        id,
+        kind: FourCC(*b"TEST"),
+        names: vec!["asset".to_string()],
+        data: Cow::Owned(w.into_inner()),
+        compressed: None,
+        meta: None,
+        info: AssetInfo { id, compression_mode: 0, orig_offset: 0, mode_prefixed: None },
+        version: 1,
+        other_version: 2,
+    });
+    let mut w = Cursor::new(Vec::new());
+    package.write(&mut w)?;
+    let original = w.into_inner();
+
+    // Uncompressed packages are passed through without copying
+    assert!(EContainerCompression::detect(&original).is_none());
+    assert!(matches!(decompress_package(&original)?, Cow::Borrowed(_)));
+
+    let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+    zlib.write_all(&original)?;
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(&original)?;
+    for (compression, data) in [
+        (EContainerCompression::Zlib, zlib.finish()?),
+        (EContainerCompression::Gzip, gzip.finish()?),
+    ] {
+        assert_eq!(EContainerCompression::detect(&data), Some(compression));
+        let decompressed = decompress_package(&data)?;
+        assert_eq!(&*decompressed, original.as_slice());
+        let read = Package::<LE>::read_full(&decompressed, Endian::Little)?;
+        assert_eq!(read.assets.len(), 1);
+        assert_eq!(read.assets[0].id, id);
+    }
+
+    // A compressed payload that isn't a package is rejected, as is an unknown container
+    let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+    zlib.write_all(&[0u8; 64])?;
+    assert!(decompress_package(&zlib.finish()?).is_err());
+    assert!(decompress_package(&[0u8; 64]).is_err());
+    Ok(())
+}
+
+#[test]
+fn pack_repack_round_trip() -> Result<()> {
+    // One asset stored uncompressed and one for each LZSS mode
+    let mut package = Package::<LE>::default();
+    for (idx, mode) in [None, Some(1), Some(2), Some(3)].into_iter().enumerate() {
+        let payload: Vec<u8> = (0..64u8).map(|v| (v / 4) ^ idx as u8).collect();
+        let mut w = Cursor::new(Vec::new());
+        form(FourCC(*b"TEST"), 1, 2).write(&mut w, |w| Ok(w.write_all(&payload)?))?;
+        let id = Uuid::from_u128(idx as u128 + 1);
+        let mut asset = Asset {
+            id,
+            kind: FourCC(*b"TEST"),
+            names: vec![format!("asset{idx}")],
+            data: Cow::Owned(w.into_inner()),
+            compressed: None,
+            meta: (idx % 2 == 0).then(|| Cow::Owned(vec![idx as u8; 8])),
+            // Reverse the data order relative to the directory
+            info: AssetInfo {
+                id,
+                compression_mode: 0,
+                orig_offset: 4 - idx as u64,
+                mode_prefixed: None,
+            },
+            version: 1,
+            other_version: 2,
+        };
+        if let Some(mode) = mode {
+            asset.compress(mode)?;
+        }
+        package.assets.push(asset);
+    }
+    let mut w = Cursor::new(Vec::new());
+    package.write(&mut w)?;
+    let original = w.into_inner();
+
+    // Extract each asset and record it in a manifest
+    let read = Package::<LE>::read_full(&original, Endian::Little)?;
+    let sparse = Package::<LE>::read_sparse(&original)?;
+    let mut manifest = PackageManifest::default();
+    let mut files = Vec::new();
+    for (asset, entry) in read.assets.iter().zip(&sparse) {
+        let mut w = Cursor::new(Vec::new());
+        asset.write_extracted(&mut w)?;
+        files.push(w.into_inner());
+        let manifest_entry = asset.manifest_entry(format!("{}.{}", asset.id, asset.kind));
+        assert_eq!(manifest_entry.id, entry.id);
+        assert_eq!(manifest_entry.offset, entry.offset);
+        assert_eq!(manifest_entry.size, entry.size);
+        assert_eq!(manifest_entry.decompressed_size, entry.decompressed_size);
+        manifest.assets.push(manifest_entry);
+    }
+    let manifest: PackageManifest = serde_json::from_str(&serde_json::to_string(&manifest)?)?;
+    assert_eq!(manifest.assets[2].compression_mode, 2);
+
+    let repacked =
+        Package::<LE>::from_extracted(manifest.assets.iter().zip(files.iter().map(Vec::as_slice)))?;
+    let mut w = Cursor::new(Vec::new());
+    repacked.write(&mut w)?;
+    assert_eq!(w.into_inner(), original);
+
+    // Without a manifest, the offset, compression and names come from each file's footer
+    let repacked = Package::<LE>::from_extracted_files(
+        manifest
+            .assets
+            .iter()
+            .map(|entry| entry.file.as_str())
+            .zip(files.iter().map(Vec::as_slice)),
+    )?;
+    assert_eq!(repacked.assets[1].names, ["asset1"]);
+    let mut w = Cursor::new(Vec::new());
+    repacked.write(&mut w)?;
+    assert_eq!(w.into_inner(), original);
+    Ok(())
+}
+
+/// Extracts every asset in `data` and repacks them without a manifest.
+fn repack_extracted(data: &[u8]) -> Result<Vec<u8>> {
+    let read = Package::<LE>::read_full(data, Endian::Little)?;
+    let mut files = Vec::new();
+    for asset in &read.assets {
+        let mut w = Cursor::new(Vec::new());
+        asset.write_extracted(&mut w)?;
+        files.push((format!("{}.{}", asset.id, asset.kind), w.into_inner()));
+    }
+    let repacked = Package::<LE>::from_extracted_files(
+        files.iter().map(|(name, data)| (name.as_str(), data.as_slice())),
+    )?;
+    let mut w = Cursor::new(Vec::new());
+    repacked.write(&mut w)?;
+    Ok(w.into_inner())
+}
+
+#[test]
+fn pack_repack_source_fixture() -> Result<()> {
+    // Stored payloads are built by hand rather than by our compressor
+    let asset = |n: u8, stored: fn(&[u8]) -> Option<Vec<u8>>| -> Result<Asset<'static>> {
+        let payload: Vec<u8> = (0..64u8).map(|v| (v / 8) ^ n).collect();
+        let mut w = Cursor::new(Vec::new());
+        form(FourCC(*b"TEST"), 1, 2).write(&mut w, |w| Ok(w.write_all(&payload)?))?;
+        let data = w.into_inner();
+        let id = Uuid::from_u128(n as u128);
+        Ok(Asset {
+            id,
+            kind: FourCC(*b"TEST"),
+            names: vec![format!("asset{n}")],
+            compressed: stored(&data).map(Cow::Owned),
+            data: Cow::Owned(data),
+            meta: None,
+            info: AssetInfo { id, compression_mode: 0, orig_offset: n as u64, mode_prefixed: None },
+            version: 1,
+            other_version: 2,
+        })
+    };
+    let write = |assets: Vec<Asset>| -> Result<Vec<u8>> {
+        let mut package = Package::<LE>::default();
+        package.assets = assets;
+        let mut w = Cursor::new(Vec::new());
+        package.write(&mut w)?;
+        Ok(w.into_inner())
+    };
+    let stored: fn(&[u8]) -> Option<Vec<u8>> = |_| None;
+    let prefixed: fn(&[u8]) -> Option<Vec<u8>> = |data| Some(uncompressed(data));
+    // LZSS mode 1 using only literals, which our compressor never emits for this data
+    let literals: fn(&[u8]) -> Option<Vec<u8>> = |data| {
+        let mut out = 1u32.to_le_bytes().to_vec();
+        for group in data.chunks(8) {
+            out.push(0);
+            out.extend_from_slice(group);
+        }
+        Some(out)
+    };
+
+    // Stored and uncompressed assets repack to the identical package
+    let source = write(vec![asset(1, stored)?, asset(2, prefixed)?])?;
+    assert_eq!(repack_extracted(&source)?, source);
+
+    // Compressed assets are recompressed, leaving the others byte for byte intact
+    let source = write(vec![asset(1, stored)?, asset(2, prefixed)?, asset(3, literals)?])?;
+    let repacked = repack_extracted(&source)?;
+    let stored_data = |data: &[u8]| -> Result<Vec<Vec<u8>>> {
+        Ok(Package::<LE>::read_sparse(data)?
+            .iter()
+            .map(|e| data[e.offset as usize..(e.offset + e.size) as usize].to_vec())
+            .collect())
+    };
+    let (before, after) = (stored_data(&source)?, stored_data(&repacked)?);
+    assert_eq!(before[..2], after[..2]);
+    assert_ne!(before[2], after[2]);
+    let before = Package::<LE>::read_full(&source, Endian::Little)?;
+    let after = Package::<LE>::read_full(&repacked, Endian::Little)?;
+    assert_eq!(before.assets[2].data, after.assets[2].data);
+    assert_eq!(after.assets[2].info.compression_mode, 1);
+    Ok(())
+}
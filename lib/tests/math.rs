@@ -0,0 +1,195 @@
+//! Tests for the math helpers on the common format types.
+
+use retrolib::format::{CAABox, COBBox, CTransform4f, CVector3f};
+
+const EPSILON: f32 = 1e-5;
+
+fn transform(rows: [[f32; 4]; 3]) -> CTransform4f { mint::RowMatrix3x4::from(rows).into() }
+
+fn rows(xf: CTransform4f) -> [[f32; 4]; 3] { mint::RowMatrix3x4::from(xf).into() }
+
+fn assert_rows_eq(actual: CTransform4f, expected: [[f32; 4]; 3]) {
+    let actual = rows(actual);
+    for (a, e) in actual.iter().flatten().zip(expected.iter().flatten()) {
+        assert!((a - e).abs() < EPSILON, "{actual:?} != {expected:?}");
+    }
+}
+
+/// 90 degree rotation about Z, scale (2, 3, 4), translation (1, 2, 3).
+fn rotate_scale_translate() -> CTransform4f {
+    transform([[0.0, -3.0, 0.0, 1.0], [2.0, 0.0, 0.0, 2.0], [0.0, 0.0, 4.0, 3.0]])
+}
+
+#[test]
+fn transform_multiply() {
+    let translate = transform([[1.0, 0.0, 0.0, 5.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0]]);
+    let rotate = transform([[0.0, -1.0, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0]]);
+    // Translate, then rotate
+    let expected = [[0.0, -1.0, 0.0, 0.0], [1.0, 0.0, 0.0, 5.0], [0.0, 0.0, 1.0, 0.0]];
+    assert_rows_eq(rotate * translate, expected);
+    // Rotate, then translate
+    let expected = [[0.0, -1.0, 0.0, 5.0], [1.0, 0.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0]];
+    assert_rows_eq(translate * rotate, expected);
+    assert_rows_eq(CTransform4f::default() * rotate, rows(rotate));
+}
+
+#[test]
+fn transform_inverse() {
+    let xf = rotate_scale_translate();
+    let inv = xf.inverse().unwrap();
+    let expected =
+        [[0.0, 0.5, 0.0, -1.0], [-1.0 / 3.0, 0.0, 0.0, 1.0 / 3.0], [0.0, 0.0, 0.25, -0.75]];
+    assert_rows_eq(inv, expected);
+    assert_rows_eq(xf * inv, rows(CTransform4f::default()));
+    assert_rows_eq(inv * xf, rows(CTransform4f::default()));
+
+    let singular = transform([[1.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0]]);
+    assert!(singular.inverse().is_none());
+}
+
+#[test]
+fn transform_decompose() {
+    let (translation, rotation, scale) = rotate_scale_translate().decompose();
+    assert_eq!(translation.to_array(), [1.0, 2.0, 3.0]);
+    for (a, e) in scale.to_array().iter().zip([2.0, 3.0, 4.0]) {
+        assert!((a - e).abs() < EPSILON, "scale {:?}", scale.to_array());
+    }
+    // 90 degrees about Z
+    let half = std::f32::consts::FRAC_1_SQRT_2;
+    let actual = [rotation.v.x, rotation.v.y, rotation.v.z, rotation.s];
+    for (a, e) in actual.iter().zip([0.0, 0.0, half, half]) {
+        assert!((a - e).abs() < EPSILON, "rotation {actual:?}");
+    }
+
+    let (_, rotation, scale) = CTransform4f::default().decompose();
+    assert_eq!(scale.to_array(), [1.0, 1.0, 1.0]);
+    assert_eq!([rotation.v.x, rotation.v.y, rotation.v.z, rotation.s], [0.0, 0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn transform_decompose_round_trip() {
+    // 120 degrees about (1, 1, 1): cycles X -> Y -> Z
+    let (sin, cos) = (60f32.to_radians().sin(), 60f32.to_radians().cos());
+    let axis = sin / 3f32.sqrt();
+    let rotation = mint::Quaternion { v: mint::Vector3 { x: axis, y: axis, z: axis }, s: cos };
+    let xf = CTransform4f::from_trs(
+        CVector3f::new(-4.0, 0.5, 10.0),
+        rotation,
+        CVector3f::new(1.5, 1.5, 1.5),
+    );
+    assert_rows_eq(xf, [[0.0, 0.0, 1.5, -4.0], [1.5, 0.0, 0.0, 0.5], [0.0, 1.5, 0.0, 10.0]]);
+
+    let (translation, actual, scale) = xf.decompose();
+    assert_eq!(translation.to_array(), [-4.0, 0.5, 10.0]);
+    for a in scale.to_array() {
+        assert!((a - 1.5).abs() < EPSILON, "scale {:?}", scale.to_array());
+    }
+    let actual = [actual.v.x, actual.v.y, actual.v.z, actual.s];
+    for (a, e) in actual.iter().zip([axis, axis, axis, cos]) {
+        assert!((a - e).abs() < EPSILON, "rotation {actual:?}");
+    }
+
+    // Recomposing reproduces the transform, including non-uniform and mirrored scale
+    let mirrored = transform([[-2.0, 0.0, 0.0, 1.0], [0.0, 0.0, -1.0, 0.0], [0.0, 3.0, 0.0, 0.0]]);
+    for xf in [xf, rotate_scale_translate(), mirrored] {
+        let (translation, rotation, scale) = xf.decompose();
+        assert_rows_eq(CTransform4f::from_trs(translation, rotation, scale), rows(xf));
+    }
+}
+
+#[test]
+fn transform_cols_array() {
+    let cols = rotate_scale_translate().to_cols_array();
+    assert_eq!(cols, [
+        0.0, 2.0, 0.0, 0.0, -3.0, 0.0, 0.0, 0.0, 0.0, 0.0, 4.0, 0.0, 1.0, 2.0, 3.0, 1.0
+    ]);
+}
+
+#[test]
+fn aabox_basics() {
+    let a = CAABox { min: CVector3f::new(-1.0, -2.0, -3.0), max: CVector3f::new(1.0, 2.0, 3.0) };
+    let b = CAABox { min: CVector3f::new(0.0, 0.0, 0.0), max: CVector3f::new(5.0, 1.0, 1.0) };
+    assert!(a.contains(CVector3f::new(0.0, 0.0, 0.0)));
+    assert!(a.contains(CVector3f::new(1.0, 2.0, 3.0)));
+    assert!(!a.contains(CVector3f::new(1.5, 0.0, 0.0)));
+    assert_eq!(a.center().to_array(), [0.0, 0.0, 0.0]);
+    assert_eq!(a.extents().to_array(), [1.0, 2.0, 3.0]);
+
+    let u = a.union(&b);
+    assert_eq!(u.min.to_array(), [-1.0, -2.0, -3.0]);
+    assert_eq!(u.max.to_array(), [5.0, 2.0, 3.0]);
+    assert_eq!(b.center().to_array(), [2.5, 0.5, 0.5]);
+
+    // Default box is empty and acts as the identity for union
+    let empty = CAABox::default();
+    assert!(empty.is_empty());
+    assert!(!empty.contains(CVector3f::new(0.0, 0.0, 0.0)));
+    let u = empty.union(&b);
+    assert_eq!(u.min.to_array(), b.min.to_array());
+    assert_eq!(u.max.to_array(), b.max.to_array());
+}
+
+#[test]
+fn aabox_transformed() {
+    let aabb = CAABox { min: CVector3f::new(0.0, 0.0, 0.0), max: CVector3f::new(2.0, 1.0, 1.0) };
+
+    // Translation only moves the box
+    let translate = transform([[1.0, 0.0, 0.0, 10.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, -1.0]]);
+    let out = aabb.transformed(&translate);
+    assert_eq!(out.min.to_array(), [10.0, 0.0, -1.0]);
+    assert_eq!(out.max.to_array(), [12.0, 1.0, 0.0]);
+
+    // 90 degrees about Z swaps X/Y extents, with the box ending up on negative X
+    let rotate = transform([[0.0, -1.0, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0]]);
+    let out = aabb.transformed(&rotate);
+    assert_vec_eq(out.min, [-1.0, 0.0, 0.0]);
+    assert_vec_eq(out.max, [0.0, 2.0, 1.0]);
+
+    // 45 degrees about Z: every corner must be enclosed, not just min/max
+    let (s, c) = std::f32::consts::FRAC_PI_4.sin_cos();
+    let rotate = transform([[c, -s, 0.0, 0.0], [s, c, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0]]);
+    let out = aabb.transformed(&rotate);
+    for corner in 0..8 {
+        let p = CVector3f::new(
+            if corner & 1 != 0 { 2.0 } else { 0.0 },
+            if corner & 2 != 0 { 1.0 } else { 0.0 },
+            if corner & 4 != 0 { 1.0 } else { 0.0 },
+        );
+        let t = rotate.transform_point(p);
+        let grown = CAABox {
+            min: CVector3f::new(out.min.x - EPSILON, out.min.y - EPSILON, out.min.z - EPSILON),
+            max: CVector3f::new(out.max.x + EPSILON, out.max.y + EPSILON, out.max.z + EPSILON),
+        };
+        assert!(grown.contains(t), "corner {:?} outside {:?}", t.to_array(), out);
+    }
+    // Corners (2, 0) and (0, 1) define the X range, (2, 1) the Y maximum
+    assert_vec_eq(out.min, [-s, 0.0, 0.0]);
+    assert_vec_eq(out.max, [2.0 * c, 3.0 * s, 1.0]);
+
+    assert!(CAABox::default().transformed(&rotate).is_empty());
+}
+
+#[test]
+fn obbox_bounds() {
+    let aabb = CAABox { min: CVector3f::new(0.0, 0.0, 0.0), max: CVector3f::new(2.0, 1.0, 1.0) };
+    let obb = COBBox::from_aabb(&aabb);
+    assert_eq!(obb.xf.translation().to_array(), [1.0, 0.5, 0.5]);
+    assert_eq!(obb.extents.to_array(), [1.0, 0.5, 0.5]);
+    assert_eq!(obb.aabb().min.to_array(), aabb.min.to_array());
+    assert_eq!(obb.aabb().max.to_array(), aabb.max.to_array());
+
+    // 90 degrees about Z around (1, 2, 3) swaps the X/Y extents
+    let obb = COBBox {
+        xf: transform([[0.0, -1.0, 0.0, 1.0], [1.0, 0.0, 0.0, 2.0], [0.0, 0.0, 1.0, 3.0]]),
+        extents: CVector3f::new(2.0, 1.0, 0.5),
+    };
+    let out = obb.aabb();
+    assert_vec_eq(out.min, [0.0, 0.0, 2.5]);
+    assert_vec_eq(out.max, [2.0, 4.0, 3.5]);
+}
+
+fn assert_vec_eq(actual: CVector3f, expected: [f32; 3]) {
+    for (a, e) in actual.to_array().iter().zip(expected) {
+        assert!((a - e).abs() < EPSILON, "{:?} != {expected:?}", actual.to_array());
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    fs::{DirBuilder, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Context, Result};
+use retrolib::{
+    format::{
+        cmdl::{CMaterialDataInner, ModelData, K_FORM_CMDL, K_FORM_SMDL, K_FORM_WMDL},
+        foot::locate_meta,
+        pack::{decompress_package, Package},
+        txtr::{TextureData, K_FORM_TXTR},
+    },
+    util::{
+        dds::write_dds,
+        file::map_file,
+        gltf::{convert_model, ConvertOptions},
+    },
+};
+use uuid::Uuid;
+use zerocopy::LittleEndian;
+
+use crate::{loaders::package::PackageDirectory, AssetRef};
+
+pub struct ExportResult {
+    pub asset: AssetRef,
+    /// Written files, or the error message.
+    pub result: Result<Vec<PathBuf>, String>,
+}
+
+#[derive(Default)]
+pub struct ExportStatus {
+    pub total: usize,
+    pub done: usize,
+    pub results: Vec<ExportResult>,
+    pub finished: bool,
+}
+
+pub type SharedExportStatus = Arc<Mutex<ExportStatus>>;
+
+/// Exports assets into `out_dir` on a background thread.
+///
+/// Textures are written as DDS and models are converted to glTF, reading the raw model and its
+/// textures from a `raw` subdirectory. Everything else is written as the raw form.
+pub fn start_export(
+    assets: Vec<AssetRef>,
+    packages: Vec<PackageDirectory>,
+    out_dir: PathBuf,
+) -> SharedExportStatus {
+    let status = Arc::new(Mutex::new(ExportStatus { total: assets.len(), ..Default::default() }));
+    let thread_status = status.clone();
+    std::thread::spawn(move || {
+        let mut exporter = Exporter {
+            raw_dir: out_dir.join("raw"),
+            out_dir,
+            packages: PackageCache::new(packages),
+            textures: HashMap::new(),
+            raw_textures: HashSet::new(),
+        };
+        let mut assets = assets;
+        // Group by package to avoid decompressing the same package repeatedly
+        assets.sort_by_key(|asset| exporter.packages.index_of(asset.id));
+        for asset in assets {
+            let result = exporter.export(asset).map_err(|e| format!("{e:#}"));
+            if let Err(e) = &result {
+                log::warn!("Failed to export {} {}: {}", asset.kind, asset.id, e);
+            }
+            let mut status = thread_status.lock().unwrap();
+            status.done += 1;
+            status.results.push(ExportResult { asset, result });
+        }
+        thread_status.lock().unwrap().finished = true;
+    });
+    status
+}
+
+/// Keeps the most recently used package decompressed.
+struct PackageCache {
+    packages: Vec<PackageDirectory>,
+    /// Package index of each asset, from the first package containing it.
+    indices: HashMap<Uuid, usize>,
+    current: Option<(usize, Vec<u8>)>,
+}
+
+impl PackageCache {
+    fn new(packages: Vec<PackageDirectory>) -> Self {
+        let mut indices = HashMap::new();
+        for (idx, package) in packages.iter().enumerate() {
+            for entry in &package.entries {
+                indices.entry(entry.id).or_insert(idx);
+            }
+        }
+        Self { packages, indices, current: None }
+    }
+
+    fn index_of(&self, id: Uuid) -> Option<usize> { self.indices.get(&id).copied() }
+
+    fn read_asset(&mut self, id: Uuid) -> Result<Vec<u8>> {
+        let idx = self.index_of(id).ok_or_else(|| anyhow!("Asset {id} not found in packages"))?;
+        if !matches!(&self.current, Some((current, _)) if *current == idx) {
+            let path = &self.packages[idx].path;
+            let data = map_file(path)?;
+            let data = decompress_package(&data)
+                .with_context(|| format!("Failed to read package '{}'", path.display()))?
+                .into_owned();
+            self.current = Some((idx, data));
+        }
+        let (_, data) = self.current.as_ref().unwrap();
+        Package::<LittleEndian>::read_asset(data, id)
+    }
+}
+
+struct Exporter {
+    out_dir: PathBuf,
+    /// Raw models and textures read by the glTF converter.
+    raw_dir: PathBuf,
+    packages: PackageCache,
+    /// Textures already written as DDS, shared between all exported assets.
+    textures: HashMap<Uuid, PathBuf>,
+    /// Textures already written to `raw_dir`, shared between all exported models.
+    raw_textures: HashSet<Uuid>,
+}
+
+impl Exporter {
+    fn export(&mut self, asset: AssetRef) -> Result<Vec<PathBuf>> {
+        DirBuilder::new().recursive(true).create(&self.out_dir)?;
+        match asset.kind {
+            K_FORM_TXTR => Ok(vec![self.export_texture(asset.id)?]),
+            K_FORM_CMDL | K_FORM_SMDL | K_FORM_WMDL => self.export_model(asset),
+            _ => {
+                let data = self.packages.read_asset(asset.id)?;
+                Ok(vec![self.write_raw(asset, &data)?])
+            }
+        }
+    }
+
+    fn export_texture(&mut self, id: Uuid) -> Result<PathBuf> {
+        if let Some(path) = self.textures.get(&id) {
+            return Ok(path.clone());
+        }
+        let data = self.packages.read_asset(id)?;
+        let meta = locate_meta::<LittleEndian>(&data)?;
+        let TextureData { head, data, .. } = TextureData::<LittleEndian>::slice(&data, meta)?;
+        let path = self.out_dir.join(format!("{id}.dds"));
+        let mut file = BufWriter::new(
+            File::create(&path)
+                .with_context(|| format!("Failed to create output file '{}'", path.display()))?,
+        );
+        write_dds(&mut file, &head, data)?;
+        file.flush()?;
+        self.textures.insert(id, path.clone());
+        Ok(path)
+    }
+
+    fn export_model(&mut self, asset: AssetRef) -> Result<Vec<PathBuf>> {
+        let data = self.packages.read_asset(asset.id)?;
+        let meta = locate_meta::<LittleEndian>(&data)?;
+        let model = ModelData::<LittleEndian>::slice(&data, meta)?;
+        let mut texture_ids = Vec::new();
+        for mat in &model.mtrl.materials {
+            for data in &mat.data {
+                match &data.data {
+                    CMaterialDataInner::Texture(texture) => texture_ids.push(texture.id),
+                    CMaterialDataInner::LayeredTexture(layered) => texture_ids
+                        .extend(layered.textures.iter().map(|t| t.id).filter(|id| !id.is_nil())),
+                    _ => {}
+                }
+            }
+        }
+        texture_ids.sort_unstable();
+        texture_ids.dedup();
+
+        // The converter reads textures as sibling TXTR files of the model
+        DirBuilder::new().recursive(true).create(&self.raw_dir)?;
+        let model_path = self.raw_dir.join(format!("{}.{}", asset.id, asset.kind));
+        write_file(&model_path, &data)?;
+        for id in texture_ids {
+            if self.raw_textures.contains(&id) {
+                continue;
+            }
+            let texture = self
+                .packages
+                .read_asset(id)
+                .with_context(|| format!("Failed to read texture {id}"))?;
+            write_file(&self.raw_dir.join(format!("{id}.{K_FORM_TXTR}")), &texture)?;
+            self.raw_textures.insert(id);
+        }
+
+        let path = convert_model(&model_path, &self.out_dir, &ConvertOptions::default())
+            .context("Conversion failed")?;
+        Ok(vec![path])
+    }
+
+    fn write_raw(&self, asset: AssetRef, data: &[u8]) -> Result<PathBuf> {
+        let path = self.out_dir.join(format!("{}.{}", asset.id, asset.kind));
+        write_file(&path, data)?;
+        Ok(path)
+    }
+}
+
+fn write_file(path: &Path, data: &[u8]) -> Result<()> {
+    fs::write(path, data).with_context(|| format!("Failed to write '{}'", path.display()))
+}
+
+/// Formats written files relative to the output directory.
+pub fn display_paths(paths: &[PathBuf], out_dir: &Path) -> String {
+    paths
+        .iter()
+        .map(|p| p.strip_prefix(out_dir).unwrap_or(p).display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
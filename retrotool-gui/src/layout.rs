@@ -0,0 +1,201 @@
+use std::{fs::File, io::BufWriter, path::PathBuf};
+
+use anyhow::{Context, Result};
+use bevy::prelude::*;
+use egui_dock::{Node, NodeIndex, Split, TabIndex, Tree};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    tabs::{
+        asset_tab, packages::PackagesTab, project::ProjectTab, raw::RawTab, splash::SplashTab,
+        templates::TemplatesTab, EditorTab, TabType,
+    },
+    AssetRef,
+};
+
+/// A tab that can be reopened on the next launch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SavedTab {
+    Splash,
+    Project,
+    Packages,
+    Templates,
+    /// A viewer tab, by canonical reference (e.g. `CMDL:<uuid>`).
+    Asset {
+        asset: String,
+    },
+    Raw {
+        asset: String,
+    },
+}
+
+impl SavedTab {
+    /// Reopens the tab. Assets that no longer exist open in their tab's error state.
+    fn open(&self, server: &AssetServer) -> Option<TabType> {
+        let asset_ref = |asset: &str| match asset.parse::<AssetRef>() {
+            Ok(asset_ref) => Some(asset_ref),
+            Err(e) => {
+                log::warn!("Skipping saved tab {asset}: {e:#}");
+                None
+            }
+        };
+        Some(match self {
+            SavedTab::Splash => SplashTab::new(),
+            SavedTab::Project => ProjectTab::new(),
+            SavedTab::Packages => PackagesTab::new(),
+            SavedTab::Templates => TemplatesTab::new(),
+            SavedTab::Asset { asset } => {
+                let asset_ref = asset_ref(asset)?;
+                let tab = asset_tab(asset_ref, server);
+                if tab.is_none() {
+                    log::warn!("Skipping saved tab {asset_ref}: no viewer for this kind");
+                }
+                tab?
+            }
+            SavedTab::Raw { asset } => {
+                let asset_ref = asset_ref(asset)?;
+                RawTab::new(asset_ref, server.load(format!("{}.raw", asset_ref.id)))
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SavedNode {
+    Leaf {
+        tabs: Vec<SavedTab>,
+        active: usize,
+    },
+    /// Two nodes side by side, or stacked if `vertical`. `fraction` is the share of the first.
+    Split {
+        vertical: bool,
+        fraction: f32,
+        first: Box<SavedNode>,
+        second: Box<SavedNode>,
+    },
+}
+
+/// The dock tree and the packages its tabs were opened from, persisted across launches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedLayout {
+    pub packages: Vec<PathBuf>,
+    pub root: Option<SavedNode>,
+}
+
+/// `retrotool/layout.json` in the user's configuration directory.
+fn layout_path() -> Option<PathBuf> {
+    let dir = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    dir.map(|dir| dir.join("retrotool").join("layout.json"))
+}
+
+fn save_node(nodes: &[&Node<TabType>], index: NodeIndex) -> Option<SavedNode> {
+    match nodes.get(index.0)? {
+        Node::Empty => None,
+        Node::Leaf { tabs, active, .. } => {
+            let mut saved = Vec::with_capacity(tabs.len());
+            let mut saved_active = 0;
+            for (i, tab) in tabs.iter().enumerate() {
+                if i == active.0 {
+                    saved_active = saved.len();
+                }
+                saved.extend(tab.saved());
+            }
+            if saved.is_empty() {
+                return None;
+            }
+            Some(SavedNode::Leaf { tabs: saved, active: saved_active })
+        }
+        node @ (Node::Horizontal { fraction, .. } | Node::Vertical { fraction, .. }) => {
+            let vertical = matches!(node, Node::Vertical { .. });
+            match (save_node(nodes, index.left()), save_node(nodes, index.right())) {
+                (Some(first), Some(second)) => Some(SavedNode::Split {
+                    vertical,
+                    fraction: *fraction,
+                    first: Box::new(first),
+                    second: Box::new(second),
+                }),
+                (first, second) => first.or(second),
+            }
+        }
+    }
+}
+
+fn build_node(tree: &mut Tree<TabType>, index: NodeIndex, node: &SavedNode, server: &AssetServer) {
+    match node {
+        SavedNode::Leaf { tabs, active } => {
+            let opened = tabs.iter().filter_map(|tab| tab.open(server)).collect::<Vec<_>>();
+            let len = opened.len();
+            if let Node::Leaf { tabs, .. } = &mut tree[index] {
+                *tabs = opened;
+            }
+            if *active < len {
+                tree.set_active_tab(index, TabIndex(*active));
+            }
+        }
+        SavedNode::Split { vertical, fraction, first, second } => {
+            let split = if *vertical { Split::Above } else { Split::Left };
+            let [a, b] = tree.split(index, split, *fraction, Node::leaf_with(vec![]));
+            build_node(tree, a, first, server);
+            build_node(tree, b, second, server);
+        }
+    }
+}
+
+impl SavedLayout {
+    pub fn from_tree(tree: &Tree<TabType>, packages: Vec<PathBuf>) -> Self {
+        let nodes = tree.iter().collect::<Vec<_>>();
+        Self { packages, root: save_node(&nodes, NodeIndex::root()) }
+    }
+
+    /// Reads the saved layout, if there is one.
+    pub fn load() -> Result<Option<Self>> {
+        let Some(path) = layout_path() else {
+            return Ok(None);
+        };
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let file = File::open(&path)
+            .with_context(|| format!("Failed to open layout '{}'", path.display()))?;
+        let layout = serde_json::from_reader(file)
+            .with_context(|| format!("Failed to parse layout '{}'", path.display()))?;
+        Ok(Some(layout))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = layout_path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create '{}'", parent.display()))?;
+        }
+        let file = File::create(&path)
+            .with_context(|| format!("Failed to create layout '{}'", path.display()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    /// Rebuilds the dock tree. Leaves whose tabs could not be reopened are left empty.
+    pub fn build(&self, server: &AssetServer) -> Tree<TabType> {
+        let mut tree = Tree::new(vec![]);
+        if let Some(root) = &self.root {
+            build_node(&mut tree, NodeIndex::root(), root, server);
+        }
+        tree
+    }
+}
+
+/// A saved layout waiting for its packages to finish loading.
+#[derive(Default, Resource)]
+pub struct PendingLayout(pub Option<SavedLayout>);
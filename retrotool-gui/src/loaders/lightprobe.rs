@@ -1,13 +1,25 @@
-use anyhow::Error;
+use anyhow::{anyhow, Error, Result};
 use bevy::{
     asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
     prelude::*,
-    render::{renderer::RenderDevice, texture::CompressedImageFormats},
+    render::{
+        render_resource::{
+            Extent3d, TextureDimension, TextureFormat, TextureViewDescriptor, TextureViewDimension,
+        },
+        renderer::RenderDevice,
+        texture::CompressedImageFormats,
+    },
 };
-use retrolib::format::{
-    foot::{locate_asset_id, locate_meta},
-    ltpb::{LightProbeBundleHeader, LightProbeData, LightProbeExtra, K_FORM_LTPB},
+use half::f16;
+use retrolib::{
+    format::{
+        foot::{locate_asset_id, locate_meta},
+        ltpb::{LightProbeBundleHeader, LightProbeData, LightProbeExtra, K_FORM_LTPB},
+        txtr::{decompress_image, slice_texture, ETextureType, TextureData},
+    },
+    util::cubemap::cube_irradiance,
 };
+use uuid::Uuid;
 use zerocopy::LittleEndian;
 
 use crate::{
@@ -18,9 +30,49 @@ use crate::{
 #[derive(Debug, Clone, bevy::reflect::TypeUuid)]
 #[uuid = "f5d65a8b-ffcc-47ea-8c9d-1ab30cca723c"]
 pub struct LightProbeAsset {
+    pub id: Uuid,
     pub head: LightProbeBundleHeader,
     pub textures: Vec<TextureAsset>,
     pub extra: Vec<LightProbeExtra>,
+    /// Diffuse irradiance computed from the cube map, if the probe contains one.
+    pub irradiance_map: Option<Handle<Image>>,
+}
+
+impl LightProbeAsset {
+    /// The cube map texture, if the probe contains one.
+    pub fn cube_map(&self) -> Option<&Handle<Image>> {
+        self.textures.iter().find(|t| t.inner.head.kind == ETextureType::Cube).map(|t| &t.texture)
+    }
+}
+
+/// Face size of the irradiance map computed from a probe's cube map.
+const IRRADIANCE_SIZE: u32 = 32;
+
+/// Convolves mip 0 of a cube map into a diffuse irradiance cube map.
+fn irradiance_image(texture: &TextureData<LittleEndian>) -> Result<Image> {
+    let slices = slice_texture(texture)?;
+    let faces = slices
+        .first()
+        .ok_or_else(|| anyhow!("Cube map has no image data"))?
+        .iter()
+        .map(|slice| {
+            let data = &texture.data[slice.data_range.clone()];
+            Ok(decompress_image(texture.head.format, slice.width, slice.height, data)?.to_rgba32f())
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let data = cube_irradiance(&faces, IRRADIANCE_SIZE)?
+        .iter()
+        .flat_map(|face| face.as_raw().iter().flat_map(|&v| f16::from_f32(v).to_le_bytes()))
+        .collect();
+    let mut image = Image::new(
+        Extent3d { width: IRRADIANCE_SIZE, height: IRRADIANCE_SIZE, depth_or_array_layers: 6 },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba16Float,
+    );
+    image.texture_view_descriptor =
+        Some(TextureViewDescriptor { dimension: Some(TextureViewDimension::Cube), ..default() });
+    Ok(image)
 }
 
 pub struct LightProbeAssetLoader {
@@ -50,6 +102,7 @@ impl AssetLoader for LightProbeAssetLoader {
             info!("Loading light probe {} {:?}", id, data.head);
 
             let mut textures = Vec::with_capacity(data.textures.len());
+            let mut irradiance_map = None;
             for (idx, texture_data) in data.textures.into_iter().enumerate() {
                 let result = load_texture_asset(texture_data, &self.supported_formats)?;
                 let mut slice_handles = Vec::with_capacity(result.slices.len());
@@ -63,19 +116,45 @@ impl AssetLoader for LightProbeAssetLoader {
                     }
                     slice_handles.push(handles);
                 }
+                // 3D BC1 textures are not supported by wgpu
+                // and we don't use the 3D texture anyway
+                let texture = if result.inner.head.kind == ETextureType::Cube {
+                    // Cube maps drive the model preview's environment lighting
+                    if irradiance_map.is_none() {
+                        match irradiance_image(&result.inner) {
+                            Ok(image) => {
+                                irradiance_map = Some(load_context.set_labeled_asset(
+                                    &format!("irradiance_{idx}"),
+                                    LoadedAsset::new(image),
+                                ));
+                            }
+                            Err(e) => {
+                                warn!("Failed to compute irradiance for texture {idx}: {e:#}")
+                            }
+                        }
+                    }
+                    let mut image = result.texture;
+                    image.texture_view_descriptor = Some(TextureViewDescriptor {
+                        dimension: Some(TextureViewDimension::Cube),
+                        ..default()
+                    });
+                    load_context.set_labeled_asset(&format!("image_{idx}"), LoadedAsset::new(image))
+                } else {
+                    default()
+                };
                 textures.push(TextureAsset {
                     asset_ref: AssetRef { id, kind: K_FORM_LTPB },
                     inner: result.inner,
-                    // 3D BC1 textures are not supported by wgpu
-                    // and we don't use the 3D texture anyway
-                    texture: default(),
+                    texture,
                     slices: slice_handles,
                 });
             }
             load_context.set_default_asset(LoadedAsset::new(LightProbeAsset {
+                id,
                 head: data.head,
                 textures,
                 extra: data.extra,
+                irradiance_map,
             }));
             Ok(())
         })
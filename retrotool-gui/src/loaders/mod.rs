@@ -3,10 +3,17 @@ pub mod material;
 pub mod modcon;
 pub mod model;
 pub mod package;
+pub mod raw;
 pub mod room;
 pub mod texture;
 
-use bevy::prelude::*;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use retrolib::error::RetroError;
 
 pub struct RetroAssetPlugin;
 
@@ -15,13 +22,16 @@ impl Plugin for RetroAssetPlugin {
         if !app.is_plugin_added::<package::RetroAssetIoPlugin>() {
             panic!("RetroAssetIoPlugin must be added before AssetPlugin");
         }
-        app.add_asset::<material::MaterialAsset>()
+        app.init_resource::<LoadErrors>()
+            .init_resource::<texture::SamplerSettings>()
+            .add_asset::<material::MaterialAsset>()
             .add_asset::<modcon::ModConAsset>()
             .add_asset::<model::ModelAsset>()
             .add_asset::<package::PackageDirectory>()
             .add_asset::<texture::TextureAsset>()
             .add_asset::<lightprobe::LightProbeAsset>()
             .add_asset::<room::RoomAsset>()
+            .add_asset::<raw::RawAsset>()
             .init_asset_loader::<material::MaterialAssetLoader>()
             .init_asset_loader::<modcon::ModConAssetLoader>()
             .init_asset_loader::<model::ModelAssetLoader>()
@@ -29,6 +39,37 @@ impl Plugin for RetroAssetPlugin {
             .init_asset_loader::<texture::TextureAssetLoader>()
             .init_asset_loader::<lightprobe::LightProbeAssetLoader>()
             .init_asset_loader::<room::RoomAssetLoader>()
-            .add_system(package::package_loader_system);
+            .init_asset_loader::<raw::RawAssetLoader>()
+            .add_system(package::package_loader_system)
+            .add_system(texture::sampler_settings_system);
+    }
+}
+
+/// A failure recorded by an asset loader.
+#[derive(Clone, Debug)]
+pub struct LoadError {
+    /// Full error message, including context.
+    pub message: String,
+    /// Typed parse failure, if one caused the error.
+    pub kind: Option<RetroError>,
+}
+
+/// Load failures by asset path, shared with the asset loaders.
+///
+/// Bevy only reports [`LoadState::Failed`], so loaders record the cause here for display.
+#[derive(Resource, Clone, Default)]
+pub struct LoadErrors(Arc<RwLock<HashMap<PathBuf, LoadError>>>);
+
+impl LoadErrors {
+    pub fn record(&self, path: &Path, err: &anyhow::Error) {
+        warn!("Failed to load {}: {:#}", path.display(), err);
+        let error = LoadError { message: format!("{err:#}"), kind: RetroError::find(err).cloned() };
+        self.0.write().unwrap().insert(path.to_path_buf(), error);
+    }
+
+    pub fn clear(&self, path: &Path) { self.0.write().unwrap().remove(path); }
+
+    pub fn get(&self, path: &Path) -> Option<LoadError> {
+        self.0.read().unwrap().get(path).cloned()
     }
 }
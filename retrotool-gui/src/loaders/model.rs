@@ -1,10 +1,13 @@
-use std::{num::NonZeroU8, path::PathBuf};
+use std::path::PathBuf;
 
 use anyhow::{Error, Result};
 use bevy::{
     asset::{AssetLoader, AssetPath, BoxedFuture, LoadContext, LoadState, LoadedAsset},
     prelude::*,
-    render::render_resource::SamplerDescriptor,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
     utils::{hashbrown::hash_map::Entry, HashMap},
 };
 use retrolib::format::{
@@ -13,17 +16,17 @@ use retrolib::format::{
         K_FORM_CMDL,
     },
     foot::{locate_asset_id, locate_meta},
-    txtr::{
-        ETextureAnisotropicRatio, ETextureFilter, ETextureMipFilter, ETextureWrap,
-        STextureSamplerData,
-    },
+    txtr::STextureSamplerData,
 };
 use uuid::Uuid;
-use wgpu_types::{AddressMode, Face, FilterMode};
+use wgpu_types::Face;
 use zerocopy::LittleEndian;
 
 use crate::{
-    loaders::texture::TextureAsset,
+    loaders::{
+        texture::{texture_sampler, TextureAsset, TRILINEAR_SAMPLER},
+        LoadErrors,
+    },
     material::CustomMaterial,
     render::{convert_color, model::MESH_FLAG_OPAQUE},
     AssetRef,
@@ -43,13 +46,19 @@ pub struct ModelAsset {
     pub inner: ModelData<LittleEndian>,
     pub textures: HashMap<Uuid, Handle<TextureAsset>>,
     pub texture_images: HashMap<Uuid, Handle<Image>>,
+    /// Samplers of the images copied for material usage overrides, by texture ID.
+    pub texture_samplers: HashMap<Uuid, ImageSampler>,
     pub materials: HashMap<MaterialKey, Handle<CustomMaterial>>,
 }
 
-pub struct ModelAssetLoader;
+pub struct ModelAssetLoader {
+    errors: LoadErrors,
+}
 
 impl FromWorld for ModelAssetLoader {
-    fn from_world(_world: &mut World) -> Self { Self }
+    fn from_world(world: &mut World) -> Self {
+        Self { errors: world.get_resource_or_insert_with(LoadErrors::default).clone() }
+    }
 }
 
 impl AssetLoader for ModelAssetLoader {
@@ -59,63 +68,80 @@ impl AssetLoader for ModelAssetLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<(), Error>> {
         Box::pin(async move {
-            let id = locate_asset_id::<LittleEndian>(bytes)?;
-            let meta = locate_meta::<LittleEndian>(bytes)?;
-            let data = ModelData::<LittleEndian>::slice(bytes, meta)?;
-            // log::info!("Loaded model {:?}", data.head);
-            // log::info!("Loaded meshes {:#?}", data.mesh);
-            let mut dependencies = HashMap::<Uuid, AssetPath>::new();
-            for mat in &data.mtrl.materials {
-                for data in &mat.data {
-                    match &data.data {
-                        CMaterialDataInner::Texture(texture) => {
+            let result = self.load_inner(bytes, load_context);
+            match &result {
+                Ok(()) => self.errors.clear(load_context.path()),
+                Err(e) => self.errors.record(load_context.path(), e),
+            }
+            result
+        })
+    }
+
+    fn extensions(&self) -> &[&str] { &["cmdl", "smdl", "wmdl"] }
+}
+
+impl ModelAssetLoader {
+    fn load_inner(&self, bytes: &[u8], load_context: &mut LoadContext) -> Result<()> {
+        let id = locate_asset_id::<LittleEndian>(bytes)?;
+        let meta = locate_meta::<LittleEndian>(bytes)?;
+        let data = ModelData::<LittleEndian>::slice(bytes, meta)?;
+        // log::info!("Loaded model {:?}", data.head);
+        // log::info!("Loaded meshes {:#?}", data.mesh);
+        let mut dependencies = HashMap::<Uuid, AssetPath>::new();
+        for mat in &data.mtrl.materials {
+            for data in &mat.data {
+                match &data.data {
+                    CMaterialDataInner::Texture(texture) => {
+                        dependencies.insert(
+                            texture.id,
+                            AssetPath::new(PathBuf::from(format!("{}.TXTR", texture.id)), None),
+                        );
+                    }
+                    CMaterialDataInner::LayeredTexture(texture) => {
+                        for texture in &texture.textures {
+                            if texture.id.is_nil() {
+                                continue;
+                            }
                             dependencies.insert(
                                 texture.id,
                                 AssetPath::new(PathBuf::from(format!("{}.TXTR", texture.id)), None),
                             );
                         }
-                        CMaterialDataInner::LayeredTexture(texture) => {
-                            for texture in &texture.textures {
-                                if texture.id.is_nil() {
-                                    continue;
-                                }
-                                dependencies.insert(
-                                    texture.id,
-                                    AssetPath::new(
-                                        PathBuf::from(format!("{}.TXTR", texture.id)),
-                                        None,
-                                    ),
-                                );
-                            }
-                        }
-                        _ => {}
                     }
+                    _ => {}
                 }
             }
-            let textures = dependencies
-                .iter()
-                .map(|(u, p)| (*u, load_context.get_handle(p.clone())))
-                .collect();
-            load_context.set_default_asset(
-                LoadedAsset::new(ModelAsset {
-                    asset_ref: AssetRef { id, kind: K_FORM_CMDL },
-                    inner: data,
-                    textures,
-                    texture_images: default(),
-                    materials: default(),
-                })
-                .with_dependencies(dependencies.into_values().collect()),
-            );
-            Ok(())
-        })
+        }
+        let textures =
+            dependencies.iter().map(|(u, p)| (*u, load_context.get_handle(p.clone()))).collect();
+        load_context.set_default_asset(
+            LoadedAsset::new(ModelAsset {
+                asset_ref: AssetRef { id, kind: K_FORM_CMDL },
+                inner: data,
+                textures,
+                texture_images: default(),
+                texture_samplers: default(),
+                materials: default(),
+            })
+            .with_dependencies(dependencies.into_values().collect()),
+        );
+        Ok(())
     }
-
-    fn extensions(&self) -> &[&str] { &["cmdl", "smdl", "wmdl"] }
 }
 
 impl ModelAsset {
+    /// Loaded once every texture has either loaded or failed. Textures that couldn't be
+    /// loaded are replaced with a placeholder by [`ModelAsset::build_texture_images`].
     pub fn get_load_state(&self, server: &AssetServer) -> LoadState {
-        server.get_group_load_state(self.textures.values().map(|h| h.id()))
+        let settled = self
+            .textures
+            .values()
+            .all(|h| matches!(server.get_load_state(h), LoadState::Loaded | LoadState::Failed));
+        if settled {
+            LoadState::Loaded
+        } else {
+            LoadState::Loading
+        }
     }
 
     #[allow(dead_code)]
@@ -130,46 +156,65 @@ impl ModelAsset {
             .map(|txtr| &txtr.inner.head.sampler_data)
     }
 
+    /// Resolves the image each material texture samples. Textures whose material usage
+    /// overrides the texture's filtering or wrapping get a copy with that sampler.
     pub fn build_texture_images(
         &mut self,
         texture_assets: &mut Assets<TextureAsset>,
-        _images: &mut Assets<Image>,
+        images: &mut Assets<Image>,
+        force_trilinear: bool,
     ) {
-        // Build sampler descriptors
-        // let mut sampler_descriptors = HashMap::<Uuid, SamplerDescriptor>::new();
-        // for mat in &self.inner.mtrl.materials {
-        //     for data in &mat.data {
-        //         match &data.data {
-        //             CMaterialDataInner::Texture(texture) => {
-        //                 if let Some(usage) = &texture.usage {
-        //                     let sampler_data = self.sampler_data(&texture.id, texture_assets);
-        //                     sampler_descriptors.insert(
-        //                         texture.id,
-        //                         sampler_descriptor_from_usage(usage, sampler_data),
-        //                     );
-        //                 }
-        //             }
-        //             CMaterialDataInner::LayeredTexture(layers) => {
-        //                 for texture in &layers.textures {
-        //                     if let Some(usage) = &texture.usage {
-        //                         let sampler_data = self.sampler_data(&texture.id, texture_assets);
-        //                         sampler_descriptors.insert(
-        //                             texture.id,
-        //                             sampler_descriptor_from_usage(usage, sampler_data),
-        //                         );
-        //                     }
-        //                 }
-        //             }
-        //             _ => continue,
-        //         }
-        //     }
-        // }
+        // First usage of each texture that specifies its own sampler settings
+        let mut usages = HashMap::<Uuid, &STextureUsageInfo>::new();
+        for mat in &self.inner.mtrl.materials {
+            for data in &mat.data {
+                let textures = match &data.data {
+                    CMaterialDataInner::Texture(texture) => std::slice::from_ref(texture),
+                    CMaterialDataInner::LayeredTexture(layers) => &layers.textures[..],
+                    _ => continue,
+                };
+                for texture in textures {
+                    let Some(usage) = &texture.usage else { continue };
+                    if [usage.filter, usage.wrap_x, usage.wrap_y, usage.wrap_z] != [-1; 4] {
+                        usages.entry(texture.id).or_insert(usage);
+                    }
+                }
+            }
+        }
 
-        // Build texture images
+        let mut placeholder = None;
         for (id, handle) in &self.textures {
-            let asset = texture_assets.get(handle).unwrap();
-            // TODO: use sampler descriptors
-            self.texture_images.insert(*id, asset.texture.clone());
+            let Some(asset) = texture_assets.get(handle) else {
+                log::warn!("Texture {id} not found in loaded packages, using placeholder");
+                let image =
+                    placeholder.get_or_insert_with(|| images.add(missing_texture_image())).clone();
+                self.texture_images.insert(*id, image);
+                continue;
+            };
+            let image = match usages.get(id) {
+                Some(usage) => {
+                    let sampler = texture_sampler(&asset.inner.head.sampler_data, Some(usage));
+                    let Some(mut image) = images.get(&asset.texture).cloned() else { continue };
+                    image.sampler_descriptor =
+                        if force_trilinear { TRILINEAR_SAMPLER } else { sampler.clone() };
+                    self.texture_samplers.insert(*id, sampler);
+                    images.add(image)
+                }
+                None => asset.texture.clone(),
+            };
+            self.texture_images.insert(*id, image);
+        }
+    }
+
+    /// Switches the images created for material usage samplers between their own sampler
+    /// and [`TRILINEAR_SAMPLER`].
+    pub fn apply_samplers(&self, images: &mut Assets<Image>, force_trilinear: bool) {
+        for (id, sampler) in &self.texture_samplers {
+            let Some(image) = self.texture_images.get(id).and_then(|h| images.get_mut(h)) else {
+                continue;
+            };
+            image.sampler_descriptor =
+                if force_trilinear { TRILINEAR_SAMPLER } else { sampler.clone() };
         }
     }
 
@@ -191,6 +236,16 @@ impl ModelAsset {
     }
 }
 
+/// Solid magenta stand-in for textures that failed to load.
+fn missing_texture_image() -> Image {
+    Image::new_fill(
+        Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        &[255, 0, 255, 255],
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
 fn build_material(
     key: &MaterialKey,
     materials: &[CMaterialCache],
@@ -331,93 +386,3 @@ fn build_material(
     }
     Ok(out_mat)
 }
-
-#[allow(dead_code)]
-fn texture_wrap(wrap: ETextureWrap) -> AddressMode {
-    match wrap {
-        ETextureWrap::ClampToEdge => AddressMode::ClampToEdge,
-        ETextureWrap::Repeat => AddressMode::Repeat,
-        ETextureWrap::MirroredRepeat => AddressMode::MirrorRepeat,
-        ETextureWrap::MirrorClamp => todo!("Mirror clamp"),
-        ETextureWrap::ClampToBorder => AddressMode::ClampToBorder,
-        ETextureWrap::Clamp => todo!("Clamp"),
-    }
-}
-
-#[allow(dead_code)]
-fn sampler_descriptor_from_usage<'desc>(
-    usage: &STextureUsageInfo,
-    data: Option<&STextureSamplerData>,
-) -> SamplerDescriptor<'desc> {
-    SamplerDescriptor {
-        label: None,
-        address_mode_u: match usage.wrap_x {
-            0 => AddressMode::ClampToEdge,
-            1 => AddressMode::Repeat,
-            2 => AddressMode::MirrorRepeat,
-            3 => todo!("Mirror clamp"),
-            4 => AddressMode::ClampToBorder,
-            5 => todo!("Clamp"),
-            -1 => data.map_or(AddressMode::Repeat, |d| texture_wrap(d.wrap_x)),
-            n => todo!("wrap {n}"),
-        },
-        address_mode_v: match usage.wrap_y {
-            0 => AddressMode::ClampToEdge,
-            1 => AddressMode::Repeat,
-            2 => AddressMode::MirrorRepeat,
-            3 => todo!("Mirror clamp"),
-            4 => AddressMode::ClampToBorder,
-            5 => todo!("Clamp"),
-            -1 => data.map_or(AddressMode::Repeat, |d| texture_wrap(d.wrap_y)),
-            n => todo!("wrap {n}"),
-        },
-        address_mode_w: match usage.wrap_z {
-            0 => AddressMode::ClampToEdge,
-            1 => AddressMode::Repeat,
-            2 => AddressMode::MirrorRepeat,
-            3 => todo!("Mirror clamp"),
-            4 => AddressMode::ClampToBorder,
-            5 => todo!("Clamp"),
-            -1 => data.map_or(AddressMode::Repeat, |d| texture_wrap(d.wrap_z)),
-            n => todo!("wrap {n}"),
-        },
-        mag_filter: match usage.filter {
-            0 => FilterMode::Nearest,
-            1 => FilterMode::Linear,
-            -1 => data.map_or(FilterMode::Nearest, |d| match d.filter {
-                ETextureFilter::Nearest => FilterMode::Nearest,
-                ETextureFilter::Linear => FilterMode::Linear,
-            }),
-            n => todo!("Filter {n}"),
-        },
-        min_filter: match usage.filter {
-            0 => FilterMode::Nearest,
-            1 => FilterMode::Linear,
-            -1 => data.map_or(FilterMode::Nearest, |d| match d.filter {
-                ETextureFilter::Nearest => FilterMode::Nearest,
-                ETextureFilter::Linear => FilterMode::Linear,
-            }),
-            n => todo!("Filter {n}"),
-        },
-        mipmap_filter: data.map_or(FilterMode::Nearest, |d| match d.mip_filter {
-            ETextureMipFilter::Nearest => FilterMode::Nearest,
-            ETextureMipFilter::Linear => FilterMode::Linear,
-        }),
-        lod_min_clamp: 0.0,
-        lod_max_clamp: f32::MAX,
-        compare: None,
-        anisotropy_clamp: NonZeroU8::new(
-            data.map(|d| match d.aniso {
-                ETextureAnisotropicRatio::None => 0,
-                ETextureAnisotropicRatio::Ratio1 => 1,
-                ETextureAnisotropicRatio::Ratio2 => 2,
-                ETextureAnisotropicRatio::Ratio4 => 4,
-                ETextureAnisotropicRatio::Ratio8 => 8,
-                ETextureAnisotropicRatio::Ratio16 => 16,
-            })
-            .unwrap_or_default(),
-        ),
-        // anisotropy_clamp: NonZeroU8::new(8),
-        border_color: None,
-    }
-}
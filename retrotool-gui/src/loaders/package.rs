@@ -10,7 +10,10 @@ use bevy::{
     prelude::*,
 };
 use retrolib::{
-    format::pack::{Package, SparsePackageEntry},
+    format::{
+        pack::{decompress_package, Package, SparsePackageEntry},
+        FourCC,
+    },
     util::file::map_file,
 };
 use uuid::Uuid;
@@ -28,12 +31,12 @@ struct RetroAssetIo {
 
 fn read_pak_header(path: &Path) -> anyhow::Result<Vec<u8>> {
     let data = map_file(path)?;
-    Package::<LittleEndian>::read_header(&data)
+    Package::<LittleEndian>::read_header(&decompress_package(&data)?)
 }
 
 fn read_asset(path: &Path, id: Uuid) -> anyhow::Result<Vec<u8>> {
     let data = map_file(path)?;
-    Package::<LittleEndian>::read_asset(&data, id)
+    Package::<LittleEndian>::read_asset(&decompress_package(&data)?, id)
 }
 
 impl AssetIo for RetroAssetIo {
@@ -127,7 +130,15 @@ pub fn package_loader_system(
                     package_info.packages.write().expect("Failed to lock shared package info");
                 package_info.push(package.clone());
             }
-            AssetEvent::Modified { .. } => {}
+            AssetEvent::Modified { handle } => {
+                let package = assets.get(handle).unwrap();
+                log::info!("Reloaded package {}", package.path.display());
+                let mut package_info =
+                    package_info.packages.write().expect("Failed to lock shared package info");
+                if let Some(info) = package_info.iter_mut().find(|p| p.path == package.path) {
+                    *info = package.clone();
+                }
+            }
             AssetEvent::Removed { handle } => {
                 let package = assets.get(handle).unwrap();
                 let mut package_info =
@@ -146,6 +157,58 @@ pub struct PackageDirectory {
     pub entries: Vec<SparsePackageEntry>,
 }
 
+/// Entry count and sizes for a set of package entries.
+#[derive(Debug, Clone, Default)]
+pub struct EntryStats {
+    pub count: usize,
+    /// Size as stored in the package.
+    pub size: u64,
+    pub decompressed_size: u64,
+}
+
+impl EntryStats {
+    pub fn add(&mut self, other: &EntryStats) {
+        self.count += other.count;
+        self.size += other.size;
+        self.decompressed_size += other.decompressed_size;
+    }
+
+    fn add_entry(&mut self, entry: &SparsePackageEntry) {
+        self.count += 1;
+        self.size += entry.size;
+        self.decompressed_size += entry.decompressed_size;
+    }
+}
+
+impl PackageDirectory {
+    pub fn total_stats(&self) -> EntryStats {
+        let mut stats = EntryStats::default();
+        for entry in &self.entries {
+            stats.add_entry(entry);
+        }
+        stats
+    }
+
+    /// Stats for each asset kind in the package, most common first.
+    pub fn kind_stats(&self) -> Vec<(FourCC, EntryStats)> {
+        let mut kinds: Vec<(FourCC, EntryStats)> = Vec::new();
+        for entry in &self.entries {
+            match kinds.iter_mut().find(|(kind, _)| *kind == entry.kind) {
+                Some((_, stats)) => stats.add_entry(entry),
+                None => {
+                    let mut stats = EntryStats::default();
+                    stats.add_entry(entry);
+                    kinds.push((entry.kind, stats));
+                }
+            }
+        }
+        kinds.sort_by(|(a_kind, a), (b_kind, b)| {
+            b.count.cmp(&a.count).then_with(|| a_kind.0.cmp(&b_kind.0))
+        });
+        kinds
+    }
+}
+
 pub struct PackageAssetLoader;
 
 impl FromWorld for PackageAssetLoader {
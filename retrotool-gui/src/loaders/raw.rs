@@ -0,0 +1,80 @@
+use anyhow::{Error, Result};
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::{FromWorld, World},
+};
+use retrolib::format::{rfrm::walk_chunks, FourCC};
+use zerocopy::LittleEndian;
+
+/// An asset's undecoded bytes, loaded from `{id}.raw` regardless of its kind.
+#[derive(Debug, Clone, bevy::reflect::TypeUuid)]
+#[uuid = "83269869-1209-408e-8835-bc6f2496e82c"]
+pub struct RawAsset {
+    pub data: Vec<u8>,
+    /// Top-level forms and chunks, as far as they could be sliced.
+    pub chunks: Vec<RawChunk>,
+    /// Why slicing stopped early, if it did.
+    pub error: Option<String>,
+}
+
+/// A form or chunk within a [`RawAsset`].
+#[derive(Debug, Clone)]
+pub struct RawChunk {
+    pub id: FourCC,
+    pub is_form: bool,
+    /// Offset of the contents (after the descriptor) within the file.
+    pub offset: usize,
+    pub size: usize,
+    pub children: Vec<RawChunk>,
+}
+
+/// Adds `chunk` as the last descendant `depth` levels below `chunks`.
+fn insert_chunk(chunks: &mut Vec<RawChunk>, depth: usize, chunk: RawChunk) {
+    if depth > 0 {
+        if let Some(parent) = chunks.last_mut() {
+            return insert_chunk(&mut parent.children, depth - 1, chunk);
+        }
+    }
+    chunks.push(chunk);
+}
+
+pub struct RawAssetLoader;
+
+impl FromWorld for RawAssetLoader {
+    fn from_world(_world: &mut World) -> Self { Self }
+}
+
+impl AssetLoader for RawAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<(), Error>> {
+        Box::pin(async move {
+            // Chunks sliced before a failure are kept
+            let mut chunks = Vec::new();
+            let error = walk_chunks::<LittleEndian, _>(bytes, |path, header, data| {
+                let offset = data.as_ptr() as usize - bytes.as_ptr() as usize;
+                let chunk = RawChunk {
+                    id: header.id(),
+                    is_form: header.is_form(),
+                    offset,
+                    size: data.len(),
+                    children: vec![],
+                };
+                insert_chunk(&mut chunks, path.matches('/').count(), chunk);
+                Ok(())
+            })
+            .err()
+            .map(|e| format!("{e:#}"));
+            load_context.set_default_asset(LoadedAsset::new(RawAsset {
+                data: bytes.to_vec(),
+                chunks,
+                error,
+            }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] { &["raw"] }
+}
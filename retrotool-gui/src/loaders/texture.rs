@@ -1,6 +1,6 @@
 use std::num::NonZeroU8;
 
-use anyhow::{anyhow, Error, Result};
+use anyhow::{anyhow, Context, Error, Result};
 use bevy::{
     asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
     prelude::*,
@@ -12,17 +12,24 @@ use bevy::{
         texture::{CompressedImageFormats, ImageSampler},
     },
 };
+use image::DynamicImage;
 use retrolib::format::{
+    cmdl::STextureUsageInfo,
     foot::{locate_asset_id, locate_meta},
     txtr::{
-        decompress_image, slice_texture, ETextureFormat, ETextureType, TextureData, K_FORM_TXTR,
+        decompress_image, slice_texture, ETextureAnisotropicRatio, ETextureFilter, ETextureFormat,
+        ETextureMipFilter, ETextureType, ETextureWrap, STextureSamplerData, TextureData,
+        K_FORM_TXTR,
     },
 };
 use wgpu::SamplerDescriptor;
 use wgpu_types::{AddressMode, FilterMode};
 use zerocopy::LittleEndian;
 
-use crate::AssetRef;
+use crate::{
+    loaders::{model::ModelAsset, LoadErrors},
+    AssetRef,
+};
 
 #[derive(Debug, Clone, bevy::reflect::TypeUuid)]
 #[uuid = "83269869-1209-408e-8835-bc6f2496e828"]
@@ -35,6 +42,7 @@ pub struct TextureAsset {
 
 pub struct TextureAssetLoader {
     supported_formats: CompressedImageFormats,
+    errors: LoadErrors,
 }
 
 impl FromWorld for TextureAssetLoader {
@@ -43,7 +51,8 @@ impl FromWorld for TextureAssetLoader {
             Some(render_device) => CompressedImageFormats::from_features(render_device.features()),
             None => CompressedImageFormats::all(),
         };
-        Self { supported_formats }
+        let errors = world.get_resource_or_insert_with(LoadErrors::default).clone();
+        Self { supported_formats, errors }
     }
 }
 
@@ -54,38 +63,49 @@ impl AssetLoader for TextureAssetLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<(), Error>> {
         Box::pin(async move {
-            let id = locate_asset_id::<LittleEndian>(bytes)?;
-            let meta = locate_meta::<LittleEndian>(bytes)?;
-            let data = TextureData::<LittleEndian>::slice(bytes, meta)?;
-            info!("Loading texture {} {:?}", id, data.head);
-
-            let result = load_texture_asset(data, &self.supported_formats)?;
-            let image_handle =
-                load_context.set_labeled_asset("image", LoadedAsset::new(result.texture));
-            let mut slice_handles = Vec::with_capacity(result.slices.len());
-            for (mip, images) in result.slices.into_iter().enumerate() {
-                let mut handles = Vec::with_capacity(images.len());
-                for (layer, image) in images.into_iter().enumerate() {
-                    handles.push(load_context.set_labeled_asset(
-                        &format!("mip_{}_layer_{}", mip, layer),
-                        LoadedAsset::new(image),
-                    ));
-                }
-                slice_handles.push(handles);
+            let result = self.load_inner(bytes, load_context);
+            match &result {
+                Ok(()) => self.errors.clear(load_context.path()),
+                Err(e) => self.errors.record(load_context.path(), e),
             }
-            load_context.set_default_asset(LoadedAsset::new(TextureAsset {
-                asset_ref: AssetRef { id, kind: K_FORM_TXTR },
-                inner: result.inner,
-                texture: image_handle,
-                slices: slice_handles,
-            }));
-            Ok(())
+            result
         })
     }
 
     fn extensions(&self) -> &[&str] { &["txtr"] }
 }
 
+impl TextureAssetLoader {
+    fn load_inner(&self, bytes: &[u8], load_context: &mut LoadContext) -> Result<()> {
+        let id = locate_asset_id::<LittleEndian>(bytes)?;
+        let meta = locate_meta::<LittleEndian>(bytes)?;
+        let data = TextureData::<LittleEndian>::slice(bytes, meta)?;
+        info!("Loading texture {} {:?}", id, data.head);
+
+        let result = load_texture_asset(data, &self.supported_formats)?;
+        let image_handle =
+            load_context.set_labeled_asset("image", LoadedAsset::new(result.texture));
+        let mut slice_handles = Vec::with_capacity(result.slices.len());
+        for (mip, images) in result.slices.into_iter().enumerate() {
+            let mut handles = Vec::with_capacity(images.len());
+            for (layer, image) in images.into_iter().enumerate() {
+                handles.push(load_context.set_labeled_asset(
+                    &format!("mip_{}_layer_{}", mip, layer),
+                    LoadedAsset::new(image),
+                ));
+            }
+            slice_handles.push(handles);
+        }
+        load_context.set_default_asset(LoadedAsset::new(TextureAsset {
+            asset_ref: AssetRef { id, kind: K_FORM_TXTR },
+            inner: result.inner,
+            texture: image_handle,
+            slices: slice_handles,
+        }));
+        Ok(())
+    }
+}
+
 pub struct LoadTextureResult {
     pub inner: TextureData<LittleEndian>,
     pub texture: Image,
@@ -101,12 +121,14 @@ pub fn load_texture_asset(
     let (bw, bh, _) = data.head.format.block_size();
     let format = wgpu_format(data.head.format)
         .ok_or_else(|| anyhow!("Texture format unsupported: {:?}", data.head.format))?;
-    let supported = texture_format_supported(data.head.kind, format, supported_formats);
+    // Integer textures can't be sampled as color, so preview a decoded visualization instead
+    let supported = !data.head.format.is_integer()
+        && texture_format_supported(data.head.kind, format, supported_formats);
 
     let mut images = Vec::with_capacity(slices.len());
-    for mip in &slices {
+    for (mip_idx, mip) in slices.iter().enumerate() {
         let mut slice_images = Vec::with_capacity(mip.len());
-        for slice in mip {
+        for (layer, slice) in mip.iter().enumerate() {
             let slice_data = &data.data[slice.data_range.clone()];
             slice_images.push(if supported {
                 texture_slice_to_image(
@@ -118,10 +140,13 @@ pub fn load_texture_asset(
                     bh,
                 )
             } else {
-                Image::from_dynamic(
-                    decompress_image(data.head.format, slice.width, slice.height, slice_data)?,
-                    is_srgb,
-                )
+                let mut image =
+                    decompress_image(data.head.format, slice.width, slice.height, slice_data)
+                        .with_context(|| format!("Failed to decode mip {mip_idx} layer {layer}"))?;
+                if data.head.format.is_integer() {
+                    image = DynamicImage::ImageRgba8(image.to_rgba8());
+                }
+                Image::from_dynamic(image, is_srgb)
             });
         }
         images.push(slice_images);
@@ -166,7 +191,7 @@ fn texture_slice_to_image(
             usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
             view_formats: &[],
         },
-        sampler_descriptor: DEFAULT_SAMPLER,
+        sampler_descriptor: TRILINEAR_SAMPLER,
         ..default()
     }
 }
@@ -181,7 +206,16 @@ fn texture_format_supported(
         && !(kind == ETextureType::D3 && matches!(format, TextureFormat::Astc { .. }))
 }
 
-const DEFAULT_SAMPLER: ImageSampler = ImageSampler::Descriptor(SamplerDescriptor {
+/// Global texture sampling options, set from the View menu.
+#[derive(Resource, Default)]
+pub struct SamplerSettings {
+    /// Sample every texture with [`TRILINEAR_SAMPLER`], ignoring its sampler data.
+    pub force_trilinear: bool,
+}
+
+/// Trilinear filtering with anisotropy. Used for UI previews and to compare against the
+/// textures' own sampler data.
+pub const TRILINEAR_SAMPLER: ImageSampler = ImageSampler::Descriptor(SamplerDescriptor {
     label: None,
     address_mode_u: AddressMode::Repeat,
     address_mode_v: AddressMode::Repeat,
@@ -223,11 +257,115 @@ fn texture_to_image(
             usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
             view_formats: &[],
         },
-        sampler_descriptor: DEFAULT_SAMPLER,
+        sampler_descriptor: texture_sampler(&data.head.sampler_data, None),
         ..default()
     })
 }
 
+/// Builds a sampler from a texture's sampler data, overridden by a material's texture usage
+/// where its values aren't `-1`. Wrap modes wgpu lacks are approximated.
+pub fn texture_sampler(
+    data: &STextureSamplerData,
+    usage: Option<&STextureUsageInfo>,
+) -> ImageSampler {
+    fn address_mode(value: Option<i32>, default: ETextureWrap) -> AddressMode {
+        let wrap = [
+            ETextureWrap::ClampToEdge,
+            ETextureWrap::Repeat,
+            ETextureWrap::MirroredRepeat,
+            ETextureWrap::MirrorClamp,
+            ETextureWrap::ClampToBorder,
+            ETextureWrap::Clamp,
+        ]
+        .into_iter()
+        .find(|&wrap| value == Some(wrap as i32))
+        .unwrap_or(default);
+        match wrap {
+            ETextureWrap::Repeat => AddressMode::Repeat,
+            ETextureWrap::MirroredRepeat | ETextureWrap::MirrorClamp => AddressMode::MirrorRepeat,
+            // Border colors require an optional device feature
+            ETextureWrap::ClampToEdge | ETextureWrap::ClampToBorder | ETextureWrap::Clamp => {
+                AddressMode::ClampToEdge
+            }
+        }
+    }
+
+    let filter = match (usage.map(|u| u.filter), data.filter) {
+        (Some(0), _) | (None | Some(-1), ETextureFilter::Nearest) => FilterMode::Nearest,
+        _ => FilterMode::Linear,
+    };
+    let mipmap_filter = match data.mip_filter {
+        ETextureMipFilter::Nearest => FilterMode::Nearest,
+        ETextureMipFilter::Linear => FilterMode::Linear,
+    };
+    let aniso = match data.aniso {
+        ETextureAnisotropicRatio::None => 0,
+        ETextureAnisotropicRatio::Ratio1 => 1,
+        ETextureAnisotropicRatio::Ratio2 => 2,
+        ETextureAnisotropicRatio::Ratio4 => 4,
+        ETextureAnisotropicRatio::Ratio8 => 8,
+        ETextureAnisotropicRatio::Ratio16 => 16,
+    };
+    ImageSampler::Descriptor(SamplerDescriptor {
+        label: None,
+        address_mode_u: address_mode(usage.map(|u| u.wrap_x), data.wrap_x),
+        address_mode_v: address_mode(usage.map(|u| u.wrap_y), data.wrap_y),
+        address_mode_w: address_mode(usage.map(|u| u.wrap_z), data.wrap_z),
+        mag_filter: filter,
+        min_filter: filter,
+        mipmap_filter,
+        lod_min_clamp: 0.0,
+        lod_max_clamp: f32::MAX,
+        compare: None,
+        // Anisotropic filtering requires linear filtering throughout
+        anisotropy_clamp: if filter == FilterMode::Linear && mipmap_filter == FilterMode::Linear {
+            NonZeroU8::new(aniso)
+        } else {
+            None
+        },
+        border_color: None,
+    })
+}
+
+/// Reapplies [`SamplerSettings`] to texture and model images when it changes, and to
+/// textures loaded while it's active.
+pub fn sampler_settings_system(
+    settings: Res<SamplerSettings>,
+    mut events: EventReader<AssetEvent<TextureAsset>>,
+    textures: Res<Assets<TextureAsset>>,
+    models: Res<Assets<ModelAsset>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let created = events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Created { handle } => Some(handle.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let handles = if settings.is_changed() {
+        textures.ids().map(|id| textures.get_handle(id)).collect()
+    } else if settings.force_trilinear {
+        created
+    } else {
+        return;
+    };
+    for asset in handles.iter().filter_map(|handle| textures.get(handle)) {
+        if let Some(image) = images.get_mut(&asset.texture) {
+            image.sampler_descriptor = if settings.force_trilinear {
+                TRILINEAR_SAMPLER
+            } else {
+                texture_sampler(&asset.inner.head.sampler_data, None)
+            };
+        }
+    }
+    if settings.is_changed() {
+        for (_, model) in models.iter() {
+            model.apply_samplers(&mut images, settings.force_trilinear);
+        }
+    }
+}
+
 fn wgpu_format(format: ETextureFormat) -> Option<TextureFormat> {
     use wgpu_types::{AstcBlock::*, AstcChannel::*, TextureFormat::*};
     Some(match format {
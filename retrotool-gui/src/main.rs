@@ -1,19 +1,30 @@
+mod export;
 mod icon;
+mod layout;
 mod loaders;
 mod material;
 mod render;
 mod tabs;
+mod watch;
 
-use std::{borrow::Cow, path::PathBuf, time::Duration};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fmt::{Display, Formatter},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
 
 use bevy::{
     app::AppExit,
-    asset::diagnostic::AssetCountDiagnosticsPlugin,
+    asset::{diagnostic::AssetCountDiagnosticsPlugin, LoadState},
     diagnostic::{
         Diagnostics, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin,
         SystemInformationDiagnosticsPlugin,
     },
     prelude::*,
+    render::{render_resource::TextureFormat, renderer::RenderAdapter, texture::BevyDefault},
     window::{PrimaryWindow, WindowResolution},
 };
 use bevy_egui::{egui, EguiContext, EguiContexts, EguiPlugin};
@@ -25,18 +36,20 @@ use uuid::Uuid;
 use walkdir::{DirEntry, WalkDir};
 
 use crate::{
+    layout::{PendingLayout, SavedLayout},
     loaders::{
         model::ModelAsset,
         package::{PackageDirectory, RetroAssetIoPlugin},
-        texture::TextureAsset,
+        texture::{SamplerSettings, TextureAsset},
         RetroAssetPlugin,
     },
     material::CustomMaterial,
-    render::{grid::GridPlugin, TemporaryLabel},
+    render::{grid::GridPlugin, CullStats, RenderScale, TemporaryLabel},
     tabs::{
-        modcon::ModConRaycastSet, project::ProjectTab, splash::SplashTab, EditorTab, TabState,
-        TabType, TabViewer,
+        asset_tab, modcon::ModConRaycastSet, project::ProjectTab, splash::SplashTab, EditorTab,
+        TabState, TabType, TabViewer,
     },
+    watch::{file_watcher_system, FileWatcher},
 };
 
 #[derive(Default, Resource)]
@@ -50,6 +63,7 @@ fn main() {
     App::new()
         .insert_resource(ClearColor(Color::rgb(0.05, 0.05, 0.05)))
         .insert_resource(Msaa::default())
+        .init_resource::<RenderScale>()
         .insert_resource(bevy::winit::WinitSettings {
             focused_mode: bevy::winit::UpdateMode::Continuous,
             unfocused_mode: bevy::winit::UpdateMode::ReactiveLowPower {
@@ -60,6 +74,9 @@ fn main() {
         .insert_resource(file_open)
         .init_resource::<UiState>()
         .init_resource::<Packages>()
+        .init_resource::<FileWatcher>()
+        .init_resource::<CullStats>()
+        .init_resource::<PendingLayout>()
         .add_plugins(
             DefaultPlugins
                 .build()
@@ -96,18 +113,36 @@ fn main() {
         .add_plugin(SystemInformationDiagnosticsPlugin::default())
         // Systems
         .add_startup_system(setup_egui)
+        .add_startup_system(restore_layout)
         .add_system(file_drop.before(load_files))
         .add_system(load_files)
+        .add_system(file_watcher_system)
         .add_system(ui_system)
+        .add_system(save_layout.in_base_set(CoreSet::Last))
         .run();
 }
 
-#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
 pub struct AssetRef {
     id: Uuid,
     kind: FourCC,
 }
 
+impl Display for AssetRef {
+    /// Canonical reference, e.g. `CMDL:<uuid>`.
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result { write!(f, "{}:{}", self.kind, self.id) }
+}
+
+impl FromStr for AssetRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (kind, id) =
+            s.split_once(':').ok_or_else(|| anyhow::anyhow!("Invalid asset reference '{s}'"))?;
+        Ok(Self { kind: kind.parse()?, id: Uuid::try_parse(id)? })
+    }
+}
+
 #[derive(Resource)]
 struct UiState {
     tree: egui_dock::Tree<TabType>,
@@ -168,10 +203,14 @@ fn close_all_tabs(world: &mut World, ui_state: &mut UiState) {
             tabs.retain_mut(|tab| !tab.close(world));
         }
     }
+    remove_empty_leaves(&mut ui_state.tree);
+}
+
+fn remove_empty_leaves(tree: &mut egui_dock::Tree<TabType>) {
     'outer: loop {
-        for (i, node) in ui_state.tree.iter().enumerate() {
+        for (i, node) in tree.iter().enumerate() {
             if matches!(node, egui_dock::Node::Leaf { tabs, .. } if tabs.is_empty()) {
-                ui_state.tree.remove_leaf(NodeIndex(i));
+                tree.remove_leaf(NodeIndex(i));
                 continue 'outer;
             }
         }
@@ -179,6 +218,130 @@ fn close_all_tabs(world: &mut World, ui_state: &mut UiState) {
     }
 }
 
+/// Queues the packages of the previous session's layout for loading.
+fn restore_layout(mut pending: ResMut<PendingLayout>, mut file_open: ResMut<FileOpen>) {
+    let layout = match SavedLayout::load() {
+        Ok(Some(layout)) => layout,
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!("{e:#}");
+            return;
+        }
+    };
+    for path in &layout.packages {
+        if !file_open.0.contains(path) {
+            file_open.0.push(path.clone());
+        }
+    }
+    pending.0 = Some(layout);
+}
+
+/// Replaces the tree with the pending layout once its packages have loaded (or failed to),
+/// so that its asset tabs can find their data.
+fn apply_pending_layout(world: &mut World, ui_state: &mut UiState) {
+    if world.resource::<PendingLayout>().0.is_none() || !world.resource::<FileOpen>().0.is_empty() {
+        return;
+    }
+    let server = world.resource::<AssetServer>().clone();
+    let loading = world.resource::<Packages>().0.iter().any(|handle| {
+        matches!(server.get_load_state(handle), LoadState::NotLoaded | LoadState::Loading)
+    });
+    if loading {
+        return;
+    }
+    let layout = world.resource_mut::<PendingLayout>().0.take().unwrap();
+    let mut tree = layout.build(&server);
+    remove_empty_leaves(&mut tree);
+    if tree.is_empty() {
+        return;
+    }
+    close_all_tabs(world, ui_state);
+    ui_state.tree = tree;
+}
+
+fn save_layout(
+    mut exit: EventReader<AppExit>,
+    ui_state: Res<UiState>,
+    packages: Res<Packages>,
+    package_assets: Res<Assets<PackageDirectory>>,
+) {
+    if exit.iter().last().is_none() {
+        return;
+    }
+    let paths = packages
+        .0
+        .iter()
+        .filter_map(|handle| package_assets.get(handle))
+        .map(|package| package.path.clone())
+        .collect();
+    if let Err(e) = SavedLayout::from_tree(&ui_state.tree, paths).save() {
+        log::warn!("Failed to save layout: {e:#}");
+    }
+}
+
+/// Reopens tabs showing assets from packages that changed on disk.
+fn reload_tabs(world: &mut World, ui_state: &mut UiState, changed: &[PathBuf]) {
+    let server = world.resource::<AssetServer>().clone();
+    let ids = world
+        .resource::<Assets<PackageDirectory>>()
+        .iter()
+        .filter(|(_, package)| changed.contains(&package.path))
+        .flat_map(|(_, package)| package.entries.iter().map(|e| e.id))
+        .collect::<HashSet<_>>();
+    for node in ui_state.tree.iter_mut() {
+        if let egui_dock::Node::Leaf { tabs, .. } = node {
+            for tab in tabs.iter_mut() {
+                let Some(asset) = tab.asset() else {
+                    continue;
+                };
+                if !ids.contains(&asset.id) {
+                    continue;
+                }
+                server.reload_asset(format!("{}.{}", asset.id, asset.kind).as_str());
+                if let Some(new_tab) = asset_tab(asset, &server) {
+                    tab.close(world);
+                    *tab = new_tab;
+                }
+            }
+        }
+    }
+}
+
+/// Sample counts usable for both the view and depth textures.
+fn supported_msaa(world: &World) -> Vec<Msaa> {
+    let modes = [Msaa::Off, Msaa::Sample2, Msaa::Sample4, Msaa::Sample8];
+    let Some(adapter) = world.get_resource::<RenderAdapter>() else {
+        return modes.to_vec();
+    };
+    let formats = [TextureFormat::bevy_default(), TextureFormat::Depth32Float];
+    modes
+        .into_iter()
+        .filter(|msaa| {
+            formats.iter().all(|&format| {
+                adapter
+                    .get_texture_format_features(format)
+                    .flags
+                    .sample_count_supported(msaa.samples())
+            })
+        })
+        .collect()
+}
+
+fn msaa_menu(world: &mut World, ui: &mut egui::Ui) {
+    let supported = supported_msaa(world);
+    let mut msaa = world.resource_mut::<Msaa>();
+    for (mode, label) in
+        [(Msaa::Off, "Off"), (Msaa::Sample2, "2x"), (Msaa::Sample4, "4x"), (Msaa::Sample8, "8x")]
+    {
+        let response =
+            ui.add_enabled(supported.contains(&mode), egui::RadioButton::new(*msaa == mode, label));
+        if response.clicked() {
+            *msaa = mode;
+            ui.close_menu();
+        }
+    }
+}
+
 fn default_tree() -> egui_dock::Tree<TabType> {
     let mut tree = egui_dock::Tree::<TabType>::new(vec![SplashTab::new()]);
     tree.split_left(NodeIndex::root(), 0.25, vec![ProjectTab::new()]);
@@ -212,10 +375,28 @@ fn ui_system(world: &mut World) {
                             }
                             ui.close_menu();
                         }
+                        ui.menu_button("Anti-aliasing", |ui| msaa_menu(world, ui));
+                        let mut render_scale = world.resource_mut::<RenderScale>();
+                        ui.add(
+                            egui::Slider::new(&mut render_scale.0, 0.25..=2.0)
+                                .step_by(0.25)
+                                .text("Render scale"),
+                        )
+                        .on_hover_text("Resolution of 3D views relative to the window");
+                        let mut watcher = world.resource_mut::<FileWatcher>();
+                        ui.checkbox(&mut watcher.enabled, "Reload changed files")
+                            .on_hover_text("Reopen tabs when their package changes on disk");
+                        let mut settings = world.resource_mut::<SamplerSettings>();
+                        ui.checkbox(&mut settings.force_trilinear, "Force trilinear filtering")
+                            .on_hover_text(
+                                "Ignore texture sampler data and filter with anisotropy",
+                            );
                     });
                 });
             });
 
+        // Counted by the tabs during the previous frame
+        let cull_stats = std::mem::take(&mut *world.resource_mut::<CullStats>());
         let diagnostics = world.resource::<Diagnostics>();
         egui::TopBottomPanel::bottom("bottom_panel").show_separator_line(false).show(
             ctx.get_mut(),
@@ -250,10 +431,23 @@ fn ui_system(world: &mut World) {
                             .map(|d| d.value)
                             .unwrap_or_default(),
                     ));
+                    if cull_stats.drawn + cull_stats.culled > 0 {
+                        ui.label(format!(
+                            "[Drawn: {} | Culled: {}]",
+                            cull_stats.drawn, cull_stats.culled
+                        ));
+                    }
                 });
             },
         );
 
+        apply_pending_layout(world, ui_state.as_mut());
+
+        let changed = world.resource_mut::<FileWatcher>().take_changed();
+        if !changed.is_empty() {
+            reload_tabs(world, ui_state.as_mut(), &changed);
+        }
+
         let mut tab_assets = vec![];
         for node in ui_state.tree.iter_mut() {
             if let egui_dock::Node::Leaf { tabs, .. } = node {
@@ -1,4 +1,7 @@
-use bevy::{prelude::*, render::primitives::Aabb};
+use bevy::{
+    prelude::*,
+    render::{camera::CameraProjection, primitives::Aabb},
+};
 use egui::PointerButton;
 
 #[derive(Default)]
@@ -24,6 +27,23 @@ impl ModelCamera {
         self.radius = radius;
     }
 
+    /// Moves the orbit origin to the center of `aabb` and zooms to fit it,
+    /// keeping the current view direction.
+    pub fn frame(&mut self, aabb: &Aabb) {
+        self.origin = aabb.center.into();
+        self.radius = f32::max((aabb.max() - aabb.min()).max_element() * 1.25, 0.05);
+        let rot_matrix = Mat3::from_quat(self.transform.rotation);
+        self.transform.translation =
+            self.origin + rot_matrix.mul_vec3(Vec3::new(0.0, 0.0, self.radius));
+    }
+
+    /// World to clip space matrix for a viewport of the given size.
+    pub fn view_projection(&self, size: Vec2) -> Mat4 {
+        let mut projection = self.projection.clone();
+        projection.update(size.x, size.y);
+        projection.get_projection_matrix() * self.transform.compute_matrix().inverse()
+    }
+
     pub fn update(
         &mut self,
         rect: &egui::Rect,
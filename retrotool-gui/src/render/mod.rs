@@ -2,12 +2,165 @@ pub mod camera;
 pub mod grid;
 pub mod model;
 
-use bevy::{prelude::*, render::primitives::Aabb};
-use retrolib::format::{CAABox, CColor4f, CTransform4f};
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{RenderTarget, Viewport},
+        mesh::Indices,
+        primitives::Aabb,
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        texture::BevyDefault,
+    },
+};
+use bevy_egui::EguiUserTextures;
+use retrolib::format::{CAABox, CColor4f, COBBox, CTransform4f};
+use wgpu_types::PrimitiveTopology;
 
 #[derive(Component)]
 pub struct TemporaryLabel;
 
+/// Objects drawn and frustum culled by tabs during the last frame.
+#[derive(Resource, Default)]
+pub struct CullStats {
+    pub drawn: usize,
+    pub culled: usize,
+}
+
+/// Resolution of scene tabs relative to the window.
+#[derive(Resource)]
+pub struct RenderScale(pub f32);
+
+impl Default for RenderScale {
+    fn default() -> Self { Self(1.0) }
+}
+
+/// Where a scene tab's camera draws.
+///
+/// At a [`RenderScale`] of 1 the camera draws straight into its part of the window.
+/// Otherwise it draws into an image of the scaled size, which egui stretches over the tab.
+pub struct SceneTarget {
+    rect: egui::Rect,
+    pixels_per_point: f32,
+    scale: f32,
+    shape: Option<egui::layers::ShapeIdx>,
+    image: Option<(Handle<Image>, egui::TextureId)>,
+}
+
+impl Default for SceneTarget {
+    fn default() -> Self {
+        Self {
+            rect: egui::Rect::NOTHING,
+            pixels_per_point: 1.0,
+            scale: 1.0,
+            shape: None,
+            image: None,
+        }
+    }
+}
+
+impl SceneTarget {
+    /// Places the target over `rect`, beneath anything the tab draws afterwards.
+    pub fn layout(&mut self, ui: &egui::Ui, rect: egui::Rect) {
+        self.rect = rect;
+        self.pixels_per_point = ui.ctx().pixels_per_point();
+        self.shape = Some(ui.painter().add(egui::Shape::Noop));
+    }
+
+    /// Camera drawing into the target, resizing or dropping its image to match `scale`.
+    pub fn camera(
+        &mut self,
+        ui: &egui::Ui,
+        scale: f32,
+        images: &mut Assets<Image>,
+        textures: &mut EguiUserTextures,
+    ) -> Camera {
+        self.scale = scale;
+        if scale == 1.0 {
+            if let Some((handle, _)) = self.image.take() {
+                textures.remove_image(&handle);
+            }
+            let left_top = self.rect.left_top().to_vec2() * self.pixels_per_point;
+            let size = self.rect.size() * self.pixels_per_point;
+            return Camera {
+                viewport: Some(Viewport {
+                    physical_position: UVec2::new(left_top.x as u32, left_top.y as u32),
+                    physical_size: UVec2::new(size.x as u32, size.y as u32),
+                    depth: 0.0..1.0,
+                }),
+                ..default()
+            };
+        }
+
+        let size = self.image_size();
+        let (handle, texture_id) = match &self.image {
+            Some((handle, texture_id)) => {
+                // Only borrow mutably on resize, as that re-uploads the image
+                if images.get(handle).is_some_and(|image| image.texture_descriptor.size != size) {
+                    if let Some(image) = images.get_mut(handle) {
+                        image.resize(size);
+                    }
+                }
+                (handle.clone(), *texture_id)
+            }
+            None => {
+                let mut image = Image {
+                    texture_descriptor: TextureDescriptor {
+                        label: Some("scene_target"),
+                        size,
+                        dimension: TextureDimension::D2,
+                        format: TextureFormat::bevy_default(),
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        usage: TextureUsages::TEXTURE_BINDING
+                            | TextureUsages::COPY_DST
+                            | TextureUsages::RENDER_ATTACHMENT,
+                        view_formats: &[],
+                    },
+                    ..default()
+                };
+                image.resize(size);
+                let handle = images.add(image);
+                let texture_id = textures.add_image(handle.clone_weak());
+                self.image = Some((handle.clone(), texture_id));
+                (handle, texture_id)
+            }
+        };
+        if let Some(shape) = self.shape.take() {
+            let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+            ui.painter()
+                .set(shape, egui::Shape::image(texture_id, self.rect, uv, egui::Color32::WHITE));
+        }
+        Camera { target: RenderTarget::Image(handle), ..default() }
+    }
+
+    /// Whether the camera draws into its own image, which it should clear itself.
+    pub fn is_offscreen(&self) -> bool { self.image.is_some() }
+
+    /// Converts a pointer position to the screen space position raycasts expect.
+    pub fn cursor_position(&self, ui: &egui::Ui, pos: egui::Pos2) -> Vec2 {
+        match &self.image {
+            Some(_) => {
+                // Image pixels, bottom-up
+                let scale = self.pixels_per_point * self.scale;
+                let offset = (pos - self.rect.left_top()) * scale;
+                Vec2::new(offset.x, self.rect.height() * scale - offset.y)
+            }
+            None => Vec2::new(pos.x, ui.ctx().screen_rect().height() - pos.y),
+        }
+    }
+
+    fn image_size(&self) -> Extent3d {
+        let size = self.rect.size() * self.pixels_per_point * self.scale;
+        Extent3d {
+            width: (size.x as u32).max(1),
+            height: (size.y as u32).max(1),
+            depth_or_array_layers: 1,
+        }
+    }
+}
+
 #[inline]
 pub fn convert_aabb(aabb: &CAABox) -> Aabb {
     let min = mint::Vector3::from(aabb.min);
@@ -25,3 +178,33 @@ pub fn convert_transform(xf: &CTransform4f) -> Transform {
 pub fn convert_color(value: &CColor4f) -> Color {
     Color::rgba_linear(value.r, value.g, value.b, value.a)
 }
+
+/// Places a [`wire_box_mesh`] to outline an oriented box.
+#[inline]
+pub fn convert_obb(obb: &COBBox) -> Transform {
+    let extents = mint::Vector3::from(obb.extents);
+    convert_transform(&obb.xf) * Transform::from_scale(extents.into())
+}
+
+/// Line list outlining the cube from -1 to 1 on each axis.
+pub fn wire_box_mesh() -> Mesh {
+    let positions = (0..8)
+        .map(|i| {
+            let axis = |bit: u32| if i & bit != 0 { 1.0 } else { -1.0 };
+            [axis(1), axis(2), axis(4)]
+        })
+        .collect::<Vec<[f32; 3]>>();
+    // Connect each pair of corners differing in exactly one axis
+    let mut indices = Vec::with_capacity(24);
+    for i in 0..8u32 {
+        for bit in [1, 2, 4] {
+            if i & bit == 0 {
+                indices.extend([i, i | bit]);
+            }
+        }
+    }
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
@@ -12,9 +12,12 @@ use bit_set::BitSet;
 use half::prelude::*;
 use retrolib::{
     array_ref,
-    format::cmdl::{
-        CMaterialCache, EBufferType, EVertexComponent, EVertexDataFormat, ModelData,
-        SVertexDataComponent,
+    format::{
+        cmdl::{
+            CMaterialCache, EBufferType, EVertexComponent, EVertexDataFormat, ModelData,
+            SVertexDataComponent,
+        },
+        CAABox,
     },
 };
 use wgpu_types::PrimitiveTopology;
@@ -35,6 +38,8 @@ pub struct BuiltMesh {
     pub visible: bool,
     pub flags: u16,
     pub unk_e: u16,
+    /// Bounds of the mesh's vertex positions in model space.
+    pub bounds: CAABox,
 }
 
 pub struct ModelLod {
@@ -59,7 +64,7 @@ pub fn load_model(asset: &ModelAsset, meshes: &mut Assets<Mesh>) -> Result<Built
     let mut buf_infos: Vec<VertexBufferInfo> = Vec::with_capacity(vtx_buffers.len());
     let mut cur_buf = 0usize;
     for info in &vbuf.info {
-        let num_buffers = info.num_buffers as usize;
+        let num_buffers = info.buffer_count();
         let mut attributes = Vec::with_capacity(info.components.len());
         for component in &info.components {
             let input = &*vtx_buffers[cur_buf + component.buffer_index as usize];
@@ -106,12 +111,14 @@ pub fn load_model(asset: &ModelAsset, meshes: &mut Assets<Mesh>) -> Result<Built
             out_mesh
                 .insert_attribute(component.clone(), slice_vertices(values, vert_range.clone()));
         }
+        let bounds = mesh_bounds(&out_mesh);
         out_meshes.push(BuiltMesh {
             mesh: meshes.add(out_mesh),
             material_idx: in_mesh.material_idx as usize,
             visible: true,
             flags: in_mesh.unk_c,
             unk_e: in_mesh.unk_e,
+            bounds,
         });
     }
 
@@ -134,6 +141,19 @@ pub fn load_model(asset: &ModelAsset, meshes: &mut Assets<Mesh>) -> Result<Built
     })
 }
 
+fn mesh_bounds(mesh: &Mesh) -> CAABox {
+    let mut bounds = CAABox::default();
+    if let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    {
+        for &position in positions {
+            let point = position.into();
+            bounds = bounds.union(&CAABox { min: point, max: point });
+        }
+    }
+    bounds
+}
+
 #[derive(Debug, Clone, Default)]
 struct VertexBufferInfo {
     pub attributes: Vec<(MeshVertexAttribute, VertexAttributeValues)>,
@@ -9,7 +9,7 @@ use retrolib::format::txtr::ETextureType;
 use crate::{
     icon,
     loaders::lightprobe::LightProbeAsset,
-    tabs::{texture::LoadedTexture, EditorTabSystem, TabState},
+    tabs::{property_with_asset, texture::LoadedTexture, EditorTabSystem, TabState},
     AssetRef,
 };
 
@@ -68,7 +68,7 @@ impl EditorTabSystem for LightProbeTab {
     ) {
         let (server, assets) = query;
 
-        ui.label(format!("{} {}", self.asset_ref.kind, self.asset_ref.id));
+        property_with_asset(ui, self.asset_ref);
 
         match server.get_load_state(&self.handle) {
             LoadState::NotLoaded | LoadState::Loading => {
@@ -1,7 +1,9 @@
 pub mod lightprobe;
 pub mod modcon;
 pub mod model;
+pub mod packages;
 pub mod project;
+pub mod raw;
 pub mod room;
 pub mod splash;
 pub mod templates;
@@ -10,9 +12,10 @@ pub mod texture;
 use bevy::{ecs::system::*, prelude::*, render::camera::*};
 use egui::Widget;
 use egui_dock::{NodeIndex, Style, TabIndex};
+use retrolib::format::registry::{find_format, Viewer};
 use uuid::Uuid;
 
-use crate::{icon, AssetRef};
+use crate::{icon, layout::SavedTab, loaders::LoadError, AssetRef};
 
 pub type TabType = Box<dyn EditorTab>;
 
@@ -54,6 +57,9 @@ pub trait EditorTab: Send + Sync {
     fn clear_background(&self) -> bool { true }
 
     fn asset(&self) -> Option<AssetRef> { None }
+
+    /// How to reopen this tab on the next launch, if it should be.
+    fn saved(&self) -> Option<SavedTab> { None }
 }
 
 pub trait EditorTabSystem: Send + Sync {
@@ -78,6 +84,10 @@ pub trait EditorTabSystem: Send + Sync {
     fn clear_background(&self) -> bool { true }
 
     fn asset(&self) -> Option<AssetRef> { None }
+
+    fn saved(&self) -> Option<SavedTab> {
+        EditorTabSystem::asset(self).map(|asset| SavedTab::Asset { asset: asset.to_string() })
+    }
 }
 
 impl<T: EditorTabSystem> EditorTab for T {
@@ -109,6 +119,21 @@ impl<T: EditorTabSystem> EditorTab for T {
     fn clear_background(&self) -> bool { EditorTabSystem::clear_background(self) }
 
     fn asset(&self) -> Option<AssetRef> { EditorTabSystem::asset(self) }
+
+    fn saved(&self) -> Option<SavedTab> { EditorTabSystem::saved(self) }
+}
+
+/// Creates a viewer tab for an asset, if its kind has one.
+pub fn asset_tab(asset_ref: AssetRef, server: &AssetServer) -> Option<TabType> {
+    let path = format!("{}.{}", asset_ref.id, asset_ref.kind);
+    let tab: TabType = match find_format(asset_ref.kind)?.viewer? {
+        Viewer::Texture => texture::TextureTab::new(asset_ref, server.load(path)),
+        Viewer::Model => model::ModelTab::new(asset_ref, server.load(path)),
+        Viewer::ModelContainer => modcon::ModConTab::new(asset_ref, server.load(path)),
+        Viewer::LightProbe => lightprobe::LightProbeTab::new(asset_ref, server.load(path)),
+        Viewer::Room => room::RoomTab::new(asset_ref, server.load(path)),
+    };
+    Some(tab)
 }
 
 pub struct TabViewer<'a> {
@@ -126,10 +151,17 @@ impl egui_dock::TabViewer for TabViewer<'_> {
     fn context_menu(
         &mut self,
         ui: &mut egui::Ui,
-        _tab: &mut Self::Tab,
+        tab: &mut Self::Tab,
         node: NodeIndex,
         tab_index: TabIndex,
     ) {
+        if let Some(asset) = tab.asset() {
+            if ui.button("Copy asset reference").clicked() {
+                ui.output_mut(|out| out.copied_text = asset.to_string());
+                ui.close_menu();
+            }
+            ui.separator();
+        }
         if ui.button("Close others in group").clicked() {
             self.state.close_others = Some((node, tab_index));
             ui.close_menu();
@@ -152,6 +184,10 @@ impl egui_dock::TabViewer for TabViewer<'_> {
             self.state.open_tab =
                 Some(OpenTab { tab: project::ProjectTab::new(), node: Some(node) });
         }
+        if ui.button(format!("{} Packages", icon::FILE_ARCHIVE)).clicked() {
+            self.state.open_tab =
+                Some(OpenTab { tab: packages::PackagesTab::new(), node: Some(node) });
+        }
         if ui.button(format!("{} Templates", icon::EDITMODE_HLT)).clicked() {
             self.state.open_tab =
                 Some(OpenTab { tab: templates::TemplatesTab::new(), node: Some(node) });
@@ -184,6 +220,11 @@ pub fn property_with_value(ui: &mut egui::Ui, name: &str, value: String) -> egui
     .inner
 }
 
+/// Shows an asset's canonical reference, copied on click.
+pub fn property_with_asset(ui: &mut egui::Ui, asset: AssetRef) -> egui::Response {
+    property_with_value(ui, "Asset", asset.to_string())
+}
+
 pub fn property_with_id(ui: &mut egui::Ui, name: &str, id: Uuid) {
     property_with_value(ui, name, id.to_string()).context_menu(|ui| {
         if ui.button("Open").clicked() {
@@ -191,3 +232,37 @@ pub fn property_with_id(ui: &mut egui::Ui, name: &str, id: Uuid) {
         }
     });
 }
+
+/// Displays why an asset failed to load, if the loader recorded a cause.
+pub fn load_error_ui(ui: &mut egui::Ui, asset_ref: AssetRef, error: Option<&LoadError>) {
+    let kind = error.and_then(|e| e.kind.as_ref());
+    let heading = match kind {
+        Some(kind) if kind.is_unsupported() => "Unsupported asset",
+        _ => "Loading failed",
+    };
+    ui.vertical_centered(|ui| {
+        egui::Label::new(egui::RichText::from(heading).heading().color(egui::Color32::RED)).ui(ui);
+        match (kind, error) {
+            (Some(kind), _) => ui.label(kind.to_string()),
+            (None, Some(error)) => ui.label(&error.message),
+            (None, None) => ui.label("No error details available"),
+        };
+        ui.label(format!("{} {}", asset_ref.kind, asset_ref.id));
+        ui.horizontal(|ui| {
+            if ui.button("Copy GUID").clicked() {
+                ui.output_mut(|out| out.copied_text = asset_ref.id.to_string());
+            }
+            if let Some(error) = error {
+                if ui.button("Copy error").clicked() {
+                    let text = format!("{} {}: {}", asset_ref.kind, asset_ref.id, error.message);
+                    ui.output_mut(|out| out.copied_text = text);
+                }
+            }
+        });
+        if let Some(error) = error {
+            egui::CollapsingHeader::new("Details").show(ui, |ui| {
+                ui.label(&error.message);
+            });
+        }
+    });
+}
@@ -2,13 +2,13 @@ use bevy::{
     asset::LoadState,
     core_pipeline::{clear_color::ClearColorConfig, tonemapping::Tonemapping},
     ecs::system::{lifetimeless::*, *},
-    math::Vec3A,
     prelude::*,
-    render::{camera::Viewport, primitives::Aabb, view::RenderLayers},
+    render::view::{NoFrustumCulling, RenderLayers},
 };
+use bevy_egui::EguiUserTextures;
 use bevy_mod_raycast::{Intersection, RaycastMesh, RaycastSource};
 use egui::{Sense, Widget};
-use retrolib::format::SumBy;
+use retrolib::format::{CAABox, CTransform4f, SumBy};
 use uuid::Uuid;
 
 use crate::{
@@ -16,12 +16,12 @@ use crate::{
     loaders::{
         modcon::ModConAsset,
         model::{MaterialKey, ModelAsset},
-        texture::TextureAsset,
+        texture::{SamplerSettings, TextureAsset},
     },
     material::CustomMaterial,
     render::{
-        camera::ModelCamera, convert_transform, grid::GridSettings, model::load_model,
-        TemporaryLabel,
+        camera::ModelCamera, convert_aabb, convert_transform, grid::GridSettings,
+        model::load_model, CullStats, RenderScale, SceneTarget, TemporaryLabel,
     },
     tabs::{model::ModelTab, EditorTabSystem, TabState},
     AssetRef,
@@ -35,8 +35,8 @@ pub struct LoadedModel {
 pub struct ModelInfo {
     pub handle: Handle<ModelAsset>,
     pub loaded: Vec<LoadedModel>,
-    pub transforms: Vec<Transform>,
-    pub aabb: Aabb,
+    pub transforms: Vec<CTransform4f>,
+    pub bounds: CAABox,
 }
 
 pub struct ModConTab {
@@ -45,10 +45,15 @@ pub struct ModConTab {
     pub handle: Handle<ModConAsset>,
     pub models: Vec<ModelInfo>,
     pub camera: ModelCamera,
+    pub target: SceneTarget,
     pub diffuse_map: Handle<Image>,
     pub specular_map: Handle<Image>,
     pub env_light: bool,
     pub selected_model: Option<AssetRef>,
+    /// Instance picked by clicking in the viewport.
+    pub focused: Option<Entity>,
+    /// Hide instances whose bounds are outside the camera's view.
+    pub frustum_cull: bool,
 }
 
 impl Default for ModConTab {
@@ -59,10 +64,13 @@ impl Default for ModConTab {
             handle: default(),
             models: default(),
             camera: default(),
+            target: default(),
             diffuse_map: default(),
             specular_map: default(),
             env_light: true,
             selected_model: None,
+            focused: None,
+            frustum_cull: true,
         }
     }
 }
@@ -100,6 +108,19 @@ impl ModConTab {
         }
         LoadState::Loaded
     }
+
+    /// Combined world bounds of the visible instances accepted by `filter`.
+    fn instance_bounds(&self, filter: impl Fn(Entity) -> bool) -> CAABox {
+        let mut bounds = CAABox::default();
+        for info in &self.models {
+            for (model, xf) in info.loaded.iter().zip(&info.transforms) {
+                if model.visible && filter(model.entity) {
+                    bounds = bounds.union(&info.bounds.transformed(xf));
+                }
+            }
+        }
+        bounds
+    }
 }
 
 pub struct ModConRaycastSet;
@@ -120,6 +141,7 @@ impl EditorTabSystem for ModConTab {
         SResMut<Assets<Image>>,
         SResMut<AssetServer>,
         SResMut<Assets<ModConAsset>>,
+        SRes<SamplerSettings>,
     );
     type UiParam = (
         SCommands,
@@ -128,6 +150,11 @@ impl EditorTabSystem for ModConTab {
         SRes<Assets<ModConAsset>>,
         SQuery<Read<Parent>, With<Intersection<ModConRaycastSet>>>,
         SQuery<(Read<ModelLabel>, Read<Children>)>,
+        SQuery<Read<ComputedVisibility>>,
+        SResMut<CullStats>,
+        SResMut<Assets<Image>>,
+        SResMut<EguiUserTextures>,
+        SRes<RenderScale>,
     );
 
     fn load(&mut self, query: SystemParamItem<Self::LoadParam>) {
@@ -140,6 +167,7 @@ impl EditorTabSystem for ModConTab {
             mut images,
             server,
             mod_con_assets,
+            settings,
         ) = query;
 
         if self.models.is_empty() {
@@ -153,13 +181,11 @@ impl EditorTabSystem for ModConTab {
                         handle: handle.clone(),
                         loaded: vec![],
                         transforms: vec![],
-                        aabb: Default::default(),
+                        bounds: Default::default(),
                     });
                 }
                 for (idx, &model_idx) in data.shorts_1.iter().enumerate() {
-                    self.models[model_idx as usize]
-                        .transforms
-                        .push(convert_transform(&data.transforms[idx]));
+                    self.models[model_idx as usize].transforms.push(data.transforms[idx]);
                 }
                 self.models.retain(|info| !info.transforms.is_empty());
             }
@@ -186,7 +212,7 @@ impl EditorTabSystem for ModConTab {
                 _ => continue,
             }
 
-            asset.build_texture_images(&mut texture_assets, &mut images);
+            asset.build_texture_images(&mut texture_assets, &mut images, settings.force_trilinear);
             let result = load_model(asset, &mut meshes);
             let built = match result {
                 Ok(value) => value,
@@ -195,7 +221,8 @@ impl EditorTabSystem for ModConTab {
                     continue;
                 }
             };
-            for &transform in &info.transforms {
+            for xf in &info.transforms {
+                let transform = convert_transform(xf);
                 let is_mirrored = transform.scale.x.is_sign_negative()
                     ^ transform.scale.y.is_sign_negative()
                     ^ transform.scale.z.is_sign_negative();
@@ -221,7 +248,7 @@ impl EditorTabSystem for ModConTab {
                                     continue;
                                 }
                             };
-                            builder.spawn((
+                            let mut child = builder.spawn((
                                 MaterialMeshBundle::<CustomMaterial> {
                                     mesh: mesh.mesh.clone(),
                                     material,
@@ -229,28 +256,23 @@ impl EditorTabSystem for ModConTab {
                                 },
                                 RaycastMesh::<ModConRaycastSet>::default(),
                             ));
+                            if !mesh.bounds.is_empty() {
+                                child.insert(convert_aabb(&mesh.bounds));
+                            }
                         }
                     })
                     .id();
                 info.loaded.push(LoadedModel { entity, visible: true });
             }
-            info.aabb = built.aabb;
+            info.bounds = asset.inner.head.bounds;
             loaded = true;
         }
 
         if loaded && self.models.iter().all(|m| !m.loaded.is_empty()) {
-            let mut min = Vec3A::splat(f32::MAX);
-            let mut max = Vec3A::splat(f32::MIN);
-            for info in &self.models {
-                let m_min = Vec3::from(info.aabb.min());
-                let m_max = Vec3::from(info.aabb.max());
-                for &xf in &info.transforms {
-                    min = min.min(Vec3A::from(xf * m_min));
-                    max = max.max(Vec3A::from(xf * m_max));
-                }
+            let bounds = self.instance_bounds(|_| true);
+            if !bounds.is_empty() {
+                self.camera.init(&convert_aabb(&bounds), true);
             }
-            let aabb = Aabb::from_min_max(min.into(), max.into());
-            self.camera.init(&aabb, true);
         }
 
         // FIXME
@@ -261,7 +283,7 @@ impl EditorTabSystem for ModConTab {
     }
 
     fn close(&mut self, query: SystemParamItem<Self::LoadParam>) -> bool {
-        let (mut commands, _, _, _, _, _, _, _) = query;
+        let (mut commands, _, _, _, _, _, _, _, _) = query;
         for model in self.models.iter().flat_map(|l| &l.loaded) {
             if let Some(commands) = commands.get_entity(model.entity) {
                 commands.despawn_recursive();
@@ -276,20 +298,25 @@ impl EditorTabSystem for ModConTab {
         query: SystemParamItem<Self::UiParam>,
         state: &mut TabState,
     ) {
-        let scale = ui.ctx().pixels_per_point();
         let rect = ui.available_rect_before_wrap();
-        let left_top = rect.left_top().to_vec2() * scale;
-        let size = rect.size() * scale;
-        let viewport = Viewport {
-            physical_position: UVec2 { x: left_top.x as u32, y: left_top.y as u32 },
-            physical_size: UVec2 { x: size.x as u32, y: size.y as u32 },
-            depth: 0.0..1.0,
-        };
+        self.target.layout(ui, rect);
         let mut response =
             ui.interact(rect, ui.make_persistent_id("background"), Sense::click_and_drag());
         self.camera.update(&rect, &response, ui.input(|i| i.scroll_delta));
 
-        let (mut commands, server, models, mod_con_assets, intersection_query, model_query) = query;
+        let (
+            mut commands,
+            server,
+            models,
+            mod_con_assets,
+            intersection_query,
+            model_query,
+            visibility_query,
+            mut cull_stats,
+            mut images,
+            mut egui_textures,
+            render_scale,
+        ) = query;
         if self.models.is_empty() || !self.models.iter().all(|m| !m.loaded.is_empty()) {
             ui.centered_and_justified(|ui| {
                 match self.get_load_state(&server, &mod_con_assets, &models) {
@@ -303,15 +330,36 @@ impl EditorTabSystem for ModConTab {
             return;
         }
 
+        let mut hovered = None;
         if let Some(parent) = intersection_query.iter().next() {
             let (label, _) = model_query.get(parent.get()).unwrap();
             if label.tab_id == self.tab_id {
                 self.selected_model = Some(label.asset_ref);
+                hovered = Some(parent.get());
             }
         }
+        if response.clicked() {
+            self.focused = hovered;
+        }
+        let mut frame_all = false;
+        let mut focus_selected = response.hovered() && ui.input(|i| i.key_pressed(egui::Key::F));
         egui::Frame::group(ui.style()).show(ui, |ui| {
             egui::ScrollArea::vertical().max_height(rect.height() * 0.25).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Frame all").clicked() {
+                        frame_all = true;
+                    }
+                    if ui
+                        .add_enabled(self.focused.is_some(), egui::Button::new("Focus selected"))
+                        .on_hover_text_at_pointer("Click an instance to select it. Shortcut: F")
+                        .clicked()
+                    {
+                        focus_selected = true;
+                    }
+                });
                 ui.checkbox(&mut self.env_light, "Environment lighting");
+                ui.checkbox(&mut self.frustum_cull, "Frustum culling")
+                    .on_hover_text_at_pointer("Skip drawing instances outside the view");
                 ui.label(format!("Models: {}", self.models.len()));
                 ui.label(format!("Instances: {}", self.models.sum_by(|m| m.loaded.len())));
                 if let Some(selected) = &self.selected_model {
@@ -319,6 +367,16 @@ impl EditorTabSystem for ModConTab {
                 }
             });
         });
+        if frame_all || focus_selected {
+            // Focus falls back to framing everything when nothing is selected
+            let bounds = match self.focused {
+                Some(focused) if focus_selected => self.instance_bounds(|e| e == focused),
+                _ => self.instance_bounds(|_| true),
+            };
+            if !bounds.is_empty() {
+                self.camera.frame(&convert_aabb(&bounds));
+            }
+        }
 
         if let Some(selected) = &self.selected_model {
             let mut shown = false;
@@ -340,10 +398,9 @@ impl EditorTabSystem for ModConTab {
         }
 
         let camera = Camera {
-            viewport: Some(viewport),
             order: state.render_layer as isize,
             // hdr: true,
-            ..default()
+            ..self.target.camera(ui, render_scale.0, &mut images, &mut egui_textures)
         };
         let mut entity = commands.spawn((
             Camera3dBundle {
@@ -355,7 +412,7 @@ impl EditorTabSystem for ModConTab {
             },
             // BloomSettings::default(),
             GridSettings {
-                clear_color: if state.render_layer == 0 {
+                clear_color: if state.render_layer == 0 || self.target.is_offscreen() {
                     ClearColorConfig::Default
                 } else {
                     ClearColorConfig::None
@@ -372,11 +429,9 @@ impl EditorTabSystem for ModConTab {
         }
         let mut is_raycasting = false;
         if response.hovered() {
-            if let Some(pos) = ui.input(|i| {
-                i.pointer.hover_pos().map(|pos| Vec2::new(pos.x, i.screen_rect.height() - pos.y))
-            }) {
+            if let Some(pos) = ui.input(|i| i.pointer.hover_pos()) {
                 entity.insert(RaycastSource::<ModConRaycastSet>::new_screenspace(
-                    pos,
+                    self.target.cursor_position(ui, pos),
                     &camera,
                     &GlobalTransform::default(),
                 ));
@@ -407,9 +462,26 @@ impl EditorTabSystem for ModConTab {
                     });
                 }
                 if let Ok((_, children)) = model_query.get(model.entity) {
+                    if model.visible {
+                        // Bevy culls each mesh by its bounds; count the instance as drawn
+                        // if any of them were in view last frame
+                        let drawn = children.iter().any(|&child| {
+                            visibility_query.get(child).is_ok_and(|v| v.is_visible_in_view())
+                        });
+                        if drawn {
+                            cull_stats.drawn += 1;
+                        } else {
+                            cull_stats.culled += 1;
+                        }
+                    }
                     for &child in children.iter() {
                         if let Some(mut commands) = commands.get_entity(child) {
                             commands.insert(RenderLayers::layer(state.render_layer));
+                            if self.frustum_cull {
+                                commands.remove::<NoFrustumCulling>();
+                            } else {
+                                commands.insert(NoFrustumCulling);
+                            }
                             if is_raycasting {
                                 commands.insert(RaycastMesh::<ModConRaycastSet>::default());
                             } else {
@@ -5,21 +5,25 @@ use bevy::{
     core_pipeline::{clear_color::ClearColorConfig, tonemapping::Tonemapping},
     ecs::system::{lifetimeless::*, *},
     prelude::*,
-    render::{camera::Viewport, view::RenderLayers},
+    render::view::RenderLayers,
 };
 use bevy_egui::EguiUserTextures;
 use egui::Widget;
 use retrolib::format::{
     cmdl::{CMaterialCache, CMaterialDataInner, CMaterialTextureTokenData},
+    ltpb::K_FORM_LTPB,
     txtr::K_FORM_TXTR,
+    CAABox,
 };
 use uuid::Uuid;
 
 use crate::{
     icon,
     loaders::{
+        lightprobe::LightProbeAsset,
         model::{MaterialKey, ModelAsset},
-        texture::TextureAsset,
+        texture::{SamplerSettings, TextureAsset},
+        LoadError, LoadErrors,
     },
     material::CustomMaterial,
     render::{
@@ -27,10 +31,10 @@ use crate::{
         convert_aabb,
         grid::GridSettings,
         model::{load_model, ModelLod},
-        TemporaryLabel,
+        RenderScale, SceneTarget, TemporaryLabel,
     },
     tabs::{
-        property_with_value,
+        load_error_ui, property_with_value,
         texture::{TextureTab, UiTexture},
         EditorTabSystem,
     },
@@ -43,6 +47,7 @@ pub struct LoadedMesh {
     pub visible: bool,
     pub unk_c: u16,
     pub unk_e: u16,
+    pub bounds: CAABox,
 }
 
 pub struct LoadedModel {
@@ -51,6 +56,22 @@ pub struct LoadedModel {
     pub materials: Vec<CMaterialCache>,
 }
 
+impl LoadedModel {
+    /// Whether a mesh is shown, given the mesh soloed (if any).
+    pub fn is_visible(&self, idx: usize, solo: Option<usize>) -> bool {
+        solo.map_or(self.meshes[idx].visible, |solo| solo == idx)
+    }
+
+    /// Combined bounds of the visible meshes in the given LOD.
+    pub fn visible_bounds(&self, lod: usize, solo: Option<usize>) -> CAABox {
+        self.lod[lod]
+            .meshes
+            .iter()
+            .filter(|&idx| self.is_visible(idx, solo))
+            .fold(CAABox::default(), |acc, idx| acc.union(&self.meshes[idx].bounds))
+    }
+}
+
 #[derive(Default)]
 pub struct ModelTab {
     pub asset_ref: AssetRef,
@@ -58,9 +79,15 @@ pub struct ModelTab {
     pub loaded: Option<LoadedModel>,
     pub selected_lod: usize,
     pub selected_material: Option<usize>,
+    /// Mesh shown on its own, overriding the visibility checkboxes.
+    pub solo_mesh: Option<usize>,
     pub camera: ModelCamera,
+    pub target: SceneTarget,
     pub diffuse_map: Handle<Image>,
     pub specular_map: Handle<Image>,
+    /// Light probe driving environment lighting; `None` uses the neutral studio light.
+    pub light_probe: Option<Handle<LightProbeAsset>>,
+    pub light_probe_input: String,
     pub egui_textures: HashMap<Uuid, UiTexture>,
 }
 
@@ -78,8 +105,19 @@ impl ModelTab {
             Some(v) => v,
             None => return LoadState::Failed,
         };
-        // Ensure all dependencies loaded
-        server.get_group_load_state(asset.textures.iter().map(|(_, h)| h.id()))
+        // Ensure all dependencies loaded or failed
+        asset.get_load_state(server)
+    }
+
+    /// Locates the model's load error. Textures that fail to load are replaced with a
+    /// placeholder rather than failing the model.
+    fn load_error(
+        &self,
+        server: &AssetServer,
+        errors: &LoadErrors,
+    ) -> (AssetRef, Option<LoadError>) {
+        let path = server.get_handle_path(&self.handle);
+        (self.asset_ref, path.and_then(|p| errors.get(p.path())))
     }
 }
 
@@ -93,8 +131,18 @@ impl EditorTabSystem for ModelTab {
         SResMut<Assets<Image>>,
         SResMut<AssetServer>,
         SResMut<EguiUserTextures>,
+        SRes<SamplerSettings>,
+    );
+    type UiParam = (
+        SCommands,
+        SRes<AssetServer>,
+        SRes<Assets<ModelAsset>>,
+        SRes<Assets<LightProbeAsset>>,
+        SRes<LoadErrors>,
+        SResMut<Assets<Image>>,
+        SResMut<EguiUserTextures>,
+        SRes<RenderScale>,
     );
-    type UiParam = (SCommands, SRes<AssetServer>, SRes<Assets<ModelAsset>>);
 
     fn load(&mut self, query: SystemParamItem<Self::LoadParam>) {
         let (
@@ -106,6 +154,7 @@ impl EditorTabSystem for ModelTab {
             mut images,
             server,
             mut egui_textures,
+            settings,
         ) = query;
         if let Some(loaded) = &self.loaded {
             for mesh in &loaded.meshes {
@@ -120,13 +169,13 @@ impl EditorTabSystem for ModelTab {
             Some(v) => v,
             None => return,
         };
-        // Ensure all dependencies loaded
-        match server.get_group_load_state(asset.textures.iter().map(|(_, h)| h.id())) {
+        // Ensure all dependencies loaded or failed
+        match asset.get_load_state(&server) {
             LoadState::Loaded => {}
             _ => return,
         }
 
-        asset.build_texture_images(&mut texture_assets, &mut images);
+        asset.build_texture_images(&mut texture_assets, &mut images, settings.force_trilinear);
         let result = load_model(asset, &mut meshes);
         let built = match result {
             Ok(value) => value,
@@ -166,6 +215,7 @@ impl EditorTabSystem for ModelTab {
                 visible: mesh.visible,
                 unk_c: mesh.flags,
                 unk_e: mesh.unk_e,
+                bounds: mesh.bounds,
             });
         }
         self.loaded = Some(LoadedModel { meshes, lod: built.lod, materials: built.materials });
@@ -175,7 +225,7 @@ impl EditorTabSystem for ModelTab {
 
         // Build egui textures
         for (texture_id, texture_handle) in &asset.textures {
-            let texture = texture_assets.get(texture_handle).unwrap();
+            let Some(texture) = texture_assets.get(texture_handle) else { continue };
             let ui_texture = UiTexture::from_handle(
                 texture.slices[0][0].clone(),
                 images.as_mut(),
@@ -187,7 +237,7 @@ impl EditorTabSystem for ModelTab {
     }
 
     fn close(&mut self, query: SystemParamItem<Self::LoadParam>) -> bool {
-        let (mut commands, _, _, _, _, _, _, _) = query;
+        let (mut commands, _, _, _, _, _, _, _, _) = query;
         if let Some(loaded) = &self.loaded {
             for mesh in &loaded.meshes {
                 if let Some(commands) = commands.get_entity(mesh.entity) {
@@ -204,41 +254,57 @@ impl EditorTabSystem for ModelTab {
         query: SystemParamItem<Self::UiParam>,
         state: &mut TabState,
     ) {
-        let scale = ui.ctx().pixels_per_point();
         let rect = ui.available_rect_before_wrap();
-        let left_top = rect.left_top().to_vec2() * scale;
-        let size = rect.size() * scale;
-        let viewport = Viewport {
-            physical_position: UVec2::new(left_top.x as u32, left_top.y as u32),
-            physical_size: UVec2::new(size.x as u32, size.y as u32),
-            depth: 0.0..1.0,
-        };
+        self.target.layout(ui, rect);
         let response =
             ui.interact(rect, ui.make_persistent_id("background"), egui::Sense::click_and_drag());
         self.camera.update(&rect, &response, ui.input(|i| i.scroll_delta));
 
-        let (mut commands, server, models) = query;
+        let (
+            mut commands,
+            server,
+            models,
+            light_probes,
+            errors,
+            mut images,
+            mut egui_textures,
+            render_scale,
+        ) = query;
         if let Some(loaded) = &mut self.loaded {
+            // The probe's cube map is used as the specular map as-is. Its mips aren't
+            // prefiltered per roughness like the studio map's, so rough surfaces only
+            // approximate the blur.
+            let environment_map = match self
+                .light_probe
+                .as_ref()
+                .and_then(|handle| light_probes.get(handle))
+                .and_then(|probe| Some((probe.irradiance_map.as_ref()?, probe.cube_map()?)))
+            {
+                Some((irradiance_map, cube_map)) => EnvironmentMapLight {
+                    diffuse_map: irradiance_map.clone(),
+                    specular_map: cube_map.clone(),
+                },
+                None => EnvironmentMapLight {
+                    diffuse_map: self.diffuse_map.clone(),
+                    specular_map: self.specular_map.clone(),
+                },
+            };
             commands.spawn((
                 Camera3dBundle {
                     camera_3d: Camera3d { clear_color: ClearColorConfig::None, ..default() },
                     camera: Camera {
-                        viewport: Some(viewport),
                         order: state.render_layer as isize,
                         // hdr: true,
-                        ..default()
+                        ..self.target.camera(ui, render_scale.0, &mut images, &mut egui_textures)
                     },
                     tonemapping: Tonemapping::TonyMcMapface,
                     transform: self.camera.transform,
                     ..default()
                 },
                 // BloomSettings::default(),
-                EnvironmentMapLight {
-                    diffuse_map: self.diffuse_map.clone(),
-                    specular_map: self.specular_map.clone(),
-                },
+                environment_map,
                 GridSettings {
-                    clear_color: if state.render_layer == 0 {
+                    clear_color: if state.render_layer == 0 || self.target.is_offscreen() {
                         ClearColorConfig::Default
                     } else {
                         ClearColorConfig::None
@@ -261,8 +327,19 @@ impl EditorTabSystem for ModelTab {
                 // ));
             }
 
+            let mut frame_all = response.hovered() && ui.input(|i| i.key_pressed(egui::Key::F));
             egui::Frame::group(ui.style()).show(ui, |ui| {
                 egui::ScrollArea::vertical().max_height(rect.height() * 0.25).show(ui, |ui| {
+                    if ui.button("Frame all").on_hover_text_at_pointer("Shortcut: F").clicked() {
+                        frame_all = true;
+                    }
+                    light_probe_ui(
+                        ui,
+                        &mut self.light_probe,
+                        &mut self.light_probe_input,
+                        server.as_ref(),
+                        light_probes.as_ref(),
+                    );
                     if loaded.lod.len() > 1 {
                         egui::Slider::new(&mut self.selected_lod, 0..=loaded.lod.len() - 1)
                             .text("LOD")
@@ -271,9 +348,27 @@ impl EditorTabSystem for ModelTab {
                             ui.label(format!("Distance: {value}"));
                         }
                     }
-                    for idx in loaded.lod[self.selected_lod].meshes.iter() {
+                    let lod_meshes = &loaded.lod[self.selected_lod].meshes;
+                    if matches!(self.solo_mesh, Some(idx) if !lod_meshes.contains(idx)) {
+                        self.solo_mesh = None;
+                    }
+                    for idx in lod_meshes.iter() {
                         let mesh = &mut loaded.meshes[idx];
                         ui.horizontal(|ui| {
+                            let solo = self.solo_mesh == Some(idx);
+                            if ui
+                                .selectable_label(
+                                    solo,
+                                    format!(
+                                        "{}",
+                                        if solo { icon::SOLO_ON } else { icon::SOLO_OFF }
+                                    ),
+                                )
+                                .on_hover_text_at_pointer("Show only this mesh")
+                                .clicked()
+                            {
+                                self.solo_mesh = if solo { None } else { Some(idx) };
+                            }
                             ui.checkbox(
                                 &mut mesh.visible,
                                 format!(
@@ -301,15 +396,55 @@ impl EditorTabSystem for ModelTab {
                                 self.selected_material = Some(mesh.material_idx);
                             }
                         });
-                        if let Some(mut commands) = commands.get_entity(mesh.entity) {
-                            commands.insert((
-                                if mesh.visible { Visibility::Visible } else { Visibility::Hidden },
-                                RenderLayers::layer(state.render_layer),
-                            ));
-                        }
                     }
+                    ui.collapsing("Materials", |ui| {
+                        let mut material_indices = lod_meshes
+                            .iter()
+                            .map(|idx| loaded.meshes[idx].material_idx)
+                            .collect::<Vec<_>>();
+                        material_indices.sort_unstable();
+                        material_indices.dedup();
+                        for material_idx in material_indices {
+                            let mut visible = lod_meshes.iter().all(|idx| {
+                                let mesh = &loaded.meshes[idx];
+                                mesh.material_idx != material_idx || mesh.visible
+                            });
+                            if ui
+                                .checkbox(
+                                    &mut visible,
+                                    format!(
+                                        "Material {material_idx} ({})",
+                                        loaded.materials[material_idx].name
+                                    ),
+                                )
+                                .changed()
+                            {
+                                for idx in lod_meshes.iter() {
+                                    let mesh = &mut loaded.meshes[idx];
+                                    if mesh.material_idx == material_idx {
+                                        mesh.visible = visible;
+                                    }
+                                }
+                            }
+                        }
+                    });
                 });
             });
+            for idx in loaded.lod[self.selected_lod].meshes.iter() {
+                let visible = loaded.is_visible(idx, self.solo_mesh);
+                if let Some(mut commands) = commands.get_entity(loaded.meshes[idx].entity) {
+                    commands.insert((
+                        if visible { Visibility::Visible } else { Visibility::Hidden },
+                        RenderLayers::layer(state.render_layer),
+                    ));
+                }
+            }
+            if frame_all {
+                let bounds = loaded.visible_bounds(self.selected_lod, self.solo_mesh);
+                if !bounds.is_empty() {
+                    self.camera.frame(&convert_aabb(&bounds));
+                }
+            }
             if let Some(material_idx) = self.selected_material {
                 ui.push_id(format!("material_{}", material_idx), |ui| {
                     egui::Frame::group(ui.style()).fill(egui::Color32::from_black_alpha(200)).show(
@@ -341,11 +476,13 @@ impl EditorTabSystem for ModelTab {
         } else {
             ui.centered_and_justified(|ui| {
                 match self.get_load_state(&server, &models) {
-                    LoadState::Failed => egui::Label::new(
-                        egui::RichText::from("Loading failed").heading().color(egui::Color32::RED),
-                    )
-                    .ui(ui),
-                    _ => egui::Spinner::new().size(50.0).ui(ui),
+                    LoadState::Failed => {
+                        let (asset_ref, error) = self.load_error(&server, &errors);
+                        load_error_ui(ui, asset_ref, error.as_ref());
+                    }
+                    _ => {
+                        egui::Spinner::new().size(50.0).ui(ui);
+                    }
                 };
             });
         }
@@ -362,6 +499,47 @@ impl EditorTabSystem for ModelTab {
     fn asset(&self) -> Option<AssetRef> { Some(self.asset_ref) }
 }
 
+fn light_probe_ui(
+    ui: &mut egui::Ui,
+    light_probe: &mut Option<Handle<LightProbeAsset>>,
+    input: &mut String,
+    server: &AssetServer,
+    light_probes: &Assets<LightProbeAsset>,
+) {
+    ui.horizontal(|ui| {
+        let selected = light_probe
+            .as_ref()
+            .map(|handle| match light_probes.get(handle) {
+                Some(probe) if probe.cube_map().is_none() => format!("{} (no cube map)", probe.id),
+                Some(probe) if probe.irradiance_map.is_none() => {
+                    format!("{} (no irradiance)", probe.id)
+                }
+                Some(probe) => probe.id.to_string(),
+                None => "Loading...".to_string(),
+            })
+            .unwrap_or_else(|| "Studio".to_string());
+        egui::ComboBox::from_label("Lighting").selected_text(selected).show_ui(ui, |ui| {
+            if ui.selectable_label(light_probe.is_none(), "Studio").clicked() {
+                *light_probe = None;
+            }
+            // Any light probe that has already been loaded, e.g. from an open tab
+            for (handle_id, probe) in light_probes.iter() {
+                let is_selected = light_probe.as_ref().is_some_and(|h| h.id() == handle_id);
+                if ui.selectable_label(is_selected, probe.id.to_string()).clicked() {
+                    *light_probe = Some(server.get_handle(handle_id));
+                }
+            }
+        });
+        egui::TextEdit::singleline(input).hint_text("Light probe ID").desired_width(280.0).ui(ui);
+        if ui.button("Load").clicked() {
+            match Uuid::try_parse(input.trim().trim_start_matches('{').trim_end_matches('}')) {
+                Ok(id) => *light_probe = Some(server.load(format!("{}.{}", id, K_FORM_LTPB))),
+                Err(e) => log::warn!("Invalid light probe ID '{}': {}", input, e),
+            }
+        }
+    });
+}
+
 fn texture_ui(
     ui: &mut egui::Ui,
     texture: &CMaterialTextureTokenData,
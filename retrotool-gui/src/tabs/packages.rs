@@ -0,0 +1,94 @@
+use bevy::{
+    ecs::system::{lifetimeless::SRes, SystemParamItem},
+    prelude::*,
+};
+
+use crate::{
+    icon,
+    layout::SavedTab,
+    loaders::package::{EntryStats, PackageDirectory},
+    tabs::{project::kind_icon, property_with_value, EditorTabSystem, TabState},
+};
+
+/// Archive-level information about the loaded packages.
+#[derive(Default)]
+pub struct PackagesTab;
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+fn sizes_ui(ui: &mut egui::Ui, stats: &EntryStats) {
+    property_with_value(ui, "Entries", stats.count.to_string());
+    property_with_value(ui, "Compressed size", format_size(stats.size));
+    property_with_value(ui, "Decompressed size", format_size(stats.decompressed_size));
+}
+
+impl EditorTabSystem for PackagesTab {
+    type LoadParam = ();
+    type UiParam = SRes<Assets<PackageDirectory>>;
+
+    fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        query: SystemParamItem<Self::UiParam>,
+        _state: &mut TabState,
+    ) {
+        let packages = query;
+        let mut packages_sorted = packages.iter().map(|(_, p)| p).collect::<Vec<_>>();
+        if packages_sorted.is_empty() {
+            ui.label("No packages loaded");
+            return;
+        }
+        packages_sorted.sort_by_key(|p| &p.name);
+
+        let mut total = EntryStats::default();
+        for package in &packages_sorted {
+            total.add(&package.total_stats());
+        }
+        ui.heading(format!("{} packages", packages_sorted.len()));
+        sizes_ui(ui, &total);
+
+        egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+            for package in packages_sorted {
+                ui.separator();
+                egui::CollapsingHeader::new(&package.name).default_open(true).show(ui, |ui| {
+                    property_with_value(ui, "Path", package.path.display().to_string());
+                    sizes_ui(ui, &package.total_stats());
+                    ui.collapsing("Kinds", |ui| {
+                        egui::Grid::new("kinds").striped(true).show(ui, |ui| {
+                            for label in ["Kind", "Entries", "Compressed", "Decompressed"] {
+                                ui.strong(label);
+                            }
+                            ui.end_row();
+                            for (kind, stats) in package.kind_stats() {
+                                ui.label(format!("{} {}", kind_icon(kind), kind));
+                                ui.label(stats.count.to_string());
+                                ui.label(format_size(stats.size));
+                                ui.label(format_size(stats.decompressed_size));
+                                ui.end_row();
+                            }
+                        });
+                    });
+                });
+            }
+        });
+    }
+
+    fn title(&self) -> egui::WidgetText { format!("{} Packages", icon::FILE_ARCHIVE).into() }
+
+    fn id(&self) -> String { "packages".to_string() }
+
+    fn saved(&self) -> Option<SavedTab> { Some(SavedTab::Packages) }
+}
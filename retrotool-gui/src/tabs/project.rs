@@ -1,32 +1,49 @@
+use std::path::PathBuf;
+
 use bevy::{
-    asset::{AssetPath, LoadState},
+    asset::LoadState,
     ecs::system::{lifetimeless::*, *},
     prelude::*,
     render::render_resource::Extent3d,
 };
 use bevy_egui::EguiUserTextures;
 use egui::{text::LayoutJob, Color32, TextFormat, Widget};
+use indexmap::IndexSet;
 use retrolib::format::{
     cmdl::{K_FORM_CMDL, K_FORM_SMDL, K_FORM_WMDL},
     ltpb::K_FORM_LTPB,
     mcon::K_FORM_MCON,
+    mtrl::K_FORM_MTRL,
+    registry::find_format,
     txtr::{ETextureFormat, ETextureType, K_FORM_TXTR},
     FourCC,
 };
 
 use crate::{
+    export::{display_paths, start_export, SharedExportStatus},
     icon,
+    layout::SavedTab,
     loaders::{package::PackageDirectory, texture::TextureAsset},
-    tabs::{
-        lightprobe::LightProbeTab, modcon::ModConTab, model::ModelTab, room::RoomTab,
-        texture::TextureTab, EditorTabSystem, TabState,
-    },
+    tabs::{asset_tab, packages::PackagesTab, raw::RawTab, EditorTabSystem, TabState},
     AssetRef,
 };
 
 pub const K_FORM_FMV0: FourCC = FourCC(*b"FMV0");
 pub const K_FORM_ROOM: FourCC = FourCC(*b"ROOM");
 
+/// Icon shown next to assets of the given kind in the browser.
+pub fn kind_icon(kind: FourCC) -> char {
+    match kind {
+        K_FORM_TXTR => icon::TEXTURE,
+        K_FORM_CMDL | K_FORM_SMDL | K_FORM_WMDL => icon::FILE_3D,
+        K_FORM_MTRL => icon::MATERIAL,
+        K_FORM_FMV0 => icon::FILE_MOVIE,
+        K_FORM_ROOM | K_FORM_MCON => icon::SCENE_DATA,
+        K_FORM_LTPB => icon::LIGHTPROBE_GRID,
+        _ => icon::FILE,
+    }
+}
+
 #[derive(Default)]
 enum HoverState {
     #[default]
@@ -50,11 +67,20 @@ pub struct ProjectTab {
     search: String,
     hover_asset: Option<AssetRef>,
     hover_state: HoverState,
+    selected: IndexSet<AssetRef>,
+    export_dir: String,
+    export: Option<(PathBuf, SharedExportStatus)>,
 }
 
 const THUMBNAIL_SIZE: f32 = 250.0;
 
 impl ProjectTab {
+    fn toggle_selected(&mut self, asset_ref: AssetRef) {
+        if !self.selected.shift_remove(&asset_ref) {
+            self.selected.insert(asset_ref);
+        }
+    }
+
     fn hover_ui(&mut self, ui: &mut egui::Ui, asset_ref: &AssetRef, server: &AssetServer) {
         if matches!(&self.hover_asset, Some(aref) if aref == asset_ref) {
             match &self.hover_state {
@@ -125,6 +151,71 @@ impl EditorTabSystem for ProjectTab {
         }
     }
 
+    fn export_ui(&mut self, ui: &mut egui::Ui, packages: &Assets<PackageDirectory>) {
+        let running =
+            self.export.as_ref().is_some_and(|(_, status)| !status.lock().unwrap().finished);
+        if !self.selected.is_empty() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} selected", self.selected.len()));
+                if ui.button("Clear selection").clicked() {
+                    self.selected.clear();
+                }
+            });
+            ui.horizontal(|ui| {
+                egui::TextEdit::singleline(&mut self.export_dir)
+                    .hint_text("Output folder")
+                    .desired_width(150.0)
+                    .ui(ui);
+                let enabled = !running && !self.export_dir.trim().is_empty();
+                if ui.add_enabled(enabled, egui::Button::new("Convert selected")).clicked() {
+                    let out_dir = PathBuf::from(self.export_dir.trim());
+                    let packages = packages.iter().map(|(_, p)| p.clone()).collect();
+                    let assets = self.selected.iter().cloned().collect();
+                    self.export = Some((out_dir.clone(), start_export(assets, packages, out_dir)));
+                }
+            });
+        }
+
+        let Some((out_dir, status)) = &self.export else {
+            return;
+        };
+        let status = status.lock().unwrap();
+        if !status.finished {
+            egui::ProgressBar::new(status.done as f32 / status.total.max(1) as f32)
+                .text(format!("Converting {}/{}", status.done, status.total))
+                .ui(ui);
+            return;
+        }
+        let failed = status.results.iter().filter(|r| r.result.is_err()).count();
+        let mut dismiss = false;
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "Converted {} of {} to {}",
+                status.total - failed,
+                status.total,
+                out_dir.display()
+            ));
+            dismiss = ui.small_button(format!("{}", icon::PANEL_CLOSE)).clicked();
+        });
+        egui::CollapsingHeader::new("Results").default_open(failed > 0).show(ui, |ui| {
+            for result in &status.results {
+                let text = format!("{} {}", result.asset.kind, result.asset.id);
+                match &result.result {
+                    Ok(paths) => {
+                        ui.label(text).on_hover_text(display_paths(paths, out_dir));
+                    }
+                    Err(e) => {
+                        ui.colored_label(Color32::RED, text).on_hover_text(e.as_str());
+                    }
+                }
+            }
+        });
+        drop(status);
+        if dismiss {
+            self.export = None;
+        }
+    }
+
     fn ui(
         &mut self,
         ui: &mut egui::Ui,
@@ -141,8 +232,12 @@ impl EditorTabSystem for ProjectTab {
             if ui.button("Collapse all").clicked() {
                 set_open = Some(false);
             }
+            if ui.button(format!("{} Package info", icon::FILE_ARCHIVE)).clicked() {
+                state.open_tab(PackagesTab::new());
+            }
         });
         egui::TextEdit::singleline(&mut self.search).hint_text("Search").ui(ui);
+        self.export_ui(ui, &packages);
 
         let mut packages_sorted =
             packages.iter().map(|(_, p)| p).collect::<Vec<&PackageDirectory>>();
@@ -169,19 +264,7 @@ impl EditorTabSystem for ProjectTab {
                     let monospace =
                         ui.style().text_styles.get(&egui::TextStyle::Monospace).unwrap().clone();
                     let mut job = LayoutJob::simple(
-                        format!(
-                            "{} {} {}",
-                            match entry.kind {
-                                K_FORM_TXTR => icon::TEXTURE,
-                                K_FORM_CMDL | K_FORM_SMDL | K_FORM_WMDL => icon::FILE_3D,
-                                K_FORM_FMV0 => icon::FILE_MOVIE,
-                                K_FORM_ROOM | K_FORM_MCON => icon::SCENE_DATA,
-                                K_FORM_LTPB => icon::LIGHTPROBE_GRID,
-                                _ => icon::FILE,
-                            },
-                            entry.kind,
-                            entry.id
-                        ),
+                        format!("{} {} {}", kind_icon(entry.kind), entry.kind, entry.id),
                         monospace.clone(),
                         Color32::GRAY,
                         0.0,
@@ -194,39 +277,42 @@ impl EditorTabSystem for ProjectTab {
                         );
                     }
                     let asset_ref = AssetRef { id: entry.id, kind: entry.kind };
-                    let mut response =
-                        egui::SelectableLabel::new(state.open_assets.contains(&asset_ref), job)
+                    let mut checked = self.selected.contains(&asset_ref);
+                    let mut response = ui
+                        .horizontal(|ui| {
+                            if ui.checkbox(&mut checked, "").changed() {
+                                self.toggle_selected(asset_ref);
+                            }
+                            egui::SelectableLabel::new(
+                                checked || state.open_assets.contains(&asset_ref),
+                                job,
+                            )
                             .ui(ui)
-                            .context_menu(|ui| {
-                                if ui.button(format!("Copy \"{}\"", entry.id)).clicked() {
-                                    ui.output_mut(|out| out.copied_text = format!("{}", entry.id));
-                                    ui.close_menu();
-                                }
-                            });
+                        })
+                        .inner
+                        .context_menu(|ui| {
+                            if ui.button(format!("Copy \"{}\"", entry.id)).clicked() {
+                                ui.output_mut(|out| out.copied_text = format!("{}", entry.id));
+                                ui.close_menu();
+                            }
+                            if ui.button("Open raw view").clicked() {
+                                let handle = server.load(format!("{}.raw", entry.id));
+                                state.open_tab(RawTab::new(asset_ref, handle));
+                                ui.close_menu();
+                            }
+                        });
                     if entry.kind == K_FORM_TXTR {
                         response = response.on_hover_ui_at_pointer(|ui| {
                             self.hover_ui(ui, &asset_ref, &server);
                         });
+                    } else if let Some(info) = find_format(entry.kind) {
+                        response = response.on_hover_text_at_pointer(info.name);
                     }
-                    if response.clicked() {
-                        let path: AssetPath = format!("{}.{}", entry.id, entry.kind).into();
-                        match entry.kind {
-                            K_FORM_TXTR => {
-                                state.open_tab(TextureTab::new(asset_ref, server.load(path)));
-                            }
-                            K_FORM_CMDL | K_FORM_SMDL | K_FORM_WMDL => {
-                                state.open_tab(ModelTab::new(asset_ref, server.load(path)));
-                            }
-                            K_FORM_MCON => {
-                                state.open_tab(ModConTab::new(asset_ref, server.load(path)));
-                            }
-                            K_FORM_LTPB => {
-                                state.open_tab(LightProbeTab::new(asset_ref, server.load(path)));
-                            }
-                            K_FORM_ROOM => {
-                                state.open_tab(RoomTab::new(asset_ref, server.load(path)));
-                            }
-                            _ => {}
+                    if response.clicked() && ui.input(|i| i.modifiers.command) {
+                        self.toggle_selected(asset_ref);
+                    } else if response.clicked() {
+                        if let Some(tab) = asset_tab(asset_ref, &server) {
+                            state.open_tab(tab);
                         }
                     }
                 }
@@ -237,4 +323,6 @@ impl EditorTabSystem for ProjectTab {
     fn title(&self) -> egui::WidgetText { format!("{} Browser", icon::FILEBROWSER).into() }
 
     fn id(&self) -> String { "project".to_string() }
+
+    fn saved(&self) -> Option<SavedTab> { Some(SavedTab::Project) }
 }
@@ -0,0 +1,220 @@
+use bevy::{
+    asset::LoadState,
+    ecs::system::{lifetimeless::*, *},
+    prelude::*,
+};
+use egui::{collapsing_header::CollapsingState, Widget};
+use uuid::Uuid;
+
+use crate::{
+    icon,
+    layout::SavedTab,
+    loaders::raw::{RawAsset, RawChunk},
+    tabs::{property_with_asset, property_with_value, EditorTabSystem, TabState},
+    AssetRef,
+};
+
+const BYTES_PER_ROW: usize = 16;
+
+/// Shows an asset's form and chunk tree alongside a hex view of the selected chunk.
+#[derive(Default)]
+pub struct RawTab {
+    pub asset_ref: AssetRef,
+    pub handle: Handle<RawAsset>,
+    /// Offset and size of the chunk shown in the hex view; the whole file if unset.
+    pub selected: Option<(usize, usize)>,
+    /// File offset inspected as numeric values.
+    pub cursor: usize,
+    pub goto_input: String,
+    scroll_to_cursor: bool,
+}
+
+impl RawTab {
+    pub fn new(asset_ref: AssetRef, handle: Handle<RawAsset>) -> Box<Self> {
+        Box::new(Self { asset_ref, handle, ..default() })
+    }
+
+    fn select(&mut self, chunk: &RawChunk) {
+        self.selected = Some((chunk.offset, chunk.size));
+        self.cursor = chunk.offset;
+        self.scroll_to_cursor = true;
+    }
+
+    fn tree_ui(&mut self, ui: &mut egui::Ui, chunks: &[RawChunk]) {
+        for chunk in chunks {
+            let selected = self.selected == Some((chunk.offset, chunk.size));
+            let text = format!("{} @ {:#X} ({:#X})", chunk.id, chunk.offset, chunk.size);
+            if chunk.is_form {
+                let id = ui.make_persistent_id(chunk.offset);
+                CollapsingState::load_with_default_open(ui.ctx(), id, true)
+                    .show_header(ui, |ui| {
+                        if ui.selectable_label(selected, text).clicked() {
+                            self.select(chunk);
+                        }
+                    })
+                    .body(|ui| self.tree_ui(ui, &chunk.children));
+            } else if ui.selectable_label(selected, text).clicked() {
+                self.select(chunk);
+            }
+        }
+    }
+
+    fn goto_ui(&mut self, ui: &mut egui::Ui, len: usize) {
+        ui.horizontal(|ui| {
+            let response = egui::TextEdit::singleline(&mut self.goto_input)
+                .hint_text("Offset (hex)")
+                .desired_width(100.0)
+                .ui(ui);
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if submitted || ui.button("Go").clicked() {
+                let input = self.goto_input.trim();
+                match usize::from_str_radix(input.trim_start_matches("0x"), 16) {
+                    Ok(offset) if offset < len => {
+                        let in_selection = self
+                            .selected
+                            .is_none_or(|(start, size)| (start..start + size).contains(&offset));
+                        if !in_selection {
+                            self.selected = None;
+                        }
+                        self.cursor = offset;
+                        self.scroll_to_cursor = true;
+                    }
+                    _ => self.goto_input = format!("{:#X}", self.cursor),
+                }
+            }
+        });
+    }
+
+    fn inspector_ui(&self, ui: &mut egui::Ui, data: &[u8]) {
+        let bytes = &data[self.cursor.min(data.len())..];
+        let read = |n: usize| bytes.get(..n);
+        property_with_value(ui, "Offset", format!("{:#X}", self.cursor));
+        if let Some(b) = read(1) {
+            property_with_value(ui, "u8", format!("{} ({:#04X})", b[0], b[0]));
+        }
+        if let Some(b) = read(2) {
+            let value = u16::from_le_bytes(b.try_into().unwrap());
+            property_with_value(ui, "u16", format!("{value} ({value:#06X})"));
+        }
+        if let Some(b) = read(4) {
+            let value = u32::from_le_bytes(b.try_into().unwrap());
+            property_with_value(ui, "u32", format!("{value} ({value:#010X})"));
+            property_with_value(ui, "i32", format!("{}", value as i32));
+            property_with_value(ui, "f32", format!("{}", f32::from_bits(value)));
+            property_with_value(ui, "FourCC", String::from_utf8_lossy(b).into_owned());
+        }
+        if let Some(b) = read(16) {
+            let id = Uuid::from_bytes_le(b.try_into().unwrap());
+            property_with_value(ui, "GUID", id.to_string());
+        }
+    }
+
+    fn hex_ui(&mut self, ui: &mut egui::Ui, data: &[u8]) {
+        let (start, size) = self.selected.unwrap_or((0, data.len()));
+        let range = &data[start..start + size];
+        let rows = range.len().div_ceil(BYTES_PER_ROW);
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let mut area = egui::ScrollArea::vertical().id_source("hex").auto_shrink([false; 2]);
+        if std::mem::take(&mut self.scroll_to_cursor) {
+            let row = self.cursor.saturating_sub(start) / BYTES_PER_ROW;
+            area = area
+                .vertical_scroll_offset(row as f32 * (row_height + ui.spacing().item_spacing.y));
+        }
+        area.show_rows(ui, row_height, rows, |ui, visible| {
+            for row in visible {
+                let row_start = row * BYTES_PER_ROW;
+                let bytes = &range[row_start..(row_start + BYTES_PER_ROW).min(range.len())];
+                let mut hex = String::with_capacity(BYTES_PER_ROW * 3);
+                for i in 0..BYTES_PER_ROW {
+                    match bytes.get(i) {
+                        Some(b) => hex.push_str(&format!("{b:02X} ")),
+                        None => hex.push_str("   "),
+                    }
+                }
+                let ascii = bytes
+                    .iter()
+                    .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                    .collect::<String>();
+                let offset = start + row_start;
+                let mut text =
+                    egui::RichText::new(format!("{offset:08X}  {hex} {ascii}")).monospace();
+                if (offset..offset + bytes.len()).contains(&self.cursor) {
+                    text = text.background_color(ui.visuals().selection.bg_fill);
+                }
+                if egui::Label::new(text).sense(egui::Sense::click()).ui(ui).clicked() {
+                    self.cursor = offset;
+                }
+            }
+        });
+    }
+}
+
+impl EditorTabSystem for RawTab {
+    type LoadParam = ();
+    type UiParam = (SRes<AssetServer>, SRes<Assets<RawAsset>>);
+
+    fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        query: SystemParamItem<Self::UiParam>,
+        _state: &mut TabState,
+    ) {
+        let (server, assets) = query;
+
+        property_with_asset(ui, self.asset_ref);
+
+        match server.get_load_state(&self.handle) {
+            LoadState::NotLoaded | LoadState::Loading => {
+                ui.spinner();
+                return;
+            }
+            LoadState::Loaded => {}
+            LoadState::Failed => {
+                ui.colored_label(egui::Color32::RED, "Loading failed");
+                return;
+            }
+            LoadState::Unloaded => {
+                ui.colored_label(egui::Color32::RED, "Unloaded");
+                return;
+            }
+        };
+
+        let Some(asset) = assets.get(&self.handle) else {
+            return;
+        };
+        if let Some(error) = &asset.error {
+            ui.colored_label(egui::Color32::YELLOW, format!("Slicing stopped early: {error}"));
+        }
+
+        ui.horizontal_top(|ui| {
+            ui.vertical(|ui| {
+                ui.set_width(220.0);
+                if ui.selectable_label(self.selected.is_none(), "Whole file").clicked() {
+                    self.selected = None;
+                    self.cursor = 0;
+                    self.scroll_to_cursor = true;
+                }
+                egui::ScrollArea::vertical().id_source("chunks").show(ui, |ui| {
+                    self.tree_ui(ui, &asset.chunks);
+                });
+            });
+            ui.separator();
+            ui.vertical(|ui| {
+                self.goto_ui(ui, asset.data.len());
+                self.inspector_ui(ui, &asset.data);
+                ui.separator();
+                self.hex_ui(ui, &asset.data);
+            });
+        });
+    }
+
+    fn title(&self) -> egui::WidgetText {
+        format!("{} {} {}", icon::FILE_BLANK, self.asset_ref.kind, self.asset_ref.id).into()
+    }
+
+    fn id(&self) -> String { format!("raw {} {}", self.asset_ref.kind, self.asset_ref.id) }
+
+    fn saved(&self) -> Option<SavedTab> {
+        Some(SavedTab::Raw { asset: self.asset_ref.to_string() })
+    }
+}
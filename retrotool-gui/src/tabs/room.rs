@@ -2,17 +2,27 @@ use bevy::{
     core_pipeline::{clear_color::ClearColorConfig, tonemapping::Tonemapping},
     ecs::system::{lifetimeless::*, *},
     prelude::*,
-    render::{camera::Viewport, view::RenderLayers},
+    render::{
+        primitives::Aabb,
+        view::{NoFrustumCulling, RenderLayers},
+    },
 };
+use bevy_egui::EguiUserTextures;
 use bevy_mod_raycast::{Intersection, RaycastSource};
 use egui::Sense;
-use retrolib::format::room::{ConstructedProperty, ConstructedPropertyValue};
+use retrolib::format::{
+    room::{ConstructedProperty, ConstructedPropertyValue},
+    CAABox, COBBox,
+};
 
 use crate::{
     icon,
     loaders::{model::ModelAsset, room::RoomAsset, texture::TextureAsset},
     material::CustomMaterial,
-    render::{camera::ModelCamera, grid::GridSettings, TemporaryLabel},
+    render::{
+        camera::ModelCamera, convert_aabb, convert_obb, grid::GridSettings, wire_box_mesh,
+        CullStats, RenderScale, SceneTarget, TemporaryLabel,
+    },
     tabs::{modcon::ModelLabel, property_with_id, property_with_value, EditorTabSystem, TabState},
     AssetRef,
 };
@@ -21,10 +31,29 @@ pub struct RoomTab {
     pub asset_ref: AssetRef,
     pub handle: Handle<RoomAsset>,
     pub camera: ModelCamera,
+    pub target: SceneTarget,
+    pub show_docks: bool,
+    /// Skip drawing boxes outside the camera's view.
+    pub frustum_cull: bool,
+    /// Outline mesh and material shared by all drawn boxes.
+    pub wire_box: Option<(Handle<Mesh>, Handle<StandardMaterial>)>,
+    /// Outlines of the dock bounds, spawned once the room loads.
+    pub docks: Vec<Entity>,
 }
 
 impl Default for RoomTab {
-    fn default() -> Self { Self { asset_ref: default(), handle: default(), camera: default() } }
+    fn default() -> Self {
+        Self {
+            asset_ref: default(),
+            handle: default(),
+            camera: default(),
+            target: default(),
+            show_docks: true,
+            frustum_cull: true,
+            wire_box: None,
+            docks: vec![],
+        }
+    }
 }
 
 impl RoomTab {
@@ -74,6 +103,7 @@ impl EditorTabSystem for RoomTab {
         SResMut<Assets<Image>>,
         SResMut<AssetServer>,
         SResMut<Assets<RoomAsset>>,
+        SResMut<Assets<StandardMaterial>>,
     );
     type UiParam = (
         SCommands,
@@ -82,23 +112,56 @@ impl EditorTabSystem for RoomTab {
         SRes<Assets<RoomAsset>>,
         SQuery<Read<Parent>, With<Intersection<RoomRaycastSet>>>,
         SQuery<Read<ModelLabel>>,
+        SQuery<Read<ComputedVisibility>>,
+        SResMut<CullStats>,
+        SResMut<Assets<Image>>,
+        SResMut<EguiUserTextures>,
+        SRes<RenderScale>,
     );
 
     fn load(&mut self, query: SystemParamItem<Self::LoadParam>) {
         let (
-            _commands,
-            _meshes,
+            mut commands,
+            mut meshes,
             _materials,
             _models,
             _texture_assets,
             _images,
             _server,
-            _room_assets,
+            room_assets,
+            mut standard_materials,
         ) = query;
+        let (mesh, material) = self.wire_box.get_or_insert_with(|| {
+            let material = StandardMaterial { base_color: Color::YELLOW, unlit: true, ..default() };
+            (meshes.add(wire_box_mesh()), standard_materials.add(material))
+        });
+        if self.docks.is_empty() {
+            if let Some(room_asset) = room_assets.get(&self.handle) {
+                for dock in &room_asset.inner.docks {
+                    let entity = commands.spawn((
+                        PbrBundle {
+                            mesh: mesh.clone(),
+                            material: material.clone(),
+                            transform: convert_obb(&COBBox::from_aabb(&dock.bounds)),
+                            visibility: Visibility::Hidden,
+                            ..default()
+                        },
+                        // Bounds of the wire box mesh, culled by Bevy
+                        Aabb::from_min_max(Vec3::NEG_ONE, Vec3::ONE),
+                    ));
+                    self.docks.push(entity.id());
+                }
+            }
+        }
     }
 
     fn close(&mut self, query: SystemParamItem<Self::LoadParam>) -> bool {
-        let (_commands, _, _, _, _, _, _, _) = query;
+        let (mut commands, _, _, _, _, _, _, _, _) = query;
+        for entity in self.docks.drain(..) {
+            if let Some(commands) = commands.get_entity(entity) {
+                commands.despawn_recursive();
+            }
+        }
         // for model in self.models.iter().flat_map(|l| &l.loaded) {
         //     if let Some(commands) = commands.get_entity(model.entity) {
         //         commands.despawn_recursive();
@@ -113,21 +176,25 @@ impl EditorTabSystem for RoomTab {
         query: SystemParamItem<Self::UiParam>,
         state: &mut TabState,
     ) {
-        let scale = ui.ctx().pixels_per_point();
         let rect = ui.available_rect_before_wrap();
-        let left_top = rect.left_top().to_vec2() * scale;
-        let size = rect.size() * scale;
-        let viewport = Viewport {
-            physical_position: UVec2 { x: left_top.x as u32, y: left_top.y as u32 },
-            physical_size: UVec2 { x: size.x as u32, y: size.y as u32 },
-            depth: 0.0..1.0,
-        };
+        self.target.layout(ui, rect);
         let response =
             ui.interact(rect, ui.make_persistent_id("background"), Sense::click_and_drag());
         self.camera.update(&rect, &response, ui.input(|i| i.scroll_delta));
 
-        let (mut commands, _server, _models, room_assets, _intersection_query, _model_query) =
-            query;
+        let (
+            mut commands,
+            _server,
+            _models,
+            room_assets,
+            _intersection_query,
+            _model_query,
+            visibility_query,
+            mut cull_stats,
+            mut images,
+            mut egui_textures,
+            render_scale,
+        ) = query;
         let room_asset = match room_assets.get(&self.handle) {
             Some(v) => v,
             None => return,
@@ -136,10 +203,20 @@ impl EditorTabSystem for RoomTab {
         // if let Some(parent) = intersection_query.iter().next() {
         //     self.selected_model = Some(model_query.get(parent.get()).unwrap().clone());
         // }
+        let mut frame_all = response.hovered() && ui.input(|i| i.key_pressed(egui::Key::F));
         egui::Frame::group(ui.style()).show(ui, |ui| {
             egui::ScrollArea::vertical()
                 // .max_height(rect.height() * 0.25)
                 .show(ui, |ui| {
+                    if ui.button("Frame all").on_hover_text_at_pointer("Shortcut: F").clicked() {
+                        frame_all = true;
+                    }
+                    ui.checkbox(
+                        &mut self.show_docks,
+                        format!("Show dock bounds ({})", room_asset.inner.docks.len()),
+                    );
+                    ui.checkbox(&mut self.frustum_cull, "Frustum culling")
+                        .on_hover_text_at_pointer("Skip drawing bounds outside the view");
                     if !room_asset.inner.room_header.parent_room_id.is_nil() {
                         property_with_id(
                             ui,
@@ -197,6 +274,13 @@ impl EditorTabSystem for RoomTab {
                             room_asset.inner.room_header.path_find_area_id.into_inner(),
                         );
                     }
+                    for stage in &room_asset.inner.room_header.production_work_stages {
+                        property_with_value(
+                            ui,
+                            &format!("Work Stage {}", stage.discipline),
+                            format!("{}", stage.stage),
+                        );
+                    }
                     if let Some(light_map) = &room_asset.inner.baked_lighting.light_map {
                         ui.collapsing("Light map data", |ui| {
                             property_with_id(ui, "Texture ID", light_map.txtr_id.into_inner());
@@ -204,13 +288,26 @@ impl EditorTabSystem for RoomTab {
                             for id in &light_map.ids {
                                 property_with_value(ui, "Unk ID", format!("{}", id));
                             }
-                            for lookup in &light_map.atlas_lookups {
-                                property_with_value(
-                                    ui,
-                                    "Atlas lookup",
-                                    format!("{:?}", lookup.0.to_array()),
-                                );
-                            }
+                            ui.collapsing(
+                                format!("Atlas lookups ({})", light_map.atlas_lookups.len()),
+                                |ui| {
+                                    egui::Grid::new("atlas_lookups").striped(true).show(ui, |ui| {
+                                        for label in ["#", "X", "Y", "Z", "W"] {
+                                            ui.strong(label);
+                                        }
+                                        ui.end_row();
+                                        for (idx, lookup) in
+                                            light_map.atlas_lookups.iter().enumerate()
+                                        {
+                                            ui.label(idx.to_string());
+                                            for value in lookup.0.to_array() {
+                                                ui.label(format!("{value:.4}"));
+                                            }
+                                            ui.end_row();
+                                        }
+                                    });
+                                },
+                            );
                         });
                     }
                     if let Some(light_probe) = &room_asset.inner.baked_lighting.light_probe {
@@ -257,11 +354,22 @@ impl EditorTabSystem for RoomTab {
                 });
         });
 
+        if frame_all {
+            // The room header carries no bounds, so frame the docks
+            let bounds = room_asset
+                .inner
+                .docks
+                .iter()
+                .fold(CAABox::default(), |bounds, dock| bounds.union(&dock.bounds));
+            if !bounds.is_empty() {
+                self.camera.frame(&convert_aabb(&bounds));
+            }
+        }
+
         let camera = Camera {
-            viewport: Some(viewport),
             order: state.render_layer as isize,
             // hdr: true,
-            ..default()
+            ..self.target.camera(ui, render_scale.0, &mut images, &mut egui_textures)
         };
         let mut entity = commands.spawn((
             Camera3dBundle {
@@ -273,7 +381,7 @@ impl EditorTabSystem for RoomTab {
             },
             // BloomSettings::default(),
             GridSettings {
-                clear_color: if state.render_layer == 0 {
+                clear_color: if state.render_layer == 0 || self.target.is_offscreen() {
                     ClearColorConfig::Default
                 } else {
                     ClearColorConfig::None
@@ -289,11 +397,9 @@ impl EditorTabSystem for RoomTab {
         //     });
         // }
         if response.hovered() {
-            if let Some(pos) = ui.input(|i| {
-                i.pointer.hover_pos().map(|pos| Vec2::new(pos.x, i.screen_rect.height() - pos.y))
-            }) {
+            if let Some(pos) = ui.input(|i| i.pointer.hover_pos()) {
                 entity.insert(RaycastSource::<RoomRaycastSet>::new_screenspace(
-                    pos,
+                    self.target.cursor_position(ui, pos),
                     &camera,
                     &GlobalTransform::default(),
                 ));
@@ -313,6 +419,29 @@ impl EditorTabSystem for RoomTab {
             // ));
         }
 
+        for &entity in &self.docks {
+            if self.show_docks {
+                // Drawn last frame, unless Bevy culled it by its bounds
+                if visibility_query.get(entity).is_ok_and(|v| v.is_visible_in_view()) {
+                    cull_stats.drawn += 1;
+                } else {
+                    cull_stats.culled += 1;
+                }
+            }
+            let Some(mut commands) = commands.get_entity(entity) else {
+                continue;
+            };
+            commands.insert((
+                if self.show_docks { Visibility::Visible } else { Visibility::Hidden },
+                RenderLayers::layer(state.render_layer),
+            ));
+            if self.frustum_cull {
+                commands.remove::<NoFrustumCulling>();
+            } else {
+                commands.insert(NoFrustumCulling);
+            }
+        }
+
         // for info in &self.models {
         //     for model in &info.loaded {
         //         if let Some(mut commands) = commands.get_entity(model.entity) {
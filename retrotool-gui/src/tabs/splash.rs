@@ -7,6 +7,7 @@ use egui::Widget;
 
 use crate::{
     icon,
+    layout::SavedTab,
     tabs::{texture::UiTexture, EditorTabSystem, TabState},
 };
 
@@ -71,4 +72,6 @@ impl EditorTabSystem for SplashTab {
     fn title(&self) -> egui::WidgetText { format!("{} Splash", icon::HOME).into() }
 
     fn id(&self) -> String { "splash".into() }
+
+    fn saved(&self) -> Option<SavedTab> { Some(SavedTab::Splash) }
 }
@@ -15,6 +15,7 @@ use strum::{EnumMessage, IntoEnumIterator};
 
 use crate::{
     icon,
+    layout::SavedTab,
     tabs::{EditorTabSystem, TabState},
 };
 
@@ -176,6 +177,8 @@ impl EditorTabSystem for TemplatesTab {
     fn title(&self) -> egui::WidgetText { format!("{} Templates", icon::EDITMODE_HLT).into() }
 
     fn id(&self) -> String { "Templates".into() }
+
+    fn saved(&self) -> Option<SavedTab> { Some(SavedTab::Templates) }
 }
 
 fn enum_ui<Type, Discriminants>(
@@ -1,3 +1,6 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, ensure, Context, Result};
 use bevy::{
     asset::LoadState,
     ecs::system::{lifetimeless::*, *},
@@ -5,9 +8,18 @@ use bevy::{
 };
 use bevy_egui::EguiUserTextures;
 use egui::Widget;
-use retrolib::format::txtr::ETextureType;
+use image::{DynamicImage, Rgba, RgbaImage};
+use retrolib::format::txtr::{
+    decode_slice, decompress_image, slice_texture, ETextureType, K_FORM_TXTR,
+};
+use uuid::Uuid;
 
-use crate::{icon, loaders::texture::TextureAsset, tabs::EditorTabSystem, AssetRef, TabState};
+use crate::{
+    icon,
+    loaders::{texture::TextureAsset, LoadErrors},
+    tabs::{load_error_ui, property_with_asset, EditorTabSystem},
+    AssetRef, TabState,
+};
 
 pub struct LoadedTexture {
     pub width: u32,
@@ -22,11 +34,63 @@ pub struct TextureTab {
     pub loaded_textures: Vec<LoadedTexture>,
     pub selected_mip: usize,
     pub v_flip: bool,
+    pub background: TextureBackground,
+    pub background_color: egui::Color32,
+    /// Display the texture with the opposite colorspace to the one it declares.
+    pub swap_colorspace: bool,
+    pub swapped_mip: Option<SwappedMip>,
+    pub compare_input: String,
+    pub compare_mode: CompareMode,
+    pub compare: CompareState,
+}
+
+/// A mip re-uploaded with the opposite colorspace.
+pub struct SwappedMip {
+    pub mip: usize,
+    pub layers: Result<Vec<UiTexture>, String>,
+}
+
+/// What to draw behind the texture, to make transparent regions visible.
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+pub enum TextureBackground {
+    #[default]
+    Checkerboard,
+    Solid,
+    None,
+}
+
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+pub enum CompareMode {
+    #[default]
+    SideBySide,
+    Difference,
+}
+
+pub enum CompareSource {
+    File(PathBuf),
+    Asset(Handle<TextureAsset>),
+}
+
+#[derive(Default)]
+pub enum CompareState {
+    #[default]
+    None,
+    Pending(CompareSource),
+    Loaded(TextureCompare),
+    Failed(String),
+}
+
+pub struct TextureCompare {
+    pub source: String,
+    pub other: UiTexture,
+    pub diff: UiTexture,
+    pub max_error: u8,
+    pub psnr: f64,
 }
 
 impl TextureTab {
     pub fn new(asset_ref: AssetRef, handle: Handle<TextureAsset>) -> Box<Self> {
-        Box::new(Self { asset_ref, handle, ..default() })
+        Box::new(Self { asset_ref, handle, background_color: egui::Color32::GRAY, ..default() })
     }
 }
 
@@ -38,7 +102,6 @@ pub struct UiTexture {
 }
 
 impl UiTexture {
-    #[allow(dead_code)]
     pub fn new(image: Image, images: &mut Assets<Image>, textures: &mut EguiUserTextures) -> Self {
         let width = image.texture_descriptor.size.width;
         let height = image.texture_descriptor.size.height;
@@ -61,7 +124,6 @@ impl UiTexture {
         Some(Self { _image: handle, texture_id: textures.add_image(weak_handle), width, height })
     }
 
-    #[allow(dead_code)]
     pub fn image(&self) -> egui::Image {
         egui::Image::new(self.texture_id, egui::Vec2::new(self.width as f32, self.height as f32))
     }
@@ -78,20 +140,128 @@ impl UiTexture {
     }
 }
 
+impl TextureTab {
+    fn paint_background(&self, ui: &egui::Ui, rect: egui::Rect) {
+        const CELL_SIZE: f32 = 8.0;
+        let painter = ui.painter();
+        match self.background {
+            TextureBackground::Checkerboard => {
+                // Only paint the cells that are visible
+                let visible = rect.intersect(ui.clip_rect());
+                if !visible.is_positive() {
+                    return;
+                }
+                painter.rect_filled(rect, 0.0, egui::Color32::from_gray(0x99));
+                let start = ((visible.min - rect.min) / CELL_SIZE).floor();
+                let end = ((visible.max - rect.min) / CELL_SIZE).ceil();
+                for y in start.y as u32..end.y as u32 {
+                    for x in start.x as u32..end.x as u32 {
+                        if (x + y) % 2 == 0 {
+                            continue;
+                        }
+                        let min = rect.min + egui::Vec2::new(x as f32, y as f32) * CELL_SIZE;
+                        let cell = egui::Rect::from_min_size(min, egui::Vec2::splat(CELL_SIZE));
+                        painter.rect_filled(
+                            cell.intersect(rect),
+                            0.0,
+                            egui::Color32::from_gray(0x66),
+                        );
+                    }
+                }
+            }
+            TextureBackground::Solid => painter.rect_filled(rect, 0.0, self.background_color),
+            TextureBackground::None => {}
+        }
+    }
+}
+
 impl EditorTabSystem for TextureTab {
-    type LoadParam =
-        (SRes<Assets<TextureAsset>>, SResMut<Assets<Image>>, SResMut<EguiUserTextures>);
-    type UiParam = (SRes<AssetServer>, SRes<Assets<TextureAsset>>);
+    type LoadParam = (
+        SRes<AssetServer>,
+        SRes<Assets<TextureAsset>>,
+        SResMut<Assets<Image>>,
+        SResMut<EguiUserTextures>,
+    );
+    type UiParam = (SRes<AssetServer>, SRes<Assets<TextureAsset>>, SRes<LoadErrors>);
 
     fn load(&mut self, query: SystemParamItem<Self::LoadParam>) {
-        if !self.loaded_textures.is_empty() {
+        let (server, textures, mut images, mut egui_textures) = query;
+        let Some(asset) = textures.get(&self.handle) else {
             return;
+        };
+
+        if let CompareState::Pending(source) = &self.compare {
+            let other = match source {
+                CompareSource::File(path) => Some(
+                    image::open(path)
+                        .map(|image| (path.display().to_string(), image))
+                        .map_err(|e| anyhow!("Failed to open {}: {e}", path.display())),
+                ),
+                CompareSource::Asset(handle) => match server.get_load_state(handle) {
+                    LoadState::Loaded => textures.get(handle).map(|other| {
+                        decode_texture(other)
+                            .map(|image| (format!("{}", other.asset_ref.id), image))
+                    }),
+                    LoadState::Failed | LoadState::Unloaded => {
+                        Some(Err(anyhow!("Failed to load comparison texture")))
+                    }
+                    LoadState::NotLoaded | LoadState::Loading => None,
+                },
+            };
+            if let Some(result) = other {
+                self.compare = match result.and_then(|(source, other)| {
+                    let is_srgb = asset.inner.head.format.is_srgb();
+                    let original = decode_texture(asset)?.to_rgba8();
+                    let other = other.to_rgba8();
+                    let (diff, max_error, psnr) = compare_images(&original, &other)?;
+                    Ok(TextureCompare {
+                        source,
+                        other: UiTexture::new(
+                            Image::from_dynamic(DynamicImage::ImageRgba8(other), is_srgb),
+                            &mut images,
+                            &mut egui_textures,
+                        ),
+                        diff: UiTexture::new(
+                            Image::from_dynamic(DynamicImage::ImageRgba8(diff), true),
+                            &mut images,
+                            &mut egui_textures,
+                        ),
+                        max_error,
+                        psnr,
+                    })
+                }) {
+                    Ok(compare) => CompareState::Loaded(compare),
+                    Err(e) => CompareState::Failed(format!("{e:#}")),
+                };
+            }
         }
 
-        let (textures, images, mut egui_textures) = query;
-        let Some(asset) = textures.get(&self.handle) else {
+        if !self.swap_colorspace {
+            self.swapped_mip = None;
+        } else if self.swapped_mip.as_ref().is_none_or(|s| s.mip != self.selected_mip) {
+            // Reinterpreting the decoded bytes applies or removes the sRGB transfer curve
+            let is_srgb = asset.inner.head.format.is_srgb();
+            let layers = decode_mip(asset, self.selected_mip)
+                .map(|layers| {
+                    layers
+                        .into_iter()
+                        .map(|image| {
+                            let image = DynamicImage::ImageRgba8(image.to_rgba8());
+                            UiTexture::new(
+                                Image::from_dynamic(image, !is_srgb),
+                                &mut images,
+                                &mut egui_textures,
+                            )
+                        })
+                        .collect()
+                })
+                .map_err(|e| format!("{e:#}"));
+            self.swapped_mip = Some(SwappedMip { mip: self.selected_mip, layers });
+        }
+
+        if !self.loaded_textures.is_empty() {
             return;
-        };
+        }
         self.loaded_textures.reserve_exact(asset.slices.len());
         for mip in &asset.slices {
             let mut texture_ids = Vec::with_capacity(mip.len());
@@ -117,9 +287,10 @@ impl EditorTabSystem for TextureTab {
         query: SystemParamItem<Self::UiParam>,
         _state: &mut TabState,
     ) {
-        let (server, textures) = query;
+        let (server, textures, errors) = query;
+        let mut compare_source = None;
 
-        ui.label(format!("{} {}", self.asset_ref.kind, self.asset_ref.id));
+        property_with_asset(ui, self.asset_ref);
 
         match server.get_load_state(&self.handle) {
             LoadState::NotLoaded | LoadState::Loading => {
@@ -128,7 +299,8 @@ impl EditorTabSystem for TextureTab {
             }
             LoadState::Loaded => {}
             LoadState::Failed => {
-                ui.colored_label(egui::Color32::RED, "Loading failed");
+                let error = server.get_handle_path(&self.handle).and_then(|p| errors.get(p.path()));
+                load_error_ui(ui, self.asset_ref, error.as_ref());
                 return;
             }
             LoadState::Unloaded => {
@@ -148,6 +320,25 @@ impl EditorTabSystem for TextureTab {
                 txtr.inner.head.mip_sizes.len()
             ));
             ui.checkbox(&mut self.v_flip, "Flip texture vertically");
+            ui.horizontal(|ui| {
+                let is_srgb = txtr.inner.head.format.is_srgb();
+                ui.label(format!("Colorspace: {}", if is_srgb { "sRGB" } else { "Linear" }));
+                let text = if is_srgb { "View as linear" } else { "View as sRGB" };
+                ui.checkbox(&mut self.swap_colorspace, text);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Background:");
+                ui.radio_value(
+                    &mut self.background,
+                    TextureBackground::Checkerboard,
+                    "Checkerboard",
+                );
+                ui.radio_value(&mut self.background, TextureBackground::Solid, "Solid");
+                ui.radio_value(&mut self.background, TextureBackground::None, "None");
+                if self.background == TextureBackground::Solid {
+                    ui.color_edit_button_srgba(&mut self.background_color);
+                }
+            });
             if self.loaded_textures.len() > 1 {
                 egui::Slider::new(&mut self.selected_mip, 0..=self.loaded_textures.len() - 1)
                     .text("Mipmap")
@@ -163,17 +354,33 @@ impl EditorTabSystem for TextureTab {
                     mip.texture_ids.len(),
                 ));
             }
+            let mut texture_ids = mip.texture_ids.clone();
+            match self.swapped_mip.as_ref().filter(|s| s.mip == self.selected_mip) {
+                Some(SwappedMip { layers: Ok(layers), .. })
+                    if layers.len() == texture_ids.len() =>
+                {
+                    texture_ids = layers.iter().map(|t| t.texture_id).collect();
+                }
+                Some(SwappedMip { layers: Err(e), .. }) => {
+                    ui.colored_label(egui::Color32::RED, format!("Colorspace swap failed: {e}"));
+                }
+                _ => {}
+            }
             let size = egui::Vec2::new(mip.width as f32, mip.height as f32);
             let draw_image =
                 |ui: &mut egui::Ui, rect: &egui::Rect, i: usize, x: u32, y: u32, flip: bool| {
                     let min = rect.min + size * egui::Vec2::new(x as f32, y as f32);
                     let y_range = if flip { 1.0..=0.0 } else { 0.0..=1.0 };
-                    egui::widgets::Image::new(mip.texture_ids[i], size)
+                    egui::widgets::Image::new(texture_ids[i], size)
                         .uv(egui::Rect::from_x_y_ranges(0.0..=1.0, y_range))
                         .paint_at(ui, egui::Rect::from_min_size(min, size));
                 };
             if txtr.inner.head.kind == ETextureType::Cube && mip.texture_ids.len() == 6 {
                 let (_, rect) = ui.allocate_space(size * egui::Vec2::new(4.0, 3.0));
+                for (x, y) in [(1, 0), (0, 1), (1, 1), (2, 1), (3, 1), (1, 2)] {
+                    let min = rect.min + size * egui::Vec2::new(x as f32, y as f32);
+                    self.paint_background(ui, egui::Rect::from_min_size(min, size));
+                }
                 draw_image(ui, &rect, 2, 1, 0, self.v_flip);
                 draw_image(ui, &rect, 1, 0, 1, self.v_flip);
                 draw_image(ui, &rect, 4, 1, 1, self.v_flip);
@@ -183,10 +390,84 @@ impl EditorTabSystem for TextureTab {
             } else {
                 let (_, rect) =
                     ui.allocate_space(size * egui::Vec2::new(mip.texture_ids.len() as f32, 1.0));
+                self.paint_background(ui, rect);
                 for i in 0..mip.texture_ids.len() {
                     draw_image(ui, &rect, i, i as u32, 0, self.v_flip);
                 }
             }
+
+            egui::CollapsingHeader::new("Compare").show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    egui::TextEdit::singleline(&mut self.compare_input)
+                        .hint_text("File path or asset ID")
+                        .ui(ui);
+                    if ui.button("Load").clicked() {
+                        let input = self.compare_input.trim();
+                        compare_source = Some(
+                            match Uuid::try_parse(
+                                input.trim_start_matches('{').trim_end_matches('}'),
+                            ) {
+                                Ok(id) => CompareSource::Asset(
+                                    server.load(format!("{}.{}", id, K_FORM_TXTR)),
+                                ),
+                                Err(_) => CompareSource::File(PathBuf::from(input)),
+                            },
+                        );
+                    }
+                });
+                ui.label("Compares against mip 0, layer 0.");
+                match &self.compare {
+                    CompareState::None => {}
+                    CompareState::Pending(_) => {
+                        ui.spinner();
+                    }
+                    CompareState::Failed(e) => {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                    CompareState::Loaded(compare) => {
+                        ui.label(format!("Source: {}", compare.source));
+                        ui.label(format!("Max error: {}", compare.max_error));
+                        if compare.psnr.is_finite() {
+                            ui.label(format!("PSNR: {:.2} dB", compare.psnr));
+                        } else {
+                            ui.label("PSNR: ∞ (identical)");
+                        }
+                        ui.horizontal(|ui| {
+                            ui.radio_value(
+                                &mut self.compare_mode,
+                                CompareMode::SideBySide,
+                                "Side by side",
+                            );
+                            ui.radio_value(
+                                &mut self.compare_mode,
+                                CompareMode::Difference,
+                                "Difference",
+                            );
+                        });
+                        let original = &self.loaded_textures[0];
+                        let size = egui::Vec2::new(original.width as f32, original.height as f32);
+                        let y_range = if self.v_flip { 1.0..=0.0 } else { 0.0..=1.0 };
+                        let uv = egui::Rect::from_x_y_ranges(0.0..=1.0, y_range);
+                        match self.compare_mode {
+                            CompareMode::SideBySide => {
+                                ui.horizontal(|ui| {
+                                    egui::widgets::Image::new(original.texture_ids[0], size)
+                                        .uv(uv)
+                                        .ui(ui);
+                                    compare.other.image().uv(uv).ui(ui);
+                                });
+                            }
+                            CompareMode::Difference => {
+                                compare.diff.image().uv(uv).ui(ui);
+                            }
+                        }
+                    }
+                }
+            });
+        }
+
+        if let Some(source) = compare_source {
+            self.compare = CompareState::Pending(source);
         }
     }
 
@@ -198,3 +479,66 @@ impl EditorTabSystem for TextureTab {
 
     fn asset(&self) -> Option<AssetRef> { Some(self.asset_ref) }
 }
+
+/// Decodes mip 0, layer 0 of a texture.
+fn decode_texture(asset: &TextureAsset) -> Result<DynamicImage> { decode_slice(&asset.inner, 0, 0) }
+
+/// Decodes every layer of a mip.
+fn decode_mip(asset: &TextureAsset, mip: usize) -> Result<Vec<DynamicImage>> {
+    let slices = slice_texture(&asset.inner)?;
+    let Some(layers) = slices.get(mip) else {
+        return Err(anyhow!("Texture has no mip {mip}"));
+    };
+    layers
+        .iter()
+        .enumerate()
+        .map(|(layer, slice)| {
+            decompress_image(
+                asset.inner.head.format,
+                slice.width,
+                slice.height,
+                &asset.inner.data[slice.data_range.clone()],
+            )
+            .with_context(|| format!("Failed to decode mip {mip} layer {layer}"))
+        })
+        .collect()
+}
+
+/// Computes an absolute difference heatmap, the max per-channel error and the PSNR.
+fn compare_images(a: &RgbaImage, b: &RgbaImage) -> Result<(RgbaImage, u8, f64)> {
+    ensure!(
+        a.dimensions() == b.dimensions(),
+        "Size mismatch: {}x{} != {}x{}",
+        a.width(),
+        a.height(),
+        b.width(),
+        b.height()
+    );
+    let mut max_error = 0u8;
+    let mut sum_sq = 0u64;
+    let errors = a
+        .pixels()
+        .zip(b.pixels())
+        .map(|(pa, pb)| {
+            let mut error = 0u8;
+            for c in 0..4 {
+                let d = pa[c].abs_diff(pb[c]);
+                sum_sq += d as u64 * d as u64;
+                error = error.max(d);
+            }
+            max_error = max_error.max(error);
+            error
+        })
+        .collect::<Vec<u8>>();
+    // Black -> red -> yellow -> white, normalized to the max error
+    let scale = if max_error == 0 { 0.0 } else { 1.0 / max_error as f32 };
+    let mut errors = errors.into_iter();
+    let diff = RgbaImage::from_fn(a.width(), a.height(), |_, _| {
+        let t = errors.next().unwrap_or_default() as f32 * scale * 3.0;
+        let channel = |v: f32| (v.clamp(0.0, 1.0) * 255.0) as u8;
+        Rgba([channel(t), channel(t - 1.0), channel(t - 2.0), 255])
+    });
+    let mse = sum_sq as f64 / (a.width() as f64 * a.height() as f64 * 4.0);
+    let psnr = if mse == 0.0 { f64::INFINITY } else { 10.0 * (255.0 * 255.0 / mse).log10() };
+    Ok((diff, max_error, psnr))
+}
@@ -0,0 +1,117 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::loaders::package::PackageDirectory;
+
+/// Changes to a file within this window are coalesced into a single reload.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watches the packages the GUI opened and reports when they change on disk.
+#[derive(Default, Resource)]
+pub struct FileWatcher {
+    pub enabled: bool,
+    watcher: Option<RecommendedWatcher>,
+    /// Watched directories. Watching the parent directory catches files that are
+    /// replaced rather than written in place.
+    dirs: HashSet<PathBuf>,
+    /// Canonical path to the path the package was loaded from.
+    files: HashMap<PathBuf, PathBuf>,
+    /// Time of the last change per file, updated from the watcher thread.
+    pending: Arc<Mutex<HashMap<PathBuf, Instant>>>,
+    /// Packages that were reloaded since the last call to `take_changed`.
+    changed: Vec<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn take_changed(&mut self) -> Vec<PathBuf> { std::mem::take(&mut self.changed) }
+
+    fn start(&mut self) -> notify::Result<()> {
+        let pending = self.pending.clone();
+        self.watcher =
+            Some(notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+                match result {
+                    Ok(event)
+                        if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) =>
+                    {
+                        let now = Instant::now();
+                        let mut pending = pending.lock().unwrap();
+                        for path in event.paths {
+                            pending.insert(path, now);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::warn!("File watcher error: {e}"),
+                }
+            })?);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        self.watcher = None;
+        self.dirs.clear();
+        self.files.clear();
+        self.pending.lock().unwrap().clear();
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf { path.canonicalize().unwrap_or_else(|_| path.to_owned()) }
+
+pub fn file_watcher_system(
+    mut state: ResMut<FileWatcher>,
+    packages: Res<Assets<PackageDirectory>>,
+    server: Res<AssetServer>,
+) {
+    if !state.enabled {
+        if state.watcher.is_some() {
+            state.stop();
+        }
+        return;
+    }
+    if state.watcher.is_none() {
+        if let Err(e) = state.start() {
+            log::error!("Failed to start file watcher: {e}");
+            state.enabled = false;
+            return;
+        }
+    }
+
+    let FileWatcher { watcher: Some(watcher), dirs, files, pending, changed, .. } = state.as_mut()
+    else {
+        return;
+    };
+    for (_, package) in packages.iter() {
+        let path = canonical(&package.path);
+        if files.contains_key(&path) {
+            continue;
+        }
+        if let Some(dir) = path.parent() {
+            if !dirs.contains(dir) {
+                if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                    log::warn!("Failed to watch '{}': {e}", dir.display());
+                }
+                dirs.insert(dir.to_owned());
+            }
+        }
+        files.insert(path, package.path.clone());
+    }
+
+    let now = Instant::now();
+    pending.lock().unwrap().retain(|path, time| {
+        if now.duration_since(*time) < DEBOUNCE {
+            return true;
+        }
+        if let Some(package_path) = files.get(&canonical(path)) {
+            log::info!("Reloading {}", package_path.display());
+            server.reload_asset(package_path.as_path());
+            changed.push(package_path.clone());
+        }
+        false
+    });
+}
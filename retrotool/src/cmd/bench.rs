@@ -0,0 +1,111 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Instant};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use argh::FromArgs;
+use retrolib::{
+    format::pack::{decompress_package, Package},
+    util::{compression::decompress_into, file::map_file},
+};
+use zerocopy::LittleEndian;
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// benchmarks asset decompression over the compressed assets in PAK files
+#[argh(subcommand, name = "bench")]
+pub struct Args {
+    #[argh(positional)]
+    /// input PAKs
+    inputs: Vec<PathBuf>,
+    #[argh(option, default = "3")]
+    /// number of passes over the collected assets (default: 3)
+    iterations: u32,
+}
+
+/// A compressed asset copied out of a package.
+struct Sample {
+    name: String,
+    compressed: Vec<u8>,
+    decompressed_size: usize,
+}
+
+#[derive(Default)]
+struct ModeStats {
+    count: usize,
+    compressed_bytes: u64,
+    decompressed_bytes: u64,
+    seconds: f64,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    ensure!(!args.inputs.is_empty(), "No input PAKs given");
+    ensure!(args.iterations > 0, "Iterations must be non-zero");
+
+    let mut samples = Vec::new();
+    for path in &args.inputs {
+        let data = map_file(path)?;
+        let data = decompress_package(&data)?;
+        let entries = Package::<LittleEndian>::read_sparse(&data)
+            .with_context(|| format!("Failed to read package '{}'", path.display()))?;
+        for entry in entries.into_iter().filter(|e| e.size != e.decompressed_size) {
+            let name = format!("{} {} {}", path.display(), entry.kind, entry.id);
+            let start = entry.offset as usize;
+            let compressed =
+                data.get(start..start.saturating_add(entry.size as usize)).ok_or_else(|| {
+                    anyhow!(
+                        "{name}: data {:#X}+{:#X} is out of bounds (package size {:#X})",
+                        entry.offset,
+                        entry.size,
+                        data.len()
+                    )
+                })?;
+            samples.push(Sample {
+                name,
+                compressed: compressed.to_vec(),
+                decompressed_size: entry.decompressed_size as usize,
+            });
+        }
+    }
+    ensure!(!samples.is_empty(), "No compressed assets found");
+    log::info!("Collected {} compressed assets", samples.len());
+
+    let max_size = samples.iter().map(|s| s.decompressed_size).max().unwrap_or_default();
+    let mut out = vec![0u8; max_size];
+    let mut stats = BTreeMap::<u32, ModeStats>::new();
+    for _ in 0..args.iterations {
+        for sample in &samples {
+            let out = &mut out[..sample.decompressed_size];
+            let start = Instant::now();
+            let mode = decompress_into(&sample.compressed, out)
+                .with_context(|| format!("Failed to decompress {}", sample.name))?;
+            let elapsed = start.elapsed().as_secs_f64();
+            let entry = stats.entry(mode).or_default();
+            entry.count += 1;
+            entry.compressed_bytes += sample.compressed.len() as u64;
+            entry.decompressed_bytes += sample.decompressed_size as u64;
+            entry.seconds += elapsed;
+        }
+    }
+
+    let mut total = ModeStats::default();
+    for (mode, stats) in &stats {
+        print_stats(&format!("Mode {mode}"), stats, args.iterations);
+        total.count += stats.count;
+        total.compressed_bytes += stats.compressed_bytes;
+        total.decompressed_bytes += stats.decompressed_bytes;
+        total.seconds += stats.seconds;
+    }
+    print_stats("Total", &total, args.iterations);
+    Ok(())
+}
+
+fn print_stats(label: &str, stats: &ModeStats, iterations: u32) {
+    const MB: f64 = 1024.0 * 1024.0;
+    let throughput = stats.decompressed_bytes as f64 / MB / stats.seconds.max(f64::EPSILON);
+    println!(
+        "{label}: {} assets, {:.2} MB -> {:.2} MB, {:.3}s, {:.2} MB/s",
+        stats.count / iterations as usize,
+        stats.compressed_bytes as f64 / MB / iterations as f64,
+        stats.decompressed_bytes as f64 / MB / iterations as f64,
+        stats.seconds,
+        throughput
+    );
+}
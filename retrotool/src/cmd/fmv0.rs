@@ -8,14 +8,15 @@ use std::{
 use anyhow::{ensure, Context, Result};
 use argh::FromArgs;
 use retrolib::{
-    format::{foot::K_FORM_FOOT, rfrm::FormDescriptor, FourCC},
+    format::{
+        fmv0::{slice_video, K_FORM_FMV0},
+        foot::K_FORM_FOOT,
+        rfrm::FormDescriptor,
+    },
     util::file::map_file,
 };
 use zerocopy::LittleEndian;
 
-// Video
-pub const K_FORM_FMV0: FourCC = FourCC(*b"FMV0");
-
 #[derive(FromArgs, PartialEq, Debug)]
 /// process FMV0 files
 #[argh(subcommand, name = "fmv0")]
@@ -64,9 +65,8 @@ pub fn run(args: Args) -> Result<()> {
 
 fn extract(args: ExtractArgs) -> Result<()> {
     let data = map_file(&args.input)?;
-    let (fmv0_desc, chunk_data, _) = FormDescriptor::<LittleEndian>::slice(&data)?;
-    ensure!(fmv0_desc.id == K_FORM_FMV0);
-    fs::write(&args.output, chunk_data)
+    let video = slice_video::<LittleEndian>(&data)?;
+    fs::write(&args.output, video)
         .with_context(|| format!("Failed to write output file '{}'", args.output.display()))?;
     Ok(())
 }
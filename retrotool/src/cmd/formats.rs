@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use argh::FromArgs;
+use retrolib::{
+    format::registry::{parse_form, FORMATS},
+    util::file::map_file,
+};
+use serde_json::json;
+
+use crate::output;
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// lists the supported form types, or identifies and parses the given files
+#[argh(subcommand, name = "formats")]
+pub struct Args {
+    #[argh(positional)]
+    /// files to identify
+    inputs: Vec<PathBuf>,
+    #[argh(switch)]
+    /// output as JSON
+    json: bool,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    if !args.inputs.is_empty() {
+        return identify(&args);
+    }
+    if args.json {
+        let value = FORMATS
+            .iter()
+            .map(|info| {
+                json!({
+                    "id": info.id.to_string(),
+                    "name": info.name,
+                    "parse": info.parse.is_some(),
+                    "commands": info.commands,
+                })
+            })
+            .collect::<Vec<_>>();
+        output::print_json(&value)?;
+    } else {
+        for info in FORMATS {
+            let note = if info.parse.is_some() { "" } else { " (not parsed)" };
+            println!("{} {}{note}", info.id, info.name);
+            if !info.commands.is_empty() {
+                println!("  {}", info.commands.join(", "));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn identify(args: &Args) -> Result<()> {
+    let mut values = Vec::with_capacity(args.inputs.len());
+    for path in &args.inputs {
+        let result = map_file(path).and_then(|data| parse_form(&data));
+        if args.json {
+            values.push(match &result {
+                Ok(info) => json!({
+                    "path": path.display().to_string(),
+                    "id": info.id.to_string(),
+                    "name": info.name,
+                }),
+                Err(e) => json!({
+                    "path": path.display().to_string(),
+                    "error": format!("{e:#}"),
+                }),
+            });
+        } else {
+            match &result {
+                Ok(info) => println!("{}: {} {}", path.display(), info.id, info.name),
+                Err(e) => println!("{}: {e:#}", path.display()),
+            }
+        }
+    }
+    if args.json {
+        output::print_json(&values)?;
+    }
+    Ok(())
+}
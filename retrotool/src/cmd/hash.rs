@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use argh::FromArgs;
+use retrolib::{format::rfrm::walk_chunks, util::file::map_file};
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use zerocopy::LittleEndian;
+
+use crate::output;
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// computes CRC32 and SHA-1 hashes of a file
+#[argh(subcommand, name = "hash")]
+pub struct Args {
+    #[argh(positional)]
+    /// input file
+    input: PathBuf,
+    #[argh(switch)]
+    /// hash each RFRM form and chunk separately
+    per_chunk: bool,
+    #[argh(switch)]
+    /// output as JSON
+    json: bool,
+}
+
+struct HashEntry {
+    path: String,
+    size: usize,
+    crc32: u32,
+    sha1: String,
+}
+
+impl HashEntry {
+    fn new(path: String, data: &[u8]) -> Self {
+        Self {
+            path,
+            size: data.len(),
+            crc32: crc32fast::hash(data),
+            sha1: format!("{:x}", Sha1::digest(data)),
+        }
+    }
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let data = map_file(&args.input)?;
+    let mut entries = vec![HashEntry::new(args.input.display().to_string(), &data)];
+    if args.per_chunk {
+        walk_chunks::<LittleEndian, _>(&data, |path, _, data| {
+            entries.push(HashEntry::new(path.to_string(), data));
+            Ok(())
+        })
+        .with_context(|| format!("Failed to slice chunks in '{}'", args.input.display()))?;
+    }
+
+    if args.json {
+        let value = entries
+            .iter()
+            .map(|e| {
+                json!({
+                    "path": e.path,
+                    "size": e.size,
+                    "crc32": format!("{:08x}", e.crc32),
+                    "sha1": e.sha1,
+                })
+            })
+            .collect::<Vec<_>>();
+        output::print_json(&value)?;
+    } else {
+        for e in &entries {
+            println!("{:08x}  {}  {:#10X}  {}", e.crc32, e.sha1, e.size, e.path);
+        }
+    }
+    Ok(())
+}
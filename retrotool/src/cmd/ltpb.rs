@@ -0,0 +1,190 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use argh::FromArgs;
+use image::{imageops, DynamicImage, ImageBuffer, Pixel};
+use retrolib::{
+    format::{
+        foot::locate_meta,
+        ltpb::LightProbeData,
+        txtr::{decompress_image, slice_texture, ETextureType, TextureData},
+    },
+    util::{
+        cubemap::{cube_cross, cube_equirect, cube_sh9, sh9_irradiance_cube, CrossLayout},
+        file::map_file,
+    },
+};
+use zerocopy::LittleEndian;
+
+use crate::cmd::txtr::{is_hdr, save_image, to_ldr, ImageOutput, Tonemap};
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// process LTPB files
+#[argh(subcommand, name = "ltpb")]
+pub struct Args {
+    #[argh(subcommand)]
+    command: SubCommand,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum SubCommand {
+    Preview(PreviewArgs),
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// writes each texture of a light probe as a previewable image
+#[argh(subcommand, name = "preview")]
+pub struct PreviewArgs {
+    #[argh(positional)]
+    /// input LTPB
+    input: PathBuf,
+    #[argh(switch)]
+    /// reproject cube maps into an equirectangular image instead of a cross
+    equirect: bool,
+    #[argh(option)]
+    /// equirectangular image width (default: 4x face size)
+    width: Option<u32>,
+    #[argh(option)]
+    /// image format for HDR textures: exr or png (default: exr)
+    format: Option<ImageOutput>,
+    #[argh(option, default = "Tonemap::None")]
+    /// tonemap HDR data when writing PNG: reinhard, aces or none (clamp) (default: none)
+    tonemap: Tonemap,
+    #[argh(switch)]
+    /// dither 16-bit and HDR data when writing PNG to avoid banding in gradients
+    dither: bool,
+    #[argh(switch)]
+    /// also write the spherical harmonics diffuse irradiance of each cube map
+    irradiance: bool,
+}
+
+/// Face size of the irradiance cube evaluated from a probe's spherical harmonics.
+const IRRADIANCE_SIZE: u32 = 32;
+
+pub fn run(args: Args) -> Result<()> {
+    match args.command {
+        SubCommand::Preview(c_args) => preview(c_args),
+    }
+}
+
+fn preview(args: PreviewArgs) -> Result<()> {
+    let data = map_file(&args.input)?;
+    let meta = locate_meta::<LittleEndian>(&data)?;
+    let probe = LightProbeData::<LittleEndian>::slice(&data, meta)
+        .with_context(|| format!("Failed to parse light probe '{}'", args.input.display()))?;
+    let head = &probe.head;
+    log::info!(
+        "Probe grid {}x{}x{} to {}x{}x{}",
+        head.grid_idx1.x,
+        head.grid_idx1.y,
+        head.grid_idx1.z,
+        head.grid_idx2.x,
+        head.grid_idx2.y,
+        head.grid_idx2.z
+    );
+    for (idx, texture) in probe.textures.iter().enumerate() {
+        preview_texture(&args, idx, texture)
+            .with_context(|| format!("Failed to write texture {idx}"))?;
+    }
+    Ok(())
+}
+
+/// Writes mip 0 of a probe texture to `[in].[idx].exr` or `.png`, and for cube maps with
+/// `--irradiance`, its spherical harmonics irradiance to `[in].[idx].irradiance.exr` (or `.png`).
+fn preview_texture(
+    args: &PreviewArgs,
+    idx: usize,
+    texture: &TextureData<LittleEndian>,
+) -> Result<()> {
+    let head = &texture.head;
+    log::info!(
+        "Texture {idx}: {} {} {}x{}x{}",
+        head.kind,
+        head.format,
+        head.width,
+        head.height,
+        head.layers
+    );
+    let slices = slice_texture(texture)?;
+    let Some(layers) = slices.first().filter(|layers| !layers.is_empty()) else {
+        bail!("Texture has no image data");
+    };
+    let images = layers
+        .iter()
+        .enumerate()
+        .map(|(layer, slice)| {
+            decompress_image(
+                head.format,
+                slice.width,
+                slice.height,
+                &texture.data[slice.data_range.clone()],
+            )
+            .with_context(|| format!("Failed to decode mip 0 layer {layer}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let cube = matches!(head.kind, ETextureType::Cube | ETextureType::CubeArray);
+    if cube && images.len() < 6 {
+        bail!("Expected 6 cube faces, got {}", images.len());
+    }
+    write_preview(args, &images, cube, &format!("{idx}"))?;
+    if cube && args.irradiance {
+        let faces = images[..6].iter().map(DynamicImage::to_rgba32f).collect::<Vec<_>>();
+        let irradiance = sh9_irradiance_cube(&cube_sh9(&faces)?, IRRADIANCE_SIZE)?
+            .into_iter()
+            .map(DynamicImage::ImageRgba32F)
+            .collect::<Vec<_>>();
+        write_preview(args, &irradiance, true, &format!("{idx}.irradiance"))?;
+    }
+    Ok(())
+}
+
+/// Writes images to `[in].[name].exr` or `.png`. Cube maps are assembled into a cross or
+/// equirectangular image; other textures are laid out as a row of their layers (or depth
+/// slices, for volumes).
+fn write_preview(
+    args: &PreviewArgs,
+    images: &[DynamicImage],
+    cube: bool,
+    name: &str,
+) -> Result<()> {
+    let width = args.width.unwrap_or(images[0].width() * 4);
+
+    // Keep HDR data as floating point (EXR) unless PNG was requested
+    let exr = images.iter().any(is_hdr) && args.format != Some(ImageOutput::Png);
+    let image = if exr {
+        let images = images.iter().map(DynamicImage::to_rgba32f).collect::<Vec<_>>();
+        DynamicImage::ImageRgba32F(if !cube {
+            layer_row(&images)
+        } else if args.equirect {
+            cube_equirect(&images[..6], width)?
+        } else {
+            cube_cross(&images[..6], CrossLayout::Horizontal)?
+        })
+    } else {
+        let images = images
+            .iter()
+            .map(|image| to_ldr(image, args.tonemap, false, args.dither).into_rgba8())
+            .collect::<Vec<_>>();
+        DynamicImage::ImageRgba8(if !cube {
+            layer_row(&images)
+        } else if args.equirect {
+            cube_equirect(&images[..6], width)?
+        } else {
+            cube_cross(&images[..6], CrossLayout::Horizontal)?
+        })
+    };
+    let extension = if exr { "exr" } else { "png" };
+    save_image(&image, &args.input.with_extension(format!("{name}.{extension}")), false)
+}
+
+/// Places equally sized images side by side.
+fn layer_row<P>(images: &[ImageBuffer<P, Vec<P::Subpixel>>]) -> ImageBuffer<P, Vec<P::Subpixel>>
+where P: Pixel + 'static {
+    let (width, height) = images[0].dimensions();
+    let mut out = ImageBuffer::new(width * images.len() as u32, height);
+    for (idx, image) in images.iter().enumerate() {
+        imageops::replace(&mut out, image, (idx as u32 * width) as i64, 0);
+    }
+    out
+}
@@ -1,5 +1,12 @@
+#[cfg(feature = "bench")]
+pub mod bench;
 pub mod clsn;
 pub mod cmdl;
 pub mod fmv0;
+pub mod formats;
+pub mod hash;
+pub mod ltpb;
 pub mod pak;
+pub mod rfrm;
+pub mod room;
 pub mod txtr;
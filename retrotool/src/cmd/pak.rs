@@ -1,25 +1,28 @@
 use std::{
-    borrow::Cow,
+    collections::{HashMap, HashSet},
     fmt::Debug,
     fs,
     fs::{DirBuilder, File},
-    io::{BufWriter, Cursor, Write},
-    path::PathBuf,
+    io::{BufReader, BufWriter, Cursor, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
 };
 
 use anyhow::{bail, ensure, Context, Result};
 use argh::FromArgs;
-use binrw::{BinReaderExt, BinWriterExt, Endian};
+use binrw::Endian;
 use retrolib::{
     format::{
-        chunk::ChunkDescriptor,
-        foot::{K_CHUNK_AINF, K_CHUNK_NAME, K_FORM_FOOT},
-        pack::{Asset, AssetInfo, Package, K_CHUNK_META},
-        rfrm::FormDescriptor,
+        pack::{decompress_package, Asset, Package, PackageManifest, MANIFEST_FILE_NAME},
+        FourCC,
     },
     util::file::map_file,
 };
-use zerocopy::{AsBytes, LittleEndian, U32, U64};
+use uuid::Uuid;
+use zerocopy::LittleEndian;
+
+use crate::{output, parallel, progress::Progress};
 
 #[derive(FromArgs, PartialEq, Debug)]
 /// process PAK files
@@ -34,6 +37,8 @@ pub struct Args {
 enum SubCommand {
     Extract(ExtractArgs),
     Package(PackageArgs),
+    Repack(RepackArgs),
+    Diff(DiffArgs),
 }
 
 #[derive(FromArgs, PartialEq, Eq, Debug)]
@@ -45,7 +50,36 @@ pub struct ExtractArgs {
     input: PathBuf,
     #[argh(positional)]
     /// output directory
-    output: PathBuf,
+    output: Option<PathBuf>,
+    #[argh(switch)]
+    /// write a single selected asset to stdout instead of a directory
+    stdout: bool,
+    #[argh(option)]
+    /// only extract the asset with this ID
+    id: Option<Uuid>,
+    #[argh(option, from_str_fn(parse_kinds))]
+    /// only extract assets of these kinds (comma-separated, e.g. TXTR,CMDL)
+    only: Option<Vec<FourCC>>,
+    #[argh(option, from_str_fn(parse_kinds))]
+    /// skip assets of these kinds (comma-separated)
+    exclude: Option<Vec<FourCC>>,
+    #[argh(switch)]
+    /// don't write manifest.json
+    no_manifest: bool,
+    #[argh(switch)]
+    /// skip assets whose output file is newer than the input PAK
+    incremental: bool,
+    #[argh(switch)]
+    /// rewrite every output file, even with --incremental
+    force: bool,
+}
+
+fn parse_kinds(value: &str) -> Result<Vec<FourCC>, String> {
+    value
+        .split(',')
+        .map(|kind| FourCC::from_str(&kind.trim().to_ascii_uppercase()))
+        .collect::<Result<_>>()
+        .map_err(|e| e.to_string())
 }
 
 #[derive(FromArgs, PartialEq, Eq, Debug)]
@@ -60,17 +94,58 @@ pub struct PackageArgs {
     output: PathBuf,
 }
 
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// rebuild a PAK file from an extracted directory and its manifest
+#[argh(subcommand, name = "repack")]
+pub struct RepackArgs {
+    #[argh(positional)]
+    /// input directory
+    input: PathBuf,
+    #[argh(positional)]
+    /// output file
+    output: PathBuf,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// compare the assets of two PAK files
+#[argh(subcommand, name = "diff")]
+pub struct DiffArgs {
+    #[argh(positional)]
+    /// original file
+    a: PathBuf,
+    #[argh(positional)]
+    /// file to compare against
+    b: PathBuf,
+}
+
 pub fn run(args: Args) -> Result<()> {
     match args.command {
         SubCommand::Extract(c_args) => extract(c_args),
         SubCommand::Package(c_args) => package(c_args),
+        SubCommand::Repack(c_args) => repack(c_args),
+        SubCommand::Diff(c_args) => diff(c_args),
     }
 }
 
 fn extract(args: ExtractArgs) -> Result<()> {
-    let data = map_file(args.input)?;
+    ensure!(
+        args.output.is_some() != args.stdout,
+        "Expected either an output directory or --stdout"
+    );
+    let input_modified = fs::metadata(&args.input)
+        .and_then(|m| m.modified())
+        .with_context(|| format!("Failed to read metadata of '{}'", args.input.display()))?;
+    let data = map_file(&args.input)?;
+    let data = decompress_package(&data)?;
     let package = Package::<LittleEndian>::read_full(&data, Endian::Little)?;
+    let mut jobs = Vec::new();
     for asset in &package.assets {
+        if args.id.is_some_and(|id| asset.id != id)
+            || args.only.as_ref().is_some_and(|kinds| !kinds.contains(&asset.kind))
+            || args.exclude.as_ref().is_some_and(|kinds| kinds.contains(&asset.kind))
+        {
+            continue;
+        }
         let asset_names = asset.names.join(", ");
         let name = if asset_names.is_empty() {
             format!("{}", asset.id)
@@ -90,111 +165,101 @@ fn extract(args: ExtractArgs) -> Result<()> {
             .first()
             .map(|name| format!("{}.{}", name, asset.kind))
             .unwrap_or_else(|| format!("{}.{}", asset.id, asset.kind));
-        let path = args.output.join(&file_name);
+        jobs.push((asset, file_name));
+    }
+
+    let Some(out_dir) = &args.output else {
+        let [(asset, _)] = jobs.as_slice() else {
+            bail!("Writing to stdout requires exactly one asset, {} selected", jobs.len());
+        };
+        let mut buf = Cursor::new(Vec::new());
+        asset.write_extracted(&mut buf)?;
+        return output::write_stdout(&buf.into_inner());
+    };
+
+    // Write in parallel, but report errors and build the manifest in package order
+    let skip_current = args.incremental && !args.force;
+    let progress = Progress::start(jobs.len());
+    let results = parallel::map(&jobs, |(asset, file_name)| -> Result<bool> {
+        let path = out_dir.join(file_name);
+        if skip_current && is_newer(&path, input_modified) {
+            progress.file(&path);
+            return Ok(false);
+        }
         if let Some(parent) = path.parent() {
             DirBuilder::new().recursive(true).create(parent)?;
         }
-
         let mut file = BufWriter::new(
             File::create(&path)
                 .with_context(|| format!("Failed to create file '{}'", path.display()))?,
         );
-        file.write_all(&asset.data)?;
-
-        // Write custom footer
-        FormDescriptor::<LittleEndian> {
-            id: K_FORM_FOOT,
-            reader_version: U32::new(1),
-            writer_version: U32::new(1),
-            ..Default::default()
+        asset.write_extracted(&mut file)?;
+        file.flush()?;
+        progress.file(&path);
+        Ok(true)
+    });
+    progress.finish();
+    let mut manifest = PackageManifest::default();
+    let mut skipped = 0;
+    for ((asset, file_name), result) in jobs.into_iter().zip(results) {
+        if !result? {
+            skipped += 1;
         }
-        .write(&mut file, |w| {
-            ChunkDescriptor::<LittleEndian> { id: K_CHUNK_AINF, ..Default::default() }.write(
-                w,
-                |w| {
-                    w.write_le(&asset.info)?;
-                    Ok(())
-                },
-            )?;
-            if let Some(meta) = &asset.meta {
-                w.write_all(
-                    ChunkDescriptor::<LittleEndian> {
-                        id: K_CHUNK_META,
-                        size: U64::new(meta.len() as u64),
-                        ..Default::default()
-                    }
-                    .as_bytes(),
-                )?;
-                w.write_all(meta)?;
-            }
-            for name in &asset.names {
-                let bytes = name.as_bytes();
-                w.write_all(
-                    ChunkDescriptor::<LittleEndian> {
-                        id: K_CHUNK_NAME,
-                        size: U64::new(bytes.len() as u64),
-                        ..Default::default()
-                    }
-                    .as_bytes(),
-                )?;
-                w.write_all(bytes)?;
-            }
-            Ok(())
-        })?;
+        manifest.assets.push(asset.manifest_entry(file_name));
+    }
+    if args.incremental {
+        log::info!(
+            "Extracted {} assets, skipped {skipped} up to date",
+            manifest.assets.len() - skipped
+        );
+    }
+    if !args.no_manifest {
+        DirBuilder::new().recursive(true).create(out_dir)?;
+        let path = out_dir.join(MANIFEST_FILE_NAME);
+        let mut file = BufWriter::new(
+            File::create(&path)
+                .with_context(|| format!("Failed to create manifest '{}'", path.display()))?,
+        );
+        serde_json::to_writer_pretty(&mut file, &manifest)?;
         file.flush()?;
     }
     Ok(())
 }
 
+/// Whether `path` exists and was modified after `time`.
+fn is_newer(path: &Path, time: SystemTime) -> bool {
+    fs::metadata(path).and_then(|m| m.modified()).is_ok_and(|modified| modified > time)
+}
+
 fn package(args: PackageArgs) -> Result<()> {
-    let files = fs::read_dir(&args.input)?;
-    let mut package = Package::<LittleEndian>::default();
-    for result in files {
+    let mut paths = Vec::new();
+    for result in fs::read_dir(&args.input)? {
         let entry = match result {
             Ok(e) => e,
             Err(e) => bail!("Failed to read directory entry: {:?}", e),
         };
 
         let path = entry.path();
+        if path.file_name() == Some(MANIFEST_FILE_NAME.as_ref()) {
+            continue;
+        }
+        paths.push(path);
+    }
+    let progress = Progress::start(paths.len());
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
         log::info!("Processing {}", path.display());
         let data = map_file(&path)?;
-        let (form, _, remain) = FormDescriptor::<LittleEndian>::slice(&data)?;
-        // log::info!("Found type {} version {}, {}", form.id, form.version, form.other_version);
-        let (foot, mut foot_data, _) = FormDescriptor::<LittleEndian>::slice(remain)?;
-        ensure!(foot.id == K_FORM_FOOT);
-        ensure!(foot.reader_version.get() == 1);
-        let mut ainfo: Option<AssetInfo> = None;
-        let mut meta: Option<&[u8]> = None;
-        let mut names: Vec<String> = vec![];
-        while !foot_data.is_empty() {
-            let (chunk, chunk_data, remain) = ChunkDescriptor::<LittleEndian>::slice(foot_data)?;
-            match chunk.id {
-                K_CHUNK_AINF => {
-                    ainfo = Some(Cursor::new(chunk_data).read_type(Endian::Little)?);
-                }
-                K_CHUNK_META => {
-                    meta = Some(chunk_data);
-                }
-                K_CHUNK_NAME => {
-                    names.push(String::from_utf8(chunk_data.to_vec())?);
-                }
-                _ => {}
-            }
-            foot_data = remain;
-        }
-        let Some(ainfo) = ainfo else {
-            bail!("Failed to locate asset info footer");
-        };
-        package.assets.push(Asset {
-            id: ainfo.id,
-            kind: form.id,
-            names,
-            data: Cow::Owned(data[..data.len() - remain.len()].to_vec()),
-            meta: meta.map(|data| Cow::Owned(data.to_vec())),
-            info: ainfo,
-            version: form.reader_version.get(),
-            other_version: form.writer_version.get(),
-        });
+        progress.file(&path);
+        files.push((path, data));
+    }
+    progress.finish();
+    let mut package = Package::<LittleEndian>::default();
+    for (path, data) in &files {
+        package.assets.push(
+            Asset::read_extracted(data)
+                .with_context(|| format!("Failed to read '{}'", path.display()))?,
+        );
     }
     package.assets.sort_by_key(|a| a.id);
     let mut file =
@@ -205,3 +270,129 @@ fn package(args: PackageArgs) -> Result<()> {
     file.flush()?;
     Ok(())
 }
+
+fn repack(args: RepackArgs) -> Result<()> {
+    let manifest_path = args.input.join(MANIFEST_FILE_NAME);
+    if !manifest_path.exists() {
+        return repack_without_manifest(&args);
+    }
+    let manifest: PackageManifest = serde_json::from_reader(BufReader::new(
+        File::open(&manifest_path)
+            .with_context(|| format!("Failed to open manifest '{}'", manifest_path.display()))?,
+    ))
+    .with_context(|| format!("Failed to parse manifest '{}'", manifest_path.display()))?;
+    let progress = Progress::start(manifest.assets.len());
+    let files = manifest
+        .assets
+        .iter()
+        .map(|entry| {
+            let path = args.input.join(&entry.file);
+            let data = map_file(&path)?;
+            progress.file(&path);
+            Ok(data)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    progress.finish();
+    let package = Package::<LittleEndian>::from_extracted(
+        manifest.assets.iter().zip(files.iter().map(|data| &**data)),
+    )?;
+    log::info!("Writing {} assets to {}", package.assets.len(), args.output.display());
+    let mut file =
+        BufWriter::new(File::create(&args.output).with_context(|| {
+            format!("Failed to create output file '{}'", args.output.display())
+        })?);
+    package.write(&mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Repacks every file under the input directory, reading each asset's offset and compression
+/// from its footer instead of the manifest.
+fn repack_without_manifest(args: &RepackArgs) -> Result<()> {
+    log::info!("No {MANIFEST_FILE_NAME} in '{}', reading every file", args.input.display());
+    let mut paths = Vec::new();
+    extracted_files(&args.input, &mut paths)?;
+    let progress = Progress::start(paths.len());
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let data = map_file(&path)?;
+        progress.file(&path);
+        files.push((path.display().to_string(), data));
+    }
+    progress.finish();
+    let package = Package::<LittleEndian>::from_extracted_files(
+        files.iter().map(|(path, data)| (path.as_str(), &**data)),
+    )?;
+    log::info!("Writing {} assets to {}", package.assets.len(), args.output.display());
+    let mut file =
+        BufWriter::new(File::create(&args.output).with_context(|| {
+            format!("Failed to create output file '{}'", args.output.display())
+        })?);
+    package.write(&mut file)?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Collects the files under `dir`, recursing into the subdirectories that asset names with
+/// path separators are extracted to.
+fn extracted_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for result in fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory '{}'", dir.display()))?
+    {
+        let path = result?.path();
+        if path.is_dir() {
+            extracted_files(&path, out)?;
+        } else {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn diff(args: DiffArgs) -> Result<()> {
+    let data_a = map_file(&args.a)?;
+    let data_a = decompress_package(&data_a)?;
+    let package_a = Package::<LittleEndian>::read_full(&data_a, Endian::Little)
+        .with_context(|| format!("Failed to read '{}'", args.a.display()))?;
+    let data_b = map_file(&args.b)?;
+    let data_b = decompress_package(&data_b)?;
+    let package_b = Package::<LittleEndian>::read_full(&data_b, Endian::Little)
+        .with_context(|| format!("Failed to read '{}'", args.b.display()))?;
+
+    let assets_b = package_b.assets.iter().map(|a| (a.id, a)).collect::<HashMap<_, _>>();
+    let mut differences = 0usize;
+    for a in &package_a.assets {
+        let Some(b) = assets_b.get(&a.id) else {
+            println!("- {} {} ({:#X} bytes)", a.kind, a.id, a.data.len());
+            differences += 1;
+            continue;
+        };
+        let mut changes = Vec::new();
+        if a.kind != b.kind {
+            changes.push(format!("kind {} -> {}", a.kind, b.kind));
+        }
+        if a.data.len() != b.data.len() {
+            changes.push(format!("size {:#X} -> {:#X}", a.data.len(), b.data.len()));
+        } else if a.data != b.data {
+            changes.push("content differs".to_string());
+        }
+        if a.meta != b.meta {
+            changes.push("metadata differs".to_string());
+        }
+        if !changes.is_empty() {
+            println!("~ {} {}: {}", a.kind, a.id, changes.join(", "));
+            differences += 1;
+        }
+    }
+    let ids_a = package_a.assets.iter().map(|a| a.id).collect::<HashSet<_>>();
+    for b in package_b.assets.iter().filter(|b| !ids_a.contains(&b.id)) {
+        println!("+ {} {} ({:#X} bytes)", b.kind, b.id, b.data.len());
+        differences += 1;
+    }
+
+    if differences > 0 {
+        bail!("{differences} assets differ");
+    }
+    log::info!("Packages match ({} assets)", package_a.assets.len());
+    Ok(())
+}
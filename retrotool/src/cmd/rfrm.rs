@@ -0,0 +1,69 @@
+use std::{fs, fs::DirBuilder, path::PathBuf, str::FromStr};
+
+use anyhow::{Context, Result};
+use argh::FromArgs;
+use retrolib::{
+    format::{rfrm::walk_chunks, FourCC},
+    util::file::map_file,
+};
+use zerocopy::LittleEndian;
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// process RFRM files
+#[argh(subcommand, name = "rfrm")]
+pub struct Args {
+    #[argh(subcommand)]
+    command: SubCommand,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum SubCommand {
+    Extract(ExtractArgs),
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// writes the raw data of every form or chunk with a given ID
+#[argh(subcommand, name = "extract")]
+pub struct ExtractArgs {
+    #[argh(positional)]
+    /// input file
+    input: PathBuf,
+    #[argh(positional, from_str_fn(parse_four_cc))]
+    /// form or chunk ID (e.g. HEAD)
+    id: FourCC,
+    #[argh(positional)]
+    /// output directory
+    out_dir: PathBuf,
+}
+
+fn parse_four_cc(value: &str) -> Result<FourCC, String> {
+    FourCC::from_str(value).map_err(|e| e.to_string())
+}
+
+pub fn run(args: Args) -> Result<()> {
+    match args.command {
+        SubCommand::Extract(c_args) => extract(c_args),
+    }
+}
+
+fn extract(args: ExtractArgs) -> Result<()> {
+    let data = map_file(&args.input)?;
+    DirBuilder::new().recursive(true).create(&args.out_dir)?;
+    let mut count = 0usize;
+    walk_chunks::<LittleEndian, _>(&data, |path, header, data| {
+        if header.id() != args.id {
+            return Ok(());
+        }
+        let file_name = format!("{}_{count}.bin", args.id.to_string().trim_end());
+        log::info!("{path} -> {file_name} ({:#X} bytes)", data.len());
+        let out_path = args.out_dir.join(file_name);
+        fs::write(&out_path, data)
+            .with_context(|| format!("Failed to write '{}'", out_path.display()))?;
+        count += 1;
+        Ok(())
+    })
+    .with_context(|| format!("Failed to slice chunks in '{}'", args.input.display()))?;
+    log::info!("Extracted {count} {} chunks", args.id);
+    Ok(())
+}
@@ -0,0 +1,545 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use argh::FromArgs;
+use retrolib::{
+    format::{
+        foot::{locate_asset_id, locate_names},
+        room::RoomData,
+        CObjectId,
+    },
+    util::file::map_file,
+};
+use schemars::{schema_for, JsonSchema};
+use serde::Serialize;
+use uuid::Uuid;
+use zerocopy::LittleEndian;
+
+use crate::{output, progress::Progress};
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// process ROOM files
+#[argh(subcommand, name = "room")]
+pub struct Args {
+    #[argh(subcommand)]
+    command: SubCommand,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum SubCommand {
+    Header(HeaderArgs),
+    Lightmap(LightmapArgs),
+    Docks(DocksArgs),
+    Generated(GeneratedArgs),
+    Graph(GraphArgs),
+    Schema(SchemaArgs),
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// prints the room header, including production work stages
+#[argh(subcommand, name = "header")]
+pub struct HeaderArgs {
+    #[argh(positional)]
+    /// input ROOM
+    input: PathBuf,
+    #[argh(switch)]
+    /// output as JSON
+    json: bool,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// lists baked lighting textures and atlas lookups
+#[argh(subcommand, name = "lightmap")]
+pub struct LightmapArgs {
+    #[argh(positional)]
+    /// input ROOM
+    input: PathBuf,
+    #[argh(switch)]
+    /// output as JSON
+    json: bool,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// lists the docks connecting a room to its neighbors
+#[argh(subcommand, name = "docks")]
+pub struct DocksArgs {
+    #[argh(positional)]
+    /// input ROOM
+    input: PathBuf,
+    #[argh(switch)]
+    /// output as JSON
+    json: bool,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// lists objects spawned at runtime and the objects that generate them
+#[argh(subcommand, name = "generated")]
+pub struct GeneratedArgs {
+    #[argh(positional)]
+    /// input ROOM
+    input: PathBuf,
+    #[argh(switch)]
+    /// output as JSON
+    json: bool,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// builds a graph of how the rooms in a directory connect via their docks
+#[argh(subcommand, name = "graph")]
+pub struct GraphArgs {
+    #[argh(positional)]
+    /// input directory of ROOM files
+    input: PathBuf,
+    #[argh(option, default = "GraphFormat::Dot")]
+    /// output format: dot or json (default: dot)
+    format: GraphFormat,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// prints the JSON Schema of a room command's --json output
+#[argh(subcommand, name = "schema")]
+pub struct SchemaArgs {
+    #[argh(positional)]
+    /// output to describe: header, lightmap, docks, generated or graph
+    output: SchemaOutput,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum SchemaOutput {
+    Header,
+    Lightmap,
+    Docks,
+    Generated,
+    Graph,
+}
+
+impl FromStr for SchemaOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "header" => Ok(SchemaOutput::Header),
+            "lightmap" => Ok(SchemaOutput::Lightmap),
+            "docks" => Ok(SchemaOutput::Docks),
+            "generated" => Ok(SchemaOutput::Generated),
+            "graph" => Ok(SchemaOutput::Graph),
+            _ => Err(format!(
+                "Invalid output '{s}' (expected header, lightmap, docks, generated or graph)"
+            )),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum GraphFormat {
+    Dot,
+    Json,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "json" => Ok(GraphFormat::Json),
+            _ => Err(format!("Invalid graph format '{s}' (expected dot or json)")),
+        }
+    }
+}
+
+pub fn run(args: Args) -> Result<()> {
+    match args.command {
+        SubCommand::Header(c_args) => header(c_args),
+        SubCommand::Lightmap(c_args) => lightmap(c_args),
+        SubCommand::Docks(c_args) => docks(c_args),
+        SubCommand::Generated(c_args) => generated(c_args),
+        SubCommand::Graph(c_args) => graph(c_args),
+        SubCommand::Schema(c_args) => schema(c_args),
+    }
+}
+
+/// `room header --json` output.
+#[derive(Serialize, JsonSchema)]
+struct HeaderJson {
+    /// Room this room is nested in, or nil.
+    parent_room_id: Uuid,
+    unk1: u16,
+    unk2: u16,
+    unk3: u8,
+    id_b: Uuid,
+    id_c: Uuid,
+    id_d: Uuid,
+    id_e: Uuid,
+    /// Path finding area for the room.
+    path_find_area_id: Uuid,
+    /// Development progress per discipline.
+    production_work_stages: Vec<WorkStageJson>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct WorkStageJson {
+    discipline: u32,
+    stage: u32,
+}
+
+/// `room lightmap --json` output.
+#[derive(Serialize, JsonSchema)]
+struct LightmapJson {
+    light_map: Option<LightMapJson>,
+    /// LTPB asset ID.
+    light_probe: Option<Uuid>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct LightMapJson {
+    /// Lightmap TXTR asset ID.
+    txtr_id: Uuid,
+    ids: Vec<Uuid>,
+    /// Per-surface atlas rectangles as [x, y, z, w].
+    atlas_lookups: Vec<[f32; 4]>,
+}
+
+/// `room docks --json` output.
+#[derive(Serialize, JsonSchema)]
+struct DocksJson {
+    parent_room_id: Uuid,
+    docks: Vec<DockJson>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct DockJson {
+    connected_room_id: Uuid,
+    dock_id: Uuid,
+    bounds: BoundsJson,
+}
+
+/// Axis-aligned box as [x, y, z] corners.
+#[derive(Serialize, JsonSchema)]
+struct BoundsJson {
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+/// `room generated --json` output.
+#[derive(Serialize, JsonSchema)]
+struct GeneratedJson {
+    objects: Vec<GeneratedObjectJson>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct GeneratedObjectJson {
+    /// Object spawned at runtime.
+    object_id: Uuid,
+    /// Placed object that spawns it.
+    generator_id: Uuid,
+    index: u32,
+}
+
+/// `room graph --format json` output.
+#[derive(Serialize, JsonSchema)]
+struct GraphJson {
+    rooms: Vec<GraphRoomJson>,
+    /// Rooms referenced by docks but not found in the directory.
+    missing: Vec<Uuid>,
+    docks: Vec<GraphDockJson>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct GraphRoomJson {
+    id: Uuid,
+    /// Asset name, or the file stem if the room has none.
+    name: String,
+    file: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct GraphDockJson {
+    from: Uuid,
+    to: Uuid,
+    dock_id: Uuid,
+}
+
+fn schema(args: SchemaArgs) -> Result<()> {
+    let schema = match args.output {
+        SchemaOutput::Header => schema_for!(HeaderJson),
+        SchemaOutput::Lightmap => schema_for!(LightmapJson),
+        SchemaOutput::Docks => schema_for!(DocksJson),
+        SchemaOutput::Generated => schema_for!(GeneratedJson),
+        SchemaOutput::Graph => schema_for!(GraphJson),
+    };
+    output::print_json(&schema)
+}
+
+fn header(args: HeaderArgs) -> Result<()> {
+    let data = map_file(&args.input)?;
+    let room = RoomData::<LittleEndian>::slice(&data)
+        .with_context(|| format!("Failed to parse room '{}'", args.input.display()))?;
+    let header = &room.room_header;
+
+    if args.json {
+        let value = HeaderJson {
+            parent_room_id: header.parent_room_id.into_inner(),
+            unk1: header.unk1,
+            unk2: header.unk2,
+            unk3: header.unk3,
+            id_b: header.id_b.into_inner(),
+            id_c: header.id_c.into_inner(),
+            id_d: header.id_d.into_inner(),
+            id_e: header.id_e.into_inner(),
+            path_find_area_id: header.path_find_area_id.into_inner(),
+            production_work_stages: header
+                .production_work_stages
+                .iter()
+                .map(|stage| WorkStageJson { discipline: stage.discipline, stage: stage.stage })
+                .collect(),
+        };
+        output::print_json(&value)?;
+        return Ok(());
+    }
+
+    println!("Parent room: {}", header.parent_room_id);
+    println!("Unk1: {}", header.unk1);
+    println!("Unk2: {}", header.unk2);
+    println!("Unk3: {}", header.unk3);
+    println!("ID b: {}", header.id_b);
+    println!("ID c: {}", header.id_c);
+    println!("ID d: {}", header.id_d);
+    println!("ID e: {}", header.id_e);
+    println!("Path find area: {}", header.path_find_area_id);
+    if header.production_work_stages.is_empty() {
+        println!("Production work stages: none");
+    }
+    for stage in &header.production_work_stages {
+        println!("Production work stage: discipline {} -> stage {}", stage.discipline, stage.stage);
+    }
+    Ok(())
+}
+
+fn lightmap(args: LightmapArgs) -> Result<()> {
+    let data = map_file(&args.input)?;
+    let room = RoomData::<LittleEndian>::slice(&data)
+        .with_context(|| format!("Failed to parse room '{}'", args.input.display()))?;
+    let lighting = &room.baked_lighting;
+
+    if args.json {
+        let value = LightmapJson {
+            light_map: lighting.light_map.as_ref().map(|light_map| LightMapJson {
+                txtr_id: light_map.txtr_id.into_inner(),
+                ids: light_map.ids.iter().map(|id| id.into_inner()).collect(),
+                atlas_lookups: light_map
+                    .atlas_lookups
+                    .iter()
+                    .map(|lookup| lookup.0.to_array())
+                    .collect(),
+            }),
+            light_probe: lighting.light_probe.as_ref().map(|probe| probe.ltpb_id.into_inner()),
+        };
+        output::print_json(&value)?;
+        return Ok(());
+    }
+
+    match &lighting.light_map {
+        Some(light_map) => {
+            println!("Light map: {}", light_map.txtr_id);
+            for id in &light_map.ids {
+                println!("  ID: {id}");
+            }
+            for (idx, lookup) in light_map.atlas_lookups.iter().enumerate() {
+                let [x, y, z, w] = lookup.0.to_array();
+                println!("  Atlas lookup {idx}: [{x}, {y}, {z}, {w}]");
+            }
+        }
+        None => println!("Light map: none"),
+    }
+    match &lighting.light_probe {
+        Some(probe) => println!("Light probe: {}", probe.ltpb_id),
+        None => println!("Light probe: none"),
+    }
+    Ok(())
+}
+
+fn docks(args: DocksArgs) -> Result<()> {
+    let data = map_file(&args.input)?;
+    let room = RoomData::<LittleEndian>::slice(&data)
+        .with_context(|| format!("Failed to parse room '{}'", args.input.display()))?;
+
+    if args.json {
+        let value = DocksJson {
+            parent_room_id: room.room_header.parent_room_id.into_inner(),
+            docks: room
+                .docks
+                .iter()
+                .map(|dock| DockJson {
+                    connected_room_id: dock.connected_room_id.into_inner(),
+                    dock_id: dock.dock_id.into_inner(),
+                    bounds: BoundsJson {
+                        min: dock.bounds.min.to_array(),
+                        max: dock.bounds.max.to_array(),
+                    },
+                })
+                .collect(),
+        };
+        output::print_json(&value)?;
+        return Ok(());
+    }
+
+    if room.docks.is_empty() {
+        println!("Docks: none");
+    }
+    for (idx, dock) in room.docks.iter().enumerate() {
+        let [min_x, min_y, min_z] = dock.bounds.min.to_array();
+        let [max_x, max_y, max_z] = dock.bounds.max.to_array();
+        println!("Dock {idx}: {} -> room {}", dock.dock_id, dock.connected_room_id);
+        println!("  Bounds: [{min_x}, {min_y}, {min_z}] - [{max_x}, {max_y}, {max_z}]");
+    }
+    Ok(())
+}
+
+fn generated(args: GeneratedArgs) -> Result<()> {
+    let data = map_file(&args.input)?;
+    let room = RoomData::<LittleEndian>::slice(&data)
+        .with_context(|| format!("Failed to parse room '{}'", args.input.display()))?;
+
+    if args.json {
+        let value = GeneratedJson {
+            objects: room
+                .generated_objects
+                .iter()
+                .map(|object| GeneratedObjectJson {
+                    object_id: object.object_id.into_inner(),
+                    generator_id: object.generator_id.into_inner(),
+                    index: object.index,
+                })
+                .collect(),
+        };
+        output::print_json(&value)?;
+        return Ok(());
+    }
+
+    if room.generated_objects.is_empty() {
+        println!("Generated objects: none");
+    }
+    for object in &room.generated_objects {
+        println!(
+            "Generated object {}: generator {} index {}",
+            object.object_id, object.generator_id, object.index
+        );
+    }
+    Ok(())
+}
+
+struct GraphRoom {
+    name: String,
+    file: String,
+}
+
+struct GraphEdge {
+    from: CObjectId,
+    to: CObjectId,
+    dock_id: CObjectId,
+}
+
+fn graph(args: GraphArgs) -> Result<()> {
+    let mut paths = fs::read_dir(&args.input)
+        .with_context(|| format!("Failed to read directory '{}'", args.input.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| {
+        path.extension().is_some_and(|ext| ext.to_string_lossy().eq_ignore_ascii_case("room"))
+    });
+    paths.sort();
+
+    let mut rooms = BTreeMap::<CObjectId, GraphRoom>::new();
+    let mut edges = Vec::new();
+    let progress = Progress::start(paths.len());
+    for path in &paths {
+        let data = map_file(path)?;
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        // Extracted files carry their asset ID in the footer, otherwise use the file name
+        let id = match locate_asset_id::<LittleEndian>(&data)
+            .ok()
+            .or_else(|| Uuid::from_str(&stem).ok())
+        {
+            Some(id) => CObjectId::from(id),
+            None => {
+                log::warn!("Skipping '{}': unknown room ID", path.display());
+                progress.file(path);
+                continue;
+            }
+        };
+        let room = match RoomData::<LittleEndian>::slice(&data) {
+            Ok(room) => room,
+            Err(e) => {
+                log::warn!("Skipping '{}': {e:#}", path.display());
+                progress.file(path);
+                continue;
+            }
+        };
+        let name = locate_names::<LittleEndian>(&data)
+            .ok()
+            .and_then(|names| names.into_iter().next())
+            .map(|name| name.rsplit(['/', '\\']).next().unwrap_or_default().to_string())
+            .filter(|name| !name.is_empty())
+            .unwrap_or(stem);
+        edges.extend(room.docks.iter().map(|dock| GraphEdge {
+            from: id,
+            to: dock.connected_room_id,
+            dock_id: dock.dock_id,
+        }));
+        rooms.insert(id, GraphRoom { name, file: path.display().to_string() });
+        progress.file(path);
+    }
+    progress.finish();
+
+    let missing =
+        edges.iter().map(|e| e.to).filter(|id| !rooms.contains_key(id)).collect::<BTreeSet<_>>();
+    for id in &missing {
+        log::warn!("Docks reference missing room {id}");
+    }
+
+    match args.format {
+        GraphFormat::Dot => {
+            println!("digraph rooms {{");
+            for (id, room) in &rooms {
+                println!("    \"{id}\" [label={:?}];", room.name);
+            }
+            for id in &missing {
+                println!("    \"{id}\" [label=\"{id}\", style=dashed];");
+            }
+            for edge in &edges {
+                println!("    \"{}\" -> \"{}\" [label=\"{}\"];", edge.from, edge.to, edge.dock_id);
+            }
+            println!("}}");
+        }
+        GraphFormat::Json => {
+            let value = GraphJson {
+                rooms: rooms
+                    .iter()
+                    .map(|(id, room)| GraphRoomJson {
+                        id: id.into_inner(),
+                        name: room.name.clone(),
+                        file: room.file.clone(),
+                    })
+                    .collect(),
+                missing: missing.iter().map(|id| id.into_inner()).collect(),
+                docks: edges
+                    .iter()
+                    .map(|edge| GraphDockJson {
+                        from: edge.from.into_inner(),
+                        to: edge.to.into_inner(),
+                        dock_id: edge.dock_id.into_inner(),
+                    })
+                    .collect(),
+            };
+            output::print_json(&value)?;
+        }
+    }
+    Ok(())
+}
@@ -1,17 +1,34 @@
 use std::{
+    fs,
     fs::File,
-    io::{BufWriter, Write},
-    path::PathBuf,
+    io::{BufWriter, Cursor, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 
-use anyhow::{bail, Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 use argh::FromArgs;
+use image::{ColorType, DynamicImage, ImageFormat, Rgba, Rgba32FImage, RgbaImage};
 use retrolib::{
-    format::{foot::locate_meta, txtr::TextureData},
-    util::{astc::write_astc, dds::write_dds, file::map_file},
+    format::{
+        foot::locate_meta,
+        txtr::{
+            decode_slice, decompress_image, deswizzle, slice_texture, thumbnail_mip, ETextureType,
+            TextureData,
+        },
+    },
+    util::{
+        astc::write_astc,
+        cubemap::{cube_cross, cube_equirect, CrossLayout},
+        dds::write_dds,
+        file::map_file,
+    },
 };
+use serde_json::json;
 use zerocopy::LittleEndian;
 
+use crate::output;
+
 #[derive(FromArgs, PartialEq, Debug)]
 /// process TXTR files
 #[argh(subcommand, name = "txtr")]
@@ -24,6 +41,7 @@ pub struct Args {
 #[argh(subcommand)]
 enum SubCommand {
     Convert(ConvertArgs),
+    Info(InfoArgs),
 }
 
 #[derive(FromArgs, PartialEq, Eq, Debug)]
@@ -36,19 +54,122 @@ pub struct ConvertArgs {
     #[argh(switch, short = 'a')]
     /// write ASTC file instead of DDS (no mips)
     astc: bool,
+    #[argh(switch)]
+    /// assemble a cube map into a single cross image
+    cross: bool,
+    #[argh(switch)]
+    /// use a vertical cross layout (with --cross)
+    vertical: bool,
+    #[argh(switch)]
+    /// reproject a cube map into an equirectangular image
+    equirect: bool,
+    #[argh(option)]
+    /// equirectangular image width (default: 4x face size)
+    width: Option<u32>,
+    #[argh(switch)]
+    /// write only a small preview PNG from the mip chain
+    thumbnail: bool,
+    #[argh(option)]
+    /// decode to an image instead: png, or exr for HDR textures
+    format: Option<ImageOutput>,
+    #[argh(option, default = "Tonemap::None")]
+    /// tonemap HDR data when writing PNG: reinhard, aces or none (clamp) (default: none)
+    tonemap: Tonemap,
+    #[argh(switch)]
+    /// write HDR data to PNG without sRGB encoding or tonemapping, for data and normal textures
+    linear: bool,
+    #[argh(switch)]
+    /// dither 16-bit and HDR data when writing PNG to avoid banding in gradients
+    dither: bool,
+    #[argh(switch)]
+    /// write the converted file to stdout instead of next to the input
+    stdout: bool,
+    #[argh(switch)]
+    /// debugging aid: skip deswizzling and convert the data in its GPU layout
+    no_swizzle: bool,
+    #[argh(switch)]
+    /// debugging aid: also write the buffer before and after deswizzling to .swizzled.bin and
+    /// .deswizzled.bin
+    dump_swizzle: bool,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum ImageOutput {
+    Png,
+    Exr,
+}
+
+impl FromStr for ImageOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "png" => Ok(ImageOutput::Png),
+            "exr" => Ok(ImageOutput::Exr),
+            _ => Err(format!("Invalid image format '{s}' (expected png or exr)")),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Tonemap {
+    None,
+    Reinhard,
+    Aces,
+}
+
+impl FromStr for Tonemap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Tonemap::None),
+            "reinhard" => Ok(Tonemap::Reinhard),
+            "aces" => Ok(Tonemap::Aces),
+            _ => Err(format!("Invalid tonemap '{s}' (expected reinhard, aces or none)")),
+        }
+    }
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// prints TXTR header and buffer layout without decoding
+#[argh(subcommand, name = "info")]
+pub struct InfoArgs {
+    #[argh(positional)]
+    /// input TXTR
+    input: PathBuf,
+    #[argh(switch)]
+    /// output as JSON
+    json: bool,
 }
 
 #[allow(unused)]
 pub fn run(args: Args) -> Result<()> {
     match args.command {
         SubCommand::Convert(c_args) => convert(c_args),
+        SubCommand::Info(c_args) => info(c_args),
     }
 }
 
 fn convert(args: ConvertArgs) -> Result<()> {
     let data = map_file(&args.input)?;
     let meta = locate_meta::<LittleEndian>(&data)?;
-    let TextureData { head, data, .. } = TextureData::<LittleEndian>::slice(&data, meta)?;
+    let texture = if args.no_swizzle || args.dump_swizzle {
+        slice_debug_swizzle(&args, &data, meta)?
+    } else {
+        TextureData::<LittleEndian>::slice(&data, meta)?
+    };
+    if args.thumbnail {
+        return convert_thumbnail(&args, &texture);
+    }
+    if args.cross || args.equirect {
+        return convert_cube(&args, &texture);
+    }
+    if let Some(format) = args.format {
+        ensure!(!args.astc, "--astc and --format are mutually exclusive");
+        return convert_image(&args, &texture, format);
+    }
+    let TextureData { head, data, .. } = texture;
 
     log::info!("Texture info:");
     log::info!("  Type: {}", head.kind);
@@ -64,6 +185,15 @@ fn convert(args: ConvertArgs) -> Result<()> {
     } else {
         args.input.with_extension("dds")
     };
+    if args.stdout {
+        let mut buf = Cursor::new(Vec::new());
+        if args.astc {
+            write_astc(&mut buf, &head, &data)?;
+        } else {
+            write_dds(&mut buf, &head, data)?;
+        }
+        return output::write_stdout(&buf.into_inner());
+    }
     let mut file = BufWriter::new(
         File::create(&path)
             .with_context(|| format!("Failed to create output file '{}'", path.display()))?,
@@ -78,3 +208,323 @@ fn convert(args: ConvertArgs) -> Result<()> {
 
     Ok(())
 }
+
+/// Reads the texture for `--no-swizzle` and `--dump-swizzle`, logging the parameters that
+/// deswizzling uses.
+fn slice_debug_swizzle(
+    args: &ConvertArgs,
+    data: &[u8],
+    meta: &[u8],
+) -> Result<TextureData<LittleEndian>> {
+    let (head, meta, buffer) = TextureData::<LittleEndian>::slice_swizzled(data, meta)?;
+    let (bw, bh, bd) = head.format.block_size();
+    let (depth, layers) =
+        if head.kind == ETextureType::D3 { (head.layers, 1) } else { (1, head.layers) };
+    log::info!(
+        "Swizzle parameters: {}x{}, block {bw}x{bh}x{bd}, {} bytes per block, depth {depth}, \
+         {layers} layers, {} mips, buffer {:#X} (align {:#X})",
+        head.width,
+        head.height,
+        head.format.bytes_per_pixel(),
+        head.mip_sizes.len(),
+        buffer.len(),
+        meta.align
+    );
+    if !args.dump_swizzle {
+        return Ok(TextureData::from_raw(head, buffer));
+    }
+    let path = args.input.with_extension("swizzled.bin");
+    log::info!("Writing {}", path.display());
+    fs::write(&path, &buffer).with_context(|| format!("Failed to write '{}'", path.display()))?;
+    let deswizzled = deswizzle(&head, &buffer, meta.align)?;
+    let path = args.input.with_extension("deswizzled.bin");
+    log::info!("Writing {}", path.display());
+    fs::write(&path, &deswizzled)
+        .with_context(|| format!("Failed to write '{}'", path.display()))?;
+    Ok(TextureData::from_raw(head, if args.no_swizzle { buffer } else { deswizzled }))
+}
+
+/// Saves an image in the format given by the path's extension, or writes it to stdout.
+pub(crate) fn save_image(image: &DynamicImage, path: &Path, to_stdout: bool) -> Result<()> {
+    if !to_stdout {
+        log::info!("Writing {}", path.display());
+        return image.save(path).with_context(|| format!("Failed to write '{}'", path.display()));
+    }
+    let format = ImageFormat::from_path(path)?;
+    let mut buf = Cursor::new(Vec::new());
+    image.write_to(&mut buf, format)?;
+    output::write_stdout(&buf.into_inner())
+}
+
+pub(crate) fn is_hdr(image: &DynamicImage) -> bool {
+    matches!(image.color(), ColorType::Rgb32F | ColorType::Rgba32F)
+}
+
+/// Encodes a linear value with the sRGB transfer function.
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// 4x4 Bayer matrix for ordered dithering.
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Quantizes to 8 bits with an ordered dither, spreading the rounding error of smooth
+/// gradients into a fine pattern instead of visible bands.
+fn dither_rgba8(image: &Rgba32FImage) -> RgbaImage {
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let threshold = (BAYER_4X4[y as usize % 4][x as usize % 4] as f32 + 0.5) / 16.0;
+        let Rgba(pixel) = *image.get_pixel(x, y);
+        Rgba(pixel.map(|c| (c.clamp(0.0, 1.0) * 255.0 + threshold).floor().min(255.0) as u8))
+    })
+}
+
+/// Converts an image to 8-bit RGBA for PNG output.
+///
+/// HDR data is linear, so unless `linear` is set it's tonemapped and sRGB encoded to match
+/// how PNGs are displayed. Other images are already display-ready and only converted.
+/// With `dither`, images with more than 8 bits per channel are dithered after tonemapping.
+pub(crate) fn to_ldr(
+    image: &DynamicImage,
+    tonemap: Tonemap,
+    linear: bool,
+    dither: bool,
+) -> DynamicImage {
+    let encode = is_hdr(image) && !linear;
+    let dither = dither
+        && !matches!(
+            image.color(),
+            ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8
+        );
+    if !encode && !dither {
+        return DynamicImage::ImageRgba8(image.to_rgba8());
+    }
+    let mut image = image.to_rgba32f();
+    if encode {
+        for pixel in image.pixels_mut() {
+            for c in &mut pixel.0[..3] {
+                let x = c.max(0.0);
+                let mapped = match tonemap {
+                    Tonemap::None => x,
+                    Tonemap::Reinhard => x / (1.0 + x),
+                    // Narkowicz's fit of the ACES filmic curve
+                    Tonemap::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+                };
+                *c = linear_to_srgb(mapped.min(1.0));
+            }
+        }
+    }
+    if dither {
+        return DynamicImage::ImageRgba8(dither_rgba8(&image));
+    }
+    DynamicImage::ImageRgba8(DynamicImage::ImageRgba32F(image).to_rgba8())
+}
+
+/// Whether images decoded from the texture should be written as EXR rather than PNG.
+/// Without an explicit format, HDR data is written as EXR.
+fn use_exr(format: Option<ImageOutput>, images: &[DynamicImage]) -> Result<bool> {
+    let hdr = images.iter().any(is_hdr);
+    match format {
+        Some(ImageOutput::Exr) => {
+            ensure!(hdr, "EXR output requires a floating point texture (use --format png)");
+            Ok(true)
+        }
+        Some(ImageOutput::Png) => Ok(false),
+        None => Ok(hdr),
+    }
+}
+
+/// Decodes mip 0 of each layer to PNG or EXR.
+fn convert_image(
+    args: &ConvertArgs,
+    texture: &TextureData<LittleEndian>,
+    format: ImageOutput,
+) -> Result<()> {
+    let head = &texture.head;
+    let slices = slice_texture(texture)?;
+    let Some(layers) = slices.first().filter(|layers| !layers.is_empty()) else {
+        bail!("Texture has no image data");
+    };
+    ensure!(
+        !args.stdout || layers.len() == 1,
+        "--stdout requires a single layer, texture has {}",
+        layers.len()
+    );
+    let images = layers
+        .iter()
+        .enumerate()
+        .map(|(layer, slice)| {
+            decompress_image(
+                head.format,
+                slice.width,
+                slice.height,
+                &texture.data[slice.data_range.clone()],
+            )
+            .with_context(|| format!("Failed to decode mip 0 layer {layer}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let exr = use_exr(Some(format), &images)?;
+    for (layer, image) in images.iter().enumerate() {
+        let image = if exr {
+            DynamicImage::ImageRgba32F(image.to_rgba32f())
+        } else {
+            to_ldr(image, args.tonemap, args.linear, args.dither)
+        };
+        let extension = if exr { "exr" } else { "png" };
+        let path = if images.len() > 1 {
+            args.input.with_extension(format!("{layer}.{extension}"))
+        } else {
+            args.input.with_extension(extension)
+        };
+        save_image(&image, &path, args.stdout)?;
+    }
+    Ok(())
+}
+
+fn convert_cube(args: &ConvertArgs, texture: &TextureData<LittleEndian>) -> Result<()> {
+    ensure!(!(args.cross && args.equirect), "--cross and --equirect are mutually exclusive");
+    let head = &texture.head;
+    if !matches!(head.kind, ETextureType::Cube | ETextureType::CubeArray) {
+        bail!("Expected cube map, got {} texture", head.kind);
+    }
+    let slices = slice_texture(texture)?;
+    ensure!(!slices.is_empty(), "Cube map has no mips");
+    ensure!(slices[0].len() >= 6, "Expected 6 cube faces, got {}", slices[0].len());
+    let faces = slices[0][..6]
+        .iter()
+        .enumerate()
+        .map(|(face, slice)| {
+            decompress_image(
+                head.format,
+                slice.width,
+                slice.height,
+                &texture.data[slice.data_range.clone()],
+            )
+            .with_context(|| format!("Failed to decode mip 0 face {face}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let size = faces[0].width();
+    let layout = if args.vertical { CrossLayout::Vertical } else { CrossLayout::Horizontal };
+    let width = args.width.unwrap_or(size * 4);
+
+    // Keep HDR data as floating point (EXR), otherwise write 8-bit PNG
+    let (image, extension) = if use_exr(args.format, &faces)? {
+        let faces = faces.iter().map(DynamicImage::to_rgba32f).collect::<Vec<_>>();
+        let image =
+            if args.cross { cube_cross(&faces, layout)? } else { cube_equirect(&faces, width)? };
+        (DynamicImage::ImageRgba32F(image), "exr")
+    } else {
+        let faces = faces
+            .iter()
+            .map(|face| to_ldr(face, args.tonemap, args.linear, args.dither).into_rgba8())
+            .collect::<Vec<_>>();
+        let image =
+            if args.cross { cube_cross(&faces, layout)? } else { cube_equirect(&faces, width)? };
+        (DynamicImage::ImageRgba8(image), "png")
+    };
+    save_image(&image, &args.input.with_extension(extension), args.stdout)
+}
+
+/// Minimum thumbnail dimension; smaller mips are skipped unless the texture has no larger one.
+const THUMBNAIL_SIZE: u32 = 64;
+
+/// No TXTR thumbnail chunk is known, so the thumbnail is a mip of the first layer picked by
+/// [`thumbnail_mip`]. Textures without a mip chain are decoded at full resolution.
+fn convert_thumbnail(args: &ConvertArgs, texture: &TextureData<LittleEndian>) -> Result<()> {
+    let slices = slice_texture(texture)?;
+    let Some(mip) = thumbnail_mip(&slices, THUMBNAIL_SIZE) else {
+        bail!("Texture has no image data");
+    };
+    let image = decode_slice(texture, mip, 0)?;
+    log::info!("Thumbnail size {}x{}", image.width(), image.height());
+    save_image(
+        &to_ldr(&image, args.tonemap, args.linear, args.dither),
+        &args.input.with_extension("thumb.png"),
+        args.stdout,
+    )
+}
+
+fn info(args: InfoArgs) -> Result<()> {
+    let data = map_file(&args.input)?;
+    let meta = locate_meta::<LittleEndian>(&data)?;
+    let (head, meta) = TextureData::<LittleEndian>::slice_header(&data, meta)
+        .with_context(|| format!("Failed to parse texture '{}'", args.input.display()))?;
+    let sampler = &head.sampler_data;
+
+    if args.json {
+        let value = json!({
+            "type": head.kind.to_string(),
+            "format": head.format.to_string(),
+            "width": head.width,
+            "height": head.height,
+            "layers": head.layers,
+            "tile_mode": head.tile_mode,
+            "swizzle": head.swizzle,
+            "mip_sizes": head.mip_sizes,
+            "sampler": {
+                "filter": format!("{:?}", sampler.filter),
+                "mip_filter": format!("{:?}", sampler.mip_filter),
+                "wrap": [
+                    format!("{:?}", sampler.wrap_x),
+                    format!("{:?}", sampler.wrap_y),
+                    format!("{:?}", sampler.wrap_z),
+                ],
+                "aniso": format!("{:?}", sampler.aniso),
+            },
+            "alloc_category": meta.alloc_category,
+            "gpu_offset": meta.gpu_offset,
+            "align": meta.align,
+            "decompressed_size": meta.decompressed_size,
+            "reads": meta.info.iter().map(|read| json!({
+                "index": read.index,
+                "offset": read.offset,
+                "size": read.size,
+            })).collect::<Vec<_>>(),
+            "buffers": meta.buffers.iter().map(|buffer| json!({
+                "index": buffer.index,
+                "offset": buffer.offset,
+                "size": buffer.size,
+                "dest_offset": buffer.dest_offset,
+                "dest_size": buffer.dest_size,
+            })).collect::<Vec<_>>(),
+        });
+        output::print_json(&value)?;
+        return Ok(());
+    }
+
+    println!("Type: {}", head.kind);
+    println!("Format: {}", head.format);
+    println!("Size: {}x{}x{}", head.width, head.height, head.layers);
+    println!("Tile mode: {}, swizzle: {}", head.tile_mode, head.swizzle);
+    println!("Mips: {}", head.mip_sizes.len());
+    for (idx, size) in head.mip_sizes.iter().enumerate() {
+        println!("  Mip {idx}: {size:#X}");
+    }
+    println!(
+        "Sampler: filter {:?}, mip filter {:?}, wrap {:?}/{:?}/{:?}, aniso {:?}",
+        sampler.filter,
+        sampler.mip_filter,
+        sampler.wrap_x,
+        sampler.wrap_y,
+        sampler.wrap_z,
+        sampler.aniso
+    );
+    println!("Decompressed size: {:#X} (align {:#X})", meta.decompressed_size, meta.align);
+    for read in &meta.info {
+        println!("  Read {}: offset {:#X}, size {:#X}", read.index, read.offset, read.size);
+    }
+    for buffer in &meta.buffers {
+        println!(
+            "  Buffer {}: offset {:#X}, size {:#X} => {:#X}..{:#X}",
+            buffer.index,
+            buffer.offset,
+            buffer.size,
+            buffer.dest_offset,
+            buffer.dest_offset + buffer.dest_size
+        );
+    }
+    Ok(())
+}
@@ -1,5 +1,8 @@
 mod argh_version;
 mod cmd;
+mod output;
+mod parallel;
+mod progress;
 
 use argh::FromArgs;
 
@@ -8,15 +11,31 @@ use argh::FromArgs;
 struct TopLevel {
     #[argh(subcommand)]
     command: SubCommand,
+    #[argh(option, default = "0")]
+    /// number of worker threads for batch operations (default: 0, one per CPU)
+    threads: usize,
+    #[argh(switch)]
+    /// print JSON output on a single line instead of pretty-printed
+    compact: bool,
+    #[argh(switch)]
+    /// report batch progress as JSON lines on stderr
+    progress_json: bool,
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
 #[argh(subcommand)]
 pub enum SubCommand {
+    #[cfg(feature = "bench")]
+    Bench(cmd::bench::Args),
     Clsn(cmd::clsn::Args),
     Cmdl(cmd::cmdl::Args),
     Fmv0(cmd::fmv0::Args),
+    Formats(cmd::formats::Args),
+    Hash(cmd::hash::Args),
+    Ltpb(cmd::ltpb::Args),
     Pak(cmd::pak::Args),
+    Rfrm(cmd::rfrm::Args),
+    Room(cmd::room::Args),
     Txtr(cmd::txtr::Args),
 }
 
@@ -28,11 +47,21 @@ fn main() {
         .init();
 
     let args: TopLevel = argh_version::from_env();
+    parallel::set_threads(args.threads);
+    output::set_compact(args.compact);
+    progress::set_json(args.progress_json);
     let result = match args.command {
+        #[cfg(feature = "bench")]
+        SubCommand::Bench(args) => cmd::bench::run(args),
         SubCommand::Clsn(args) => cmd::clsn::run(args),
         SubCommand::Cmdl(args) => cmd::cmdl::run(args),
         SubCommand::Fmv0(args) => cmd::fmv0::run(args),
+        SubCommand::Formats(args) => cmd::formats::run(args),
+        SubCommand::Hash(args) => cmd::hash::run(args),
+        SubCommand::Ltpb(args) => cmd::ltpb::run(args),
         SubCommand::Pak(args) => cmd::pak::run(args),
+        SubCommand::Rfrm(args) => cmd::rfrm::run(args),
+        SubCommand::Room(args) => cmd::room::run(args),
         SubCommand::Txtr(args) => cmd::txtr::run(args),
     };
     if let Err(e) = result {
@@ -0,0 +1,34 @@
+use std::{
+    io::{stdout, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::Result;
+use serde::Serialize;
+
+static COMPACT: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether JSON output is compact instead of pretty-printed.
+pub fn set_compact(compact: bool) { COMPACT.store(compact, Ordering::Relaxed) }
+
+/// Prints a value as JSON to stdout, pretty-printed unless `--compact` was given.
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    let mut out = stdout().lock();
+    if COMPACT.load(Ordering::Relaxed) {
+        serde_json::to_writer(&mut out, value)?;
+    } else {
+        serde_json::to_writer_pretty(&mut out, value)?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Writes raw bytes to stdout for piping into other tools.
+///
+/// Logging goes to stderr, so it never mixes with the data.
+pub fn write_stdout(data: &[u8]) -> Result<()> {
+    let mut out = stdout().lock();
+    out.write_all(data)?;
+    out.flush()?;
+    Ok(())
+}
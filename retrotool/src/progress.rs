@@ -0,0 +1,68 @@
+use std::{
+    io::{stderr, Write},
+    path::Path,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use serde::Serialize;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether batch operations report progress as JSON lines on stderr.
+pub fn set_json(json: bool) { JSON.store(json, Ordering::Relaxed) }
+
+/// A `--progress-json` event. Each is written to stderr as a single line of JSON.
+///
+/// The schema is stable: new events and fields may be added, but existing ones won't change.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    /// A batch operation is starting with `total` files.
+    Start { total: usize },
+    /// `path` finished processing (or was skipped), `done` of `total`.
+    File { done: usize, total: usize, path: &'a Path },
+    /// The batch operation completed.
+    Finish { done: usize, total: usize },
+}
+
+fn emit(event: &Event) {
+    let mut line = match serde_json::to_vec(event) {
+        Ok(line) => line,
+        Err(e) => {
+            log::warn!("Failed to serialize progress event: {e}");
+            return;
+        }
+    };
+    line.push(b'\n');
+    // A single write per event keeps lines whole when reported from worker threads
+    let _ = stderr().lock().write_all(&line);
+}
+
+/// Progress through a batch of files. Does nothing unless `--progress-json` was given.
+pub struct Progress {
+    total: usize,
+    done: AtomicUsize,
+}
+
+impl Progress {
+    pub fn start(total: usize) -> Self {
+        if JSON.load(Ordering::Relaxed) {
+            emit(&Event::Start { total });
+        }
+        Self { total, done: AtomicUsize::new(0) }
+    }
+
+    /// Reports that `path` is done. Safe to call from worker threads.
+    pub fn file(&self, path: &Path) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if JSON.load(Ordering::Relaxed) {
+            emit(&Event::File { done, total: self.total, path });
+        }
+    }
+
+    pub fn finish(self) {
+        if JSON.load(Ordering::Relaxed) {
+            emit(&Event::Finish { done: self.done.into_inner(), total: self.total });
+        }
+    }
+}